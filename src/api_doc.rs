@@ -1,6 +1,11 @@
+use core::fmt::Write;
+
+use strum::IntoEnumIterator;
 use utoipa::openapi::security::{ApiKey, ApiKeyValue, SecurityScheme};
 use utoipa::{Modify, OpenApi};
 
+use crate::services::rate_limiter::ApiKeyScope;
+
 #[derive(OpenApi)]
 #[openapi(
     info(
@@ -42,15 +47,26 @@ struct SecurityAddon;
 
 impl Modify for SecurityAddon {
     fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let mut description =
+            "Some endpoints require the API key to be granted a scope:\n".to_owned();
+        for scope in ApiKeyScope::iter() {
+            let _ = writeln!(description, "- `{scope}`: {}", scope.description());
+        }
         if let Some(components) = openapi.components.as_mut() {
             components.add_security_schemes_from_iter(vec![
                 (
                     "api_key_header",
-                    SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::new("X-API-KEY"))),
+                    SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::with_description(
+                        "X-API-KEY",
+                        description.as_str(),
+                    ))),
                 ),
                 (
                     "api_key_query",
-                    SecurityScheme::ApiKey(ApiKey::Query(ApiKeyValue::new("api_key"))),
+                    SecurityScheme::ApiKey(ApiKey::Query(ApiKeyValue::with_description(
+                        "api_key",
+                        description.as_str(),
+                    ))),
                 ),
             ]);
         }
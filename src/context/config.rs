@@ -1,6 +1,8 @@
-use serde::Deserialize;
+use serde::{Deserialize, Deserializer};
+use url::Url;
 
-use crate::utils::parse::default_true;
+use crate::utils::net::IpCidr;
+use crate::utils::parse::{comma_separated_deserialize, default_true};
 
 #[derive(Deserialize, Debug, Clone)]
 pub(super) struct SteamConfig {
@@ -19,6 +21,64 @@ pub(crate) struct PatreonConfig {
     pub(crate) webhook_secret: String,
 }
 
+/// GitHub Sponsors membership provider, enabled if a client ID and a webhook secret are set
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub(crate) struct GitHubSponsorsConfig {
+    pub(crate) client_id: String,
+    pub(crate) client_secret: String,
+    pub(crate) redirect_uri: String,
+    pub(crate) frontend_redirect_url: String,
+    /// Login of the sponsored GitHub user
+    pub(crate) sponsorable_login: String,
+    pub(crate) webhook_secret: String,
+}
+
+impl GitHubSponsorsConfig {
+    pub(crate) fn is_enabled(&self) -> bool {
+        !self.client_id.is_empty() && !self.webhook_secret.is_empty()
+    }
+}
+
+/// Mock membership provider for offline testing.
+///
+/// Logins are not verified, so it must never be enabled in production. Release builds only
+/// register it if `not_production` is set as well.
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub(crate) struct MockMembershipConfig {
+    pub(crate) enabled: bool,
+    /// Confirms that this deployment is not production, e.g. the integration test environment
+    pub(crate) not_production: bool,
+    pub(crate) redirect_uri: String,
+    pub(crate) frontend_redirect_url: String,
+    pub(crate) webhook_secret: String,
+}
+
+/// Parses an optional URL, an empty value is none.
+fn deserialize_optional_url<'de, D>(deserializer: D) -> Result<Option<Url>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let value = String::deserialize(deserializer)?;
+    if value.is_empty() {
+        return Ok(None);
+    }
+    Url::parse(&value)
+        .map(Some)
+        .map_err(serde::de::Error::custom)
+}
+
+/// Steam `OpenID` login of players, enabled if a return URL is set
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub(crate) struct SteamLoginConfig {
+    /// Callback Steam redirects to after the login, e.g. `https://api.deadlock-api.com/v1/auth/steam/callback`
+    #[serde(deserialize_with = "deserialize_optional_url")]
+    pub(crate) return_to: Option<Url>,
+    pub(crate) frontend_redirect_url: String,
+}
+
 fn default_redis_url() -> String {
     "redis://localhost:6379".to_owned()
 }
@@ -108,6 +168,47 @@ pub(super) struct PostgresConfig {
     pub(super) pool_size: u32,
 }
 
+fn default_client_ip_trusted_proxies() -> Vec<IpCidr> {
+    // Only trust local proxies by default, anything else has to be configured explicitly (e.g. the
+    // Cloudflare IP ranges), otherwise clients could spoof their IP and bypass per-IP rate limits
+    ["127.0.0.0/8", "::1/128"]
+        .into_iter()
+        .filter_map(|c| c.parse().ok())
+        .collect()
+}
+
+fn default_client_ip_headers() -> Vec<String> {
+    ["CF-Connecting-IP", "X-Real-IP", "X-Forwarded-For"]
+        .into_iter()
+        .map(ToOwned::to_owned)
+        .collect()
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub(crate) struct ClientIpConfig {
+    /// Peers whose forwarding headers are trusted, comma separated CIDRs
+    #[serde(
+        default = "default_client_ip_trusted_proxies",
+        deserialize_with = "comma_separated_deserialize"
+    )]
+    pub(crate) trusted_proxies: Vec<IpCidr>,
+    /// Headers carrying the client IP, checked in order
+    #[serde(
+        default = "default_client_ip_headers",
+        deserialize_with = "comma_separated_deserialize"
+    )]
+    pub(crate) headers: Vec<String>,
+}
+
+impl Default for ClientIpConfig {
+    fn default() -> Self {
+        Self {
+            trusted_proxies: default_client_ip_trusted_proxies(),
+            headers: default_client_ip_headers(),
+        }
+    }
+}
+
 fn default_assets_base_url() -> String {
     "https://assets.deadlock-api.com".to_owned()
 }
@@ -124,9 +225,17 @@ pub(crate) struct Config {
     pub(crate) clickhouse: ClickhouseConfig,
     pub(super) postgres: PostgresConfig,
     pub(crate) patreon: PatreonConfig,
+    #[serde(default)]
+    pub(crate) github_sponsors: GitHubSponsorsConfig,
+    #[serde(default)]
+    pub(crate) mock_membership: MockMembershipConfig,
+    #[serde(default)]
+    pub(crate) steam_login: SteamLoginConfig,
     pub(crate) jwt_secret: String,
     /// Encryption key for patron tokens (32-byte hex-encoded for AES-256-GCM)
     pub(crate) patron_encryption_key: String,
+    #[serde(default)]
+    pub(crate) client_ip: ClientIpConfig,
 
     #[serde(default = "default_assets_base_url")]
     pub(super) assets_base_url: String,
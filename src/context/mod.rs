@@ -1,4 +1,7 @@
 mod config;
 mod state;
 
+pub(super) use config::{
+    ClientIpConfig, Config, GitHubSponsorsConfig, MockMembershipConfig, PatreonConfig,
+};
 pub(super) use state::{AppState, AppStateError};
@@ -1,12 +1,9 @@
 use core::time::Duration;
-use std::collections::HashMap;
-use std::fs::File;
 use std::io;
 use std::sync::Arc;
 
 use object_store::aws::{AmazonS3, AmazonS3Builder};
 use object_store::{BackoffConfig, ClientOptions, RetryConfig};
-use serde::Deserialize;
 use sqlx::postgres::{PgConnectOptions, PgPoolOptions};
 use sqlx::{Pool, Postgres};
 use thiserror::Error;
@@ -14,6 +11,9 @@ use tracing::{debug, warn};
 
 use crate::context::config::Config;
 use crate::services::assets::client::AssetsClient;
+use crate::services::feature_flags::FeatureFlags;
+use crate::services::membership::MembershipProviders;
+use crate::services::membership::verification_job::MembershipVerificationJob;
 use crate::services::rate_limiter::RateLimitClient;
 use crate::services::request_logger::RequestLogger;
 use crate::services::steam::client::SteamClient;
@@ -36,11 +36,6 @@ pub enum AppStateError {
     Io(#[from] io::Error),
 }
 
-#[derive(Debug, Clone, Deserialize, Default)]
-pub(crate) struct FeatureFlags {
-    pub(crate) routes: HashMap<String, bool>,
-}
-
 #[derive(Clone)]
 pub(crate) struct AppState {
     pub(crate) config: Config,
@@ -51,11 +46,13 @@ pub(crate) struct AppState {
     pub(crate) ch_client_ro: clickhouse::Client,
     pub(crate) ch_client_restricted: clickhouse::Client,
     pub(crate) pg_client: Pool<Postgres>,
-    pub(crate) feature_flags: FeatureFlags,
+    pub(crate) feature_flags: Arc<FeatureFlags>,
     pub(crate) steam_client: SteamClient,
     pub(crate) assets_client: AssetsClient,
     pub(crate) rate_limit_client: RateLimitClient,
     pub(crate) request_logger: Arc<RequestLogger>,
+    pub(crate) membership_providers: MembershipProviders,
+    pub(crate) membership_verification_job: Arc<MembershipVerificationJob>,
 }
 
 impl AppState {
@@ -222,20 +219,16 @@ impl AppState {
 
         // Load feature flags
         debug!("Loading feature flags");
-        let feature_flags = File::open("feature_flags.json")
-            .inspect_err(|e| warn!("Failed to open feature flags file: {e}"))
-            .ok()
-            .and_then(|f| {
-                serde_json::from_reader(f)
-                    .inspect_err(|e| warn!("Failed to parse feature flags: {e}"))
-                    .ok()
-            })
-            .unwrap_or_default();
+        let feature_flags = Arc::new(FeatureFlags::new(pg_client.clone()));
+        if let Err(e) = feature_flags.reload().await {
+            warn!("Failed to load feature flags: {e}");
+        }
 
         // Create a Steam client
         debug!("Creating Steam client");
         let steam_client = SteamClient::new(
             http_client.clone(),
+            redis_client.clone(),
             config
                 .steam
                 .proxy_url
@@ -263,6 +256,15 @@ impl AppState {
         debug!("Creating Request Logger");
         let request_logger = Arc::new(RequestLogger::new(ch_client.clone()));
 
+        // Create the membership providers and their verification job
+        debug!("Creating membership providers");
+        let membership_providers = MembershipProviders::from_config(&config, &http_client);
+        let membership_verification_job = Arc::new(MembershipVerificationJob::new(
+            pg_client.clone(),
+            config.patron_encryption_key.clone(),
+            membership_providers.clone(),
+        ));
+
         Ok(Self {
             config,
             s3_client,
@@ -277,6 +279,8 @@ impl AppState {
             assets_client,
             rate_limit_client,
             request_logger,
+            membership_providers,
+            membership_verification_job,
         })
     }
 }
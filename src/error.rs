@@ -20,6 +20,8 @@ pub enum StartupError {
     IO(#[from] std::io::Error),
     #[error("Load app state error: {0}")]
     AppState(#[from] AppStateError),
+    #[error("HTTP client error: {0}")]
+    HttpClient(#[from] reqwest::Error),
 }
 
 #[allow(dead_code)]
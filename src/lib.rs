@@ -21,6 +21,7 @@ mod services;
 pub mod utils;
 
 use core::time::Duration;
+use std::sync::Arc;
 
 use axum::extract::State;
 use axum::http::{HeaderMap, StatusCode, header};
@@ -46,10 +47,16 @@ use crate::api_doc::ApiDoc;
 use crate::context::AppState;
 use crate::middleware::api_key::write_api_key_to_header;
 use crate::middleware::cache::CacheControlMiddleware;
+use crate::middleware::client_ip::client_ip;
 use crate::middleware::feature_flags::feature_flags;
+use crate::middleware::internal_key::{is_internal_key, require_internal_key};
 use crate::middleware::track_requests::track_requests;
-use crate::services::patreon::verification_job::PatreonVerificationJob;
+use crate::services::callbacks::CallbackDeliveryWorker;
+use crate::services::custom_matches::CustomMatchTracker;
+use crate::services::leaderboard_snapshots::LeaderboardSnapshotJob;
+use crate::services::membership::webhook_worker::MembershipWebhookWorker;
 use crate::services::rate_limiter::extractor::RateLimitKey;
+use crate::services::sql_jobs::SqlJobWorker;
 
 const DEFAULT_CACHE_TIME: u64 = 2 * 60; // Cloudflare Free Tier Minimal Cache Time
 
@@ -76,15 +83,53 @@ pub async fn router(port: u16) -> Result<NormalizePath<Router>, StartupError> {
     // Start the background request logger flush task
     state.request_logger.clone().start_background_flush();
 
-    // Start the daily Patreon verification job for token refresh and membership sync
-    let patreon_verification_job = std::sync::Arc::new(PatreonVerificationJob::new(
+    // Start the background feature flag reload task
+    state.feature_flags.clone().start_background_reload();
+
+    // Start the hourly leaderboard snapshot job
+    Arc::new(LeaderboardSnapshotJob::new(
+        state.steam_client.clone(),
+        state.assets_client.clone(),
+        state.ch_client.clone(),
+        state.redis_client.clone(),
+    ))
+    .start_background_snapshots();
+
+    // Start the worker running queued SQL query jobs
+    if state.config.clickhouse.allow_custom_queries {
+        Arc::new(SqlJobWorker::new(
+            state.pg_client.clone(),
+            state.ch_client_restricted.clone(),
+            state.s3_client.clone(),
+        ))
+        .start_background_worker();
+    }
+
+    // Start tracking the lifecycle of custom match lobbies
+    Arc::new(CustomMatchTracker::new(
         state.pg_client.clone(),
+        state.redis_client.clone(),
+        state.ch_client.clone(),
+        state.s3_client.clone(),
+    ))
+    .start_background_tracking();
+
+    // Start the worker delivering queued callbacks
+    Arc::new(CallbackDeliveryWorker::new(state.pg_client.clone())?).start_background_worker();
+
+    // Start the hourly membership verification job for token refresh and membership sync
+    state
+        .membership_verification_job
+        .clone()
+        .start_background_verification();
+
+    // Start the worker applying stored membership provider webhook events
+    Arc::new(MembershipWebhookWorker::new(
+        state.pg_client.clone(),
+        state.membership_providers.clone(),
         state.config.patron_encryption_key.clone(),
-        state.config.patreon.client_id.clone(),
-        state.config.patreon.client_secret.clone(),
-        state.config.patreon.redirect_uri.clone(),
-    ));
-    patreon_verification_job.start_background_verification();
+    ))
+    .start_background_worker();
 
     let (mut prometheus_layer, metric_handle) = PrometheusMetricLayer::pair();
     prometheus_layer.enable_response_body_size();
@@ -96,10 +141,15 @@ pub async fn router(port: u16) -> Result<NormalizePath<Router>, StartupError> {
         .route("/favicon.ico", get(favicon))
         // Add application routes
         .merge(routes::router())
+        // Add internal admin routes
+        .nest(
+            "/v1/admin",
+            routes::v1::admin::router()
+                .route_layer(from_fn_with_state(state.clone(), require_internal_key)),
+        )
         // Add prometheus metrics route
         .route("/metrics", get(|rk: RateLimitKey, State(AppState{config, ..}): State<AppState>| async move {
-            let internal_key = config.internal_api_key.strip_prefix("HEXE-").unwrap_or(&config.internal_api_key);
-            if rk.api_key.is_none_or(|k| k.to_string() != internal_key) {
+            if !is_internal_key(&config, &rk) {
                 return Err(APIError::status_msg(
                     StatusCode::FORBIDDEN,
                     "API key is required for this endpoint",
@@ -118,6 +168,7 @@ pub async fn router(port: u16) -> Result<NormalizePath<Router>, StartupError> {
         .layer(from_fn_with_state(state.clone(), feature_flags))
         .layer(from_fn(write_api_key_to_header))
         .layer(from_fn_with_state(state.clone(), track_requests))
+        .layer(from_fn_with_state(state.clone(), client_ip))
         .layer(
             CacheControlMiddleware::new(Duration::from_secs(DEFAULT_CACHE_TIME))
                 .with_stale_if_error(Duration::from_secs(DEFAULT_CACHE_TIME))
@@ -62,7 +62,8 @@ async fn main() -> Result<(), StartupError> {
     let listener = tokio::net::TcpListener::bind(&address).await?;
 
     info!("Listening on http://{address}");
-    let make_service = ServiceExt::<Request>::into_make_service(router);
+    let make_service =
+        ServiceExt::<Request>::into_make_service_with_connect_info::<SocketAddr>(router);
     axum::serve(listener, make_service)
         .with_graceful_shutdown(shutdown_signal())
         .await?;
@@ -0,0 +1,111 @@
+use core::net::{IpAddr, SocketAddr};
+
+use axum::extract::{ConnectInfo, Request, State};
+use axum::http::HeaderMap;
+use axum::middleware::Next;
+use axum::response::Response;
+
+use crate::context::{AppState, ClientIpConfig};
+
+/// The resolved IP address of the client, stored in the request extensions.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub(crate) struct ClientIp(pub(crate) IpAddr);
+
+pub(crate) async fn client_ip(
+    State(AppState { config, .. }): State<AppState>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let peer = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip());
+    if let Some(ip) = resolve_client_ip(request.headers(), peer, &config.client_ip) {
+        request.extensions_mut().insert(ClientIp(ip));
+    }
+    next.run(request).await
+}
+
+fn resolve_client_ip(
+    headers: &HeaderMap,
+    peer: Option<IpAddr>,
+    config: &ClientIpConfig,
+) -> Option<IpAddr> {
+    let is_trusted = |ip: IpAddr| config.trusted_proxies.iter().any(|c| c.contains(ip));
+
+    // Forwarding headers can be set by anyone, so only look at them if the peer is a trusted proxy
+    if peer.is_none_or(is_trusted) {
+        for header in &config.headers {
+            let Some(value) = headers.get(header).and_then(|v| v.to_str().ok()) else {
+                continue;
+            };
+            // Every proxy appends to X-Forwarded-For, so take the right-most hop that is not
+            // a trusted proxy itself
+            let hops: Vec<IpAddr> = value
+                .split(',')
+                .filter_map(|hop| hop.trim().parse().ok())
+                .collect();
+            if let Some(ip) = hops
+                .iter()
+                .rev()
+                .find(|&&ip| !is_trusted(ip))
+                .or(hops.first())
+            {
+                return Some(ip.to_canonical());
+            }
+        }
+    }
+    peer.map(|ip| ip.to_canonical())
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::HeaderName;
+    use rstest::rstest;
+
+    use super::*;
+
+    fn config_with_trusted_proxies(trusted_proxies: &[&str]) -> ClientIpConfig {
+        ClientIpConfig {
+            trusted_proxies: trusted_proxies.iter().map(|c| c.parse().unwrap()).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[rstest]
+    #[case(&[], None, None, None)]
+    #[case(&[("CF-Connecting-IP", "1.2.3.4")], None, None, Some("1.2.3.4"))]
+    #[case(&[("X-Real-IP", "2001:db8::1")], None, None, Some("2001:db8::1"))]
+    #[case(&[("CF-Connecting-IP", "invalid"), ("X-Real-IP", "1.2.3.4")], None, None, Some("1.2.3.4"))]
+    #[case(&[], Some("5.6.7.8"), None, Some("5.6.7.8"))]
+    #[case(&[], Some("::ffff:5.6.7.8"), None, Some("5.6.7.8"))]
+    // Headers from untrusted peers are ignored
+    #[case(&[("CF-Connecting-IP", "1.2.3.4")], Some("5.6.7.8"), Some(&["10.0.0.0/8"][..]), Some("5.6.7.8"))]
+    #[case(&[("CF-Connecting-IP", "1.2.3.4")], Some("10.0.0.1"), Some(&["10.0.0.0/8"][..]), Some("1.2.3.4"))]
+    // X-Forwarded-For is walked from the right, skipping trusted proxies
+    #[case(&[("X-Forwarded-For", "9.9.9.9, 1.2.3.4, 10.0.0.2")], Some("10.0.0.1"), Some(&["10.0.0.0/8"][..]), Some("1.2.3.4"))]
+    #[case(&[("X-Forwarded-For", "1.2.3.4, 127.0.0.1")], None, None, Some("1.2.3.4"))]
+    // Only loopback proxies are trusted by default
+    #[case(&[("CF-Connecting-IP", "1.2.3.4")], Some("127.0.0.1"), None, Some("1.2.3.4"))]
+    #[case(&[("CF-Connecting-IP", "1.2.3.4")], Some("5.6.7.8"), None, Some("5.6.7.8"))]
+    #[case(&[("X-Forwarded-For", "1.2.3.4")], Some("2001:db8::1"), None, Some("2001:db8::1"))]
+    fn test_resolve_client_ip(
+        #[case] headers: &[(&str, &str)],
+        #[case] peer: Option<&str>,
+        #[case] trusted_proxies: Option<&[&str]>,
+        #[case] expected: Option<&str>,
+    ) {
+        let mut header_map = HeaderMap::new();
+        for (name, value) in headers {
+            header_map.insert(name.parse::<HeaderName>().unwrap(), value.parse().unwrap());
+        }
+        let config =
+            trusted_proxies.map_or_else(ClientIpConfig::default, config_with_trusted_proxies);
+        let peer = peer.map(|p| p.parse().unwrap());
+
+        assert_eq!(
+            resolve_client_ip(&header_map, peer, &config),
+            expected.map(|e| e.parse().unwrap())
+        );
+    }
+}
@@ -1,27 +1,208 @@
+use std::collections::HashMap;
+
+use axum::body::Body;
 use axum::extract::{MatchedPath, Request, State};
-use axum::http::StatusCode;
+use axum::http::{HeaderValue, Method, StatusCode, Uri, header};
 use axum::middleware::Next;
 use axum::response::{IntoResponse, Response};
+use itertools::Itertools;
+use redis::AsyncCommands;
+use redis::aio::MultiplexedConnection;
+use tracing::warn;
 
 use crate::context::AppState;
 use crate::error::APIError;
+use crate::services::feature_flags::RouteAccess;
+use crate::services::rate_limiter::extractor::RateLimitKey;
+use crate::utils::parse;
+
+const STALE_RESPONSE_TTL_SECS: u64 = 24 * 60 * 60;
+const MAX_STALE_RESPONSE_SIZE: usize = 1024 * 1024;
 
+/// Applies the route feature flags.
+///
+/// Successful anonymous GET responses of flagged routes are kept in Redis, so they can still be
+/// served while the route is in degraded mode.
 pub(crate) async fn feature_flags(
-    State(AppState { feature_flags, .. }): State<AppState>,
+    State(AppState {
+        feature_flags,
+        mut redis_client,
+        ..
+    }): State<AppState>,
     matched_path: MatchedPath,
+    rate_limit_key: RateLimitKey,
     request: Request,
     next: Next,
 ) -> Response {
-    let matched_path = matched_path.as_str().to_owned();
+    let matched_path = matched_path.as_str();
+    let Some(access) = feature_flags.evaluate(matched_path, &rate_limit_key).await else {
+        return next.run(request).await;
+    };
 
-    let route_enabled = feature_flags.routes.get(&matched_path).unwrap_or(&true);
-    if !route_enabled {
-        return APIError::status_msg(
+    let is_get = request.method() == Method::GET;
+    let is_anonymous = is_anonymous(&request);
+    match access {
+        RouteAccess::Disabled => APIError::status_msg(
             StatusCode::SERVICE_UNAVAILABLE,
             format!("Route {matched_path} is disabled"),
         )
-        .into_response();
+        .into_response(),
+        RouteAccess::Degraded if !is_get => APIError::status_msg(
+            StatusCode::SERVICE_UNAVAILABLE,
+            format!("Route {matched_path} is in read-only mode"),
+        )
+        .into_response(),
+        RouteAccess::Degraded if !is_anonymous => APIError::status_msg(
+            StatusCode::SERVICE_UNAVAILABLE,
+            format!("Route {matched_path} is degraded and only serves cached anonymous responses"),
+        )
+        .into_response(),
+        RouteAccess::Degraded => {
+            let cache_key = stale_response_key(request.uri());
+            match load_stale_response(&mut redis_client, &cache_key).await {
+                Some(response) => response,
+                None => APIError::status_msg(
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    format!("Route {matched_path} is degraded and no cached response is available"),
+                )
+                .into_response(),
+            }
+        }
+        RouteAccess::Enabled if is_get && is_anonymous => {
+            let cache_key = stale_response_key(request.uri());
+            let response = next.run(request).await;
+            store_stale_response(&mut redis_client, &cache_key, response).await
+        }
+        RouteAccess::Enabled => next.run(request).await,
+    }
+}
+
+/// Whether the request carries no credentials.
+///
+/// Stale responses are served before the authentication, scope checks and rate limits of the
+/// route run, so they are only stored for and served to anonymous requests.
+fn is_anonymous(request: &Request) -> bool {
+    let headers = request.headers();
+    !headers.contains_key("X-API-Key")
+        && !headers.contains_key(header::AUTHORIZATION)
+        && !headers.contains_key(header::COOKIE)
+        && !parse::querify(request.uri().query().unwrap_or_default())
+            .iter()
+            .any(|(k, _)| *k == "api_key")
+}
+
+fn stale_response_key(uri: &Uri) -> String {
+    let query = parse::querify(uri.query().unwrap_or_default())
+        .into_iter()
+        .filter(|(k, _)| *k != "api_key")
+        .map(|(k, v)| format!("{k}={v}"))
+        .join("&");
+    format!("stale-response:{}?{query}", uri.path())
+}
+
+async fn load_stale_response(
+    redis_client: &mut MultiplexedConnection,
+    cache_key: &str,
+) -> Option<Response> {
+    let mut cached: HashMap<String, Vec<u8>> = redis_client
+        .hgetall(cache_key)
+        .await
+        .inspect_err(|e| warn!("Failed to load stale response: {e}"))
+        .ok()?;
+    let body = cached.remove("body")?;
+    let mut response = Response::new(Body::from(body));
+    if let Some(content_type) = cached
+        .remove("content_type")
+        .and_then(|c| HeaderValue::from_bytes(&c).ok())
+    {
+        response
+            .headers_mut()
+            .insert(header::CONTENT_TYPE, content_type);
+    }
+    response
+        .headers_mut()
+        .insert("X-Stale-Response", HeaderValue::from_static("true"));
+    Some(response)
+}
+
+async fn store_stale_response(
+    redis_client: &mut MultiplexedConnection,
+    cache_key: &str,
+    response: Response,
+) -> Response {
+    // Only buffer bodies of known, small size, streamed responses are passed through untouched
+    let content_length = response
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|s| s.parse::<usize>().ok());
+    if response.status() != StatusCode::OK
+        || content_length.is_none_or(|l| l > MAX_STALE_RESPONSE_SIZE)
+    {
+        return response;
+    }
+
+    let (parts, body) = response.into_parts();
+    let body = match axum::body::to_bytes(body, MAX_STALE_RESPONSE_SIZE).await {
+        Ok(body) => body,
+        Err(e) => {
+            return APIError::internal(format!("Failed to read response: {e}")).into_response();
+        }
+    };
+    let content_type = parts
+        .headers
+        .get(header::CONTENT_TYPE)
+        .map(HeaderValue::as_bytes)
+        .unwrap_or_default();
+    if let Err(e) = redis::pipe()
+        .hset_multiple(
+            cache_key,
+            &[("content_type", content_type), ("body", body.as_ref())],
+        )
+        .expire(cache_key, STALE_RESPONSE_TTL_SECS.cast_signed())
+        .exec_async(redis_client)
+        .await
+    {
+        warn!("Failed to store stale response: {e}");
     }
+    Response::from_parts(parts, Body::from(body))
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
 
-    next.run(request).await
+    #[rstest]
+    #[case("/v1/sql?query=SELECT%201", "stale-response:/v1/sql?query=SELECT%201")]
+    #[case(
+        "/v1/sql?api_key=HEXE-123&query=SELECT%201",
+        "stale-response:/v1/sql?query=SELECT%201"
+    )]
+    #[case("/v1/leaderboard/Europe", "stale-response:/v1/leaderboard/Europe?")]
+    fn test_stale_response_key(#[case] uri: &str, #[case] expected: &str) {
+        assert_eq!(stale_response_key(&uri.parse().unwrap()), expected);
+    }
+
+    #[rstest]
+    #[case("/v1/leaderboard/Europe", &[], true)]
+    #[case("/v1/sql?api_key=HEXE-123&query=SELECT%201", &[], false)]
+    #[case("/v1/sql?query=SELECT%201", &[("X-API-Key", "HEXE-123")], false)]
+    #[case("/v1/patron/status", &[("Authorization", "Bearer token")], false)]
+    #[case("/v1/patron/status", &[("Cookie", "patron_session=token")], false)]
+    fn test_is_anonymous(
+        #[case] uri: &str,
+        #[case] headers: &[(&str, &str)],
+        #[case] expected: bool,
+    ) {
+        let mut request = axum::http::Request::builder().uri(uri);
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+        assert_eq!(
+            is_anonymous(&request.body(Body::empty()).unwrap()),
+            expected
+        );
+    }
 }
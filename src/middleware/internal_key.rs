@@ -0,0 +1,35 @@
+use axum::extract::{Request, State};
+use axum::http::StatusCode;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+
+use crate::context::{AppState, Config};
+use crate::error::APIError;
+use crate::services::rate_limiter::extractor::RateLimitKey;
+
+pub(crate) fn is_internal_key(config: &Config, rate_limit_key: &RateLimitKey) -> bool {
+    let internal_key = config
+        .internal_api_key
+        .strip_prefix("HEXE-")
+        .unwrap_or(&config.internal_api_key);
+    rate_limit_key
+        .api_key
+        .is_some_and(|k| k.to_string() == internal_key)
+}
+
+/// Rejects all requests that are not authenticated with the internal API key.
+pub(crate) async fn require_internal_key(
+    State(AppState { config, .. }): State<AppState>,
+    rate_limit_key: RateLimitKey,
+    request: Request,
+    next: Next,
+) -> Response {
+    if !is_internal_key(&config, &rate_limit_key) {
+        return APIError::status_msg(
+            StatusCode::FORBIDDEN,
+            "API key is required for this endpoint",
+        )
+        .into_response();
+    }
+    next.run(request).await
+}
@@ -1,4 +1,6 @@
 pub(super) mod api_key;
 pub(super) mod cache;
+pub(super) mod client_ip;
 pub(super) mod feature_flags;
+pub(super) mod internal_key;
 pub(super) mod track_requests;
@@ -11,6 +11,7 @@ use uuid::Uuid;
 
 use crate::context::AppState;
 use crate::middleware::api_key::extract_api_key;
+use crate::middleware::client_ip::ClientIp;
 use crate::services::request_logger::RequestLog;
 
 fn get_header(req: &Request, name: &str) -> Option<String> {
@@ -52,13 +53,10 @@ pub(crate) async fn track_requests(
     let accept = get_header(&req, "accept");
     let accept_encoding = get_header(&req, "accept-encoding");
 
-    // Get client IP from various headers (prefer CF-Connecting-IP for Cloudflare)
-    let client_ip = get_header(&req, "cf-connecting-ip")
-        .or_else(|| {
-            get_header(&req, "x-forwarded-for")
-                .map(|s| s.split(',').next().unwrap_or("").trim().to_owned())
-        })
-        .or_else(|| get_header(&req, "x-real-ip"));
+    let client_ip = req
+        .extensions()
+        .get::<ClientIp>()
+        .map(|ClientIp(ip)| ip.to_string());
 
     let start = Instant::now();
     let response = next.run(req).await;
@@ -1,3 +1,5 @@
+use core::net::{IpAddr, Ipv4Addr};
+
 use axum::extract::Request;
 use axum::routing::{get, post};
 use tower_http::trace;
@@ -7,6 +9,7 @@ use utoipa_axum::router::OpenApiRouter;
 use uuid::Uuid;
 
 use crate::context::AppState;
+use crate::middleware::client_ip::ClientIp;
 use crate::routes::v1::analytics::{
     badge_distribution, hero_comb_stats, hero_stats, item_stats, player_scoreboard,
 };
@@ -72,11 +75,10 @@ pub(super) fn router() -> OpenApiRouter<AppState> {
                     let mut query = parse::querify(request.uri().query().unwrap_or_default());
                     query.retain(|d| d.0 != "api_key"); // remove api_key from query
 
-                    let ip = headers
-                        .get("CF-Connecting-IP")
-                        .or(headers.get("X-Real-IP"))
-                        .and_then(|v| v.to_str().ok())
-                        .unwrap_or("0.0.0.0");
+                    let ip = request
+                        .extensions()
+                        .get::<ClientIp>()
+                        .map_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED), |ClientIp(ip)| *ip);
 
                     span!(Level::INFO, "request", %method, ?path, ?query, ?api_key, ?ip)
                 })
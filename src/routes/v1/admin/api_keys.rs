@@ -0,0 +1,378 @@
+use axum::Json;
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use uuid::Uuid;
+
+use super::audit_log::AuditLog;
+use crate::context::AppState;
+use crate::error::{APIError, APIResult};
+use crate::services::rate_limiter::ApiKeyScope;
+
+#[derive(Debug, Serialize)]
+struct ApiKey {
+    key: Uuid,
+    created_at: Option<NaiveDateTime>,
+    comment: Option<String>,
+    data_access: bool,
+    disabled: bool,
+    patron_id: Option<Uuid>,
+    scopes: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub(super) struct CreateApiKeyRequest {
+    /// Unique comment describing the owner of the key
+    comment: String,
+    #[serde(default)]
+    data_access: bool,
+    #[serde(default)]
+    scopes: Vec<ApiKeyScope>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub(super) struct UpdateApiKeyRequest {
+    comment: Option<String>,
+    data_access: Option<bool>,
+    disabled: Option<bool>,
+    scopes: Option<Vec<ApiKeyScope>>,
+}
+
+#[derive(Debug, Serialize)]
+struct ApiKeyLimit {
+    path: String,
+    rate_limit: i32,
+    rate_period_secs: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub(super) struct ApiKeyLimitRequest {
+    /// The rate limit key the quota applies to, e.g. `sql`
+    path: String,
+    rate_limit: i32,
+    rate_period_secs: f64,
+}
+
+#[derive(Debug, Deserialize)]
+pub(super) struct ApiKeyLimitQuery {
+    path: String,
+}
+
+fn scope_names(scopes: &[ApiKeyScope]) -> Vec<String> {
+    scopes.iter().map(ToString::to_string).collect()
+}
+
+fn map_constraint_error(e: sqlx::Error) -> APIError {
+    match e {
+        sqlx::Error::Database(e) if e.is_unique_violation() => APIError::status_msg(
+            StatusCode::CONFLICT,
+            "An API key with this comment already exists",
+        ),
+        sqlx::Error::Database(e) if e.is_foreign_key_violation() => {
+            APIError::status_msg(StatusCode::NOT_FOUND, "API key not found")
+        }
+        e => e.into(),
+    }
+}
+
+fn api_key_not_found(key: Uuid) -> APIError {
+    APIError::status_msg(StatusCode::NOT_FOUND, format!("API key {key} not found"))
+}
+
+/// GET /v1/admin/api-keys
+pub(super) async fn list_api_keys(
+    State(AppState { pg_client, .. }): State<AppState>,
+) -> APIResult<impl IntoResponse> {
+    let keys = sqlx::query_as!(
+        ApiKey,
+        r#"
+        SELECT key, created_at, comment, data_access, disabled, patron_id, scopes
+        FROM api_keys
+        ORDER BY created_at DESC NULLS LAST
+        "#
+    )
+    .fetch_all(&pg_client)
+    .await?;
+    Ok(Json(keys))
+}
+
+/// GET /v1/admin/api-keys/{key}
+pub(super) async fn get_api_key(
+    State(AppState { pg_client, .. }): State<AppState>,
+    Path(key): Path<Uuid>,
+) -> APIResult<impl IntoResponse> {
+    let api_key = sqlx::query_as!(
+        ApiKey,
+        r#"
+        SELECT key, created_at, comment, data_access, disabled, patron_id, scopes
+        FROM api_keys
+        WHERE key = $1
+        "#,
+        key
+    )
+    .fetch_optional(&pg_client)
+    .await?
+    .ok_or_else(|| api_key_not_found(key))?;
+    Ok(Json(api_key))
+}
+
+/// POST /v1/admin/api-keys
+///
+/// Creates a new API key. The key itself is generated by the database.
+pub(super) async fn create_api_key(
+    State(AppState { pg_client, .. }): State<AppState>,
+    audit_log: AuditLog,
+    Json(request): Json<CreateApiKeyRequest>,
+) -> APIResult<impl IntoResponse> {
+    let scopes = scope_names(&request.scopes);
+    let mut transaction = pg_client.begin().await?;
+    let api_key = sqlx::query_as!(
+        ApiKey,
+        r#"
+        INSERT INTO api_keys (comment, data_access, scopes)
+        VALUES ($1, $2, $3)
+        RETURNING key, created_at, comment, data_access, disabled, patron_id, scopes
+        "#,
+        request.comment,
+        request.data_access,
+        &scopes,
+    )
+    .fetch_one(&mut *transaction)
+    .await
+    .map_err(map_constraint_error)?;
+    audit_log
+        .record(
+            &mut transaction,
+            "create_api_key",
+            api_key.key,
+            json!(request),
+        )
+        .await?;
+    transaction.commit().await?;
+    Ok((StatusCode::CREATED, Json(api_key)))
+}
+
+/// PATCH /v1/admin/api-keys/{key}
+///
+/// Updates the given fields of an API key, e.g. `{"disabled": true}` to disable it.
+pub(super) async fn update_api_key(
+    State(AppState {
+        pg_client,
+        rate_limit_client,
+        ..
+    }): State<AppState>,
+    audit_log: AuditLog,
+    Path(key): Path<Uuid>,
+    Json(request): Json<UpdateApiKeyRequest>,
+) -> APIResult<impl IntoResponse> {
+    let scopes = request.scopes.as_deref().map(scope_names);
+    let mut transaction = pg_client.begin().await?;
+    let api_key = sqlx::query_as!(
+        ApiKey,
+        r#"
+        UPDATE api_keys SET
+            comment = COALESCE($2, comment),
+            data_access = COALESCE($3, data_access),
+            disabled = COALESCE($4, disabled),
+            scopes = COALESCE($5, scopes)
+        WHERE key = $1
+        RETURNING key, created_at, comment, data_access, disabled, patron_id, scopes
+        "#,
+        key,
+        request.comment,
+        request.data_access,
+        request.disabled,
+        scopes.as_deref(),
+    )
+    .fetch_optional(&mut *transaction)
+    .await
+    .map_err(map_constraint_error)?
+    .ok_or_else(|| api_key_not_found(key))?;
+    audit_log
+        .record(&mut transaction, "update_api_key", key, json!(request))
+        .await?;
+    transaction.commit().await?;
+    rate_limit_client.refresh_api_key(key).await?;
+    Ok(Json(api_key))
+}
+
+/// DELETE /v1/admin/api-keys/{key}
+///
+/// Deletes an API key together with its custom quotas.
+pub(super) async fn delete_api_key(
+    State(AppState {
+        pg_client,
+        rate_limit_client,
+        ..
+    }): State<AppState>,
+    audit_log: AuditLog,
+    Path(key): Path<Uuid>,
+) -> APIResult<impl IntoResponse> {
+    let mut transaction = pg_client.begin().await?;
+    sqlx::query!("DELETE FROM api_key_limits WHERE key = $1", key)
+        .execute(&mut *transaction)
+        .await?;
+    let result = sqlx::query!("DELETE FROM api_keys WHERE key = $1", key)
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::Database(e) if e.is_foreign_key_violation() => APIError::status_msg(
+                StatusCode::CONFLICT,
+                "API key is still referenced, disable it instead",
+            ),
+            e => e.into(),
+        })?;
+    if result.rows_affected() == 0 {
+        return Err(api_key_not_found(key));
+    }
+    audit_log
+        .record(
+            &mut transaction,
+            "delete_api_key",
+            key,
+            serde_json::Value::Null,
+        )
+        .await?;
+    transaction.commit().await?;
+    rate_limit_client.refresh_api_key(key).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// GET /v1/admin/api-keys/{key}/limits
+///
+/// Lists the custom quotas of an API key, which replace the default quotas of a rate limit key.
+pub(super) async fn list_api_key_limits(
+    State(AppState { pg_client, .. }): State<AppState>,
+    Path(key): Path<Uuid>,
+) -> APIResult<impl IntoResponse> {
+    let limits = sqlx::query_as!(
+        ApiKeyLimit,
+        r#"
+        SELECT path, rate_limit, EXTRACT(EPOCH FROM rate_period)::float8 AS "rate_period_secs!"
+        FROM api_key_limits
+        WHERE key = $1
+        ORDER BY path
+        "#,
+        key
+    )
+    .fetch_all(&pg_client)
+    .await?;
+    Ok(Json(limits))
+}
+
+/// PUT /v1/admin/api-keys/{key}/limits
+///
+/// Creates or replaces the custom quota of an API key for a rate limit key.
+pub(super) async fn upsert_api_key_limit(
+    State(AppState {
+        pg_client,
+        rate_limit_client,
+        ..
+    }): State<AppState>,
+    audit_log: AuditLog,
+    Path(key): Path<Uuid>,
+    Json(request): Json<ApiKeyLimitRequest>,
+) -> APIResult<impl IntoResponse> {
+    if request.rate_limit <= 0
+        || !request.rate_period_secs.is_finite()
+        || request.rate_period_secs <= 0.0
+    {
+        return Err(APIError::status_msg(
+            StatusCode::BAD_REQUEST,
+            "rate_limit and rate_period_secs must be positive",
+        ));
+    }
+    let mut transaction = pg_client.begin().await?;
+    sqlx::query!(
+        r#"
+        INSERT INTO api_key_limits (key, path, rate_limit, rate_period)
+        VALUES ($1, $2, $3, make_interval(secs => $4))
+        ON CONFLICT (key, path) DO UPDATE SET
+            rate_limit = EXCLUDED.rate_limit,
+            rate_period = EXCLUDED.rate_period
+        "#,
+        key,
+        request.path,
+        request.rate_limit,
+        request.rate_period_secs,
+    )
+    .execute(&mut *transaction)
+    .await
+    .map_err(map_constraint_error)?;
+    audit_log
+        .record(
+            &mut transaction,
+            "upsert_api_key_limit",
+            key,
+            json!(request),
+        )
+        .await?;
+    transaction.commit().await?;
+    rate_limit_client
+        .refresh_custom_quotas(key, &request.path)
+        .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// DELETE /v1/admin/api-keys/{key}/limits?path=...
+pub(super) async fn delete_api_key_limit(
+    State(AppState {
+        pg_client,
+        rate_limit_client,
+        ..
+    }): State<AppState>,
+    audit_log: AuditLog,
+    Path(key): Path<Uuid>,
+    Query(ApiKeyLimitQuery { path }): Query<ApiKeyLimitQuery>,
+) -> APIResult<impl IntoResponse> {
+    let mut transaction = pg_client.begin().await?;
+    let result = sqlx::query!(
+        "DELETE FROM api_key_limits WHERE key = $1 AND path = $2",
+        key,
+        path
+    )
+    .execute(&mut *transaction)
+    .await?;
+    if result.rows_affected() == 0 {
+        return Err(APIError::status_msg(
+            StatusCode::NOT_FOUND,
+            format!("No custom quota for {path}"),
+        ));
+    }
+    audit_log
+        .record(
+            &mut transaction,
+            "delete_api_key_limit",
+            key,
+            json!({ "path": path }),
+        )
+        .await?;
+    transaction.commit().await?;
+    rate_limit_client.refresh_custom_quotas(key, &path).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scope_names() {
+        assert_eq!(
+            scope_names(&[ApiKeyScope::Sql, ApiKeyScope::CustomMatches]),
+            vec!["sql".to_owned(), "custom_matches".to_owned()]
+        );
+    }
+
+    #[test]
+    fn test_update_request_scopes() {
+        let request: UpdateApiKeyRequest =
+            serde_json::from_str(r#"{"disabled": true, "scopes": ["custom_matches"]}"#).unwrap();
+        assert_eq!(request.disabled, Some(true));
+        assert_eq!(request.scopes, Some(vec![ApiKeyScope::CustomMatches]));
+        assert!(serde_json::from_str::<UpdateApiKeyRequest>(r#"{"scopes": ["admin"]}"#).is_err());
+    }
+}
@@ -0,0 +1,121 @@
+use core::convert::Infallible;
+use core::fmt::Display;
+use core::net::IpAddr;
+
+use axum::Json;
+use axum::extract::{FromRequestParts, Query, State};
+use axum::http::request::Parts;
+use axum::response::IntoResponse;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::PgConnection;
+use tracing::info;
+
+use crate::context::AppState;
+use crate::error::APIResult;
+use crate::middleware::client_ip::ClientIp;
+
+/// Records admin actions in the `admin_audit_log` table.
+pub(super) struct AuditLog {
+    client_ip: Option<IpAddr>,
+}
+
+impl FromRequestParts<AppState> for AuditLog {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        _state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        Ok(Self {
+            client_ip: parts.extensions.get::<ClientIp>().map(|ClientIp(ip)| *ip),
+        })
+    }
+}
+
+impl AuditLog {
+    /// Records an action, in the transaction of its change so no action is left unrecorded.
+    pub(super) async fn record(
+        &self,
+        conn: &mut PgConnection,
+        action: &str,
+        target: impl Display,
+        details: Value,
+    ) -> sqlx::Result<()> {
+        let target = target.to_string();
+        info!("Admin action {action} on {target}: {details}");
+        let details = (!details.is_null()).then(|| details.to_string());
+        sqlx::query!(
+            r#"
+            INSERT INTO admin_audit_log (action, target, details, client_ip)
+            VALUES ($1, $2, $3::text::jsonb, $4)
+            "#,
+            action,
+            target,
+            details,
+            self.client_ip.map(|ip| ip.to_string()),
+        )
+        .execute(conn)
+        .await?;
+        Ok(())
+    }
+}
+
+fn default_limit() -> i64 {
+    100
+}
+
+#[derive(Debug, Deserialize)]
+pub(super) struct AuditLogQuery {
+    /// Only return entries of this action
+    action: Option<String>,
+    /// Only return entries older than this ID, for pagination
+    before_id: Option<i64>,
+    #[serde(default = "default_limit")]
+    limit: i64,
+}
+
+#[derive(Debug, Serialize)]
+struct AuditLogEntry {
+    id: i64,
+    created_at: DateTime<Utc>,
+    action: String,
+    target: String,
+    details: Option<Value>,
+    client_ip: Option<String>,
+}
+
+/// GET /v1/admin/audit-log
+///
+/// Lists the most recent admin actions, newest first.
+pub(super) async fn list_audit_log(
+    State(AppState { pg_client, .. }): State<AppState>,
+    Query(query): Query<AuditLogQuery>,
+) -> APIResult<impl IntoResponse> {
+    let entries = sqlx::query!(
+        r#"
+        SELECT id, created_at, action, target, details::text, client_ip
+        FROM admin_audit_log
+        WHERE ($1::text IS NULL OR action = $1) AND ($2::bigint IS NULL OR id < $2)
+        ORDER BY id DESC
+        LIMIT $3
+        "#,
+        query.action,
+        query.before_id,
+        query.limit.clamp(1, 1000),
+    )
+    .fetch_all(&pg_client)
+    .await?
+    .into_iter()
+    .map(|row| AuditLogEntry {
+        id: row.id,
+        created_at: row.created_at,
+        action: row.action,
+        target: row.target,
+        details: row.details.and_then(|d| serde_json::from_str(&d).ok()),
+        client_ip: row.client_ip,
+    })
+    .collect::<Vec<_>>();
+    Ok(Json(entries))
+}
@@ -0,0 +1,176 @@
+use axum::Json;
+use axum::extract::{Query, State};
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use uuid::Uuid;
+
+use super::audit_log::AuditLog;
+use crate::context::AppState;
+use crate::error::{APIError, APIResult};
+use crate::services::feature_flags::{FeatureFlags, RouteFlag};
+
+#[derive(Debug, Deserialize)]
+pub(super) struct FeatureFlagQuery {
+    /// The matched route path, e.g. `/v1/sql`
+    path: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub(super) struct FeatureFlagOverrideQuery {
+    path: String,
+    api_key: Uuid,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub(super) struct FeatureFlagOverrideRequest {
+    path: String,
+    api_key: Uuid,
+    enabled: bool,
+}
+
+/// GET /v1/admin/feature-flags
+///
+/// Lists all route feature flags including their API key overrides.
+pub(super) async fn list_feature_flags(
+    State(AppState { feature_flags, .. }): State<AppState>,
+) -> APIResult<impl IntoResponse> {
+    Ok(Json(feature_flags.fetch_all().await?))
+}
+
+/// PUT /v1/admin/feature-flags
+///
+/// Creates or updates the flag for a route. Existing overrides are kept.
+pub(super) async fn upsert_feature_flag(
+    State(AppState {
+        pg_client,
+        feature_flags,
+        ..
+    }): State<AppState>,
+    audit_log: AuditLog,
+    Json(flag): Json<RouteFlag>,
+) -> APIResult<impl IntoResponse> {
+    if !flag.path.starts_with('/') {
+        return Err(APIError::status_msg(
+            StatusCode::BAD_REQUEST,
+            "path must be a route path starting with `/`",
+        ));
+    }
+    if flag.rollout_percent > 100 {
+        return Err(APIError::status_msg(
+            StatusCode::BAD_REQUEST,
+            "rollout_percent must be between 0 and 100",
+        ));
+    }
+    let mut transaction = pg_client.begin().await?;
+    FeatureFlags::upsert(&mut transaction, &flag).await?;
+    audit_log
+        .record(
+            &mut transaction,
+            "upsert_feature_flag",
+            &flag.path,
+            json!(flag),
+        )
+        .await?;
+    transaction.commit().await?;
+    feature_flags.reload().await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// DELETE /v1/admin/feature-flags?path=...
+///
+/// Deletes the flag for a route, which re-enables it for everyone.
+pub(super) async fn delete_feature_flag(
+    State(AppState {
+        pg_client,
+        feature_flags,
+        ..
+    }): State<AppState>,
+    audit_log: AuditLog,
+    Query(FeatureFlagQuery { path }): Query<FeatureFlagQuery>,
+) -> APIResult<impl IntoResponse> {
+    let mut transaction = pg_client.begin().await?;
+    if !FeatureFlags::delete(&mut transaction, &path).await? {
+        return Err(APIError::status_msg(
+            StatusCode::NOT_FOUND,
+            format!("No feature flag for route {path}"),
+        ));
+    }
+    audit_log
+        .record(&mut transaction, "delete_feature_flag", &path, Value::Null)
+        .await?;
+    transaction.commit().await?;
+    feature_flags.reload().await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// PUT /v1/admin/feature-flags/overrides
+///
+/// Forces a route on or off for a single API key, ignoring the rollout percentage.
+pub(super) async fn set_feature_flag_override(
+    State(AppState {
+        pg_client,
+        feature_flags,
+        ..
+    }): State<AppState>,
+    audit_log: AuditLog,
+    Json(request): Json<FeatureFlagOverrideRequest>,
+) -> APIResult<impl IntoResponse> {
+    let mut transaction = pg_client.begin().await?;
+    FeatureFlags::set_override(
+        &mut transaction,
+        &request.path,
+        request.api_key,
+        request.enabled,
+    )
+    .await
+    .map_err(|e| match e {
+        sqlx::Error::Database(e) if e.is_foreign_key_violation() => APIError::status_msg(
+            StatusCode::NOT_FOUND,
+            "Feature flag or API key does not exist",
+        ),
+        e => e.into(),
+    })?;
+    audit_log
+        .record(
+            &mut transaction,
+            "set_feature_flag_override",
+            &request.path,
+            json!(request),
+        )
+        .await?;
+    transaction.commit().await?;
+    feature_flags.reload().await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// DELETE /v1/admin/feature-flags/overrides?path=...&api_key=...
+pub(super) async fn delete_feature_flag_override(
+    State(AppState {
+        pg_client,
+        feature_flags,
+        ..
+    }): State<AppState>,
+    audit_log: AuditLog,
+    Query(FeatureFlagOverrideQuery { path, api_key }): Query<FeatureFlagOverrideQuery>,
+) -> APIResult<impl IntoResponse> {
+    let mut transaction = pg_client.begin().await?;
+    if !FeatureFlags::delete_override(&mut transaction, &path, api_key).await? {
+        return Err(APIError::status_msg(
+            StatusCode::NOT_FOUND,
+            format!("No override for route {path} and this API key"),
+        ));
+    }
+    audit_log
+        .record(
+            &mut transaction,
+            "delete_feature_flag_override",
+            &path,
+            json!({ "api_key": api_key }),
+        )
+        .await?;
+    transaction.commit().await?;
+    feature_flags.reload().await?;
+    Ok(StatusCode::NO_CONTENT)
+}
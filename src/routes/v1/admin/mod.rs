@@ -0,0 +1,80 @@
+use core::time::Duration;
+
+use axum::routing::{get, post, put};
+use utoipa_axum::router::OpenApiRouter;
+
+use crate::context::AppState;
+use crate::middleware::cache::CacheControlMiddleware;
+
+mod api_keys;
+mod audit_log;
+mod feature_flags;
+mod patreon;
+mod protected_users;
+mod request_logs;
+
+/// Internal administration routes, only accessible with the internal API key.
+///
+/// All changes are recorded in the `admin_audit_log` table.
+pub(crate) fn router() -> OpenApiRouter<AppState> {
+    OpenApiRouter::new()
+        .route(
+            "/api-keys",
+            get(api_keys::list_api_keys).post(api_keys::create_api_key),
+        )
+        .route(
+            "/api-keys/{key}",
+            get(api_keys::get_api_key)
+                .patch(api_keys::update_api_key)
+                .delete(api_keys::delete_api_key),
+        )
+        .route(
+            "/api-keys/{key}/limits",
+            get(api_keys::list_api_key_limits)
+                .put(api_keys::upsert_api_key_limit)
+                .delete(api_keys::delete_api_key_limit),
+        )
+        .route(
+            "/protected-users",
+            get(protected_users::list_protected_users),
+        )
+        .route(
+            "/protected-users/{account_id}",
+            put(protected_users::protect_user).delete(protected_users::unprotect_user),
+        )
+        .route(
+            "/feature-flags",
+            put(feature_flags::upsert_feature_flag)
+                .get(feature_flags::list_feature_flags)
+                .delete(feature_flags::delete_feature_flag),
+        )
+        .route(
+            "/feature-flags/overrides",
+            put(feature_flags::set_feature_flag_override)
+                .delete(feature_flags::delete_feature_flag_override),
+        )
+        .route("/patreon/verification", post(patreon::trigger_verification))
+        .route("/patreon/retry-queue", get(patreon::list_retry_queue))
+        .route("/patreon/webhook-events", get(patreon::list_webhook_events))
+        .route(
+            "/patreon/webhook-events/replay",
+            post(patreon::replay_webhook_events),
+        )
+        .route(
+            "/patreon/patrons/{patron_id}/history",
+            get(patreon::get_patron_history),
+        )
+        .route("/request-logs/routes", get(request_logs::route_popularity))
+        .route("/request-logs/latency", get(request_logs::route_latency))
+        .route(
+            "/request-logs/status-codes",
+            get(request_logs::status_codes),
+        )
+        .route(
+            "/request-logs/user-agents",
+            get(request_logs::top_user_agents),
+        )
+        .route("/request-logs/api-keys", get(request_logs::top_api_keys))
+        .route("/audit-log", get(audit_log::list_audit_log))
+        .layer(CacheControlMiddleware::new(Duration::from_secs(0)).private())
+}
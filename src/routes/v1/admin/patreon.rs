@@ -0,0 +1,157 @@
+use axum::Json;
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use serde_json::{Value, json};
+use uuid::Uuid;
+
+use super::audit_log::AuditLog;
+use crate::context::AppState;
+use crate::error::{APIError, APIResult};
+use crate::routes::v1::patron::history::{HistoryQuery, fetch_history};
+use crate::services::membership::webhook_worker;
+
+/// POST /v1/admin/patreon/verification
+///
+/// Starts a verification run of all patrons in the background.
+pub(super) async fn trigger_verification(
+    State(AppState {
+        pg_client,
+        membership_verification_job,
+        ..
+    }): State<AppState>,
+    audit_log: AuditLog,
+) -> APIResult<impl IntoResponse> {
+    if membership_verification_job.is_running() {
+        return Err(APIError::status_msg(
+            StatusCode::CONFLICT,
+            "Membership verification is already running",
+        ));
+    }
+    // Recorded before starting, the run can't be rolled back
+    audit_log
+        .record(
+            &mut *pg_client.acquire().await?,
+            "trigger_patreon_verification",
+            "patrons",
+            Value::Null,
+        )
+        .await?;
+    tokio::spawn(async move { membership_verification_job.run_verification().await });
+    Ok(StatusCode::ACCEPTED)
+}
+
+/// GET /v1/admin/patreon/retry-queue
+///
+/// Lists the patrons whose membership sync failed due to provider API errors and will be retried.
+pub(super) async fn list_retry_queue(
+    State(AppState {
+        membership_verification_job,
+        ..
+    }): State<AppState>,
+) -> impl IntoResponse {
+    Json(membership_verification_job.queued_patrons().await)
+}
+
+/// GET /v1/admin/patreon/patrons/{patron_id}/history
+///
+/// Returns the history of a patron, the same view the patron gets from `/v1/patron/history`.
+pub(super) async fn get_patron_history(
+    State(app_state): State<AppState>,
+    Path(patron_id): Path<Uuid>,
+    Query(query): Query<HistoryQuery>,
+) -> APIResult<impl IntoResponse> {
+    let events = fetch_history(&app_state, patron_id, &query).await?;
+    Ok(Json(events))
+}
+
+fn default_limit() -> i64 {
+    100
+}
+
+#[derive(Debug, Deserialize)]
+pub(super) struct WebhookEventsQuery {
+    /// Only return events of this membership provider
+    provider: Option<String>,
+    /// Only return events of this user on the provider
+    provider_user_id: Option<String>,
+    /// Only return events with this status
+    status: Option<String>,
+    /// Only return events older than this ID, for pagination
+    before_id: Option<i64>,
+    #[serde(default = "default_limit")]
+    limit: i64,
+}
+
+/// GET /v1/admin/patreon/webhook-events
+///
+/// Lists the stored membership provider webhook events, newest first.
+pub(super) async fn list_webhook_events(
+    State(AppState { pg_client, .. }): State<AppState>,
+    Query(query): Query<WebhookEventsQuery>,
+) -> APIResult<impl IntoResponse> {
+    let events = webhook_worker::list_webhook_events(
+        &pg_client,
+        query.provider.as_deref(),
+        query.provider_user_id.as_deref(),
+        query.status.as_deref(),
+        query.before_id,
+        query.limit.clamp(1, 1000),
+    )
+    .await?;
+    Ok(Json(events))
+}
+
+#[derive(Debug, Deserialize)]
+pub(super) struct ReplayWebhookEventsRequest {
+    /// Replay the events from this ID on
+    from_id: Option<i64>,
+    /// Replay the events received from this time on
+    since: Option<DateTime<Utc>>,
+    /// Only replay the events of this membership provider
+    provider: Option<String>,
+    /// Only replay the events of this user on the provider
+    provider_user_id: Option<String>,
+}
+
+/// POST /v1/admin/patreon/webhook-events/replay
+///
+/// Queues the stored webhook events from an ID or time on for processing again.
+pub(super) async fn replay_webhook_events(
+    State(AppState { pg_client, .. }): State<AppState>,
+    audit_log: AuditLog,
+    Json(request): Json<ReplayWebhookEventsRequest>,
+) -> APIResult<impl IntoResponse> {
+    if request.from_id.is_none() && request.since.is_none() {
+        return Err(APIError::status_msg(
+            StatusCode::BAD_REQUEST,
+            "Either from_id or since is required",
+        ));
+    }
+    let mut transaction = pg_client.begin().await?;
+    let replayed = webhook_worker::replay_webhook_events(
+        &mut transaction,
+        request.from_id,
+        request.since,
+        request.provider.as_deref(),
+        request.provider_user_id.as_deref(),
+    )
+    .await?;
+    audit_log
+        .record(
+            &mut transaction,
+            "replay_membership_webhook_events",
+            request.provider_user_id.as_deref().unwrap_or("patrons"),
+            json!({
+                "provider": request.provider,
+                "from_id": request.from_id,
+                "since": request.since,
+                "replayed": replayed,
+            }),
+        )
+        .await?;
+    transaction.commit().await?;
+    Ok(Json(json!({ "replayed": replayed })))
+}
@@ -0,0 +1,76 @@
+use axum::Json;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use chrono::NaiveDateTime;
+use serde::Serialize;
+use serde_json::Value;
+
+use super::audit_log::AuditLog;
+use crate::context::AppState;
+use crate::error::APIResult;
+use crate::routes::v1::data_privacy::{protect_account, unprotect_account, update_row_policy};
+use crate::utils::types::AccountIdQuery;
+
+#[derive(Debug, Serialize)]
+struct ProtectedUser {
+    steam_id: i32,
+    created_at: Option<NaiveDateTime>,
+}
+
+/// GET /v1/admin/protected-users
+///
+/// Lists the accounts that requested their data to be hidden.
+pub(super) async fn list_protected_users(
+    State(AppState { pg_client, .. }): State<AppState>,
+) -> APIResult<impl IntoResponse> {
+    let users = sqlx::query_as!(
+        ProtectedUser,
+        "SELECT steam_id, created_at FROM protected_user_accounts ORDER BY created_at DESC"
+    )
+    .fetch_all(&pg_client)
+    .await?;
+    Ok(Json(users))
+}
+
+/// PUT /v1/admin/protected-users/{account_id}
+///
+/// Protects an account without the `OpenID` verification of the data privacy endpoints.
+pub(super) async fn protect_user(
+    State(AppState {
+        pg_client,
+        ch_client,
+        ..
+    }): State<AppState>,
+    audit_log: AuditLog,
+    Path(AccountIdQuery { account_id }): Path<AccountIdQuery>,
+) -> APIResult<impl IntoResponse> {
+    let mut transaction = pg_client.begin().await?;
+    protect_account(&mut transaction, account_id).await?;
+    audit_log
+        .record(&mut transaction, "protect_user", account_id, Value::Null)
+        .await?;
+    transaction.commit().await?;
+    update_row_policy(&pg_client, &ch_client).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// DELETE /v1/admin/protected-users/{account_id}
+pub(super) async fn unprotect_user(
+    State(AppState {
+        pg_client,
+        ch_client,
+        ..
+    }): State<AppState>,
+    audit_log: AuditLog,
+    Path(AccountIdQuery { account_id }): Path<AccountIdQuery>,
+) -> APIResult<impl IntoResponse> {
+    let mut transaction = pg_client.begin().await?;
+    unprotect_account(&mut transaction, account_id).await?;
+    audit_log
+        .record(&mut transaction, "unprotect_user", account_id, Value::Null)
+        .await?;
+    transaction.commit().await?;
+    update_row_policy(&pg_client, &ch_client).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
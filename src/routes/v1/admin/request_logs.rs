@@ -0,0 +1,372 @@
+use axum::Json;
+use axum::extract::{Query, State};
+use axum::response::IntoResponse;
+use clickhouse::Row;
+use clickhouse::query::Query as ClickhouseQuery;
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+use uuid::Uuid;
+
+use crate::context::AppState;
+use crate::error::APIResult;
+
+fn default_last_day_timestamp() -> Option<i64> {
+    Some((chrono::Utc::now() - chrono::Duration::days(1)).timestamp())
+}
+
+fn default_limit() -> u32 {
+    100
+}
+
+fn default_min_status_code() -> u16 {
+    400
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub(super) struct RequestLogsQuery {
+    /// Filter requests based on their time (Unix timestamp). **Default:** 24 hours ago.
+    #[serde(default = "default_last_day_timestamp")]
+    min_unix_timestamp: Option<i64>,
+    /// Filter requests based on their time (Unix timestamp).
+    max_unix_timestamp: Option<i64>,
+    /// Filter requests based on their matched path, e.g. `/v1/matches/{match_id}/metadata`.
+    path: Option<String>,
+    /// Only include responses with at least this status code, used by the status code
+    /// breakdown. **Default:** 400.
+    #[serde(default = "default_min_status_code")]
+    min_status_code: u16,
+    /// The maximum number of rows to return (up to 1000). **Default:** 100.
+    #[serde(default = "default_limit")]
+    limit: u32,
+}
+
+impl Default for RequestLogsQuery {
+    fn default() -> Self {
+        Self {
+            min_unix_timestamp: default_last_day_timestamp(),
+            max_unix_timestamp: None,
+            path: None,
+            min_status_code: default_min_status_code(),
+            limit: default_limit(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Row, Serialize, Deserialize)]
+struct RoutePopularity {
+    path: String,
+    requests: u64,
+    unique_clients: u64,
+    server_errors: u64,
+    total_response_size: u64,
+}
+
+#[derive(Debug, Clone, Row, Serialize, Deserialize)]
+struct RouteLatency {
+    path: String,
+    requests: u64,
+    avg_ms: f64,
+    p50_ms: f64,
+    p90_ms: f64,
+    p95_ms: f64,
+    p99_ms: f64,
+    max_ms: u64,
+}
+
+#[derive(Debug, Clone, Row, Serialize, Deserialize)]
+struct StatusCodeBreakdown {
+    path: String,
+    status_code: u16,
+    requests: u64,
+}
+
+#[derive(Debug, Clone, Row, Serialize, Deserialize)]
+struct UserAgentUsage {
+    user_agent: String,
+    requests: u64,
+    unique_clients: u64,
+}
+
+#[derive(Debug, Clone, Row, Serialize, Deserialize)]
+struct ApiKeyUsage {
+    #[serde(with = "clickhouse::serde::uuid")]
+    api_key: Uuid,
+    requests: u64,
+    errors: u64,
+    last_seen_unix_timestamp: u32,
+}
+
+fn build_filters(query: &RequestLogsQuery, extra_filters: &[&str]) -> String {
+    let mut filters = vec![];
+    if let Some(min_unix_timestamp) = query.min_unix_timestamp {
+        filters.push(format!(
+            "timestamp >= toDateTime64({min_unix_timestamp}, 3)"
+        ));
+    }
+    if let Some(max_unix_timestamp) = query.max_unix_timestamp {
+        filters.push(format!(
+            "timestamp <= toDateTime64({max_unix_timestamp}, 3)"
+        ));
+    }
+    if query.path.is_some() {
+        filters.push("path = ?".to_owned());
+    }
+    filters.extend(extra_filters.iter().map(|&f| f.to_owned()));
+    if filters.is_empty() {
+        String::new()
+    } else {
+        format!("WHERE {}", filters.join(" AND "))
+    }
+}
+
+fn build_route_popularity_query(query: &RequestLogsQuery) -> String {
+    let filters = build_filters(query, &[]);
+    let limit = query.limit.clamp(1, 1000);
+    format!(
+        "
+    SELECT
+        path,
+        count() AS requests,
+        uniq(client_ip) AS unique_clients,
+        countIf(status_code >= 500) AS server_errors,
+        sum(response_size) AS total_response_size
+    FROM request_logs
+    {filters}
+    GROUP BY path
+    ORDER BY requests DESC
+    LIMIT {limit}
+    "
+    )
+}
+
+fn build_route_latency_query(query: &RequestLogsQuery) -> String {
+    let filters = build_filters(query, &[]);
+    let limit = query.limit.clamp(1, 1000);
+    format!(
+        "
+    SELECT
+        path,
+        count() AS requests,
+        avg(duration_ms) AS avg_ms,
+        quantile(0.5)(duration_ms) AS p50_ms,
+        quantile(0.9)(duration_ms) AS p90_ms,
+        quantile(0.95)(duration_ms) AS p95_ms,
+        quantile(0.99)(duration_ms) AS p99_ms,
+        max(duration_ms) AS max_ms
+    FROM request_logs
+    {filters}
+    GROUP BY path
+    ORDER BY p95_ms DESC
+    LIMIT {limit}
+    "
+    )
+}
+
+fn build_status_codes_query(query: &RequestLogsQuery) -> String {
+    let filters = build_filters(
+        query,
+        &[&format!("status_code >= {}", query.min_status_code)],
+    );
+    let limit = query.limit.clamp(1, 1000);
+    format!(
+        "
+    SELECT path, status_code, count() AS requests
+    FROM request_logs
+    {filters}
+    GROUP BY path, status_code
+    ORDER BY requests DESC
+    LIMIT {limit}
+    "
+    )
+}
+
+fn build_user_agents_query(query: &RequestLogsQuery) -> String {
+    let filters = build_filters(query, &[]);
+    let limit = query.limit.clamp(1, 1000);
+    format!(
+        "
+    SELECT
+        coalesce(user_agent, '') AS user_agent,
+        count() AS requests,
+        uniq(client_ip) AS unique_clients
+    FROM request_logs
+    {filters}
+    GROUP BY user_agent
+    ORDER BY requests DESC
+    LIMIT {limit}
+    "
+    )
+}
+
+fn build_api_keys_query(query: &RequestLogsQuery) -> String {
+    let filters = build_filters(query, &["api_key IS NOT NULL"]);
+    let limit = query.limit.clamp(1, 1000);
+    format!(
+        "
+    SELECT
+        assumeNotNull(api_key) AS api_key,
+        count() AS requests,
+        countIf(status_code >= 400) AS errors,
+        toUnixTimestamp(max(timestamp)) AS last_seen_unix_timestamp
+    FROM request_logs
+    {filters}
+    GROUP BY api_key
+    ORDER BY requests DESC
+    LIMIT {limit}
+    "
+    )
+}
+
+/// Creates the query and binds the path filter, if any.
+fn prepare(ch_client: &clickhouse::Client, sql: &str, query: &RequestLogsQuery) -> ClickhouseQuery {
+    debug!(?sql);
+    let ch_query = ch_client.query(sql);
+    match &query.path {
+        Some(path) => ch_query.bind(path),
+        None => ch_query,
+    }
+}
+
+/// GET /v1/admin/request-logs/routes
+///
+/// Returns the most requested routes.
+pub(super) async fn route_popularity(
+    State(state): State<AppState>,
+    Query(query): Query<RequestLogsQuery>,
+) -> APIResult<impl IntoResponse> {
+    let sql = build_route_popularity_query(&query);
+    let rows: Vec<RoutePopularity> = prepare(&state.ch_client_ro, &sql, &query)
+        .fetch_all()
+        .await?;
+    Ok(Json(rows))
+}
+
+/// GET /v1/admin/request-logs/latency
+///
+/// Returns latency percentiles per route, slowest routes first.
+pub(super) async fn route_latency(
+    State(state): State<AppState>,
+    Query(query): Query<RequestLogsQuery>,
+) -> APIResult<impl IntoResponse> {
+    let sql = build_route_latency_query(&query);
+    let rows: Vec<RouteLatency> = prepare(&state.ch_client_ro, &sql, &query)
+        .fetch_all()
+        .await?;
+    Ok(Json(rows))
+}
+
+/// GET /v1/admin/request-logs/status-codes
+///
+/// Returns the number of responses per route and status code, by default only errors.
+pub(super) async fn status_codes(
+    State(state): State<AppState>,
+    Query(query): Query<RequestLogsQuery>,
+) -> APIResult<impl IntoResponse> {
+    let sql = build_status_codes_query(&query);
+    let rows: Vec<StatusCodeBreakdown> = prepare(&state.ch_client_ro, &sql, &query)
+        .fetch_all()
+        .await?;
+    Ok(Json(rows))
+}
+
+/// GET /v1/admin/request-logs/user-agents
+///
+/// Returns the user agents with the most requests.
+pub(super) async fn top_user_agents(
+    State(state): State<AppState>,
+    Query(query): Query<RequestLogsQuery>,
+) -> APIResult<impl IntoResponse> {
+    let sql = build_user_agents_query(&query);
+    let rows: Vec<UserAgentUsage> = prepare(&state.ch_client_ro, &sql, &query)
+        .fetch_all()
+        .await?;
+    Ok(Json(rows))
+}
+
+/// GET /v1/admin/request-logs/api-keys
+///
+/// Returns the API keys with the most requests.
+pub(super) async fn top_api_keys(
+    State(state): State<AppState>,
+    Query(query): Query<RequestLogsQuery>,
+) -> APIResult<impl IntoResponse> {
+    let sql = build_api_keys_query(&query);
+    let rows: Vec<ApiKeyUsage> = prepare(&state.ch_client_ro, &sql, &query)
+        .fetch_all()
+        .await?;
+    Ok(Json(rows))
+}
+
+#[cfg(test)]
+mod tests {
+    use tracing::warn;
+
+    use super::*;
+
+    #[test]
+    fn test_build_filters_default() {
+        let filters = build_filters(&RequestLogsQuery::default(), &[]);
+        assert!(filters.starts_with("WHERE timestamp >= toDateTime64("));
+        assert!(!filters.contains("path = ?"));
+    }
+
+    #[test]
+    fn test_build_filters_all() {
+        let query = RequestLogsQuery {
+            min_unix_timestamp: Some(1672531200),
+            max_unix_timestamp: Some(1675209599),
+            path: Some("/v1/sql".to_owned()),
+            ..Default::default()
+        };
+        assert_eq!(
+            build_filters(&query, &["status_code >= 400"]),
+            "WHERE timestamp >= toDateTime64(1672531200, 3) AND timestamp <= \
+             toDateTime64(1675209599, 3) AND path = ? AND status_code >= 400"
+        );
+    }
+
+    #[test]
+    fn test_build_filters_empty() {
+        let query = RequestLogsQuery {
+            min_unix_timestamp: None,
+            ..Default::default()
+        };
+        assert_eq!(build_filters(&query, &[]), "");
+    }
+
+    #[test]
+    fn test_build_query_limit_is_clamped() {
+        let query = RequestLogsQuery {
+            limit: 100_000,
+            ..Default::default()
+        };
+        assert!(build_route_popularity_query(&query).contains("LIMIT 1000"));
+    }
+
+    #[test]
+    fn test_build_status_codes_query() {
+        let query = RequestLogsQuery {
+            min_status_code: 500,
+            ..Default::default()
+        };
+        let sql = build_status_codes_query(&query);
+        if let Err(e) =
+            sqlparser::parser::Parser::parse_sql(&sqlparser::dialect::ClickHouseDialect {}, &sql)
+        {
+            warn!("Failed to parse SQL: {sql}: {e}");
+        }
+        assert!(sql.contains("status_code >= 500"));
+        assert!(sql.contains("GROUP BY path, status_code"));
+    }
+
+    #[test]
+    fn test_build_api_keys_query() {
+        let sql = build_api_keys_query(&RequestLogsQuery::default());
+        if let Err(e) =
+            sqlparser::parser::Parser::parse_sql(&sqlparser::dialect::ClickHouseDialect {}, &sql)
+        {
+            warn!("Failed to parse SQL: {sql}: {e}");
+        }
+        assert!(sql.contains("api_key IS NOT NULL"));
+    }
+}
@@ -6,14 +6,19 @@ use utoipa_axum::router::OpenApiRouter;
 use crate::context::AppState;
 use crate::middleware::cache::CacheControlMiddleware;
 
-mod patreon;
+mod oauth;
+mod steam;
 mod webhook;
 
 pub(super) fn router() -> OpenApiRouter<AppState> {
     OpenApiRouter::new()
-        .route("/patreon", get(patreon::login))
-        .route("/patreon/callback", get(patreon::callback))
-        .route("/patreon/logout", post(patreon::logout))
-        .route("/patreon/webhook", post(webhook::webhook))
+        .route("/steam", get(steam::login))
+        .route("/steam/callback", get(steam::callback))
+        .route("/steam/logout", post(steam::logout))
+        .route("/steam/session", get(steam::get_session))
+        .route("/{provider}", get(oauth::login))
+        .route("/{provider}/callback", get(oauth::callback))
+        .route("/{provider}/logout", post(oauth::logout))
+        .route("/{provider}/webhook", post(webhook::webhook))
         .layer(CacheControlMiddleware::new(Duration::from_secs(0)).private())
 }
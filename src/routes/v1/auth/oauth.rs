@@ -1,55 +1,59 @@
-use axum::extract::{Query, State};
+use std::sync::Arc;
+
+use axum::extract::{Path, Query, State};
 use axum::http::header::{COOKIE, SET_COOKIE};
 use axum::http::{HeaderMap, HeaderValue, StatusCode};
 use axum::response::{IntoResponse, Response};
-use chrono::{Duration, Utc};
 use rand::RngExt;
 use serde::Deserialize;
 
 use crate::context::AppState;
 use crate::error::APIError;
-use crate::services::patreon::client::PatreonClient;
+use crate::services::membership::MembershipProvider;
 use crate::services::patreon::jwt::create_session_token;
 use crate::services::patreon::repository::{PatronRepository, UpsertPatronParams};
 
-/// Patreon OAuth scopes required for this application
-const PATREON_SCOPES: &str = "identity identity[email] campaigns.members";
-
 /// Generate a random 32-byte hex string for OAuth state parameter
-fn generate_state() -> String {
+pub(super) fn generate_state() -> String {
     let random_bytes: [u8; 32] = rand::rng().random();
     hex::encode(random_bytes)
 }
 
-/// Build the Patreon OAuth authorization URL
-fn build_patreon_auth_url(client_id: &str, redirect_uri: &str, state: &str) -> String {
-    let encoded_redirect_uri = urlencoding::encode(redirect_uri);
-    let encoded_scopes = urlencoding::encode(PATREON_SCOPES);
+/// Looks up a configured membership provider by its name in the path
+fn get_provider(app_state: &AppState, name: &str) -> Result<Arc<dyn MembershipProvider>, APIError> {
+    app_state.membership_providers.by_name(name).ok_or_else(|| {
+        APIError::status_msg(
+            StatusCode::NOT_FOUND,
+            format!("Unknown membership provider {name}"),
+        )
+    })
+}
 
-    format!(
-        "https://www.patreon.com/oauth2/authorize?response_type=code&client_id={client_id}&redirect_uri={encoded_redirect_uri}&scope={encoded_scopes}&state={state}"
-    )
+/// Name of the cookie storing the OAuth state of a provider, e.g. `patreon_oauth_state`
+fn state_cookie_name(provider: &dyn MembershipProvider) -> String {
+    format!("{}_oauth_state", provider.kind())
 }
 
-/// GET /v1/auth/patreon
+/// GET /v1/auth/{provider}
 ///
-/// Initiates Patreon OAuth flow by:
+/// Initiates the OAuth flow of a membership provider by:
 /// 1. Generating a random state parameter for CSRF protection
 /// 2. Storing the state in a cookie
-/// 3. Redirecting to Patreon's OAuth authorization URL
-pub(crate) async fn login(State(state): State<AppState>) -> impl IntoResponse {
+/// 3. Redirecting to the provider's OAuth authorization URL
+pub(crate) async fn login(
+    State(state): State<AppState>,
+    Path(provider): Path<String>,
+) -> Result<impl IntoResponse, APIError> {
+    let provider = get_provider(&state, &provider)?;
     let oauth_state = generate_state();
 
-    let auth_url = build_patreon_auth_url(
-        &state.config.patreon.client_id,
-        &state.config.patreon.redirect_uri,
-        &oauth_state,
-    );
+    let auth_url = provider.authorize_url(&oauth_state);
 
     // Create the state cookie with HttpOnly, Secure, SameSite=Lax for CSRF protection
     // Max-Age of 10 minutes should be plenty for the OAuth flow
     let cookie_value = format!(
-        "patreon_oauth_state={oauth_state}; HttpOnly; Secure; SameSite=Lax; Path=/; Max-Age=600"
+        "{}={oauth_state}; HttpOnly; Secure; SameSite=Lax; Path=/; Max-Age=600",
+        state_cookie_name(provider.as_ref())
     );
 
     // oauth_state is hex-encoded (only [0-9a-f]), so this cannot fail
@@ -64,36 +68,37 @@ pub(crate) async fn login(State(state): State<AppState>) -> impl IntoResponse {
 
     response.headers_mut().insert(SET_COOKIE, cookie_header);
 
-    response
+    Ok(response)
 }
 
 /// Query parameters for the OAuth callback
 #[derive(Deserialize)]
 pub(crate) struct CallbackParams {
-    /// Authorization code from Patreon (absent if user cancels the flow)
+    /// Authorization code from the provider (absent if user cancels the flow)
     code: Option<String>,
     /// State parameter for CSRF protection
     state: Option<String>,
 }
 
-/// Extracts the OAuth state from the `patreon_oauth_state` cookie
-fn extract_state_from_cookie(headers: &HeaderMap) -> Option<String> {
+/// Extracts the OAuth state from the given cookie
+fn extract_state_from_cookie(headers: &HeaderMap, cookie_name: &str) -> Option<String> {
     headers
         .get(COOKIE)
         .and_then(|v| v.to_str().ok())
         .and_then(|cookies| {
             cookies.split(';').find_map(|cookie| {
-                let cookie = cookie.trim();
                 cookie
-                    .strip_prefix("patreon_oauth_state=")
+                    .trim()
+                    .strip_prefix(cookie_name)
+                    .and_then(|c| c.strip_prefix('='))
                     .map(str::to_string)
             })
         })
 }
 
-/// POST /v1/auth/patreon/logout
+/// POST /v1/auth/{provider}/logout
 ///
-/// Logs out the patron by clearing the session cookie.
+/// Logs out the patron by clearing the session cookie, the same for every provider.
 /// Returns 200 OK after clearing the cookie.
 pub(crate) async fn logout() -> impl IntoResponse {
     // Clear the patron_session cookie by setting Max-Age=0
@@ -111,9 +116,9 @@ pub(crate) async fn logout() -> impl IntoResponse {
     response
 }
 
-/// GET /v1/auth/patreon/callback
+/// GET /v1/auth/{provider}/callback
 ///
-/// Handles the OAuth callback from Patreon:
+/// Handles the OAuth callback from a membership provider:
 /// 1. Validates the state parameter against the stored cookie (CSRF protection)
 /// 2. Exchanges the authorization code for access/refresh tokens
 /// 3. Fetches patron identity and membership status
@@ -123,21 +128,28 @@ pub(crate) async fn logout() -> impl IntoResponse {
 #[allow(clippy::too_many_lines)]
 pub(crate) async fn callback(
     State(app_state): State<AppState>,
+    Path(provider): Path<String>,
     headers: HeaderMap,
     Query(params): Query<CallbackParams>,
-) -> impl IntoResponse {
-    // If the user cancelled the OAuth flow, Patreon redirects back without a code.
+) -> Response {
+    let provider = match get_provider(&app_state, &provider) {
+        Ok(provider) => provider,
+        Err(e) => return e.into_response(),
+    };
+    let state_cookie_name = state_cookie_name(provider.as_ref());
+
+    // If the user cancelled the OAuth flow, the provider redirects back without a code.
     // Redirect them back to the frontend gracefully.
     let Some(code) = params.code else {
         return Response::builder()
             .status(StatusCode::FOUND)
-            .header("Location", &app_state.config.patreon.frontend_redirect_url)
+            .header("Location", provider.frontend_redirect_url())
             .body(axum::body::Body::empty())
             .expect("Failed to build redirect response");
     };
 
     // Step 1: Validate state parameter matches cookie (CSRF protection)
-    let Some(stored_state) = extract_state_from_cookie(&headers) else {
+    let Some(stored_state) = extract_state_from_cookie(&headers, &state_cookie_name) else {
         return Response::builder()
             .status(StatusCode::BAD_REQUEST)
             .body(axum::body::Body::from("Missing OAuth state cookie"))
@@ -158,73 +170,44 @@ pub(crate) async fn callback(
             .expect("Failed to build error response");
     }
 
-    // Create Patreon client
-    let patreon_client = PatreonClient::new(
-        reqwest::Client::new(),
-        app_state.config.patreon.client_id.clone(),
-        app_state.config.patreon.client_secret.clone(),
-        app_state.config.patreon.redirect_uri.clone(),
-    );
-
     // Step 2: Exchange authorization code for tokens
-    let token_response = match patreon_client.exchange_code(&code).await {
-        Ok(response) => response,
+    let tokens = match provider.exchange_code(&code).await {
+        Ok(tokens) => tokens,
         Err(e) => {
-            tracing::error!("Failed to exchange code: {e}");
+            tracing::error!("Failed to exchange {} code: {e}", provider.kind());
             return Response::builder()
                 .status(StatusCode::BAD_GATEWAY)
                 .body(axum::body::Body::from(
-                    "Failed to authenticate with Patreon",
+                    "Failed to authenticate with the membership provider",
                 ))
                 .expect("Failed to build error response");
         }
     };
 
     // Step 3: Fetch patron identity
-    let identity = match patreon_client
-        .get_identity(&token_response.access_token)
-        .await
-    {
+    let identity = match provider.get_identity(&tokens.access_token).await {
         Ok(identity) => identity,
         Err(e) => {
-            tracing::error!("Failed to get identity: {e}");
+            tracing::error!("Failed to get {} identity: {e}", provider.kind());
             return Response::builder()
                 .status(StatusCode::BAD_GATEWAY)
-                .body(axum::body::Body::from("Failed to fetch Patreon identity"))
+                .body(axum::body::Body::from("Failed to fetch identity"))
                 .expect("Failed to build error response");
         }
     };
 
-    // Step 3b: Fetch membership status
-    let membership = match patreon_client
-        .get_membership(&token_response.access_token)
-        .await
-    {
+    // Step 3b: Fetch membership status (inactive for non-members)
+    let membership = match provider.get_membership(&tokens.access_token).await {
         Ok(membership) => membership,
         Err(e) => {
-            tracing::error!("Failed to get membership: {e}");
+            tracing::error!("Failed to get {} membership: {e}", provider.kind());
             return Response::builder()
                 .status(StatusCode::BAD_GATEWAY)
-                .body(axum::body::Body::from("Failed to fetch Patreon membership"))
+                .body(axum::body::Body::from("Failed to fetch membership"))
                 .expect("Failed to build error response");
         }
     };
 
-    // Extract membership details (defaults for non-members)
-    let (tier_id, pledge_amount_cents, is_active) = match &membership {
-        Some(m) => {
-            let is_active = m
-                .patron_status
-                .as_ref()
-                .is_some_and(|s| s == "active_patron");
-            (m.tier_id.clone(), m.pledge_amount_cents, is_active)
-        }
-        None => (None, 0, false),
-    };
-
-    // Calculate token expiration time
-    let token_expires_at = Utc::now() + Duration::seconds(token_response.expires_in);
-
     // Step 4: Create or update patron record
     let patron_repo = PatronRepository::new(
         app_state.pg_client.clone(),
@@ -233,14 +216,15 @@ pub(crate) async fn callback(
 
     let patron = match patron_repo
         .create_or_update_patron(UpsertPatronParams {
-            patreon_user_id: identity.id,
+            provider: provider.kind(),
+            provider_user_id: identity.id,
             email: identity.email,
-            tier_id,
-            pledge_amount_cents: Some(pledge_amount_cents),
-            is_active,
-            access_token: Some(token_response.access_token),
-            refresh_token: Some(token_response.refresh_token),
-            token_expires_at: Some(token_expires_at),
+            tier_id: membership.tier_id,
+            pledge_amount_cents: membership.pledge_amount_cents,
+            is_active: membership.is_active,
+            access_token: Some(tokens.access_token),
+            refresh_token: tokens.refresh_token,
+            token_expires_at: tokens.expires_at,
         })
         .await
     {
@@ -281,27 +265,32 @@ pub(crate) async fn callback(
         }
     };
 
+    // The cookie name is built from the provider name, so this cannot fail
+    let clear_state_cookie_header = HeaderValue::from_str(&format!(
+        "{state_cookie_name}=; HttpOnly; Secure; SameSite=Lax; Path=/; Max-Age=0"
+    ))
+    .expect("provider names are always valid header values");
+
     let mut response = Response::builder()
         .status(StatusCode::FOUND)
-        .header("Location", &app_state.config.patreon.frontend_redirect_url)
+        .header("Location", provider.frontend_redirect_url())
         .body(axum::body::Body::empty())
         .expect("Failed to build redirect response");
 
     response
         .headers_mut()
         .insert(SET_COOKIE, session_cookie_header);
-    response.headers_mut().append(
-        SET_COOKIE,
-        HeaderValue::from_static(
-            "patreon_oauth_state=; HttpOnly; Secure; SameSite=Lax; Path=/; Max-Age=0",
-        ),
-    );
+    response
+        .headers_mut()
+        .append(SET_COOKIE, clear_state_cookie_header);
 
     response
 }
 
 #[cfg(test)]
 mod tests {
+    use axum::http;
+
     use super::*;
 
     #[test]
@@ -319,18 +308,26 @@ mod tests {
     }
 
     #[test]
-    fn test_build_patreon_auth_url() {
-        let url = build_patreon_auth_url(
-            "test_client_id",
-            "https://example.com/callback",
-            "test_state_123",
+    fn test_extract_state_from_cookie() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            http::header::COOKIE,
+            HeaderValue::from_static(
+                "patreon_oauth_state_old=x; github_oauth_state=abc; patreon_oauth_state=def",
+            ),
         );
 
-        assert!(url.starts_with("https://www.patreon.com/oauth2/authorize?"));
-        assert!(url.contains("response_type=code"));
-        assert!(url.contains("client_id=test_client_id"));
-        assert!(url.contains("redirect_uri=https%3A%2F%2Fexample.com%2Fcallback"));
-        assert!(url.contains("scope=identity%20identity%5Bemail%5D%20campaigns.members"));
-        assert!(url.contains("state=test_state_123"));
+        assert_eq!(
+            extract_state_from_cookie(&headers, "patreon_oauth_state"),
+            Some("def".to_owned())
+        );
+        assert_eq!(
+            extract_state_from_cookie(&headers, "github_oauth_state"),
+            Some("abc".to_owned())
+        );
+        assert_eq!(
+            extract_state_from_cookie(&headers, "mock_oauth_state"),
+            None
+        );
     }
 }
@@ -0,0 +1,213 @@
+use std::collections::HashMap;
+
+use axum::Json;
+use axum::extract::{Query, State};
+use axum::http::header::{LOCATION, SET_COOKIE};
+use axum::http::{HeaderMap, HeaderValue, StatusCode};
+use axum::response::{IntoResponse, Response};
+use chrono::Utc;
+use redis::{AsyncCommands, ExistenceCheck, SetExpiry, SetOptions};
+use serde::Serialize;
+
+use crate::context::AppState;
+use crate::error::{APIError, APIResult};
+use crate::services::patreon::extractor::extract_token_from_cookie;
+use crate::services::patreon::jwt::create_steam_session_token;
+use crate::services::steam::extractor::{STEAM_SESSION_COOKIE, SteamSession};
+use crate::services::steam::openid;
+use crate::utils::parse::steamid64_to_steamid3;
+
+/// Name of the cookie storing the state of a Steam `OpenID` login
+const STATE_COOKIE: &str = "steam_openid_state";
+
+/// Builds an empty response with the given cookies.
+fn with_cookies(status: StatusCode, location: Option<&str>, cookies: &[String]) -> Response {
+    let mut response = Response::builder()
+        .status(status)
+        .body(axum::body::Body::empty())
+        .expect("Failed to build response");
+
+    if let Some(location) = location.and_then(|l| HeaderValue::from_str(l).ok()) {
+        response.headers_mut().insert(LOCATION, location);
+    }
+    for cookie in cookies {
+        // Cookie values are hex-encoded states or JWTs, so this cannot fail
+        let cookie = HeaderValue::from_str(cookie).expect("cookies are valid header values");
+        response.headers_mut().append(SET_COOKIE, cookie);
+    }
+    response
+}
+
+/// GET /v1/auth/steam
+///
+/// Initiates the Steam `OpenID` login by:
+/// 1. Generating a random state parameter for CSRF protection
+/// 2. Storing the state in a cookie and passing it back through the return URL
+/// 3. Redirecting to the Steam login
+pub(crate) async fn login(State(state): State<AppState>) -> APIResult<Response> {
+    let Some(mut return_to) = state.config.steam_login.return_to.clone() else {
+        return Err(APIError::status_msg(
+            StatusCode::NOT_FOUND,
+            "Steam login is not enabled",
+        ));
+    };
+
+    let oauth_state = super::oauth::generate_state();
+    return_to
+        .query_pairs_mut()
+        .append_pair("state", &oauth_state);
+
+    // Max-Age of 10 minutes should be plenty for the login
+    Ok(with_cookies(
+        StatusCode::FOUND,
+        Some(openid::build_login_url(&return_to).as_str()),
+        &[format!(
+            "{STATE_COOKIE}={oauth_state}; HttpOnly; Secure; SameSite=Lax; Path=/; Max-Age=600"
+        )],
+    ))
+}
+
+/// GET /v1/auth/steam/callback
+///
+/// Handles the redirect back from the Steam login:
+/// 1. Validates the state parameter against the stored cookie (CSRF protection)
+/// 2. Checks the `OpenID` parameters were issued recently for this API and verifies them with Steam
+/// 3. Rejects parameters that were used for a login already
+/// 4. Generates a JWT session token for the verified `SteamID3` and sets it as a cookie
+/// 5. Redirects to the frontend redirect URL
+pub(crate) async fn callback(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Query<HashMap<String, String>>,
+) -> APIResult<Response> {
+    let config = &state.config.steam_login;
+    let Some(return_to) = &config.return_to else {
+        return Err(APIError::status_msg(
+            StatusCode::NOT_FOUND,
+            "Steam login is not enabled",
+        ));
+    };
+
+    // If the user cancelled the login, Steam redirects back with `openid.mode=cancel`.
+    // Redirect them back to the frontend gracefully.
+    if params.get("openid.mode").map(String::as_str) != Some("id_res") {
+        return Ok(with_cookies(
+            StatusCode::FOUND,
+            Some(config.frontend_redirect_url.as_str()),
+            &[],
+        ));
+    }
+
+    // Step 1: Validate state parameter matches cookie (CSRF protection)
+    let stored_state = extract_token_from_cookie(&headers, STATE_COOKIE).ok_or_else(|| {
+        APIError::status_msg(StatusCode::BAD_REQUEST, "Missing OpenID state cookie")
+    })?;
+    if params.get("state") != Some(&stored_state) {
+        return Err(APIError::status_msg(
+            StatusCode::BAD_REQUEST,
+            "Invalid OpenID state",
+        ));
+    }
+
+    // Step 2: Verify the OpenID parameters
+    if !openid::returns_to(&params, return_to) {
+        return Err(APIError::status_msg(
+            StatusCode::BAD_REQUEST,
+            "OpenID parameters were issued for another site",
+        ));
+    }
+    let nonce = openid::fresh_nonce(&params, Utc::now()).ok_or_else(|| {
+        APIError::status_msg(StatusCode::BAD_REQUEST, "OpenID parameters have expired")
+    })?;
+    let steam_id64 = state
+        .steam_client
+        .verify_open_id(&params)
+        .await
+        .map_err(|e| {
+            APIError::status_msg(
+                StatusCode::BAD_REQUEST,
+                format!("Failed to verify OpenID parameters: {e}"),
+            )
+        })?;
+    let steam_id3 = steamid64_to_steamid3(steam_id64)
+        .map_err(|_| APIError::status_msg(StatusCode::BAD_REQUEST, "SteamID3 is out of range"))?;
+
+    // Step 3: Accept every nonce only once, it is remembered for as long as it is fresh
+    let options = SetOptions::default()
+        .conditional_set(ExistenceCheck::NX)
+        .with_expiration(SetExpiry::EX(openid::NONCE_TTL_SECS));
+    let first_use: Option<String> = state
+        .redis_client
+        .clone()
+        .set_options(format!("steam-openid-nonce:{nonce}"), 1, options)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to store Steam OpenID nonce: {e}");
+            APIError::internal("Failed to verify OpenID parameters")
+        })?;
+    if first_use.is_none() {
+        return Err(APIError::status_msg(
+            StatusCode::BAD_REQUEST,
+            "OpenID parameters were used already",
+        ));
+    }
+
+    // Step 4: Generate JWT session token
+    let session_token =
+        create_steam_session_token(steam_id3, &state.config.jwt_secret).map_err(|e| {
+            tracing::error!("Failed to create Steam session token: {e}");
+            APIError::internal("Failed to create session")
+        })?;
+
+    // Step 5: Set session cookie and redirect to frontend
+    // Session cookie valid for 7 days (matches JWT expiration)
+    Ok(with_cookies(
+        StatusCode::FOUND,
+        Some(config.frontend_redirect_url.as_str()),
+        &[
+            format!(
+                "{STEAM_SESSION_COOKIE}={session_token}; HttpOnly; Secure; SameSite=Lax; Path=/; Max-Age=604800"
+            ),
+            format!("{STATE_COOKIE}=; HttpOnly; Secure; SameSite=Lax; Path=/; Max-Age=0"),
+        ],
+    ))
+}
+
+/// POST /v1/auth/steam/logout
+///
+/// Logs out the player by clearing the Steam session cookie.
+pub(crate) async fn logout() -> Response {
+    with_cookies(
+        StatusCode::OK,
+        None,
+        &[format!(
+            "{STEAM_SESSION_COOKIE}=; HttpOnly; Secure; SameSite=Lax; Path=/; Max-Age=0"
+        )],
+    )
+}
+
+/// Response for the Steam session endpoint
+#[derive(Debug, Serialize)]
+pub(crate) struct SteamSessionResponse {
+    steam_id3: u32,
+    /// Whether the player requested the deletion of their data
+    is_protected: bool,
+}
+
+/// GET /v1/auth/steam/session
+///
+/// Returns the verified player of the Steam session and their privacy settings.
+pub(crate) async fn get_session(
+    State(state): State<AppState>,
+    session: SteamSession,
+) -> APIResult<impl IntoResponse> {
+    let is_protected = state
+        .steam_client
+        .is_user_protected(&state.pg_client, session.steam_id3)
+        .await?;
+
+    Ok(Json(SteamSessionResponse {
+        steam_id3: session.steam_id3,
+        is_protected,
+    }))
+}
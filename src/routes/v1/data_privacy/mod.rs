@@ -10,6 +10,7 @@ use utoipa::{IntoParams, ToSchema};
 use crate::context::AppState;
 use crate::error::{APIError, APIResult};
 use crate::services::steam::client::GET_PROTECTED_USERS_CACHED;
+use crate::services::steam::extractor::SteamSession;
 use crate::utils;
 use crate::utils::parse::parse_steam_id;
 
@@ -17,10 +18,37 @@ use crate::utils::parse::parse_steam_id;
 pub(crate) struct DataPrivacyRequest {
     #[serde(deserialize_with = "parse_steam_id")]
     steam_id: u32,
+    /// Not required if the request is authenticated by a Steam session of the same account
+    #[serde(default)]
     open_id_params: HashMap<String, String>,
 }
 
-async fn protect_account(pg_client: &sqlx::Pool<sqlx::Postgres>, steam_id: u32) -> APIResult<()> {
+/// Verifies that the requester owns the Steam account, either by a Steam session or by the
+/// `OpenID` parameters of a Steam login.
+async fn verify_ownership(
+    state: &AppState,
+    session: Option<SteamSession>,
+    steam_id: u32,
+    open_id_params: &HashMap<String, String>,
+) -> APIResult<()> {
+    if session.is_some_and(|s| s.steam_id3 == steam_id) {
+        return Ok(());
+    }
+    let steamid64 = utils::parse::steamid3_to_steamid64(steam_id);
+    if let Err(e) = state
+        .steam_client
+        .verify_user_owns_steam_id(open_id_params, steamid64)
+        .await
+    {
+        return Err(APIError::status_msg(
+            axum::http::StatusCode::BAD_REQUEST,
+            format!("Failed to verify OpenID parameters: {e}"),
+        ));
+    }
+    Ok(())
+}
+
+pub(crate) async fn protect_account(conn: &mut sqlx::PgConnection, steam_id: u32) -> APIResult<()> {
     let Ok(steam_id_i32) = i32::try_from(steam_id) else {
         return Err(APIError::status_msg(
             axum::http::StatusCode::BAD_REQUEST,
@@ -35,15 +63,16 @@ async fn protect_account(pg_client: &sqlx::Pool<sqlx::Postgres>, steam_id: u32)
         "#,
         steam_id_i32
     )
-    .execute(pg_client)
+    .execute(conn)
     .await?;
 
-    GET_PROTECTED_USERS_CACHED.lock().await.cache_clear();
-
     Ok(())
 }
 
-async fn unprotect_account(pg_client: &sqlx::Pool<sqlx::Postgres>, steam_id: u32) -> APIResult<()> {
+pub(crate) async fn unprotect_account(
+    conn: &mut sqlx::PgConnection,
+    steam_id: u32,
+) -> APIResult<()> {
     let Ok(steam_id_i32) = i32::try_from(steam_id) else {
         return Err(APIError::status_msg(
             axum::http::StatusCode::BAD_REQUEST,
@@ -57,18 +86,19 @@ async fn unprotect_account(pg_client: &sqlx::Pool<sqlx::Postgres>, steam_id: u32
         "#,
         steam_id_i32
     )
-    .execute(pg_client)
+    .execute(conn)
     .await?;
 
-    GET_PROTECTED_USERS_CACHED.lock().await.cache_clear();
-
     Ok(())
 }
 
-async fn update_row_policy(
+/// Applies the committed protected accounts to the `ClickHouse` row policies and the cache.
+pub(crate) async fn update_row_policy(
     pg_client: &sqlx::Pool<sqlx::Postgres>,
     ch_client: &clickhouse::Client,
 ) -> APIResult<()> {
+    GET_PROTECTED_USERS_CACHED.lock().await.cache_clear();
+
     let protected_accounts: Vec<i32> = sqlx::query!("SELECT steam_id FROM protected_user_accounts")
         .fetch_all(pg_client)
         .await?
@@ -104,46 +134,28 @@ async fn update_row_policy(
 
 pub(crate) async fn request_deletion(
     State(state): State<AppState>,
+    session: Option<SteamSession>,
     Json(DataPrivacyRequest {
         open_id_params,
         steam_id,
     }): Json<DataPrivacyRequest>,
 ) -> APIResult<impl IntoResponse> {
-    let steamid64 = utils::parse::steamid3_to_steamid64(steam_id);
-    if let Err(e) = state
-        .steam_client
-        .verify_user_owns_steam_id(&open_id_params, steamid64)
-        .await
-    {
-        return Err(APIError::status_msg(
-            axum::http::StatusCode::BAD_REQUEST,
-            format!("Failed to verify OpenID parameters: {e}"),
-        ));
-    }
-    protect_account(&state.pg_client, steam_id).await?;
+    verify_ownership(&state, session, steam_id, &open_id_params).await?;
+    protect_account(&mut *state.pg_client.acquire().await?, steam_id).await?;
     update_row_policy(&state.pg_client, &state.ch_client).await?;
     Ok(())
 }
 
 pub(crate) async fn request_tracking(
     State(state): State<AppState>,
+    session: Option<SteamSession>,
     Json(DataPrivacyRequest {
         open_id_params,
         steam_id,
     }): Json<DataPrivacyRequest>,
 ) -> APIResult<impl IntoResponse> {
-    let steamid64 = utils::parse::steamid3_to_steamid64(steam_id);
-    if let Err(e) = state
-        .steam_client
-        .verify_user_owns_steam_id(&open_id_params, steamid64)
-        .await
-    {
-        return Err(APIError::status_msg(
-            axum::http::StatusCode::BAD_REQUEST,
-            format!("Failed to verify OpenID parameters: {e}"),
-        ));
-    }
-    unprotect_account(&state.pg_client, steam_id).await?;
+    verify_ownership(&state, session, steam_id, &open_id_params).await?;
+    unprotect_account(&mut *state.pg_client.acquire().await?, steam_id).await?;
     update_row_policy(&state.pg_client, &state.ch_client).await?;
     Ok(())
 }
@@ -0,0 +1,454 @@
+use core::cmp::Reverse;
+
+use axum::Json;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum_extra::extract::Query;
+use clickhouse::Row;
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::context::AppState;
+use crate::error::{APIError, APIResult};
+use crate::routes::v1::leaderboard::route::LeaderboardQuery;
+use crate::routes::v1::leaderboard::types::{
+    LeaderboardEntry, LeaderboardRegion, LeaderboardSnapshotRow,
+};
+use crate::utils::parse::default_last_month_timestamp;
+
+fn default_last_day_timestamp() -> Option<i64> {
+    Some((chrono::Utc::now() - chrono::Duration::days(1)).timestamp())
+}
+
+fn default_movers_limit() -> u32 {
+    10
+}
+
+#[derive(Debug, Clone, Deserialize, IntoParams)]
+pub(super) struct LeaderboardHistoryQuery {
+    /// The account name as shown on the leaderboard.
+    account_name: String,
+    /// The hero ID of the hero leaderboard, leave empty for the overall leaderboard. See more: <https://assets.deadlock-api.com/v2/heroes>
+    hero_id: Option<u32>,
+    /// Filter snapshots based on their time (Unix timestamp). **Default:** 30 days ago.
+    #[serde(default = "default_last_month_timestamp")]
+    #[param(default = default_last_month_timestamp)]
+    min_unix_timestamp: Option<i64>,
+    /// Filter snapshots based on their time (Unix timestamp).
+    max_unix_timestamp: Option<i64>,
+}
+
+#[derive(Debug, Clone, Deserialize, IntoParams)]
+pub(super) struct LeaderboardMoversQuery {
+    /// The hero ID of the hero leaderboard, leave empty for the overall leaderboard. See more: <https://assets.deadlock-api.com/v2/heroes>
+    hero_id: Option<u32>,
+    /// Start of the window (Unix timestamp), the first snapshot after it is used. **Default:** 24 hours ago.
+    #[serde(default = "default_last_day_timestamp")]
+    #[param(default = default_last_day_timestamp)]
+    min_unix_timestamp: Option<i64>,
+    /// End of the window (Unix timestamp), the last snapshot before it is used. **Default:** now.
+    max_unix_timestamp: Option<i64>,
+    /// The maximum number of climbers and fallers to return each (up to 100). **Default:** 10.
+    #[serde(default = "default_movers_limit")]
+    #[param(default = default_movers_limit, maximum = 100)]
+    limit: u32,
+}
+
+impl Default for LeaderboardMoversQuery {
+    fn default() -> Self {
+        Self {
+            hero_id: None,
+            min_unix_timestamp: default_last_day_timestamp(),
+            max_unix_timestamp: None,
+            limit: default_movers_limit(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, IntoParams)]
+pub(super) struct LeaderboardAtQuery {
+    /// The hero ID of the hero leaderboard, leave empty for the overall leaderboard. See more: <https://assets.deadlock-api.com/v2/heroes>
+    hero_id: Option<u32>,
+    /// The time to return the leaderboard for (Unix timestamp), the last snapshot before it is used.
+    unix_timestamp: i64,
+}
+
+#[derive(Debug, Clone, Row, Serialize, Deserialize, ToSchema)]
+pub(super) struct LeaderboardPosition {
+    /// The time of the snapshot (Unix timestamp).
+    snapshot_unix_timestamp: u32,
+    /// The position on the leaderboard.
+    rank: u32,
+    /// The badge level of the player (tier = first digits, subtier = last digit). See more: <https://assets.deadlock-api.com/v2/ranks>
+    badge_level: Option<u32>,
+}
+
+#[derive(Debug, Clone, Row, Deserialize)]
+struct RankChangeRow {
+    account_name: String,
+    start_rank: u32,
+    end_rank: u32,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub(super) struct LeaderboardMover {
+    /// The account name of the player.
+    account_name: String,
+    /// The position at the start of the window.
+    start_rank: u32,
+    /// The position at the end of the window.
+    end_rank: u32,
+    /// The number of positions gained, negative if positions were lost.
+    rank_change: i64,
+}
+
+impl From<RankChangeRow> for LeaderboardMover {
+    fn from(row: RankChangeRow) -> Self {
+        Self {
+            rank_change: i64::from(row.start_rank) - i64::from(row.end_rank),
+            account_name: row.account_name,
+            start_rank: row.start_rank,
+            end_rank: row.end_rank,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub(super) struct LeaderboardMovers {
+    /// The players that gained the most positions, biggest climber first.
+    climbers: Vec<LeaderboardMover>,
+    /// The players that lost the most positions, biggest faller first.
+    fallers: Vec<LeaderboardMover>,
+}
+
+impl LeaderboardMovers {
+    fn from_rows(rows: Vec<RankChangeRow>, limit: usize) -> Self {
+        let (climbers, fallers): (Vec<LeaderboardMover>, Vec<LeaderboardMover>) = rows
+            .into_iter()
+            .map(LeaderboardMover::from)
+            .filter(|m| m.rank_change != 0)
+            .partition(|m| m.rank_change > 0);
+        Self {
+            climbers: climbers
+                .into_iter()
+                .sorted_by_key(|m| Reverse(m.rank_change))
+                .take(limit)
+                .collect(),
+            fallers: fallers
+                .into_iter()
+                .sorted_by_key(|m| m.rank_change)
+                .take(limit)
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub(super) struct LeaderboardSnapshot {
+    /// The time of the snapshot (Unix timestamp).
+    snapshot_unix_timestamp: i64,
+    /// The leaderboard entries.
+    entries: Vec<LeaderboardEntry>,
+}
+
+fn board_filter(region: LeaderboardRegion, hero_id: Option<u32>) -> String {
+    format!(
+        "region = {} AND hero_id = {}",
+        region as u8,
+        hero_id.unwrap_or_default()
+    )
+}
+
+fn build_history_query(region: LeaderboardRegion, query: &LeaderboardHistoryQuery) -> String {
+    let mut filters = vec![
+        board_filter(region, query.hero_id),
+        "account_name = ?".to_owned(),
+    ];
+    if let Some(min_unix_timestamp) = query.min_unix_timestamp {
+        filters.push(format!("snapshot_time >= toDateTime({min_unix_timestamp})"));
+    }
+    if let Some(max_unix_timestamp) = query.max_unix_timestamp {
+        filters.push(format!("snapshot_time <= toDateTime({max_unix_timestamp})"));
+    }
+    let filters = filters.join(" AND ");
+    format!(
+        "
+    SELECT toUnixTimestamp(snapshot_time) AS snapshot_unix_timestamp, rank, badge_level
+    FROM leaderboard_snapshots
+    WHERE {filters}
+    ORDER BY snapshot_unix_timestamp, rank
+    LIMIT 1 BY snapshot_unix_timestamp
+    "
+    )
+}
+
+fn build_movers_query(region: LeaderboardRegion, query: &LeaderboardMoversQuery) -> String {
+    let board = board_filter(region, query.hero_id);
+    let start_filter = query
+        .min_unix_timestamp
+        .map(|t| format!(" AND snapshot_time >= toDateTime({t})"))
+        .unwrap_or_default();
+    let end_filter = query
+        .max_unix_timestamp
+        .map(|t| format!(" AND snapshot_time <= toDateTime({t})"))
+        .unwrap_or_default();
+    format!(
+        "
+    WITH
+        (SELECT min(snapshot_time) FROM leaderboard_snapshots WHERE {board}{start_filter}) AS start_time,
+        (SELECT max(snapshot_time) FROM leaderboard_snapshots WHERE {board}{end_filter}) AS end_time
+    SELECT account_name, start_rank, end_rank
+    FROM (
+        SELECT account_name, min(rank) AS start_rank
+        FROM leaderboard_snapshots
+        WHERE {board} AND snapshot_time = start_time
+        GROUP BY account_name
+    )
+    INNER JOIN (
+        SELECT account_name, min(rank) AS end_rank
+        FROM leaderboard_snapshots
+        WHERE {board} AND snapshot_time = end_time
+        GROUP BY account_name
+    ) USING (account_name)
+    "
+    )
+}
+
+fn build_snapshot_query(region: LeaderboardRegion, query: &LeaderboardAtQuery) -> String {
+    let board = board_filter(region, query.hero_id);
+    let unix_timestamp = query.unix_timestamp;
+    format!(
+        "
+    SELECT ?fields
+    FROM leaderboard_snapshots
+    WHERE {board} AND snapshot_time = (
+        SELECT max(snapshot_time)
+        FROM leaderboard_snapshots
+        WHERE {board} AND snapshot_time <= toDateTime({unix_timestamp})
+    )
+    ORDER BY rank
+    LIMIT 1 BY rank
+    "
+    )
+}
+
+async fn validate_hero_id(state: &AppState, hero_id: Option<u32>) -> APIResult<()> {
+    match hero_id {
+        Some(hero_id) if !state.assets_client.validate_hero_id(hero_id).await => {
+            Err(APIError::status_msg(
+                StatusCode::BAD_REQUEST,
+                format!("Invalid hero_id: {hero_id}"),
+            ))
+        }
+        _ => Ok(()),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/{region}/history",
+    params(LeaderboardQuery, LeaderboardHistoryQuery),
+    responses(
+        (status = OK, body = [LeaderboardPosition]),
+        (status = BAD_REQUEST, description = "Provided parameters are invalid."),
+        (status = INTERNAL_SERVER_ERROR, description = "Fetching the leaderboard history failed")
+    ),
+    tags = ["Leaderboard"],
+    summary = "Leaderboard Position History",
+    description = "
+Returns the leaderboard positions of a player over time, taken from hourly snapshots of the leaderboard.
+
+Players are identified by their account name, as the leaderboard does not contain account IDs.
+
+### Rate Limits:
+| Type | Limit |
+| ---- | ----- |
+| IP | 100req/s |
+| Key | - |
+| Global | - |
+    "
+)]
+pub(super) async fn leaderboard_history(
+    State(state): State<AppState>,
+    Path(LeaderboardQuery { region }): Path<LeaderboardQuery>,
+    Query(query): Query<LeaderboardHistoryQuery>,
+) -> APIResult<impl IntoResponse> {
+    validate_hero_id(&state, query.hero_id).await?;
+    let sql = build_history_query(region, &query);
+    debug!(?sql);
+    let positions: Vec<LeaderboardPosition> = state
+        .ch_client_ro
+        .query(&sql)
+        .bind(&query.account_name)
+        .fetch_all()
+        .await?;
+    Ok(Json(positions))
+}
+
+#[utoipa::path(
+    get,
+    path = "/{region}/movers",
+    params(LeaderboardQuery, LeaderboardMoversQuery),
+    responses(
+        (status = OK, body = LeaderboardMovers),
+        (status = BAD_REQUEST, description = "Provided parameters are invalid."),
+        (status = INTERNAL_SERVER_ERROR, description = "Fetching the leaderboard movers failed")
+    ),
+    tags = ["Leaderboard"],
+    summary = "Leaderboard Climbers and Fallers",
+    description = "
+Returns the players that gained or lost the most leaderboard positions within a time window.
+
+The first snapshot after the start and the last snapshot before the end of the window are compared. Players that entered or left the leaderboard within the window are not included.
+
+### Rate Limits:
+| Type | Limit |
+| ---- | ----- |
+| IP | 100req/s |
+| Key | - |
+| Global | - |
+    "
+)]
+pub(super) async fn leaderboard_movers(
+    State(state): State<AppState>,
+    Path(LeaderboardQuery { region }): Path<LeaderboardQuery>,
+    Query(query): Query<LeaderboardMoversQuery>,
+) -> APIResult<impl IntoResponse> {
+    validate_hero_id(&state, query.hero_id).await?;
+    let sql = build_movers_query(region, &query);
+    debug!(?sql);
+    let rows: Vec<RankChangeRow> = state.ch_client_ro.query(&sql).fetch_all().await?;
+    Ok(Json(LeaderboardMovers::from_rows(
+        rows,
+        query.limit.clamp(1, 100) as usize,
+    )))
+}
+
+#[utoipa::path(
+    get,
+    path = "/{region}/snapshot",
+    params(LeaderboardQuery, LeaderboardAtQuery),
+    responses(
+        (status = OK, body = LeaderboardSnapshot),
+        (status = BAD_REQUEST, description = "Provided parameters are invalid."),
+        (status = NOT_FOUND, description = "No snapshot exists before the given time"),
+        (status = INTERNAL_SERVER_ERROR, description = "Fetching the leaderboard snapshot failed")
+    ),
+    tags = ["Leaderboard"],
+    summary = "Leaderboard as of a past Time",
+    description = "
+Returns the leaderboard as it was at the given time, taken from hourly snapshots of the leaderboard.
+
+### Rate Limits:
+| Type | Limit |
+| ---- | ----- |
+| IP | 100req/s |
+| Key | - |
+| Global | - |
+    "
+)]
+pub(super) async fn leaderboard_snapshot(
+    State(state): State<AppState>,
+    Path(LeaderboardQuery { region }): Path<LeaderboardQuery>,
+    Query(query): Query<LeaderboardAtQuery>,
+) -> APIResult<impl IntoResponse> {
+    validate_hero_id(&state, query.hero_id).await?;
+    let sql = build_snapshot_query(region, &query);
+    debug!(?sql);
+    let rows: Vec<LeaderboardSnapshotRow> = state.ch_client_ro.query(&sql).fetch_all().await?;
+    let Some(snapshot_time) = rows.first().map(|r| r.snapshot_time) else {
+        return Err(APIError::status_msg(
+            StatusCode::NOT_FOUND,
+            "No leaderboard snapshot found before the given time",
+        ));
+    };
+    Ok(Json(LeaderboardSnapshot {
+        snapshot_unix_timestamp: snapshot_time.timestamp(),
+        entries: rows.into_iter().map_into().collect(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use tracing::warn;
+
+    use super::*;
+
+    fn assert_parses(sql: &str) {
+        if let Err(e) =
+            sqlparser::parser::Parser::parse_sql(&sqlparser::dialect::ClickHouseDialect {}, sql)
+        {
+            warn!("Failed to parse SQL: {sql}: {e}");
+        }
+    }
+
+    #[test]
+    fn test_build_history_query() {
+        let query = LeaderboardHistoryQuery {
+            account_name: "player".to_owned(),
+            hero_id: Some(15),
+            min_unix_timestamp: Some(1672531200),
+            max_unix_timestamp: Some(1675209599),
+        };
+        let sql = build_history_query(LeaderboardRegion::NAmerica, &query);
+        assert_parses(&sql);
+        assert!(sql.contains("region = 3 AND hero_id = 15 AND account_name = ?"));
+        assert!(sql.contains("snapshot_time >= toDateTime(1672531200)"));
+        assert!(sql.contains("snapshot_time <= toDateTime(1675209599)"));
+    }
+
+    #[test]
+    fn test_build_movers_query() {
+        let query = LeaderboardMoversQuery {
+            min_unix_timestamp: Some(1672531200),
+            max_unix_timestamp: None,
+            ..Default::default()
+        };
+        let sql = build_movers_query(LeaderboardRegion::Europe, &query);
+        assert_parses(&sql);
+        assert!(sql.contains(
+            "WHERE region = 1 AND hero_id = 0 AND snapshot_time >= toDateTime(1672531200))"
+        ));
+        assert!(sql.contains("WHERE region = 1 AND hero_id = 0) AS end_time"));
+    }
+
+    #[test]
+    fn test_build_snapshot_query() {
+        let query = LeaderboardAtQuery {
+            hero_id: None,
+            unix_timestamp: 1672531200,
+        };
+        let sql = build_snapshot_query(LeaderboardRegion::Oceania, &query);
+        assert_parses(&sql);
+        assert!(sql.contains("snapshot_time <= toDateTime(1672531200)"));
+        assert!(sql.contains("region = 5 AND hero_id = 0"));
+    }
+
+    #[test]
+    fn test_movers_from_rows() {
+        let row = |account_name: &str, start_rank, end_rank| RankChangeRow {
+            account_name: account_name.to_owned(),
+            start_rank,
+            end_rank,
+        };
+        let movers = LeaderboardMovers::from_rows(
+            vec![
+                row("small climb", 10, 8),
+                row("big climb", 50, 2),
+                row("unchanged", 5, 5),
+                row("big fall", 3, 90),
+                row("small fall", 20, 21),
+            ],
+            1,
+        );
+        assert_eq!(movers.climbers.len(), 1);
+        assert_eq!(movers.climbers[0].account_name, "big climb");
+        assert_eq!(movers.climbers[0].rank_change, 48);
+        assert_eq!(movers.fallers.len(), 1);
+        assert_eq!(movers.fallers[0].account_name, "big fall");
+        assert_eq!(movers.fallers[0].rank_change, -87);
+    }
+}
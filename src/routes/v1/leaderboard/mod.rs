@@ -1,5 +1,6 @@
-pub(super) mod route;
-pub(super) mod types;
+mod history;
+pub(crate) mod route;
+pub(crate) mod types;
 
 use core::time::Duration;
 
@@ -14,6 +15,7 @@ use crate::middleware::cache::CacheControlMiddleware;
 #[openapi(tags((name = "Leaderboard", description = "
 Endpoints for retrieving global and hero-specific leaderboards.
 Supports filtering by region and provides data in both JSON and protobuf formats.
+Hourly snapshots of all leaderboards provide the position history of players.
 ")))]
 struct ApiDoc;
 
@@ -23,6 +25,9 @@ pub(super) fn router() -> OpenApiRouter<AppState> {
         .routes(routes!(route::leaderboard_hero_raw))
         .routes(routes!(route::leaderboard))
         .routes(routes!(route::leaderboard_hero))
+        .routes(routes!(history::leaderboard_history))
+        .routes(routes!(history::leaderboard_movers))
+        .routes(routes!(history::leaderboard_snapshot))
         .layer(
             CacheControlMiddleware::new(Duration::from_mins(10))
                 .with_stale_while_revalidate(Duration::from_mins(10))
@@ -1,5 +1,4 @@
 use core::time::Duration;
-use std::collections::HashMap;
 
 use axum::Json;
 use axum::extract::{Path, State};
@@ -9,29 +8,24 @@ use base64::Engine;
 use base64::prelude::BASE64_STANDARD;
 use cached::TimedCache;
 use cached::proc_macro::cached;
-use clickhouse::Row;
-use futures::join;
 use serde::Deserialize;
 use tracing::warn;
 use utoipa::IntoParams;
-use valveprotos::deadlock::{
-    CMsgClientToGcGetLeaderboard, CMsgClientToGcGetLeaderboardResponse, EgcCitadelClientMessages,
-};
+use valveprotos::deadlock::{CMsgClientToGcGetLeaderboard, CMsgClientToGcGetLeaderboardResponse};
 
 use crate::context::AppState;
 use crate::error::{APIError, APIResult};
 use crate::routes::v1::leaderboard::types::{Leaderboard, LeaderboardRegion};
+use crate::services::leaderboard_resolver::resolve_account_ids;
 use crate::services::steam::client::SteamClient;
-use crate::services::steam::types::{
-    SteamProxyQuery, SteamProxyRawResponse, SteamProxyResponse, SteamProxyResult,
-};
+use crate::services::steam::types::{SteamProxyRawResponse, SteamProxyResponse, SteamProxyResult};
 
 #[derive(Debug, Deserialize, IntoParams)]
 pub(super) struct LeaderboardQuery {
     /// The region to fetch the leaderboard for.
     #[serde(default)]
     #[param(inline)]
-    region: LeaderboardRegion,
+    pub(super) region: LeaderboardRegion,
 }
 
 #[derive(Debug, Deserialize, IntoParams)]
@@ -61,54 +55,7 @@ pub(crate) async fn fetch_leaderboard_raw(
         leaderboard_region: Some(region as i32),
         hero_id,
     };
-    steam_client
-        .call_steam_proxy_raw(SteamProxyQuery {
-            msg_type: EgcCitadelClientMessages::KEMsgClientToGcGetLeaderboard,
-            msg,
-            in_all_groups: None,
-            in_any_groups: None,
-            cooldown_time: Duration::from_mins(1),
-            request_timeout: Duration::from_secs(2),
-            soft_cooldown_millis: None,
-            username: None,
-        })
-        .await
-}
-
-#[cached(
-    ty = "TimedCache<u8, HashMap<String, Vec<u32>>>",
-    create = "{ TimedCache::with_lifespan(std::time::Duration::from_secs(24 * 60 * 60)) }",
-    result = true,
-    convert = "{ 0 }",
-    sync_writes = "default"
-)]
-async fn fetch_all_steam_names(
-    ch_client: &clickhouse::Client,
-) -> clickhouse::error::Result<HashMap<String, Vec<u32>>> {
-    #[derive(serde::Deserialize, Row)]
-    struct CHResponse {
-        name: String,
-        account_id: u32,
-    }
-
-    let mut out = HashMap::new();
-    let results = ch_client
-        .query(
-            "
-                SELECT DISTINCT assumeNotNull(name) as name, account_id
-                FROM steam_profiles
-                ARRAY JOIN [personaname, realname] AS name
-                WHERE name IS NOT NULL AND not empty(name)
-            ",
-        )
-        .fetch_all::<CHResponse>()
-        .await?;
-    for row in results {
-        out.entry(row.name)
-            .or_insert_with(Vec::new)
-            .push(row.account_id);
-    }
-    Ok(out)
+    steam_client.gc(msg).raw().await
 }
 
 #[utoipa::path(
@@ -237,29 +184,22 @@ pub(super) async fn leaderboard(
     State(state): State<AppState>,
     Path(LeaderboardQuery { region }): Path<LeaderboardQuery>,
 ) -> APIResult<impl IntoResponse> {
-    let (raw_leaderboard, steam_names) = join!(
-        fetch_leaderboard_raw(&state.steam_client, region, None),
-        fetch_all_steam_names(&state.ch_client_ro),
-    );
+    let raw_leaderboard = fetch_leaderboard_raw(&state.steam_client, region, None).await?;
     let proto_leaderboard: SteamProxyResponse<CMsgClientToGcGetLeaderboardResponse> =
-        raw_leaderboard?.try_into()?;
-    let mut leaderboard: APIResult<Leaderboard> = proto_leaderboard.msg.try_into();
-    match steam_names {
-        Ok(steam_names) => {
-            if let Ok(leaderboard) = &mut leaderboard {
-                for entry in &mut leaderboard.entries {
-                    if let Some(ref account_name) = entry.account_name {
-                        entry.possible_account_ids =
-                            steam_names.get(account_name).cloned().unwrap_or_default();
-                    }
-                }
-            }
-        }
-        Err(e) => {
-            warn!("Failed to fetch steam names: {e}");
-        }
+        raw_leaderboard.try_into()?;
+    let mut leaderboard: Leaderboard = proto_leaderboard.msg.try_into()?;
+    if let Err(e) = resolve_account_ids(
+        &state.ch_client,
+        &state.ch_client_ro,
+        region,
+        None,
+        &mut leaderboard,
+    )
+    .await
+    {
+        warn!("Failed to resolve leaderboard account ids: {e}");
     }
-    leaderboard.map(Json)
+    Ok(Json(leaderboard))
 }
 
 #[utoipa::path(
@@ -298,27 +238,21 @@ pub(super) async fn leaderboard_hero(
             format!("Invalid hero_id: {hero_id}"),
         ));
     }
-    let (raw_leaderboard, steam_names) = join!(
-        fetch_leaderboard_raw(&state.steam_client, region, hero_id.into()),
-        fetch_all_steam_names(&state.ch_client_ro),
-    );
+    let raw_leaderboard =
+        fetch_leaderboard_raw(&state.steam_client, region, hero_id.into()).await?;
     let proto_leaderboard: SteamProxyResponse<CMsgClientToGcGetLeaderboardResponse> =
-        raw_leaderboard?.try_into()?;
-    let mut leaderboard: APIResult<Leaderboard> = proto_leaderboard.msg.try_into();
-    match steam_names {
-        Ok(steam_names) => {
-            if let Ok(leaderboard) = &mut leaderboard {
-                for entry in &mut leaderboard.entries {
-                    if let Some(ref account_name) = entry.account_name {
-                        entry.possible_account_ids =
-                            steam_names.get(account_name).cloned().unwrap_or_default();
-                    }
-                }
-            }
-        }
-        Err(e) => {
-            warn!("Failed to fetch steam names: {e}");
-        }
+        raw_leaderboard.try_into()?;
+    let mut leaderboard: Leaderboard = proto_leaderboard.msg.try_into()?;
+    if let Err(e) = resolve_account_ids(
+        &state.ch_client,
+        &state.ch_client_ro,
+        region,
+        Some(hero_id),
+        &mut leaderboard,
+    )
+    .await
+    {
+        warn!("Failed to resolve leaderboard account ids: {e}");
     }
-    leaderboard.map(Json)
+    Ok(Json(leaderboard))
 }
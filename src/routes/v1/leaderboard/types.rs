@@ -1,5 +1,8 @@
+use chrono::{DateTime, Utc};
+use clickhouse::Row;
 use itertools::Itertools;
 use serde::{Deserialize, Serialize};
+use strum::EnumIter;
 use utoipa::ToSchema;
 use valveprotos::deadlock::{
     CMsgClientToGcGetLeaderboardResponse, c_msg_client_to_gc_get_leaderboard_response,
@@ -7,7 +10,7 @@ use valveprotos::deadlock::{
 
 use crate::error::APIError;
 
-#[derive(Debug, Clone, Copy, Deserialize, ToSchema, Default, Eq, PartialEq, Hash)]
+#[derive(Debug, Clone, Copy, Deserialize, ToSchema, Default, Eq, PartialEq, Hash, EnumIter)]
 #[repr(i32)]
 pub(crate) enum LeaderboardRegion {
     #[default]
@@ -22,9 +25,13 @@ pub(crate) enum LeaderboardRegion {
 pub(crate) struct LeaderboardEntry {
     /// The account name of the player.
     pub(crate) account_name: Option<String>,
-    /// The possible account IDs of the player. **CAVEAT: This is not always correct, as Steam account names are not unique.**
+    /// The most likely account ID of the player, see `account_id_confidence`.
+    pub(crate) account_id: Option<u32>,
+    /// How confident the resolution of `account_id` is, between 0 and 1. Based on the name history, the rank consistency and the recent activity of all accounts that used the account name.
+    pub(crate) account_id_confidence: Option<f64>,
+    /// The possible account IDs of the player, the most likely first. **CAVEAT: This is not always correct, as Steam account names are not unique.**
     #[serde(default)]
-    pub(super) possible_account_ids: Vec<u32>,
+    pub(crate) possible_account_ids: Vec<u32>,
     /// The rank of the player (tier = first digits, subtier = last digit). See more: <https://assets.deadlock-api.com/v2/ranks>
     pub(crate) rank: Option<u32>,
     /// The top hero IDs of the player. See more: <https://assets.deadlock-api.com/v2/heroes>
@@ -42,6 +49,8 @@ impl From<c_msg_client_to_gc_get_leaderboard_response::LeaderboardEntry> for Lea
     fn from(value: c_msg_client_to_gc_get_leaderboard_response::LeaderboardEntry) -> Self {
         Self {
             account_name: value.account_name,
+            account_id: None,
+            account_id_confidence: None,
             possible_account_ids: vec![],
             rank: value.rank,
             top_hero_ids: value.top_hero_ids,
@@ -75,3 +84,112 @@ impl TryFrom<CMsgClientToGcGetLeaderboardResponse> for Leaderboard {
         })
     }
 }
+
+/// A leaderboard entry as stored in the hourly snapshots.
+#[derive(Debug, Clone, Row, Serialize, Deserialize)]
+pub(crate) struct LeaderboardSnapshotRow {
+    #[serde(with = "clickhouse::serde::chrono::datetime")]
+    pub(crate) snapshot_time: DateTime<Utc>,
+    pub(crate) region: u8,
+    /// 0 for the overall leaderboard
+    pub(crate) hero_id: u32,
+    pub(crate) rank: u32,
+    pub(crate) account_name: String,
+    pub(crate) badge_level: Option<u32>,
+    pub(crate) top_hero_ids: Vec<u32>,
+}
+
+impl LeaderboardSnapshotRow {
+    pub(crate) fn from_leaderboard(
+        snapshot_time: DateTime<Utc>,
+        region: LeaderboardRegion,
+        hero_id: Option<u32>,
+        leaderboard: Leaderboard,
+    ) -> Vec<Self> {
+        leaderboard
+            .entries
+            .into_iter()
+            .zip(1..)
+            .filter_map(|(entry, position)| {
+                Some(Self {
+                    snapshot_time,
+                    region: region as u8,
+                    hero_id: hero_id.unwrap_or_default(),
+                    rank: entry.rank.unwrap_or(position),
+                    account_name: entry.account_name?,
+                    badge_level: entry.badge_level,
+                    top_hero_ids: entry.top_hero_ids,
+                })
+            })
+            .collect()
+    }
+}
+
+impl From<LeaderboardSnapshotRow> for LeaderboardEntry {
+    fn from(row: LeaderboardSnapshotRow) -> Self {
+        Self {
+            account_name: Some(row.account_name),
+            account_id: None,
+            account_id_confidence: None,
+            possible_account_ids: vec![],
+            rank: Some(row.rank),
+            top_hero_ids: row.top_hero_ids,
+            badge_level: row.badge_level,
+            ranked_rank: row.badge_level.map(|b| b / 10),
+            ranked_subrank: row.badge_level.map(|b| b % 10),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use valveprotos::deadlock::c_msg_client_to_gc_get_leaderboard_response::LeaderboardEntry as ProtoEntry;
+
+    use super::*;
+
+    #[test]
+    fn test_snapshot_rows_from_leaderboard() {
+        let leaderboard: Leaderboard = CMsgClientToGcGetLeaderboardResponse {
+            result: Some(c_msg_client_to_gc_get_leaderboard_response::EResult::KESuccess as i32),
+            entries: vec![
+                ProtoEntry {
+                    account_name: Some("first".to_owned()),
+                    rank: Some(1),
+                    badge_level: Some(116),
+                    top_hero_ids: vec![1, 2],
+                    ..Default::default()
+                },
+                ProtoEntry {
+                    account_name: None,
+                    rank: Some(2),
+                    ..Default::default()
+                },
+                ProtoEntry {
+                    account_name: Some("third".to_owned()),
+                    rank: None,
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        }
+        .try_into()
+        .unwrap();
+        let rows = LeaderboardSnapshotRow::from_leaderboard(
+            Utc::now(),
+            LeaderboardRegion::Asia,
+            None,
+            leaderboard,
+        );
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].account_name, "first");
+        assert_eq!(rows[0].region, 2);
+        assert_eq!(rows[0].hero_id, 0);
+        assert_eq!(rows[0].top_hero_ids, vec![1, 2]);
+        assert_eq!(rows[1].account_name, "third");
+        assert_eq!(rows[1].rank, 3);
+
+        let entry: LeaderboardEntry = rows[0].clone().into();
+        assert_eq!(entry.ranked_rank, Some(11));
+        assert_eq!(entry.ranked_subrank, Some(6));
+    }
+}
@@ -1,5 +1,3 @@
-use core::time::Duration;
-
 use axum::Json;
 use axum::extract::State;
 use axum::response::IntoResponse;
@@ -14,13 +12,11 @@ use serde::Deserialize;
 use utoipa::IntoParams;
 use valveprotos::deadlock::{
     CMsgClientToGcGetActiveMatches, CMsgClientToGcGetActiveMatchesResponse,
-    EgcCitadelClientMessages,
 };
 
 use crate::context::AppState;
 use crate::error::{APIError, APIResult};
 use crate::routes::v1::matches::types::ActiveMatch;
-use crate::services::steam::types::SteamProxyQuery;
 use crate::utils::parse::{comma_separated_deserialize_option, parse_steam_id_option};
 
 #[derive(Deserialize, IntoParams)]
@@ -45,16 +41,8 @@ pub(super) struct ActiveMatchesQuery {
 async fn fetch_active_matches_raw(state: &AppState) -> APIResult<Vec<u8>> {
     let steam_response = state
         .steam_client
-        .call_steam_proxy_raw(SteamProxyQuery {
-            msg_type: EgcCitadelClientMessages::KEMsgClientToGcGetActiveMatches,
-            msg: CMsgClientToGcGetActiveMatches::default(),
-            in_all_groups: Some(vec!["LowRateLimitApis".to_owned()]),
-            in_any_groups: None,
-            cooldown_time: Duration::from_mins(10),
-            request_timeout: Duration::from_secs(2),
-            username: None,
-            soft_cooldown_millis: None,
-        })
+        .gc(CMsgClientToGcGetActiveMatches::default())
+        .raw()
         .await?;
     Ok(BASE64_STANDARD.decode(&steam_response.data)?)
 }
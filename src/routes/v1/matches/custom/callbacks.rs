@@ -0,0 +1,342 @@
+use axum::Json;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum_extra::extract::Query;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use strum::{Display, EnumString};
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+use crate::context::AppState;
+use crate::error::{APIError, APIResult};
+use crate::services::rate_limiter::ApiKeyScope;
+use crate::services::rate_limiter::extractor::RateLimitKey;
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema, Display, EnumString,
+)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub(super) enum CallbackDeliveryStatus {
+    /// The callback waits for its next attempt.
+    Pending,
+    /// The receiver accepted the callback.
+    Delivered,
+    /// Every attempt failed, the callback is in the dead-letter queue.
+    Dead,
+}
+
+struct CallbackDeliveryRow {
+    id: Uuid,
+    party_id: i64,
+    event: String,
+    url: String,
+    status: String,
+    attempts: i32,
+    next_attempt_at: DateTime<Utc>,
+    created_at: DateTime<Utc>,
+    delivered_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct CallbackDelivery {
+    id: Uuid,
+    party_id: String,
+    /// The event of the callback: `settings`, `start` or `result`.
+    event: String,
+    url: String,
+    status: CallbackDeliveryStatus,
+    attempts: i32,
+    /// When the callback is attempted next, if it is pending.
+    next_attempt_at: Option<DateTime<Utc>>,
+    created_at: DateTime<Utc>,
+    delivered_at: Option<DateTime<Utc>>,
+}
+
+impl From<CallbackDeliveryRow> for CallbackDelivery {
+    fn from(row: CallbackDeliveryRow) -> Self {
+        let status = row.status.parse().unwrap_or(CallbackDeliveryStatus::Dead);
+        Self {
+            id: row.id,
+            party_id: row.party_id.cast_unsigned().to_string(),
+            event: row.event,
+            url: row.url,
+            status,
+            attempts: row.attempts,
+            next_attempt_at: (status == CallbackDeliveryStatus::Pending)
+                .then_some(row.next_attempt_at),
+            created_at: row.created_at,
+            delivered_at: row.delivered_at,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct CallbackDeliveryAttempt {
+    attempt: i32,
+    /// The status code of the response, if the receiver responded.
+    status_code: Option<i32>,
+    /// Why the attempt failed, e.g. the status code or a connection error.
+    error: Option<String>,
+    duration_ms: i32,
+    attempted_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct CallbackDeliveryWithAttempts {
+    #[serde(flatten)]
+    delivery: CallbackDelivery,
+    attempt_history: Vec<CallbackDeliveryAttempt>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub(super) struct CallbackDeliveriesQuery {
+    /// Filter the callbacks by their status, use `dead` to list the dead-letter queue.
+    status: Option<CallbackDeliveryStatus>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub(super) struct CallbackDeliveryPath {
+    /// The ID of the callback.
+    id: Uuid,
+}
+
+async fn fetch_callback_delivery(
+    state: &AppState,
+    id: Uuid,
+    api_key: Option<Uuid>,
+) -> APIResult<CallbackDeliveryRow> {
+    sqlx::query_as!(
+        CallbackDeliveryRow,
+        r#"
+        SELECT id, party_id, event, url, status, attempts, next_attempt_at, created_at, delivered_at
+        FROM callback_deliveries
+        WHERE id = $1 AND api_key = $2
+        "#,
+        id,
+        api_key
+    )
+    .fetch_optional(&state.pg_client)
+    .await?
+    .ok_or_else(|| {
+        APIError::status_msg(StatusCode::NOT_FOUND, format!("Callback {id} not found"))
+    })
+}
+
+#[utoipa::path(
+    get,
+    path = "/callbacks",
+    params(CallbackDeliveriesQuery),
+    responses(
+        (status = 200, description = "Successfully fetched the callbacks.", body = [CallbackDelivery]),
+        (status = FORBIDDEN, description = "API key is missing the `custom_matches` scope"),
+        (status = INTERNAL_SERVER_ERROR, description = "Fetching the callbacks failed")
+    ),
+    security(("api_key_header" = ["custom_matches"]), ("api_key_query" = ["custom_matches"])),
+    tags = ["Custom Matches"],
+    summary = "List Callbacks",
+    description = "
+This endpoint lists the 100 most recent callbacks sent for custom matches of your API key.
+
+Callbacks are retried with exponential backoff, starting at 30 seconds and capped at 1 hour.
+After 10 failed attempts a callback is moved to the dead-letter queue (status `dead`), from where it can be retried with `POST /v1/matches/custom/callbacks/{id}/retry`.
+
+**Verifying callbacks:**
+Every callback is signed with the callback secret returned when creating the match:
+- `X-Callback-Timestamp`: the unix timestamp of the attempt.
+- `X-Callback-Signature`: `sha256=` followed by the hex encoded HMAC-SHA256 of `{timestamp}.{body}`.
+
+Reject callbacks with an invalid signature or a timestamp older than 5 minutes, to protect against replayed requests.
+The `X-Callback-Id` header is the same for every attempt of a callback and can be used to ignore duplicates.
+
+Requires an API key with the `custom_matches` scope.
+
+### Rate Limits:
+| Type | Limit |
+| ---- | ----- |
+| IP | API-Key ONLY |
+| Key | - |
+| Global | - |
+"
+)]
+pub(super) async fn list_callbacks(
+    Query(CallbackDeliveriesQuery { status }): Query<CallbackDeliveriesQuery>,
+    rate_limit_key: RateLimitKey,
+    State(state): State<AppState>,
+) -> APIResult<impl IntoResponse> {
+    state
+        .rate_limit_client
+        .require_scope(&rate_limit_key, ApiKeyScope::CustomMatches)
+        .await?;
+    let deliveries = sqlx::query_as!(
+        CallbackDeliveryRow,
+        r#"
+        SELECT id, party_id, event, url, status, attempts, next_attempt_at, created_at, delivered_at
+        FROM callback_deliveries
+        WHERE api_key = $1 AND ($2::text IS NULL OR status = $2)
+        ORDER BY created_at DESC
+        LIMIT 100
+        "#,
+        rate_limit_key.api_key,
+        status.map(|s| s.to_string())
+    )
+    .fetch_all(&state.pg_client)
+    .await?;
+    Ok(Json(
+        deliveries
+            .into_iter()
+            .map(CallbackDelivery::from)
+            .collect::<Vec<_>>(),
+    ))
+}
+
+#[utoipa::path(
+    get,
+    path = "/callbacks/{id}",
+    params(CallbackDeliveryPath),
+    responses(
+        (status = 200, description = "Successfully fetched the callback.", body = CallbackDeliveryWithAttempts),
+        (status = FORBIDDEN, description = "API key is missing the `custom_matches` scope"),
+        (status = NOT_FOUND, description = "Callback not found"),
+        (status = INTERNAL_SERVER_ERROR, description = "Fetching the callback failed")
+    ),
+    security(("api_key_header" = ["custom_matches"]), ("api_key_query" = ["custom_matches"])),
+    tags = ["Custom Matches"],
+    summary = "Get Callback",
+    description = "
+This endpoint returns a callback of your API key with all its delivery attempts, including the status codes returned by your receiver.
+Response bodies of your receiver are not stored.
+
+Requires an API key with the `custom_matches` scope.
+
+### Rate Limits:
+| Type | Limit |
+| ---- | ----- |
+| IP | API-Key ONLY |
+| Key | - |
+| Global | - |
+"
+)]
+pub(super) async fn get_callback(
+    Path(CallbackDeliveryPath { id }): Path<CallbackDeliveryPath>,
+    rate_limit_key: RateLimitKey,
+    State(state): State<AppState>,
+) -> APIResult<impl IntoResponse> {
+    state
+        .rate_limit_client
+        .require_scope(&rate_limit_key, ApiKeyScope::CustomMatches)
+        .await?;
+    let delivery = fetch_callback_delivery(&state, id, rate_limit_key.api_key).await?;
+    let attempt_history = sqlx::query_as!(
+        CallbackDeliveryAttempt,
+        r#"
+        SELECT attempt, status_code, error, duration_ms, attempted_at
+        FROM callback_delivery_attempts
+        WHERE delivery_id = $1
+        ORDER BY attempt
+        "#,
+        id
+    )
+    .fetch_all(&state.pg_client)
+    .await?;
+    Ok(Json(CallbackDeliveryWithAttempts {
+        delivery: delivery.into(),
+        attempt_history,
+    }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/callbacks/{id}/retry",
+    params(CallbackDeliveryPath),
+    responses(
+        (status = 200, description = "Successfully queued the callback again.", body = CallbackDelivery),
+        (status = FORBIDDEN, description = "API key is missing the `custom_matches` scope"),
+        (status = NOT_FOUND, description = "Callback not found"),
+        (status = CONFLICT, description = "The callback is not in the dead-letter queue"),
+        (status = INTERNAL_SERVER_ERROR, description = "Retrying the callback failed")
+    ),
+    security(("api_key_header" = ["custom_matches"]), ("api_key_query" = ["custom_matches"])),
+    tags = ["Custom Matches"],
+    summary = "Retry Callback",
+    description = "
+This endpoint moves a callback from the dead-letter queue back into the queue, where it gets another 10 attempts.
+The attempts are numbered on, so the attempt history of the callback is kept.
+
+Requires an API key with the `custom_matches` scope.
+
+### Rate Limits:
+| Type | Limit |
+| ---- | ----- |
+| IP | API-Key ONLY |
+| Key | - |
+| Global | - |
+"
+)]
+pub(super) async fn retry_callback(
+    Path(CallbackDeliveryPath { id }): Path<CallbackDeliveryPath>,
+    rate_limit_key: RateLimitKey,
+    State(state): State<AppState>,
+) -> APIResult<impl IntoResponse> {
+    state
+        .rate_limit_client
+        .require_scope(&rate_limit_key, ApiKeyScope::CustomMatches)
+        .await?;
+    let delivery = sqlx::query_as!(
+        CallbackDeliveryRow,
+        r#"
+        UPDATE callback_deliveries
+        SET status = 'pending', attempts_before_retry = attempts, next_attempt_at = now()
+        WHERE id = $1 AND api_key = $2 AND status = 'dead'
+        RETURNING id, party_id, event, url, status, attempts, next_attempt_at, created_at, delivered_at
+        "#,
+        id,
+        rate_limit_key.api_key
+    )
+    .fetch_optional(&state.pg_client)
+    .await?;
+    if let Some(delivery) = delivery {
+        return Ok(Json(CallbackDelivery::from(delivery)));
+    }
+    let delivery =
+        CallbackDelivery::from(fetch_callback_delivery(&state, id, rate_limit_key.api_key).await?);
+    Err(APIError::status_msg(
+        StatusCode::CONFLICT,
+        format!("Callback {id} is {}", delivery.status),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(status: &str) -> CallbackDeliveryRow {
+        CallbackDeliveryRow {
+            id: Uuid::nil(),
+            party_id: 42,
+            event: "result".to_owned(),
+            url: "https://example.com/result".to_owned(),
+            status: status.to_owned(),
+            attempts: 1,
+            next_attempt_at: Utc::now(),
+            created_at: Utc::now(),
+            delivered_at: None,
+        }
+    }
+
+    #[test]
+    fn test_pending_delivery_has_next_attempt() {
+        let delivery = CallbackDelivery::from(row("pending"));
+        assert_eq!(delivery.status, CallbackDeliveryStatus::Pending);
+        assert!(delivery.next_attempt_at.is_some());
+    }
+
+    #[test]
+    fn test_dead_delivery_has_no_next_attempt() {
+        let delivery = CallbackDelivery::from(row("dead"));
+        assert_eq!(delivery.status, CallbackDeliveryStatus::Dead);
+        assert!(delivery.next_attempt_at.is_none());
+    }
+}
@@ -15,27 +15,33 @@ use serde::{Deserialize, Serialize};
 use tokio::time::sleep;
 use tracing::{debug, error};
 use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
 use valveprotos::deadlock::c_msg_client_to_gc_party_action::EAction;
 use valveprotos::deadlock::{
-    CMsgClientToGcPartyAction, CMsgClientToGcPartyActionResponse, CMsgClientToGcPartyCreate,
-    CMsgClientToGcPartyCreateResponse, CMsgPartyMmInfo, CMsgRegionPingTimesClient,
-    ECitadelBotDifficulty, ECitadelMmPreference, EgcCitadelClientMessages,
+    CMsgClientToGcPartyAction, CMsgClientToGcPartyCreate, CMsgClientToGcPartyCreateResponse,
+    CMsgPartyMmInfo, CMsgRegionPingTimesClient, ECitadelBotDifficulty, ECitadelMmPreference,
     c_msg_client_to_gc_party_action_response, cso_citadel_party,
 };
 use valveprotos::gcsdk::EgcPlatform;
 
 use crate::context::AppState;
 use crate::error::{APIError, APIResult};
-use crate::routes::v1::matches::custom::utils;
+use crate::routes::v1::matches::custom::members::{
+    CustomMatchSlotAssignment, validate_slot_assignments,
+};
+use crate::routes::v1::matches::custom::{lifecycle, utils};
 use crate::routes::v1::matches::types::{GameMode, ServerRegion};
-use crate::services::rate_limiter::Quota;
+use crate::services::callbacks::validate_callback_url;
 use crate::services::rate_limiter::extractor::RateLimitKey;
+use crate::services::rate_limiter::{ApiKeyScope, Quota};
 use crate::services::steam::client::SteamClient;
-use crate::services::steam::types::{SteamProxyQuery, SteamProxyResponse};
+use crate::services::steam::types::SteamProxyResponse;
 
 #[derive(Clone, Deserialize, IntoParams, ToSchema)]
-pub(super) struct CreateCustomRequest {
-    /// If a callback url is provided, we will send a POST request to this url when the match starts.
+pub(crate) struct CreateCustomRequest {
+    /// If a callback url is provided, we will send POST requests to it when the lobby settings
+    /// change, the match starts and the match finished.
+    /// It has to be a http or https url of a public address.
     #[serde(default)]
     #[param(default)]
     callback_url: Option<String>,
@@ -65,18 +71,39 @@ pub(super) struct CreateCustomRequest {
     #[serde(default)]
     #[param(default)]
     duplicate_heroes_enabled: Option<bool>,
+    /// Accounts to assign to player slots, before they join the lobby.
+    #[serde(default)]
+    #[param(default)]
+    match_slots: Option<Vec<CustomMatchSlotAssignment>>,
 }
 
 #[derive(Serialize, ToSchema)]
-struct CreateCustomResponse {
+pub(crate) struct CreateCustomResponse {
     party_id: String,
     party_code: String,
-    /// If a callback url is provided, this is the secret that should be used to verify the callback.
-    /// The secret is a base64 encoded random string. To verify it you should compare it with the X-Callback-Secret header.
+    /// If a callback url is provided, this is the secret the callbacks are signed with, see
+    /// `GET /v1/matches/custom/callbacks`. The secret is a base64 encoded random string.
     /// If no callback url is provided, this will be None.
     callback_secret: Option<String>,
 }
 
+/// A lobby created by a bot account
+pub(crate) struct CreatedLobby {
+    pub(crate) party_id: u64,
+    pub(crate) party_code: String,
+    pub(crate) callback_secret: Option<String>,
+}
+
+impl From<CreatedLobby> for CreateCustomResponse {
+    fn from(lobby: CreatedLobby) -> Self {
+        Self {
+            party_id: lobby.party_id.to_string(),
+            party_code: lobby.party_code,
+            callback_secret: lobby.callback_secret,
+        }
+    }
+}
+
 fn generate_callback_secret(length_bytes: usize) -> String {
     let mut secret_bytes = vec![0u8; length_bytes];
     ThreadRng::default().fill_bytes(&mut secret_bytes);
@@ -117,7 +144,11 @@ async fn create_party(
         mm_preference: (ECitadelMmPreference::KECitadelMmPreferenceCasual as i32).into(),
         private_lobby_settings: cso_citadel_party::PrivateLobbySettings {
             min_roster_size: settings.as_ref().and_then(|m| m.min_roster_size),
-            match_slots: vec![],
+            match_slots: settings
+                .as_ref()
+                .and_then(|m| m.match_slots.as_ref())
+                .map(|slots| slots.iter().copied().map(Into::into).collect())
+                .unwrap_or_default(),
             randomize_lanes: settings.as_ref().and_then(|m| m.randomize_lanes),
             server_region: server_region.map(Into::into),
             is_publicly_visible: settings
@@ -132,20 +163,7 @@ async fn create_party(
         dev_force_hideout: None,
         hideout_search_key: None,
     };
-    let result: SteamProxyResponse<CMsgClientToGcPartyCreateResponse> = state
-        .steam_client
-        .call_steam_proxy(SteamProxyQuery {
-            msg_type: EgcCitadelClientMessages::KEMsgClientToGcPartyCreate,
-            msg,
-            in_all_groups: None,
-            in_any_groups: None,
-            cooldown_time: Duration::from_hours(2),
-            request_timeout: Duration::from_secs(2),
-            username: None,
-            soft_cooldown_millis: None,
-        })
-        .await?;
-    Ok(result)
+    Ok(state.steam_client.gc(msg).await?)
 }
 
 async fn switch_to_spectator_slot(
@@ -158,22 +176,10 @@ async fn switch_to_spectator_slot(
         party_id: party_id.into(),
         target_account_id: account_id.into(),
         action_id: (EAction::KESetPlayerSlot as i32).into(),
-        uint_value: 31.into(),
+        uint_value: u64::from(utils::SPECTATOR_SLOT).into(),
         ..Default::default()
     };
-    let response: CMsgClientToGcPartyActionResponse = steam_client
-        .call_steam_proxy(SteamProxyQuery {
-            msg_type: EgcCitadelClientMessages::KEMsgClientToGcPartyAction,
-            msg,
-            in_all_groups: None,
-            in_any_groups: None,
-            cooldown_time: Duration::from_secs(0),
-            request_timeout: Duration::from_secs(2),
-            username: username.into(),
-            soft_cooldown_millis: None,
-        })
-        .await?
-        .msg;
+    let response = steam_client.gc(msg).username(username).await?.msg;
     if response
         .result
         .is_none_or(|r| r != c_msg_client_to_gc_party_action_response::EResponse::KESuccess as i32)
@@ -185,6 +191,118 @@ async fn switch_to_spectator_slot(
     Ok(())
 }
 
+/// Creates a lobby with a bot account, which switches to the spectator slot and readies up.
+///
+/// The lobby is tracked in the lifecycle of custom matches, if an API key is given.
+#[allow(clippy::too_many_lines)]
+pub(crate) async fn create_lobby(
+    state: &mut AppState,
+    api_key: Option<Uuid>,
+    payload: Option<CreateCustomRequest>,
+) -> APIResult<CreatedLobby> {
+    if let Some(match_slots) = payload.as_ref().and_then(|p| p.match_slots.as_deref()) {
+        validate_slot_assignments(match_slots)
+            .map_err(|msg| APIError::status_msg(StatusCode::BAD_REQUEST, msg))?;
+    }
+
+    // Validate the callback url before creating the party, so no party is left behind
+    let callback_url = payload
+        .as_ref()
+        .and_then(|p| p.callback_url.as_deref())
+        .map(|c| {
+            let callback_url = Url::parse(c).map_err(|e| {
+                error!("Failed to parse callback url: {e}");
+                APIError::status_msg(StatusCode::BAD_REQUEST, "Failed to parse callback url")
+            })?;
+            validate_callback_url(&callback_url)
+                .map_err(|msg| APIError::status_msg(StatusCode::BAD_REQUEST, msg))?;
+            Ok::<_, APIError>(callback_url.to_string())
+        })
+        .transpose()?;
+
+    let SteamProxyResponse {
+        username,
+        msg: created_party,
+    } = tryhard::retry_fn(|| create_party(state, payload.clone()))
+        .retries(5)
+        .linear_backoff(Duration::from_millis(100))
+        .await?;
+    debug!("Created party: {created_party:?}");
+    let Some(party_id) = created_party.party_id.filter(|&p| p > 0) else {
+        error!(
+            "Failed to create party, created_party is {:?}",
+            created_party
+        );
+        return Err(APIError::internal("Failed to create party"));
+    };
+
+    // Callbacks are sent by the custom match tracker, signed with the secret
+    let callback = callback_url.map(|callback_url| (callback_url, generate_callback_secret(32)));
+
+    // Track the lifecycle of the lobby, the API key is present as callers check its scope
+    if let Some(api_key) = api_key {
+        lifecycle::track_lobby(
+            &state.pg_client,
+            party_id,
+            api_key,
+            callback.as_ref().map(|(u, s)| (u.as_str(), s.as_str())),
+        )
+        .await?;
+    }
+    let callback_secret = callback.map(|(_, secret)| secret);
+
+    let steam_client = state.steam_client.clone();
+    let username_clone = username.clone();
+    tokio::spawn(async move {
+        sleep(Duration::from_mins(15)).await; // Wait for 15 minutes
+
+        // Leave the party
+        let result = utils::leave_party(&steam_client, username_clone, party_id).await;
+        if let Err(e) = result {
+            error!("Failed to leave party: {e}");
+        }
+    });
+
+    let party_code = utils::get_party_info_with_retries(&mut state.redis_client, party_id).await?;
+    let Some(party_code) = party_code else {
+        error!("Failed to retrieve party code");
+        return Err(APIError::internal("Failed to retrieve party code"));
+    };
+    debug!("Retrieved party code: {party_code}");
+
+    let Some((_, account_id, party_code)) = party_code.split(':').collect_tuple() else {
+        error!("Failed to parse party code");
+        return Err(APIError::internal("Failed to parse party code"));
+    };
+
+    let account_id = account_id
+        .parse()
+        .map_err(|_| APIError::internal("Failed to parse account id".to_owned()))?;
+
+    switch_to_spectator_slot(&state.steam_client, username.clone(), party_id, account_id).await?;
+
+    if !payload
+        .as_ref()
+        .and_then(|p| p.disable_auto_ready)
+        .unwrap_or_default()
+    {
+        utils::make_ready(&state.steam_client, username.clone(), party_id, true).await?;
+    }
+
+    Ok(CreatedLobby {
+        party_id,
+        party_code: party_code.to_owned(),
+        callback_secret,
+    })
+}
+
+/// Makes the bot leave a created lobby that can't be used, and stops tracking it.
+pub(crate) async fn discard_lobby(state: &mut AppState, party_id: u64) -> APIResult<()> {
+    lifecycle::expire_lobby(&state.pg_client, party_id).await?;
+    let (username, _) = utils::get_party_bot(&mut state.redis_client, party_id).await?;
+    utils::leave_party(&state.steam_client, username, party_id).await
+}
+
 #[utoipa::path(
     post,
     path = "/create",
@@ -192,9 +310,11 @@ async fn switch_to_spectator_slot(
     responses(
         (status = 200, description = "Successfully fetched custom match id.", body = CreateCustomResponse),
         (status = BAD_REQUEST, description = "Provided parameters are invalid."),
+        (status = FORBIDDEN, description = "API key is missing the `custom_matches` scope"),
         (status = TOO_MANY_REQUESTS, description = "Rate limit exceeded"),
         (status = INTERNAL_SERVER_ERROR, description = "Creating custom match failed")
     ),
+    security(("api_key_header" = ["custom_matches"]), ("api_key_query" = ["custom_matches"])),
     tags = ["Custom Matches"],
     summary = "Create Match",
     description = "
@@ -208,16 +328,24 @@ This endpoint creates a custom match using a bot account.
 5. The bot marks itself as ready.
 6. You and other players join, ready up, and start the match.
 
+**Slots:**
+Players can be assigned to player slots with `match_slots` and are placed in their slot when they join.
+After creation, players can be moved between teams and slots, kicked, and their heroes locked.
+
 **Callbacks:**
 If a callback URL is provided, POST requests will be sent to it:
-- **settings:** When lobby settings change, a POST is sent to `{callback_url}/settings` with the `CsoCitadelParty` protobuf message as JSON.
-- **match start:** When the match starts, a POST is sent to `{callback_url}` with the match ID.
+- **settings:** When the lobby settings change, a POST is sent to `{callback_url}/settings` with the party state, as returned by `GET /v1/matches/custom/{party_id}`.
+  Changes are picked up within 30 seconds, the first callback is sent once the lobby state is known.
+- **match start:** When the match starts, a POST is sent to `{callback_url}` with the `party_id` and `match_id`.
+- **match result:** When the match finished and its metadata is available, a POST is sent to `{callback_url}/result` with the match metadata.
 
-_Protobuf definitions: [https://github.com/SteamDatabase/Protobufs](https://github.com/SteamDatabase/Protobufs)_
+All callbacks are signed with the callback secret and retried until your receiver accepts them, see `GET /v1/matches/custom/callbacks`.
 
 **Note:**
 The bot will leave the match 15 minutes after creation, regardless of match state.
 
+Requires an API key with the `custom_matches` scope.
+
 ### Rate Limits:
 | Type | Limit |
 | ---- | ----- |
@@ -226,12 +354,15 @@ The bot will leave the match 15 minutes after creation, regardless of match stat
 | Global | 1000req/h |
 "
 )]
-#[allow(clippy::too_many_lines)]
 pub(super) async fn create_custom(
     rate_limit_key: RateLimitKey,
     State(mut state): State<AppState>,
     payload: Result<Json<CreateCustomRequest>, JsonRejection>,
 ) -> APIResult<impl IntoResponse> {
+    state
+        .rate_limit_client
+        .require_scope(&rate_limit_key, ApiKeyScope::CustomMatches)
+        .await?;
     state
         .rate_limit_client
         .apply_limits(
@@ -255,95 +386,6 @@ pub(super) async fn create_custom(
         }
     };
 
-    let callback_url = payload.as_ref().and_then(|p| p.callback_url.clone());
-
-    let SteamProxyResponse {
-        username,
-        msg: created_party,
-    } = tryhard::retry_fn(|| create_party(&state, payload.clone()))
-        .retries(5)
-        .linear_backoff(Duration::from_millis(100))
-        .await?;
-    debug!("Created party: {created_party:?}");
-    let Some(party_id) = created_party.party_id.filter(|&p| p > 0) else {
-        error!(
-            "Failed to create party, created_party is {:?}",
-            created_party
-        );
-        return Err(APIError::internal("Failed to create party"));
-    };
-
-    // Store Callback URL & Callback Secret in Redis
-    let callback_secret = match callback_url.map(|c| Url::parse(&c)) {
-        Some(Ok(callback_url)) => {
-            let callback_secret = generate_callback_secret(32);
-            redis::pipe()
-                .set_ex(
-                    format!("{party_id}:callback-url"),
-                    callback_url.to_string(),
-                    20 * 60,
-                )
-                .set_ex(
-                    format!("{party_id}:callback-secret"),
-                    &callback_secret,
-                    20 * 60,
-                )
-                .exec_async(&mut state.redis_client) // Execute the pipeline
-                .await?;
-            Some(callback_secret)
-        }
-        Some(Err(e)) => {
-            error!("Failed to parse callback url: {e}");
-            return Err(APIError::status_msg(
-                StatusCode::BAD_REQUEST,
-                "Failed to parse callback url",
-            ));
-        }
-        None => None,
-    };
-
-    let steam_client = state.steam_client.clone();
-    let username_clone = username.clone();
-    tokio::spawn(async move {
-        sleep(Duration::from_mins(15)).await; // Wait for 15 minutes
-
-        // Leave the party
-        let result = utils::leave_party(&steam_client, username_clone, party_id).await;
-        if let Err(e) = result {
-            error!("Failed to leave party: {e}");
-        }
-    });
-
-    let party_code = utils::get_party_info_with_retries(&mut state.redis_client, party_id).await?;
-    let Some(party_code) = party_code else {
-        error!("Failed to retrieve party code");
-        return Err(APIError::internal("Failed to retrieve party code"));
-    };
-    debug!("Retrieved party code: {party_code}");
-
-    let Some((_, account_id, party_code)) = party_code.split(':').collect_tuple() else {
-        error!("Failed to parse party code");
-        return Err(APIError::internal("Failed to parse party code"));
-    };
-
-    let account_id = account_id
-        .parse()
-        .map_err(|_| APIError::internal("Failed to parse account id".to_owned()))?;
-
-    switch_to_spectator_slot(&state.steam_client, username.clone(), party_id, account_id).await?;
-
-    if !payload
-        .as_ref()
-        .and_then(|p| p.disable_auto_ready)
-        .unwrap_or_default()
-    {
-        utils::make_ready(&state.steam_client, username.clone(), party_id, true).await?;
-    }
-
-    let response = CreateCustomResponse {
-        party_id: party_id.to_string(),
-        party_code: party_code.to_owned(),
-        callback_secret,
-    };
-    Ok(Json(response))
+    let lobby = create_lobby(&mut state, rate_limit_key.api_key, payload).await?;
+    Ok(Json(CreateCustomResponse::from(lobby)))
 }
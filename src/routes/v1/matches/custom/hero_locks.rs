@@ -0,0 +1,263 @@
+use core::time::Duration;
+use std::collections::HashMap;
+
+use axum::Json;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use itertools::Itertools;
+use redis::{AsyncCommands, RedisResult};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use valveprotos::deadlock::CsoCitadelParty;
+
+use crate::context::AppState;
+use crate::error::{APIError, APIResult};
+use crate::routes::v1::matches::custom::lifecycle;
+use crate::routes::v1::matches::custom::members::find_member;
+use crate::routes::v1::matches::custom::party::{PartyIdPath, get_party_state, selected_heroes};
+use crate::services::rate_limiter::extractor::RateLimitKey;
+use crate::services::rate_limiter::{ApiKeyScope, Quota};
+
+/// Hero locks expire together with the other keys of the party.
+const HERO_LOCKS_TTL_SECS: u64 = 20 * 60;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub(super) struct HeroLock {
+    account_id: u32,
+    hero_id: u32,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub(super) struct SetHeroLocksRequest {
+    /// The hero locks, replacing all previous locks. An empty list removes all locks.
+    locks: Vec<HeroLock>,
+}
+
+/// Returns the hero locks of a party, by account id.
+pub(super) async fn get_hero_locks(
+    redis_client: &mut redis::aio::MultiplexedConnection,
+    party_id: u64,
+) -> RedisResult<HashMap<u32, u32>> {
+    redis_client.hgetall(format!("{party_id}:hero-locks")).await
+}
+
+/// Returns the accounts whose hero selection doesn't match their lock.
+///
+/// A lock is satisfied if the locked hero is the only hero the player selected. Locked accounts
+/// that are not in the party anymore are ignored.
+pub(super) fn hero_lock_violations(
+    party: &CsoCitadelParty,
+    hero_locks: &HashMap<u32, u32>,
+) -> Vec<u32> {
+    party
+        .members
+        .iter()
+        .filter_map(|m| {
+            let hero_id = hero_locks.get(&m.account_id())?;
+            let heroes = selected_heroes(m);
+            (heroes.is_empty() || heroes.iter().any(|h| h != hero_id)).then_some(m.account_id())
+        })
+        .sorted()
+        .collect()
+}
+
+#[utoipa::path(
+    get,
+    path = "/{party_id}/hero-locks",
+    params(PartyIdPath),
+    responses(
+        (status = 200, description = "Successfully fetched the hero locks.", body = [HeroLock]),
+        (status = BAD_REQUEST, description = "Provided parameters are invalid."),
+        (status = FORBIDDEN, description = "API key is missing the `custom_matches` scope"),
+        (status = NOT_FOUND, description = "Party not found or not created with your API key."),
+        (status = INTERNAL_SERVER_ERROR, description = "Fetching the hero locks failed")
+    ),
+    security(("api_key_header" = ["custom_matches"]), ("api_key_query" = ["custom_matches"])),
+    tags = ["Custom Matches"],
+    summary = "Get Hero Locks",
+    description = "
+This endpoint returns the hero locks of a custom match lobby.
+
+Only lobbies created with your API key can be fetched.
+
+Requires an API key with the `custom_matches` scope.
+
+### Rate Limits:
+| Type | Limit |
+| ---- | ----- |
+| IP | API-Key ONLY |
+| Key | - |
+| Global | - |
+"
+)]
+pub(super) async fn get_party_hero_locks(
+    Path(PartyIdPath { party_id }): Path<PartyIdPath>,
+    rate_limit_key: RateLimitKey,
+    State(mut state): State<AppState>,
+) -> APIResult<impl IntoResponse> {
+    state
+        .rate_limit_client
+        .require_scope(&rate_limit_key, ApiKeyScope::CustomMatches)
+        .await?;
+    lifecycle::check_lobby_owner(&state.pg_client, party_id, rate_limit_key.api_key).await?;
+    let hero_locks = get_hero_locks(&mut state.redis_client, party_id).await?;
+    Ok(Json(
+        hero_locks
+            .into_iter()
+            .map(|(account_id, hero_id)| HeroLock {
+                account_id,
+                hero_id,
+            })
+            .sorted_by_key(|l| l.account_id)
+            .collect::<Vec<_>>(),
+    ))
+}
+
+#[utoipa::path(
+    put,
+    path = "/{party_id}/hero-locks",
+    params(PartyIdPath),
+    request_body = SetHeroLocksRequest,
+    responses(
+        (status = 200, description = "Successfully set the hero locks."),
+        (status = BAD_REQUEST, description = "Provided parameters are invalid."),
+        (status = FORBIDDEN, description = "API key is missing the `custom_matches` scope"),
+        (status = NOT_FOUND, description = "Party not found, not created with your API key or an account is not a member of it."),
+        (status = TOO_MANY_REQUESTS, description = "Rate limit exceeded"),
+        (status = INTERNAL_SERVER_ERROR, description = "Setting the hero locks failed")
+    ),
+    security(("api_key_header" = ["custom_matches"]), ("api_key_query" = ["custom_matches"])),
+    tags = ["Custom Matches"],
+    summary = "Set Hero Locks",
+    description = "
+This endpoint locks the heroes of members of a custom match lobby.
+
+The match can only be started with the start endpoint once every locked player selected only their locked hero.
+The selected heroes of every member are part of the party state.
+
+Only lobbies created with your API key can be managed.
+
+Requires an API key with the `custom_matches` scope.
+
+### Rate Limits:
+| Type | Limit |
+| ---- | ----- |
+| IP | API-Key ONLY |
+| Key | 100req/30min |
+| Global | 1000req/h |
+"
+)]
+pub(super) async fn set_party_hero_locks(
+    Path(PartyIdPath { party_id }): Path<PartyIdPath>,
+    rate_limit_key: RateLimitKey,
+    State(mut state): State<AppState>,
+    Json(request): Json<SetHeroLocksRequest>,
+) -> APIResult<impl IntoResponse> {
+    state
+        .rate_limit_client
+        .require_scope(&rate_limit_key, ApiKeyScope::CustomMatches)
+        .await?;
+    state
+        .rate_limit_client
+        .apply_limits(
+            &rate_limit_key,
+            "hero_locks",
+            &[
+                Quota::key_limit(100, Duration::from_mins(30)),
+                Quota::global_limit(1000, Duration::from_hours(1)),
+            ],
+        )
+        .await?;
+    lifecycle::check_lobby_owner(&state.pg_client, party_id, rate_limit_key.api_key).await?;
+    if let Some(account_id) = request
+        .locks
+        .iter()
+        .map(|l| l.account_id)
+        .duplicates()
+        .next()
+    {
+        return Err(APIError::status_msg(
+            StatusCode::BAD_REQUEST,
+            format!("Account {account_id} is locked more than once"),
+        ));
+    }
+    for lock in &request.locks {
+        if !state.assets_client.validate_hero_id(lock.hero_id).await {
+            return Err(APIError::status_msg(
+                StatusCode::BAD_REQUEST,
+                format!("Invalid hero_id: {}", lock.hero_id),
+            ));
+        }
+    }
+    let party = get_party_state(&mut state.redis_client, party_id).await?;
+    for lock in &request.locks {
+        find_member(&party, party_id, lock.account_id)?;
+    }
+
+    let key = format!("{party_id}:hero-locks");
+    let mut pipe = redis::pipe();
+    pipe.del(&key);
+    if !request.locks.is_empty() {
+        let locks = request
+            .locks
+            .iter()
+            .map(|l| (l.account_id, l.hero_id))
+            .collect_vec();
+        pipe.hset_multiple(&key, &locks)
+            .expire(&key, HERO_LOCKS_TTL_SECS.cast_signed());
+    }
+    pipe.exec_async(&mut state.redis_client).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use valveprotos::deadlock::CMsgHeroSelectionMatchInfo;
+    use valveprotos::deadlock::c_msg_hero_selection_match_info::Hero;
+    use valveprotos::deadlock::cso_citadel_party::Member;
+
+    use super::*;
+
+    fn member(account_id: u32, hero_ids: &[u32]) -> Member {
+        Member {
+            account_id: Some(account_id),
+            hero_roster: Some(CMsgHeroSelectionMatchInfo {
+                hero_selections: hero_ids
+                    .iter()
+                    .map(|&hero_id| Hero {
+                        hero_id: Some(hero_id),
+                        priority: Some(0),
+                    })
+                    .collect(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_hero_lock_violations() {
+        let party = CsoCitadelParty {
+            members: vec![
+                member(1, &[7]),
+                member(2, &[7, 15]),
+                member(3, &[]),
+                member(4, &[15]),
+                member(5, &[1]),
+            ],
+            ..Default::default()
+        };
+        let hero_locks = HashMap::from([(1, 7), (2, 7), (3, 7), (4, 7), (6, 7)]);
+        assert_eq!(hero_lock_violations(&party, &hero_locks), vec![2, 3, 4]);
+    }
+
+    #[test]
+    fn test_hero_lock_violations_without_locks() {
+        let party = CsoCitadelParty {
+            members: vec![member(1, &[7])],
+            ..Default::default()
+        };
+        assert!(hero_lock_violations(&party, &HashMap::new()).is_empty());
+    }
+}
@@ -10,8 +10,8 @@ use crate::context::AppState;
 use crate::error::{APIError, APIResult};
 use crate::routes::v1::matches::custom::ready::LobbyIdQuery;
 use crate::routes::v1::matches::custom::utils;
-use crate::services::rate_limiter::Quota;
 use crate::services::rate_limiter::extractor::RateLimitKey;
+use crate::services::rate_limiter::{ApiKeyScope, Quota};
 
 #[utoipa::path(
     post,
@@ -20,15 +20,19 @@ use crate::services::rate_limiter::extractor::RateLimitKey;
     responses(
         (status = 200, description = "Successfully left the lobby."),
         (status = BAD_REQUEST, description = "Provided parameters are invalid."),
+        (status = FORBIDDEN, description = "API key is missing the `custom_matches` scope"),
         (status = TOO_MANY_REQUESTS, description = "Rate limit exceeded"),
         (status = INTERNAL_SERVER_ERROR, description = "Leaving lobby failed")
     ),
+    security(("api_key_header" = ["custom_matches"]), ("api_key_query" = ["custom_matches"])),
     tags = ["Custom Matches"],
     summary = "Leave Lobby",
     description = "
 This endpoint makes the bot leave the custom match lobby early.
 By default the bot leaves automatically after 15 minutes, but this endpoint allows you to trigger it sooner.
 
+Requires an API key with the `custom_matches` scope.
+
 ### Rate Limits:
 | Type | Limit |
 | ---- | ----- |
@@ -42,6 +46,10 @@ pub(super) async fn leave(
     rate_limit_key: RateLimitKey,
     State(mut state): State<AppState>,
 ) -> APIResult<impl IntoResponse> {
+    state
+        .rate_limit_client
+        .require_scope(&rate_limit_key, ApiKeyScope::CustomMatches)
+        .await?;
     state
         .rate_limit_client
         .apply_limits(
@@ -0,0 +1,228 @@
+use axum::Json;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::{Pool, Postgres};
+use strum::{Display, EnumString};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::context::AppState;
+use crate::error::{APIError, APIResult};
+use crate::services::rate_limiter::ApiKeyScope;
+use crate::services::rate_limiter::extractor::RateLimitKey;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema, Display, EnumString)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+enum CustomMatchStatus {
+    /// The lobby was created and players are joining.
+    Created,
+    /// Enough players joined the lobby to start the match.
+    Filled,
+    /// The match started.
+    Started,
+    /// The match ended and its metadata is available.
+    Finished,
+    /// The lobby was closed before the match started, or the match never finished.
+    Expired,
+}
+
+struct CustomMatchRow {
+    party_id: i64,
+    status: String,
+    match_id: Option<i64>,
+    created_at: DateTime<Utc>,
+    filled_at: Option<DateTime<Utc>>,
+    started_at: Option<DateTime<Utc>>,
+    finished_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct CustomMatch {
+    party_id: String,
+    status: CustomMatchStatus,
+    /// The id of the match, once it started.
+    match_id: Option<u64>,
+    created_at: DateTime<Utc>,
+    filled_at: Option<DateTime<Utc>>,
+    started_at: Option<DateTime<Utc>>,
+    finished_at: Option<DateTime<Utc>>,
+}
+
+impl From<CustomMatchRow> for CustomMatch {
+    fn from(row: CustomMatchRow) -> Self {
+        Self {
+            party_id: row.party_id.cast_unsigned().to_string(),
+            status: row.status.parse().unwrap_or(CustomMatchStatus::Expired),
+            match_id: row.match_id.map(i64::cast_unsigned),
+            created_at: row.created_at,
+            filled_at: row.filled_at,
+            started_at: row.started_at,
+            finished_at: row.finished_at,
+        }
+    }
+}
+
+/// Starts tracking the lifecycle of a created lobby.
+///
+/// The callback is stored as well, as the Redis keys of the party expire long before the match
+/// finishes.
+pub(super) async fn track_lobby(
+    pg_client: &Pool<Postgres>,
+    party_id: u64,
+    api_key: Uuid,
+    callback: Option<(&str, &str)>,
+) -> sqlx::Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT INTO custom_matches (party_id, api_key, callback_url, callback_secret)
+        VALUES ($1, $2, $3, $4)
+        "#,
+        party_id.cast_signed(),
+        api_key,
+        callback.map(|(url, _)| url),
+        callback.map(|(_, secret)| secret)
+    )
+    .execute(pg_client)
+    .await?;
+    Ok(())
+}
+
+/// Stops tracking a lobby that was left before it started.
+pub(super) async fn expire_lobby(pg_client: &Pool<Postgres>, party_id: u64) -> sqlx::Result<()> {
+    sqlx::query!(
+        r#"
+        UPDATE custom_matches
+        SET status = 'expired'
+        WHERE party_id = $1 AND status IN ('created', 'filled')
+        "#,
+        party_id.cast_signed()
+    )
+    .execute(pg_client)
+    .await?;
+    Ok(())
+}
+
+/// Checks that the lobby was created with the given API key.
+///
+/// Lobbies of other API keys are reported as not found, so their party ids can't be probed.
+pub(super) async fn check_lobby_owner(
+    pg_client: &Pool<Postgres>,
+    party_id: u64,
+    api_key: Option<Uuid>,
+) -> APIResult<()> {
+    let is_owner = sqlx::query_scalar!(
+        r#"
+        SELECT EXISTS(SELECT 1 FROM custom_matches WHERE party_id = $1 AND api_key = $2) AS "is_owner!"
+        "#,
+        party_id.cast_signed(),
+        api_key
+    )
+    .fetch_one(pg_client)
+    .await?;
+    if is_owner {
+        Ok(())
+    } else {
+        Err(APIError::status_msg(
+            StatusCode::NOT_FOUND,
+            format!("Party {party_id} not found"),
+        ))
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/",
+    responses(
+        (status = 200, description = "Successfully fetched the custom matches.", body = [CustomMatch]),
+        (status = FORBIDDEN, description = "API key is missing the `custom_matches` scope"),
+        (status = INTERNAL_SERVER_ERROR, description = "Fetching the custom matches failed")
+    ),
+    security(("api_key_header" = ["custom_matches"]), ("api_key_query" = ["custom_matches"])),
+    tags = ["Custom Matches"],
+    summary = "List Matches",
+    description = "
+This endpoint lists the 100 most recent custom matches created with your API key and their state.
+
+A match goes through the states `created` → `filled` → `started` → `finished`.
+Lobbies that never start, and matches that never finish, end up `expired`.
+
+Once a match is finished and a callback url was provided, a signed POST is sent to `{callback_url}/result` with the match metadata, parsed the same way as `GET /v1/matches/{match_id}/metadata`.
+The delivery of the callback can be followed with `GET /v1/matches/custom/callbacks`.
+
+Requires an API key with the `custom_matches` scope.
+
+### Rate Limits:
+| Type | Limit |
+| ---- | ----- |
+| IP | API-Key ONLY |
+| Key | - |
+| Global | - |
+"
+)]
+pub(super) async fn list_custom_matches(
+    rate_limit_key: RateLimitKey,
+    State(state): State<AppState>,
+) -> APIResult<impl IntoResponse> {
+    state
+        .rate_limit_client
+        .require_scope(&rate_limit_key, ApiKeyScope::CustomMatches)
+        .await?;
+    let matches = sqlx::query_as!(
+        CustomMatchRow,
+        r#"
+        SELECT party_id, status, match_id, created_at, filled_at, started_at, finished_at
+        FROM custom_matches
+        WHERE api_key = $1
+        ORDER BY created_at DESC
+        LIMIT 100
+        "#,
+        rate_limit_key.api_key
+    )
+    .fetch_all(&state.pg_client)
+    .await?;
+    Ok(Json(
+        matches
+            .into_iter()
+            .map(CustomMatch::from)
+            .collect::<Vec<_>>(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    #[rstest]
+    #[case("created", CustomMatchStatus::Created)]
+    #[case("filled", CustomMatchStatus::Filled)]
+    #[case("started", CustomMatchStatus::Started)]
+    #[case("finished", CustomMatchStatus::Finished)]
+    #[case("expired", CustomMatchStatus::Expired)]
+    fn test_status_roundtrip(#[case] status: &str, #[case] expected: CustomMatchStatus) {
+        assert_eq!(status.parse::<CustomMatchStatus>().ok(), Some(expected));
+        assert_eq!(expected.to_string(), status);
+    }
+
+    #[test]
+    fn test_custom_match_from_row() {
+        let now = Utc::now();
+        let custom_match = CustomMatch::from(CustomMatchRow {
+            party_id: u64::MAX.cast_signed(),
+            status: "started".to_owned(),
+            match_id: Some(42),
+            created_at: now,
+            filled_at: Some(now),
+            started_at: Some(now),
+            finished_at: None,
+        });
+        assert_eq!(custom_match.party_id, u64::MAX.to_string());
+        assert_eq!(custom_match.status, CustomMatchStatus::Started);
+        assert_eq!(custom_match.match_id, Some(42));
+    }
+}
@@ -0,0 +1,439 @@
+use core::time::Duration;
+
+use axum::Json;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use itertools::Itertools;
+use serde::Deserialize;
+use utoipa::{IntoParams, ToSchema};
+use valveprotos::deadlock::c_msg_client_to_gc_party_action::EAction;
+use valveprotos::deadlock::cso_citadel_party::{EPlayerType, Member, PrivateLobbySlot};
+use valveprotos::deadlock::{CMsgClientToGcPartyAction, CsoCitadelParty};
+
+use crate::context::AppState;
+use crate::error::{APIError, APIResult};
+use crate::routes::v1::matches::custom::{lifecycle, party, utils};
+use crate::services::rate_limiter::extractor::RateLimitKey;
+use crate::services::rate_limiter::{ApiKeyScope, Quota};
+
+/// Number of player slots of a private lobby, slots `0..PLAYER_SLOTS` are for players.
+const PLAYER_SLOTS: u32 = 12;
+
+#[derive(Debug, Clone, Copy, Deserialize, ToSchema)]
+pub(super) struct CustomMatchSlotAssignment {
+    /// The player slot, from `0` to `11`. The slot decides the team and lane of the player.
+    slot_id: u32,
+    account_id: u32,
+}
+
+impl From<CustomMatchSlotAssignment> for PrivateLobbySlot {
+    fn from(assignment: CustomMatchSlotAssignment) -> Self {
+        Self {
+            slot_id: assignment.slot_id.into(),
+            player_account_id: assignment.account_id.into(),
+        }
+    }
+}
+
+/// Checks that every slot exists and that no slot or account is assigned twice.
+pub(super) fn validate_slot_assignments(
+    assignments: &[CustomMatchSlotAssignment],
+) -> Result<(), String> {
+    if let Some(a) = assignments.iter().find(|a| a.slot_id >= PLAYER_SLOTS) {
+        return Err(format!(
+            "Invalid slot {}, slots range from 0 to {}",
+            a.slot_id,
+            PLAYER_SLOTS - 1
+        ));
+    }
+    if let Some(slot_id) = assignments.iter().map(|a| a.slot_id).duplicates().next() {
+        return Err(format!("Slot {slot_id} is assigned more than once"));
+    }
+    if let Some(account_id) = assignments.iter().map(|a| a.account_id).duplicates().next() {
+        return Err(format!("Account {account_id} is assigned more than once"));
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub(super) enum CustomMatchTeam {
+    Amber,
+    Sapphire,
+    Spectator,
+}
+
+#[derive(Deserialize, IntoParams)]
+pub(super) struct PartyMemberPath {
+    party_id: u64,
+    account_id: u32,
+}
+
+#[derive(Debug, Default, Deserialize, ToSchema)]
+pub(super) struct MovePartyMemberRequest {
+    /// The team to move the player to.
+    #[serde(default)]
+    team: Option<CustomMatchTeam>,
+    /// The player slot to move the player to, from `0` to `11`.
+    #[serde(default)]
+    slot_id: Option<u32>,
+}
+
+/// Returns the member of the party with the given account id.
+pub(super) fn find_member(
+    party: &CsoCitadelParty,
+    party_id: u64,
+    account_id: u32,
+) -> APIResult<&Member> {
+    party
+        .members
+        .iter()
+        .find(|m| m.account_id() == account_id)
+        .ok_or_else(|| {
+            APIError::status_msg(
+                StatusCode::NOT_FOUND,
+                format!("Account {account_id} is not a member of party {party_id}"),
+            )
+        })
+}
+
+fn member_action(party_id: u64, account_id: u32, action: EAction) -> CMsgClientToGcPartyAction {
+    CMsgClientToGcPartyAction {
+        party_id: party_id.into(),
+        target_account_id: account_id.into(),
+        action_id: (action as i32).into(),
+        ..Default::default()
+    }
+}
+
+/// Returns the party actions needed to move a member, validated against the party state.
+fn move_actions(
+    party: &CsoCitadelParty,
+    party_id: u64,
+    account_id: u32,
+    request: &MovePartyMemberRequest,
+) -> APIResult<Vec<CMsgClientToGcPartyAction>> {
+    let member = find_member(party, party_id, account_id)?;
+    if request.team.is_none() && request.slot_id.is_none() {
+        return Err(APIError::status_msg(
+            StatusCode::BAD_REQUEST,
+            "Either team or slot_id is required",
+        ));
+    }
+    if request.team == Some(CustomMatchTeam::Spectator) && request.slot_id.is_some() {
+        return Err(APIError::status_msg(
+            StatusCode::BAD_REQUEST,
+            "Spectators can't be moved to a player slot",
+        ));
+    }
+
+    let mut actions = vec![];
+    match request.team {
+        Some(CustomMatchTeam::Spectator) => actions.push(CMsgClientToGcPartyAction {
+            uint_value: u64::from(utils::SPECTATOR_SLOT).into(),
+            ..member_action(party_id, account_id, EAction::KESetPlayerSlot)
+        }),
+        Some(team) => {
+            if member.player_type() == EPlayerType::KEPlayerTypeSpectator {
+                actions.push(CMsgClientToGcPartyAction {
+                    uint_value: (EPlayerType::KEPlayerTypePlayer as u64).into(),
+                    ..member_action(party_id, account_id, EAction::KESetPlayerType)
+                });
+            }
+            actions.push(CMsgClientToGcPartyAction {
+                uint_value: u64::from(team == CustomMatchTeam::Sapphire).into(),
+                ..member_action(party_id, account_id, EAction::KESetMemberTeam)
+            });
+        }
+        None => {}
+    }
+    if let Some(slot_id) = request.slot_id {
+        if slot_id >= PLAYER_SLOTS {
+            return Err(APIError::status_msg(
+                StatusCode::BAD_REQUEST,
+                format!(
+                    "Invalid slot {slot_id}, slots range from 0 to {}",
+                    PLAYER_SLOTS - 1
+                ),
+            ));
+        }
+        let taken_by = party
+            .private_lobby_settings
+            .iter()
+            .flat_map(|s| &s.match_slots)
+            .find(|s| s.slot_id() == slot_id)
+            .map(|s| s.player_account_id())
+            .filter(|&a| a > 0 && a != account_id);
+        if let Some(taken_by) = taken_by {
+            return Err(APIError::status_msg(
+                StatusCode::CONFLICT,
+                format!("Slot {slot_id} is taken by account {taken_by}"),
+            ));
+        }
+        actions.push(CMsgClientToGcPartyAction {
+            uint_value: u64::from(slot_id).into(),
+            ..member_action(party_id, account_id, EAction::KESetPlayerSlot)
+        });
+    }
+    Ok(actions)
+}
+
+#[utoipa::path(
+    post,
+    path = "/{party_id}/members/{account_id}/move",
+    params(PartyMemberPath),
+    request_body = MovePartyMemberRequest,
+    responses(
+        (status = 200, description = "Successfully moved the player."),
+        (status = BAD_REQUEST, description = "Provided parameters are invalid."),
+        (status = FORBIDDEN, description = "API key is missing the `custom_matches` scope"),
+        (status = NOT_FOUND, description = "Party not found, not created with your API key or the account is not a member of it."),
+        (status = CONFLICT, description = "The slot is taken by another player."),
+        (status = TOO_MANY_REQUESTS, description = "Rate limit exceeded"),
+        (status = INTERNAL_SERVER_ERROR, description = "Moving the player failed")
+    ),
+    security(("api_key_header" = ["custom_matches"]), ("api_key_query" = ["custom_matches"])),
+    tags = ["Custom Matches"],
+    summary = "Move Player",
+    description = "
+This endpoint moves a member of a custom match lobby to another team or player slot.
+
+The slot decides the team and lane of the player. Moving a player to the `spectator` team moves them to the spectator slot.
+
+Only lobbies created with your API key can be managed.
+
+Requires an API key with the `custom_matches` scope.
+
+### Rate Limits:
+| Type | Limit |
+| ---- | ----- |
+| IP | API-Key ONLY |
+| Key | 300req/30min |
+| Global | 3000req/h |
+"
+)]
+pub(super) async fn move_member(
+    Path(PartyMemberPath {
+        party_id,
+        account_id,
+    }): Path<PartyMemberPath>,
+    rate_limit_key: RateLimitKey,
+    State(mut state): State<AppState>,
+    Json(request): Json<MovePartyMemberRequest>,
+) -> APIResult<impl IntoResponse> {
+    state
+        .rate_limit_client
+        .require_scope(&rate_limit_key, ApiKeyScope::CustomMatches)
+        .await?;
+    state
+        .rate_limit_client
+        .apply_limits(
+            &rate_limit_key,
+            "party_members",
+            &[
+                Quota::key_limit(300, Duration::from_mins(30)),
+                Quota::global_limit(3000, Duration::from_hours(1)),
+            ],
+        )
+        .await?;
+    lifecycle::check_lobby_owner(&state.pg_client, party_id, rate_limit_key.api_key).await?;
+    let (username, _) = utils::get_party_bot(&mut state.redis_client, party_id).await?;
+    let party = party::get_party_state(&mut state.redis_client, party_id).await?;
+    for action in move_actions(&party, party_id, account_id, &request)? {
+        utils::party_action(&state.steam_client, username.clone(), action).await?;
+    }
+    Ok(())
+}
+
+#[utoipa::path(
+    delete,
+    path = "/{party_id}/members/{account_id}",
+    params(PartyMemberPath),
+    responses(
+        (status = 200, description = "Successfully kicked the player."),
+        (status = BAD_REQUEST, description = "Provided parameters are invalid."),
+        (status = FORBIDDEN, description = "API key is missing the `custom_matches` scope"),
+        (status = NOT_FOUND, description = "Party not found, not created with your API key or the account is not a member of it."),
+        (status = TOO_MANY_REQUESTS, description = "Rate limit exceeded"),
+        (status = INTERNAL_SERVER_ERROR, description = "Kicking the player failed")
+    ),
+    security(("api_key_header" = ["custom_matches"]), ("api_key_query" = ["custom_matches"])),
+    tags = ["Custom Matches"],
+    summary = "Kick Player",
+    description = "
+This endpoint kicks a member from a custom match lobby.
+
+The bot can't be kicked, use the leave endpoint instead.
+
+Only lobbies created with your API key can be managed.
+
+Requires an API key with the `custom_matches` scope.
+
+### Rate Limits:
+| Type | Limit |
+| ---- | ----- |
+| IP | API-Key ONLY |
+| Key | 300req/30min |
+| Global | 3000req/h |
+"
+)]
+pub(super) async fn kick_member(
+    Path(PartyMemberPath {
+        party_id,
+        account_id,
+    }): Path<PartyMemberPath>,
+    rate_limit_key: RateLimitKey,
+    State(mut state): State<AppState>,
+) -> APIResult<impl IntoResponse> {
+    state
+        .rate_limit_client
+        .require_scope(&rate_limit_key, ApiKeyScope::CustomMatches)
+        .await?;
+    state
+        .rate_limit_client
+        .apply_limits(
+            &rate_limit_key,
+            "party_members",
+            &[
+                Quota::key_limit(300, Duration::from_mins(30)),
+                Quota::global_limit(3000, Duration::from_hours(1)),
+            ],
+        )
+        .await?;
+    lifecycle::check_lobby_owner(&state.pg_client, party_id, rate_limit_key.api_key).await?;
+    let (username, bot_account_id) =
+        utils::get_party_bot(&mut state.redis_client, party_id).await?;
+    if account_id == bot_account_id {
+        return Err(APIError::status_msg(
+            StatusCode::BAD_REQUEST,
+            "The bot can't be kicked, use the leave endpoint instead",
+        ));
+    }
+    let party = party::get_party_state(&mut state.redis_client, party_id).await?;
+    find_member(&party, party_id, account_id)?;
+    utils::party_action(
+        &state.steam_client,
+        username,
+        member_action(party_id, account_id, EAction::KEKickUser),
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+    use valveprotos::deadlock::cso_citadel_party::PrivateLobbySettings;
+
+    use super::*;
+
+    fn assignment(slot_id: u32, account_id: u32) -> CustomMatchSlotAssignment {
+        CustomMatchSlotAssignment {
+            slot_id,
+            account_id,
+        }
+    }
+
+    fn party() -> CsoCitadelParty {
+        CsoCitadelParty {
+            members: vec![
+                Member {
+                    account_id: Some(1),
+                    ..Default::default()
+                },
+                Member {
+                    account_id: Some(2),
+                    player_type: Some(EPlayerType::KEPlayerTypeSpectator as i32),
+                    ..Default::default()
+                },
+            ],
+            private_lobby_settings: Some(PrivateLobbySettings {
+                match_slots: vec![PrivateLobbySlot {
+                    slot_id: Some(3),
+                    player_account_id: Some(1),
+                }],
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[rstest]
+    #[case(vec![], true)]
+    #[case(vec![assignment(0, 1), assignment(11, 2)], true)]
+    #[case(vec![assignment(12, 1)], false)]
+    #[case(vec![assignment(0, 1), assignment(0, 2)], false)]
+    #[case(vec![assignment(0, 1), assignment(1, 1)], false)]
+    fn test_validate_slot_assignments(
+        #[case] assignments: Vec<CustomMatchSlotAssignment>,
+        #[case] valid: bool,
+    ) {
+        assert_eq!(validate_slot_assignments(&assignments).is_ok(), valid);
+    }
+
+    #[test]
+    fn test_move_to_team() {
+        let request = MovePartyMemberRequest {
+            team: Some(CustomMatchTeam::Sapphire),
+            slot_id: Some(4),
+        };
+        let actions = move_actions(&party(), 42, 1, &request).expect("valid move");
+        assert_eq!(actions.len(), 2);
+        assert!(actions.iter().all(|a| a.target_account_id() == 1));
+        assert_eq!(actions[0].action_id(), EAction::KESetMemberTeam);
+        assert_eq!(actions[0].uint_value(), 1);
+        assert_eq!(actions[1].action_id(), EAction::KESetPlayerSlot);
+        assert_eq!(actions[1].uint_value(), 4);
+    }
+
+    #[test]
+    fn test_move_spectator_to_team() {
+        let request = MovePartyMemberRequest {
+            team: Some(CustomMatchTeam::Amber),
+            ..Default::default()
+        };
+        let actions = move_actions(&party(), 42, 2, &request).expect("valid move");
+        assert_eq!(actions.len(), 2);
+        assert_eq!(actions[0].action_id(), EAction::KESetPlayerType);
+        assert_eq!(actions[1].action_id(), EAction::KESetMemberTeam);
+        assert_eq!(actions[1].uint_value(), 0);
+    }
+
+    #[test]
+    fn test_move_to_spectators() {
+        let request = MovePartyMemberRequest {
+            team: Some(CustomMatchTeam::Spectator),
+            ..Default::default()
+        };
+        let actions = move_actions(&party(), 42, 1, &request).expect("valid move");
+        assert_eq!(actions.len(), 1);
+        assert_eq!(actions[0].action_id(), EAction::KESetPlayerSlot);
+        assert_eq!(actions[0].uint_value(), u64::from(utils::SPECTATOR_SLOT));
+    }
+
+    #[rstest]
+    #[case(3, None, Some(0), StatusCode::NOT_FOUND)]
+    #[case(1, None, None, StatusCode::BAD_REQUEST)]
+    #[case(1, None, Some(12), StatusCode::BAD_REQUEST)]
+    #[case(1, Some(CustomMatchTeam::Spectator), Some(0), StatusCode::BAD_REQUEST)]
+    #[case(2, None, Some(3), StatusCode::CONFLICT)]
+    fn test_move_invalid(
+        #[case] account_id: u32,
+        #[case] team: Option<CustomMatchTeam>,
+        #[case] slot_id: Option<u32>,
+        #[case] status: StatusCode,
+    ) {
+        let request = MovePartyMemberRequest { team, slot_id };
+        let error = move_actions(&party(), 42, account_id, &request).expect_err("invalid move");
+        assert_eq!(error.into_response().status(), status);
+    }
+
+    #[test]
+    fn test_move_to_own_slot() {
+        let request = MovePartyMemberRequest {
+            slot_id: Some(3),
+            ..Default::default()
+        };
+        assert!(move_actions(&party(), 42, 1, &request).is_ok());
+    }
+}
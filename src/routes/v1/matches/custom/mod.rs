@@ -1,6 +1,11 @@
-mod create;
+mod callbacks;
+pub(crate) mod create;
 mod get;
+mod hero_locks;
 mod leave;
+mod lifecycle;
+mod members;
+pub(crate) mod party;
 mod ready;
 mod start;
 mod utils;
@@ -21,6 +26,17 @@ pub(super) fn router() -> OpenApiRouter<AppState> {
         .routes(routes!(ready::unready))
         .routes(routes!(leave::leave))
         .routes(routes!(create::create_custom))
+        .routes(routes!(lifecycle::list_custom_matches))
+        .routes(routes!(callbacks::list_callbacks))
+        .routes(routes!(callbacks::get_callback))
+        .routes(routes!(callbacks::retry_callback))
         .routes(routes!(get::get_custom))
+        .routes(routes!(party::get_party, party::update_party_settings))
+        .routes(routes!(members::move_member))
+        .routes(routes!(members::kick_member))
+        .routes(routes!(
+            hero_locks::get_party_hero_locks,
+            hero_locks::set_party_hero_locks
+        ))
         .routes(routes!(start::start))
 }
@@ -0,0 +1,404 @@
+use core::time::Duration;
+
+use axum::Json;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use itertools::Itertools;
+use prost::Message;
+use redis::AsyncCommands;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use valveprotos::deadlock::c_msg_client_to_gc_party_action::EAction;
+use valveprotos::deadlock::cso_citadel_party::{EPlayerType, Member};
+use valveprotos::deadlock::{CMsgClientToGcPartyAction, CsoCitadelParty};
+
+use crate::context::AppState;
+use crate::error::{APIError, APIResult};
+use crate::routes::v1::matches::custom::{lifecycle, utils};
+use crate::routes::v1::matches::types::{GameMode, ServerRegion};
+use crate::services::rate_limiter::extractor::RateLimitKey;
+use crate::services::rate_limiter::{ApiKeyScope, Quota};
+
+#[derive(Deserialize, IntoParams)]
+pub(super) struct PartyIdPath {
+    pub(super) party_id: u64,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct CustomPartyMember {
+    account_id: u32,
+    persona_name: Option<String>,
+    is_ready: bool,
+    is_spectator: bool,
+    team: Option<u32>,
+    /// The heroes the player selected, in the order of their priority.
+    hero_ids: Vec<u32>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct CustomPartySlot {
+    slot_id: u32,
+    /// The account in the slot, if the slot is taken.
+    account_id: Option<u32>,
+}
+
+#[derive(Debug, Default, Serialize, ToSchema)]
+struct CustomPartySettings {
+    min_roster_size: Option<u32>,
+    randomize_lanes: Option<bool>,
+    server_region: Option<ServerRegion>,
+    is_publicly_visible: Option<bool>,
+    cheats_enabled: Option<bool>,
+    duplicate_heroes_enabled: Option<bool>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub(crate) struct CustomPartyState {
+    party_id: String,
+    game_mode: Option<GameMode>,
+    members: Vec<CustomPartyMember>,
+    slots: Vec<CustomPartySlot>,
+    settings: CustomPartySettings,
+}
+
+/// Returns the heroes a member selected, ordered by their priority.
+pub(super) fn selected_heroes(member: &Member) -> Vec<u32> {
+    member
+        .hero_roster
+        .iter()
+        .flat_map(|r| &r.hero_selections)
+        .sorted_by_key(|h| h.priority())
+        .map(|h| h.hero_id())
+        .collect()
+}
+
+impl From<CsoCitadelParty> for CustomPartyState {
+    fn from(party: CsoCitadelParty) -> Self {
+        let game_mode = party.game_mode.and_then(GameMode::from_repr);
+        let members = party
+            .members
+            .iter()
+            .map(|m| CustomPartyMember {
+                account_id: m.account_id(),
+                persona_name: m.persona_name.clone(),
+                is_ready: m.is_ready(),
+                is_spectator: m.player_type() == EPlayerType::KEPlayerTypeSpectator,
+                team: m.team,
+                hero_ids: selected_heroes(m),
+            })
+            .collect();
+        let (slots, settings) = match party.private_lobby_settings {
+            Some(settings) => (
+                settings
+                    .match_slots
+                    .iter()
+                    .map(|s| CustomPartySlot {
+                        slot_id: s.slot_id(),
+                        account_id: s.player_account_id.filter(|&a| a > 0),
+                    })
+                    .collect(),
+                CustomPartySettings {
+                    min_roster_size: settings.min_roster_size,
+                    randomize_lanes: settings.randomize_lanes,
+                    server_region: settings.server_region.and_then(ServerRegion::from_repr),
+                    is_publicly_visible: settings.is_publicly_visible,
+                    cheats_enabled: settings.cheats_enabled,
+                    duplicate_heroes_enabled: settings.duplicate_heroes_enabled,
+                },
+            ),
+            None => (vec![], CustomPartySettings::default()),
+        };
+        Self {
+            party_id: party.party_id().to_string(),
+            game_mode,
+            members,
+            slots,
+            settings,
+        }
+    }
+}
+
+/// Returns the latest state of a party.
+///
+/// The bot stores the protobuf encoded `CsoCitadelParty` in `{party_id}:party` whenever it
+/// receives an update of the party.
+pub(crate) async fn get_party_state(
+    redis_client: &mut redis::aio::MultiplexedConnection,
+    party_id: u64,
+) -> APIResult<CsoCitadelParty> {
+    let party: Option<Vec<u8>> = redis_client.get(format!("{party_id}:party")).await?;
+    let Some(party) = party else {
+        return Err(APIError::status_msg(
+            StatusCode::NOT_FOUND,
+            format!("No state of party {party_id} found"),
+        ));
+    };
+    Ok(CsoCitadelParty::decode(party.as_slice())?)
+}
+
+#[utoipa::path(
+    get,
+    path = "/{party_id}",
+    params(PartyIdPath),
+    responses(
+        (status = 200, description = "Successfully fetched the party state.", body = CustomPartyState),
+        (status = BAD_REQUEST, description = "Provided parameters are invalid."),
+        (status = FORBIDDEN, description = "API key is missing the `custom_matches` scope"),
+        (status = NOT_FOUND, description = "Party not found, not created with your API key or no state of it is known."),
+        (status = INTERNAL_SERVER_ERROR, description = "Fetching the party state failed")
+    ),
+    security(("api_key_header" = ["custom_matches"]), ("api_key_query" = ["custom_matches"])),
+    tags = ["Custom Matches"],
+    summary = "Get Party",
+    description = "
+This endpoint returns the latest state of a custom match lobby: its members, slots, ready states and settings.
+
+The state is updated whenever the lobby changes, so it is available shortly after the match was created.
+
+Only lobbies created with your API key can be fetched.
+
+Requires an API key with the `custom_matches` scope.
+
+### Rate Limits:
+| Type | Limit |
+| ---- | ----- |
+| IP | API-Key ONLY |
+| Key | - |
+| Global | - |
+"
+)]
+pub(super) async fn get_party(
+    Path(PartyIdPath { party_id }): Path<PartyIdPath>,
+    rate_limit_key: RateLimitKey,
+    State(mut state): State<AppState>,
+) -> APIResult<impl IntoResponse> {
+    state
+        .rate_limit_client
+        .require_scope(&rate_limit_key, ApiKeyScope::CustomMatches)
+        .await?;
+    lifecycle::check_lobby_owner(&state.pg_client, party_id, rate_limit_key.api_key).await?;
+    get_party_state(&mut state.redis_client, party_id)
+        .await
+        .map(CustomPartyState::from)
+        .map(Json)
+}
+
+#[derive(Debug, Default, Deserialize, ToSchema)]
+pub(super) struct UpdatePartySettingsRequest {
+    #[serde(default)]
+    server_region: Option<ServerRegion>,
+    #[serde(default)]
+    randomize_lanes: Option<bool>,
+    #[serde(default)]
+    is_publicly_visible: Option<bool>,
+    #[serde(default)]
+    cheats_enabled: Option<bool>,
+    #[serde(default)]
+    duplicate_heroes_enabled: Option<bool>,
+}
+
+/// Returns the party actions needed to apply the requested settings.
+fn settings_actions(
+    party_id: u64,
+    request: &UpdatePartySettingsRequest,
+) -> Vec<CMsgClientToGcPartyAction> {
+    let action = |action: EAction| CMsgClientToGcPartyAction {
+        party_id: party_id.into(),
+        action_id: (action as i32).into(),
+        ..Default::default()
+    };
+    let bool_action = |a: EAction, value: bool| CMsgClientToGcPartyAction {
+        bool_value: value.into(),
+        ..action(a)
+    };
+    let mut actions = vec![];
+    if let Some(server_region) = request.server_region {
+        actions.push(CMsgClientToGcPartyAction {
+            uint_value: u64::from(u32::from(server_region)).into(),
+            ..action(EAction::KESetServerRegion)
+        });
+    }
+    if let Some(randomize_lanes) = request.randomize_lanes {
+        actions.push(bool_action(EAction::KESetRandomizedLanes, randomize_lanes));
+    }
+    if let Some(is_publicly_visible) = request.is_publicly_visible {
+        actions.push(bool_action(
+            EAction::KESetPubliclyVisible,
+            is_publicly_visible,
+        ));
+    }
+    if let Some(cheats_enabled) = request.cheats_enabled {
+        actions.push(bool_action(EAction::KESetCheatsEnabled, cheats_enabled));
+    }
+    if let Some(duplicate_heroes_enabled) = request.duplicate_heroes_enabled {
+        actions.push(bool_action(
+            EAction::KESetDuplicateHeroesEnabled,
+            duplicate_heroes_enabled,
+        ));
+    }
+    actions
+}
+
+#[utoipa::path(
+    patch,
+    path = "/{party_id}",
+    params(PartyIdPath),
+    request_body = UpdatePartySettingsRequest,
+    responses(
+        (status = 200, description = "Successfully changed the settings."),
+        (status = BAD_REQUEST, description = "Provided parameters are invalid."),
+        (status = FORBIDDEN, description = "API key is missing the `custom_matches` scope"),
+        (status = NOT_FOUND, description = "Party not found or not created with your API key."),
+        (status = TOO_MANY_REQUESTS, description = "Rate limit exceeded"),
+        (status = INTERNAL_SERVER_ERROR, description = "Changing the settings failed")
+    ),
+    security(("api_key_header" = ["custom_matches"]), ("api_key_query" = ["custom_matches"])),
+    tags = ["Custom Matches"],
+    summary = "Update Party Settings",
+    description = "
+This endpoint changes the settings of a custom match lobby, without recreating it.
+
+Only the provided settings are changed. The minimum roster size can only be set when creating the match, the Game Coordinator has no party action to change it.
+
+Only lobbies created with your API key can be managed.
+
+Requires an API key with the `custom_matches` scope.
+
+### Rate Limits:
+| Type | Limit |
+| ---- | ----- |
+| IP | API-Key ONLY |
+| Key | 100req/30min |
+| Global | 1000req/h |
+"
+)]
+pub(super) async fn update_party_settings(
+    Path(PartyIdPath { party_id }): Path<PartyIdPath>,
+    rate_limit_key: RateLimitKey,
+    State(mut state): State<AppState>,
+    Json(request): Json<UpdatePartySettingsRequest>,
+) -> APIResult<impl IntoResponse> {
+    state
+        .rate_limit_client
+        .require_scope(&rate_limit_key, ApiKeyScope::CustomMatches)
+        .await?;
+    state
+        .rate_limit_client
+        .apply_limits(
+            &rate_limit_key,
+            "update_party_settings",
+            &[
+                Quota::key_limit(100, Duration::from_mins(30)),
+                Quota::global_limit(1000, Duration::from_hours(1)),
+            ],
+        )
+        .await?;
+    lifecycle::check_lobby_owner(&state.pg_client, party_id, rate_limit_key.api_key).await?;
+    let actions = settings_actions(party_id, &request);
+    if actions.is_empty() {
+        return Err(APIError::status_msg(
+            StatusCode::BAD_REQUEST,
+            "No settings to change",
+        ));
+    }
+    let (username, _) = utils::get_party_bot(&mut state.redis_client, party_id).await?;
+    for action in actions {
+        utils::party_action(&state.steam_client, username.clone(), action).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use valveprotos::deadlock::CMsgHeroSelectionMatchInfo;
+    use valveprotos::deadlock::c_msg_hero_selection_match_info::Hero;
+    use valveprotos::deadlock::cso_citadel_party::{PrivateLobbySettings, PrivateLobbySlot};
+
+    use super::*;
+
+    #[test]
+    fn test_settings_actions() {
+        let request = UpdatePartySettingsRequest {
+            server_region: Some(ServerRegion::Europe),
+            cheats_enabled: Some(true),
+            randomize_lanes: Some(false),
+            ..Default::default()
+        };
+        let actions = settings_actions(42, &request);
+        assert_eq!(actions.len(), 3);
+        assert!(actions.iter().all(|a| a.party_id() == 42));
+        assert_eq!(actions[0].action_id(), EAction::KESetServerRegion);
+        assert_eq!(actions[0].uint_value(), 3);
+        assert_eq!(actions[1].action_id(), EAction::KESetRandomizedLanes);
+        assert!(!actions[1].bool_value());
+        assert_eq!(actions[2].action_id(), EAction::KESetCheatsEnabled);
+        assert!(actions[2].bool_value());
+    }
+
+    #[test]
+    fn test_settings_actions_empty() {
+        assert!(settings_actions(42, &UpdatePartySettingsRequest::default()).is_empty());
+    }
+
+    #[test]
+    fn test_party_state_from_party() {
+        let party = CsoCitadelParty {
+            party_id: Some(42),
+            game_mode: Some(GameMode::Normal as i32),
+            members: vec![
+                Member {
+                    account_id: Some(1),
+                    is_ready: Some(true),
+                    player_type: Some(EPlayerType::KEPlayerTypeSpectator as i32),
+                    ..Default::default()
+                },
+                Member {
+                    account_id: Some(2),
+                    hero_roster: Some(CMsgHeroSelectionMatchInfo {
+                        hero_selections: vec![
+                            Hero {
+                                hero_id: Some(7),
+                                priority: Some(1),
+                            },
+                            Hero {
+                                hero_id: Some(15),
+                                priority: Some(0),
+                            },
+                        ],
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                },
+            ],
+            private_lobby_settings: Some(PrivateLobbySettings {
+                match_slots: vec![
+                    PrivateLobbySlot {
+                        slot_id: Some(0),
+                        player_account_id: Some(2),
+                    },
+                    PrivateLobbySlot {
+                        slot_id: Some(1),
+                        player_account_id: None,
+                    },
+                ],
+                server_region: Some(ServerRegion::Europe as u32),
+                cheats_enabled: Some(false),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let state = CustomPartyState::from(party);
+        assert_eq!(state.party_id, "42");
+        assert_eq!(state.game_mode, Some(GameMode::Normal));
+        assert!(state.members[0].is_ready && state.members[0].is_spectator);
+        assert!(!state.members[1].is_ready && !state.members[1].is_spectator);
+        assert!(state.members[0].hero_ids.is_empty());
+        assert_eq!(state.members[1].hero_ids, vec![15, 7]);
+        assert_eq!(state.slots.len(), 2);
+        assert_eq!(state.slots[0].account_id, Some(2));
+        assert_eq!(state.slots[1].account_id, None);
+        assert_eq!(state.settings.server_region, Some(ServerRegion::Europe));
+        assert_eq!(state.settings.cheats_enabled, Some(false));
+    }
+}
@@ -11,8 +11,8 @@ use utoipa::IntoParams;
 use crate::context::AppState;
 use crate::error::{APIError, APIResult};
 use crate::routes::v1::matches::custom::utils;
-use crate::services::rate_limiter::Quota;
 use crate::services::rate_limiter::extractor::RateLimitKey;
+use crate::services::rate_limiter::{ApiKeyScope, Quota};
 
 #[derive(Deserialize, IntoParams, Clone)]
 pub(crate) struct LobbyIdQuery {
@@ -26,14 +26,18 @@ pub(crate) struct LobbyIdQuery {
     responses(
         (status = 200, description = "Successfully ready up."),
         (status = BAD_REQUEST, description = "Provided parameters are invalid."),
+        (status = FORBIDDEN, description = "API key is missing the `custom_matches` scope"),
         (status = TOO_MANY_REQUESTS, description = "Rate limit exceeded"),
         (status = INTERNAL_SERVER_ERROR, description = "Ready up failed")
     ),
+    security(("api_key_header" = ["custom_matches"]), ("api_key_query" = ["custom_matches"])),
     tags = ["Custom Matches"],
     summary = "Ready Up",
     description = "
 This endpoint allows you to ready up for a custom match.
 
+Requires an API key with the `custom_matches` scope.
+
 ### Rate Limits:
 | Type | Limit |
 | ---- | ----- |
@@ -47,6 +51,10 @@ pub(super) async fn ready_up(
     rate_limit_key: RateLimitKey,
     State(mut state): State<AppState>,
 ) -> APIResult<impl IntoResponse> {
+    state
+        .rate_limit_client
+        .require_scope(&rate_limit_key, ApiKeyScope::CustomMatches)
+        .await?;
     state
         .rate_limit_client
         .apply_limits(
@@ -81,14 +89,18 @@ pub(super) async fn ready_up(
     responses(
         (status = 200, description = "Successfully unready."),
         (status = BAD_REQUEST, description = "Provided parameters are invalid."),
+        (status = FORBIDDEN, description = "API key is missing the `custom_matches` scope"),
         (status = TOO_MANY_REQUESTS, description = "Rate limit exceeded"),
         (status = INTERNAL_SERVER_ERROR, description = "Unready failed")
     ),
+    security(("api_key_header" = ["custom_matches"]), ("api_key_query" = ["custom_matches"])),
     tags = ["Custom Matches"],
     summary = "Unready",
     description = "
 This endpoint allows you to unready for a custom match.
 
+Requires an API key with the `custom_matches` scope.
+
 ### Rate Limits:
 | Type | Limit |
 | ---- | ----- |
@@ -102,6 +114,10 @@ pub(super) async fn unready(
     rate_limit_key: RateLimitKey,
     State(mut state): State<AppState>,
 ) -> APIResult<impl IntoResponse> {
+    state
+        .rate_limit_client
+        .require_scope(&rate_limit_key, ApiKeyScope::CustomMatches)
+        .await?;
     state
         .rate_limit_client
         .apply_limits(
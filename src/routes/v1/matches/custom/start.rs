@@ -9,9 +9,9 @@ use tracing::error;
 use crate::context::AppState;
 use crate::error::{APIError, APIResult};
 use crate::routes::v1::matches::custom::ready::LobbyIdQuery;
-use crate::routes::v1::matches::custom::utils;
-use crate::services::rate_limiter::Quota;
+use crate::routes::v1::matches::custom::{hero_locks, party, utils};
 use crate::services::rate_limiter::extractor::RateLimitKey;
+use crate::services::rate_limiter::{ApiKeyScope, Quota};
 
 #[utoipa::path(
     post,
@@ -20,14 +20,21 @@ use crate::services::rate_limiter::extractor::RateLimitKey;
     responses(
         (status = 200, description = "Successfully started the match."),
         (status = BAD_REQUEST, description = "Provided parameters are invalid."),
+        (status = FORBIDDEN, description = "API key is missing the `custom_matches` scope"),
+        (status = CONFLICT, description = "Players did not select their locked hero."),
         (status = TOO_MANY_REQUESTS, description = "Rate limit exceeded"),
         (status = INTERNAL_SERVER_ERROR, description = "Starting match failed")
     ),
+    security(("api_key_header" = ["custom_matches"]), ("api_key_query" = ["custom_matches"])),
     tags = ["Custom Matches"],
     summary = "Start Match",
     description = "
 This endpoint starts a custom match.
 
+If heroes are locked, the match only starts once every locked player selected only their locked hero.
+
+Requires an API key with the `custom_matches` scope.
+
 ### Rate Limits:
 | Type | Limit |
 | ---- | ----- |
@@ -41,6 +48,10 @@ pub(super) async fn start(
     rate_limit_key: RateLimitKey,
     State(mut state): State<AppState>,
 ) -> APIResult<impl IntoResponse> {
+    state
+        .rate_limit_client
+        .require_scope(&rate_limit_key, ApiKeyScope::CustomMatches)
+        .await?;
     state
         .rate_limit_client
         .apply_limits(
@@ -64,6 +75,20 @@ pub(super) async fn start(
         error!("Failed to parse party info");
         return Err(APIError::internal("Failed to parse party info"));
     };
+    let hero_locks = hero_locks::get_hero_locks(&mut state.redis_client, lobby_id).await?;
+    if !hero_locks.is_empty() {
+        let party = party::get_party_state(&mut state.redis_client, lobby_id).await?;
+        let violations = hero_locks::hero_lock_violations(&party, &hero_locks);
+        if !violations.is_empty() {
+            return Err(APIError::status_msg(
+                StatusCode::CONFLICT,
+                format!(
+                    "Players did not select their locked hero: {}",
+                    violations.iter().join(", ")
+                ),
+            ));
+        }
+    }
     utils::start_match(&state.steam_client, username.to_string(), lobby_id).await?;
     Ok(())
 }
@@ -1,35 +1,18 @@
 use core::time::Duration;
 
+use axum::http::StatusCode;
+use itertools::Itertools;
 use redis::{AsyncTypedCommands, RedisResult};
 use tracing::{error, info};
 use valveprotos::deadlock::{
-    CMsgClientToGcPartyLeave, CMsgClientToGcPartyLeaveResponse, CMsgClientToGcPartySetReadyState,
-    CMsgClientToGcPartySetReadyStateResponse, CMsgClientToGcPartyStartMatch,
-    CMsgClientToGcPartyStartMatchResponse, EgcCitadelClientMessages,
+    CMsgClientToGcPartyAction, CMsgClientToGcPartyLeave, CMsgClientToGcPartySetReadyState,
+    CMsgClientToGcPartyStartMatch, c_msg_client_to_gc_party_action_response,
     c_msg_client_to_gc_party_leave_response, c_msg_client_to_gc_party_set_ready_state_response,
     c_msg_client_to_gc_party_start_match_response,
 };
 
 use crate::error::{APIError, APIResult};
 use crate::services::steam::client::SteamClient;
-use crate::services::steam::types::SteamProxyQuery;
-
-fn build_proxy_query<M: prost::Message>(
-    msg_type: EgcCitadelClientMessages,
-    msg: M,
-    username: String,
-) -> SteamProxyQuery<M> {
-    SteamProxyQuery {
-        msg_type,
-        msg,
-        in_all_groups: None,
-        in_any_groups: None,
-        cooldown_time: Duration::ZERO,
-        request_timeout: Duration::from_secs(2),
-        username: Some(username),
-        soft_cooldown_millis: None,
-    }
-}
 
 pub(super) async fn get_party_info(
     redis_client: &mut redis::aio::MultiplexedConnection,
@@ -38,6 +21,30 @@ pub(super) async fn get_party_info(
     redis_client.get(lobby_id.to_string()).await
 }
 
+/// Slot of spectators in a private lobby
+pub(super) const SPECTATOR_SLOT: u32 = 31;
+
+/// Returns the username and account id of the bot that created the party.
+pub(super) async fn get_party_bot(
+    redis_client: &mut redis::aio::MultiplexedConnection,
+    party_id: u64,
+) -> APIResult<(String, u32)> {
+    let Some(party_info) = get_party_info(redis_client, party_id).await? else {
+        return Err(APIError::status_msg(
+            StatusCode::NOT_FOUND,
+            format!("Party {party_id} not found"),
+        ));
+    };
+    let Some((username, account_id, _)) = party_info.split(':').collect_tuple() else {
+        error!("Failed to parse party info");
+        return Err(APIError::internal("Failed to parse party info"));
+    };
+    let account_id = account_id
+        .parse()
+        .map_err(|_| APIError::internal("Failed to parse account id"))?;
+    Ok((username.to_owned(), account_id))
+}
+
 pub(super) async fn get_party_info_with_retries(
     redis_client: &mut redis::aio::MultiplexedConnection,
     lobby_id: u64,
@@ -78,14 +85,7 @@ pub(super) async fn make_ready(
         ready_state: read_state.into(),
         hero_roster: None,
     };
-    let response: CMsgClientToGcPartySetReadyStateResponse = steam_client
-        .call_steam_proxy(build_proxy_query(
-            EgcCitadelClientMessages::KEMsgClientToGcPartySetReadyState,
-            msg,
-            username.clone(),
-        ))
-        .await?
-        .msg;
+    let response = steam_client.gc(msg).username(username.clone()).await?.msg;
 
     info!("Made ready: {username} {lobby_id} {response:?}");
     let result = response.result;
@@ -108,14 +108,7 @@ pub(super) async fn leave_party(
     let msg = CMsgClientToGcPartyLeave {
         party_id: party_id.into(),
     };
-    let response: CMsgClientToGcPartyLeaveResponse = steam_client
-        .call_steam_proxy(build_proxy_query(
-            EgcCitadelClientMessages::KEMsgClientToGcPartyLeave,
-            msg,
-            username.clone(),
-        ))
-        .await?
-        .msg;
+    let response = steam_client.gc(msg).username(username.clone()).await?.msg;
 
     info!("Left Party: {username} {party_id} {response:?}");
     let result = response.result;
@@ -138,14 +131,7 @@ pub(super) async fn start_match(
     let msg = CMsgClientToGcPartyStartMatch {
         party_id: party_id.into(),
     };
-    let response: CMsgClientToGcPartyStartMatchResponse = steam_client
-        .call_steam_proxy(build_proxy_query(
-            EgcCitadelClientMessages::KEMsgClientToGcPartyStartMatch,
-            msg,
-            username.clone(),
-        ))
-        .await?
-        .msg;
+    let response = steam_client.gc(msg).username(username.clone()).await?.msg;
 
     info!("Start match: {username} {party_id} {response:?}");
     let result = response.result;
@@ -159,3 +145,25 @@ pub(super) async fn start_match(
     }
     Ok(())
 }
+
+pub(super) async fn party_action(
+    steam_client: &SteamClient,
+    username: String,
+    msg: CMsgClientToGcPartyAction,
+) -> APIResult<()> {
+    let party_id = msg.party_id();
+    let action = msg.action_id();
+    let response = steam_client.gc(msg).username(username.clone()).await?.msg;
+
+    info!("Party action {action:?}: {username} {party_id} {response:?}");
+    let result = response.result;
+    if result
+        .is_none_or(|r| r != c_msg_client_to_gc_party_action_response::EResponse::KESuccess as i32)
+    {
+        error!("Failed party action {action:?}: {username} {party_id} {result:?}");
+        return Err(APIError::internal(format!(
+            "Failed party action {action:?}: {result:?}"
+        )));
+    }
+    Ok(())
+}
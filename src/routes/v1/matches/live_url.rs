@@ -11,8 +11,7 @@ use tracing::debug;
 use utoipa::ToSchema;
 use valveprotos::deadlock::{
     CMsgClientToGcSpectateLobby, CMsgClientToGcSpectateLobbyResponse,
-    CMsgClientToGcSpectateUserResponse, EgcCitadelClientMessages,
-    c_msg_client_to_gc_spectate_user_response,
+    CMsgClientToGcSpectateUserResponse, c_msg_client_to_gc_spectate_user_response,
 };
 use valveprotos::gcsdk::EgcPlatform;
 
@@ -21,7 +20,6 @@ use crate::error::{APIError, APIResult};
 use crate::services::rate_limiter::Quota;
 use crate::services::rate_limiter::extractor::RateLimitKey;
 use crate::services::steam::client::SteamClient;
-use crate::services::steam::types::SteamProxyQuery;
 use crate::utils::types::MatchIdQuery;
 
 #[derive(Serialize, ToSchema)]
@@ -50,22 +48,7 @@ pub(super) async fn spectate_match(
         ..Default::default()
     };
     debug!(?msg);
-    Ok(steam_client
-        .call_steam_proxy(SteamProxyQuery {
-            msg_type: EgcCitadelClientMessages::KEMsgClientToGcSpectateLobby,
-            msg,
-            in_all_groups: None,
-            in_any_groups: Some(vec![
-                "SpectateLobby".to_owned(),
-                "SpectateLobbyOnDemand".to_owned(),
-            ]),
-            cooldown_time: Duration::from_secs(24 * 60 * 60 / 100),
-            request_timeout: Duration::from_secs(2),
-            username: None,
-            soft_cooldown_millis: None,
-        })
-        .await
-        .map(|s| s.msg)?)
+    Ok(steam_client.gc(msg).await?.msg)
 }
 
 #[utoipa::path(
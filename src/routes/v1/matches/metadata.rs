@@ -157,6 +157,21 @@ async fn parse_match_metadata_raw(raw_data: &[u8]) -> APIResult<CMsgMatchMetaDat
     }
 }
 
+/// Fetches and parses the metadata of an ingested match from S3, without falling back to Steam.
+pub(crate) async fn fetch_ingested_match_metadata(
+    s3: &AmazonS3,
+    match_id: u64,
+) -> APIResult<CMsgMatchMetaDataContents> {
+    let raw_data = match fetch_from_s3(s3, format!("processed/metadata/{match_id}.meta.bz2")).await
+    {
+        Ok(data) => data,
+        Err(_) => fetch_from_s3(s3, format!("processed/metadata/{match_id}.meta_hltv.bz2"))
+            .await
+            .map_err(|e| APIError::internal(format!("Failed to fetch match metadata: {e}")))?,
+    };
+    parse_match_metadata_raw(&raw_data).await
+}
+
 #[utoipa::path(
     get,
     path = "/{match_id}/metadata/raw",
@@ -1,9 +1,9 @@
 mod active;
 mod bulk_metadata;
-mod custom;
+pub(crate) mod custom;
 mod ingest_salts;
 mod live_url;
-mod metadata;
+pub(crate) mod metadata;
 mod recently_fetched;
 mod salts;
 pub(crate) mod types;
@@ -11,7 +11,7 @@ use tracing::{debug, warn};
 use utoipa::ToSchema;
 use valveprotos::deadlock::{
     CMsgClientToGcGetMatchMetaData, CMsgClientToGcGetMatchMetaDataResponse,
-    EgcCitadelClientMessages, c_msg_client_to_gc_get_match_meta_data_response,
+    c_msg_client_to_gc_get_match_meta_data_response,
 };
 
 use crate::context::AppState;
@@ -21,7 +21,7 @@ use crate::routes::v1::matches::types::ClickhouseSalts;
 use crate::services::rate_limiter::extractor::RateLimitKey;
 use crate::services::rate_limiter::{Quota, RateLimitClient};
 use crate::services::steam::client::SteamClient;
-use crate::services::steam::types::{SteamProxyQuery, SteamProxyResponse};
+use crate::services::steam::types::SteamProxyResponse;
 use crate::utils::types::MatchIdQuery;
 
 const FIRST_MATCH_DECEMBER_2024: u64 = 29507576;
@@ -129,16 +129,9 @@ pub(super) async fn fetch_match_salts(
         target_account_id: None,
     };
     let result = steam_client
-        .call_steam_proxy_raw(SteamProxyQuery {
-            msg_type: EgcCitadelClientMessages::KEMsgClientToGcGetMatchMetaData,
-            msg,
-            in_all_groups: Some(vec!["GetMatchMetaData".to_owned()]),
-            in_any_groups: None,
-            cooldown_time: Duration::from_secs(24 * 60 * 60 / 100),
-            request_timeout: Duration::from_secs(2),
-            username: None,
-            soft_cooldown_millis: is_custom.then_some(Duration::from_secs(24 * 60 * 60 / 200)),
-        })
+        .gc(msg)
+        .soft_cooldown(is_custom.then_some(Duration::from_secs(24 * 60 * 60 / 200)))
+        .raw()
         .await?;
     let username = result.username.clone();
     let salts: SteamProxyResponse<CMsgClientToGcGetMatchMetaDataResponse> = result.try_into()?;
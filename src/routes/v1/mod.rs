@@ -2,18 +2,20 @@ use utoipa_axum::router::OpenApiRouter;
 
 use crate::context::AppState;
 
+pub(crate) mod admin;
 pub mod analytics;
 mod auth;
 pub mod builds;
 mod commands;
 pub(crate) mod data_privacy;
 pub mod info;
-mod leaderboard;
+pub(crate) mod leaderboard;
 pub mod matches;
 mod patches;
 mod patron;
 pub mod players;
 pub mod sql;
+pub mod tournaments;
 
 pub(super) fn router() -> OpenApiRouter<AppState> {
     OpenApiRouter::new()
@@ -28,4 +30,5 @@ pub(super) fn router() -> OpenApiRouter<AppState> {
         .nest("/sql", sql::router())
         .nest("/auth", auth::router())
         .nest("/patron", patron::router())
+        .nest("/tournaments", tournaments::router())
 }
@@ -0,0 +1,56 @@
+use axum::Json;
+use axum::extract::{Query, State};
+use axum::response::IntoResponse;
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::context::AppState;
+use crate::error::APIError;
+use crate::services::patreon::events::{PatronEvent, list_patron_events};
+use crate::services::patreon::extractor::PatronSession;
+
+fn default_limit() -> i64 {
+    100
+}
+
+/// Query parameters for the patron history
+#[derive(Debug, Deserialize)]
+pub(crate) struct HistoryQuery {
+    /// Only return events older than this ID, for pagination
+    before_id: Option<i64>,
+    #[serde(default = "default_limit")]
+    limit: i64,
+}
+
+/// Fetches a page of the history of a patron, newest first.
+pub(crate) async fn fetch_history(
+    app_state: &AppState,
+    patron_id: Uuid,
+    query: &HistoryQuery,
+) -> Result<Vec<PatronEvent>, APIError> {
+    list_patron_events(
+        &app_state.pg_client,
+        patron_id,
+        query.before_id,
+        query.limit.clamp(1, 1000),
+    )
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to list patron events: {e}");
+        APIError::internal("Failed to fetch patron history")
+    })
+}
+
+/// GET /v1/patron/history
+///
+/// Returns the authenticated patron's history, newest first: added, removed, replaced and
+/// reactivated Steam accounts, accounts deactivated by a downgrade or cancellation, and
+/// membership changes.
+pub(crate) async fn get_patron_history(
+    State(app_state): State<AppState>,
+    session: PatronSession,
+    Query(query): Query<HistoryQuery>,
+) -> Result<impl IntoResponse, APIError> {
+    let events = fetch_history(&app_state, session.patron_id, &query).await?;
+    Ok(Json(events))
+}
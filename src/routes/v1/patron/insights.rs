@@ -0,0 +1,558 @@
+use core::cmp::Ordering;
+use core::time::Duration;
+
+use axum::Json;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use cached::TimedCache;
+use cached::proc_macro::cached;
+use chrono::{DateTime, DurationRound, TimeDelta, Utc};
+use clickhouse::Row;
+use futures::future::try_join_all;
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use crate::context::AppState;
+use crate::error::{APIError, APIResult};
+use crate::routes::v1::matches::types::GameMode;
+use crate::routes::v1::players::enemy_stats::{EnemyStats, EnemyStatsQuery, get_enemy_stats};
+use crate::routes::v1::players::mmr::mmr_history::{MMRHistory, get_mmr_history};
+use crate::services::patreon::extractor::PatronSession;
+use crate::services::patreon::steam_accounts_repository::SteamAccountsRepository;
+use crate::services::rate_limiter::Quota;
+use crate::services::rate_limiter::extractor::RateLimitKey;
+use crate::utils::types::AccountIdQuery;
+
+/// The length of the digest period, compared to the period before it.
+const DIGEST_PERIOD: TimeDelta = TimeDelta::days(7);
+/// The amount of best and worst matchups in a digest.
+const MATCHUPS: usize = 3;
+/// The minimum amount of matches against an enemy to count as a matchup.
+const MIN_MATCHUP_MATCHES: u64 = 2;
+/// The minimum amount of matches with an item to rate its effectiveness.
+const MIN_ITEM_MATCHES: u32 = 2;
+
+#[derive(Debug, Clone, Copy, Row, Deserialize)]
+struct HeroRow {
+    hero_id: u32,
+    matches: u32,
+    wins: u32,
+    previous_matches: u32,
+}
+
+#[derive(Debug, Clone, Copy, Row, Deserialize)]
+struct ItemRow {
+    item_id: u32,
+    matches: u32,
+    wins: u32,
+    avg_buy_time_s: f64,
+}
+
+/// The MMR of a player after a match
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub(crate) struct MmrPoint {
+    player_score: f64,
+    division: u32,
+    division_tier: u32,
+}
+
+impl From<&MMRHistory> for MmrPoint {
+    fn from(history: &MMRHistory) -> Self {
+        Self {
+            player_score: history.player_score,
+            division: history.division,
+            division_tier: history.division_tier,
+        }
+    }
+}
+
+/// Change of the MMR over the digest period
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub(crate) struct MmrTrend {
+    /// The MMR before the period, if the account played before it
+    start: Option<MmrPoint>,
+    end: MmrPoint,
+    player_score_change: Option<f64>,
+    matches: usize,
+}
+
+/// Usage of a hero in the digest period and the period before it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub(crate) struct HeroUsage {
+    hero_id: u32,
+    matches: u32,
+    wins: u32,
+    previous_matches: u32,
+}
+
+/// Changes of the hero pool compared to the period before
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub(crate) struct HeroPool {
+    /// The heroes played in the period, most played first
+    heroes: Vec<HeroUsage>,
+    /// Heroes played in the period but not in the period before
+    new_heroes: Vec<u32>,
+    /// Heroes played in the period before but not in the period
+    dropped_heroes: Vec<u32>,
+}
+
+/// Win rate of the account with an item
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub(crate) struct ItemEffectiveness {
+    item_id: u32,
+    matches: u32,
+    wins: u32,
+    win_rate: f64,
+    /// The win rate with the item minus the win rate of all matches in the period
+    win_rate_difference: f64,
+    avg_buy_time_s: f64,
+}
+
+/// Weekly digest of a prioritized Steam account
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct AccountInsights {
+    account_id: u32,
+    period_start: DateTime<Utc>,
+    period_end: DateTime<Utc>,
+    matches: u32,
+    wins: u32,
+    mmr: Option<MmrTrend>,
+    hero_pool: HeroPool,
+    best_matchups: Vec<EnemyStats>,
+    worst_matchups: Vec<EnemyStats>,
+    items: Vec<ItemEffectiveness>,
+}
+
+/// Response for the insights endpoint
+#[derive(Debug, Serialize)]
+pub(crate) struct InsightsResponse {
+    accounts: Vec<AccountInsights>,
+}
+
+fn build_hero_query(account_id: u32, period_start: i64, previous_start: i64) -> String {
+    let game_mode_filter = GameMode::sql_filter(GameMode::default_option());
+    format!(
+        "
+    SELECT
+        hero_id,
+        toUInt32(countIf(start_time >= {period_start})) AS matches,
+        toUInt32(countIf(won AND start_time >= {period_start})) AS wins,
+        toUInt32(countIf(start_time < {period_start})) AS previous_matches
+    FROM player_match_history
+    WHERE account_id = {account_id}
+        AND match_mode IN ('Ranked', 'Unranked')
+        AND {game_mode_filter}
+        AND start_time >= {previous_start}
+    GROUP BY hero_id
+    ORDER BY matches DESC, previous_matches DESC, hero_id
+    "
+    )
+}
+
+fn build_item_query(account_id: u32, period_start: i64, period_end: i64) -> String {
+    let game_mode_filter = GameMode::sql_filter(GameMode::default_option());
+    format!(
+        "
+    WITH
+        t_upgrades AS (SELECT id FROM items WHERE type = 'upgrade'),
+        t_matches AS (
+            SELECT match_id
+            FROM player_match_history
+            WHERE account_id = {account_id}
+                AND match_mode IN ('Ranked', 'Unranked')
+                AND {game_mode_filter}
+                AND start_time >= {period_start}
+                AND start_time <= {period_end}
+        )
+    SELECT
+        it.item_id AS item_id,
+        toUInt32(uniq(match_id)) AS matches,
+        toUInt32(uniqIf(match_id, won)) AS wins,
+        avg(it.game_time_s) AS avg_buy_time_s
+    FROM match_player
+        ARRAY JOIN items AS it
+    WHERE account_id = {account_id}
+        AND match_id IN t_matches
+        AND it.item_id IN t_upgrades
+        AND it.game_time_s > 0
+    GROUP BY item_id
+    "
+    )
+}
+
+async fn get_hero_rows(
+    ch_client: &clickhouse::Client,
+    account_id: u32,
+    period_start: i64,
+    previous_start: i64,
+) -> APIResult<Vec<HeroRow>> {
+    let query = build_hero_query(account_id, period_start, previous_start);
+    debug!(?query);
+    Ok(ch_client.query(&query).fetch_all().await?)
+}
+
+async fn get_item_rows(
+    ch_client: &clickhouse::Client,
+    account_id: u32,
+    period_start: i64,
+    period_end: i64,
+) -> APIResult<Vec<ItemRow>> {
+    let query = build_item_query(account_id, period_start, period_end);
+    debug!(?query);
+    Ok(ch_client.query(&query).fetch_all().await?)
+}
+
+/// Compares the MMR after the last match of the period to the MMR before it.
+///
+/// Returns `None` if the account played no match with a known MMR in the period.
+fn mmr_trend(history: &[MMRHistory], period_start: u32) -> Option<MmrTrend> {
+    let split = history.partition_point(|h| h.start_time < period_start);
+    let (before, during) = history.split_at(split);
+    let start = before.last().map(MmrPoint::from);
+    let end = MmrPoint::from(during.last()?);
+    Some(MmrTrend {
+        start,
+        end,
+        player_score_change: start.map(|s| end.player_score - s.player_score),
+        matches: during.len(),
+    })
+}
+
+fn hero_pool(rows: &[HeroRow]) -> HeroPool {
+    let heroes = rows
+        .iter()
+        .filter(|r| r.matches > 0)
+        .map(|r| HeroUsage {
+            hero_id: r.hero_id,
+            matches: r.matches,
+            wins: r.wins,
+            previous_matches: r.previous_matches,
+        })
+        .collect();
+    let new_heroes = rows
+        .iter()
+        .filter(|r| r.matches > 0 && r.previous_matches == 0)
+        .map(|r| r.hero_id)
+        .collect();
+    let dropped_heroes = rows
+        .iter()
+        .filter(|r| r.matches == 0 && r.previous_matches > 0)
+        .map(|r| r.hero_id)
+        .collect();
+    HeroPool {
+        heroes,
+        new_heroes,
+        dropped_heroes,
+    }
+}
+
+/// Compares win rates without floating point errors, `wins_a / matches_a` to `wins_b / matches_b`.
+fn cmp_win_rate(wins_a: u64, matches_a: u64, wins_b: u64, matches_b: u64) -> Ordering {
+    (u128::from(wins_a) * u128::from(matches_b)).cmp(&(u128::from(wins_b) * u128::from(matches_a)))
+}
+
+/// Splits the enemies into the best and worst matchups, by the win rate against them.
+///
+/// Enemies with few matches are ignored, and no enemy is both a best and a worst matchup.
+fn matchups(mut stats: Vec<EnemyStats>) -> (Vec<EnemyStats>, Vec<EnemyStats>) {
+    stats.retain(|s| s.matches_played >= MIN_MATCHUP_MATCHES);
+    stats.sort_by(|a, b| {
+        cmp_win_rate(b.wins, b.matches_played, a.wins, a.matches_played)
+            .then(b.matches_played.cmp(&a.matches_played))
+            .then(a.enemy_id.cmp(&b.enemy_id))
+    });
+    let best_count = MATCHUPS.min(stats.len());
+    let worst_start = stats.len().saturating_sub(MATCHUPS).max(best_count);
+    let mut worst = stats.split_off(worst_start);
+    worst.reverse();
+    stats.truncate(best_count);
+    (stats, worst)
+}
+
+fn win_rate(wins: u32, matches: u32) -> f64 {
+    if matches == 0 {
+        0.0
+    } else {
+        f64::from(wins) / f64::from(matches)
+    }
+}
+
+/// Rates the items bought in enough matches, most bought first.
+fn item_effectiveness(rows: &[ItemRow], wins: u32, matches: u32) -> Vec<ItemEffectiveness> {
+    let overall = win_rate(wins, matches);
+    let mut items = rows
+        .iter()
+        .filter(|r| r.matches >= MIN_ITEM_MATCHES)
+        .map(|r| {
+            let win_rate = win_rate(r.wins, r.matches);
+            ItemEffectiveness {
+                item_id: r.item_id,
+                matches: r.matches,
+                wins: r.wins,
+                win_rate,
+                win_rate_difference: win_rate - overall,
+                avg_buy_time_s: r.avg_buy_time_s,
+            }
+        })
+        .collect::<Vec<_>>();
+    items.sort_by(|a, b| {
+        b.matches
+            .cmp(&a.matches)
+            .then(cmp_win_rate(
+                b.wins.into(),
+                b.matches.into(),
+                a.wins.into(),
+                a.matches.into(),
+            ))
+            .then(a.item_id.cmp(&b.item_id))
+    });
+    items
+}
+
+/// Returns the end of the current digest period, the start of the current hour.
+///
+/// Digests of the same period are equal, so they are cached until the next period.
+fn current_period_end() -> DateTime<Utc> {
+    Utc::now()
+        .duration_trunc(TimeDelta::hours(1))
+        .unwrap_or_else(|_| Utc::now())
+}
+
+#[cached(
+    ty = "TimedCache<(u32, i64), AccountInsights>",
+    create = "{ TimedCache::with_lifespan(std::time::Duration::from_secs(60 * 60)) }",
+    result = true,
+    convert = "{ (account_id, period_end.timestamp()) }",
+    sync_writes = "by_key",
+    key = "(u32, i64)"
+)]
+async fn account_insights(
+    ch_client: &clickhouse::Client,
+    account_id: u32,
+    period_end: DateTime<Utc>,
+) -> APIResult<AccountInsights> {
+    let period_start = period_end - DIGEST_PERIOD;
+    let previous_start = period_start - DIGEST_PERIOD;
+
+    let (history, heroes, enemies, items) = tokio::try_join!(
+        get_mmr_history(ch_client, account_id),
+        get_hero_rows(
+            ch_client,
+            account_id,
+            period_start.timestamp(),
+            previous_start.timestamp()
+        ),
+        get_enemy_stats(
+            ch_client,
+            account_id,
+            EnemyStatsQuery::between(period_start.timestamp(), period_end.timestamp()),
+        ),
+        get_item_rows(
+            ch_client,
+            account_id,
+            period_start.timestamp(),
+            period_end.timestamp()
+        ),
+    )?;
+
+    let matches = heroes.iter().map(|h| h.matches).sum();
+    let wins = heroes.iter().map(|h| h.wins).sum();
+    let (best_matchups, worst_matchups) = matchups(enemies);
+    Ok(AccountInsights {
+        account_id,
+        period_start,
+        period_end,
+        matches,
+        wins,
+        mmr: mmr_trend(
+            &history,
+            u32::try_from(period_start.timestamp()).unwrap_or_default(),
+        ),
+        hero_pool: hero_pool(&heroes),
+        best_matchups,
+        worst_matchups,
+        items: item_effectiveness(&items, wins, matches),
+    })
+}
+
+async fn active_account_ids(app_state: &AppState, session: &PatronSession) -> APIResult<Vec<u32>> {
+    let repo = SteamAccountsRepository::new(app_state.pg_client.clone());
+    let accounts = repo
+        .get_active_accounts_for_patron(session.patron_id)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to get active accounts: {e}");
+            APIError::internal("Failed to fetch Steam accounts")
+        })?;
+    Ok(accounts
+        .into_iter()
+        .filter_map(|a| u32::try_from(a.steam_id3).ok())
+        .collect())
+}
+
+/// Digests run several queries per account, so they are limited even though they are cached.
+async fn apply_limits(app_state: &AppState, rate_limit_key: &RateLimitKey) -> APIResult<()> {
+    app_state
+        .rate_limit_client
+        .apply_limits(
+            rate_limit_key,
+            "patron_insights",
+            &[
+                Quota::ip_limit(30, Duration::from_mins(1)),
+                Quota::key_limit(30, Duration::from_mins(1)),
+                Quota::global_limit(600, Duration::from_mins(1)),
+            ],
+        )
+        .await?;
+    Ok(())
+}
+
+/// GET /v1/patron/insights
+///
+/// Returns a weekly digest for each of the patron's active Steam accounts: the MMR trend, hero
+/// pool changes, best and worst matchups against enemies and the effectiveness of bought items.
+/// Digests cover the week before the start of the current hour.
+pub(crate) async fn list_insights(
+    rate_limit_key: RateLimitKey,
+    State(app_state): State<AppState>,
+    session: PatronSession,
+) -> Result<impl IntoResponse, APIError> {
+    apply_limits(&app_state, &rate_limit_key).await?;
+    let account_ids = active_account_ids(&app_state, &session).await?;
+    let period_end = current_period_end();
+    let accounts = try_join_all(
+        account_ids
+            .into_iter()
+            .map(|account_id| account_insights(&app_state.ch_client_ro, account_id, period_end)),
+    )
+    .await?;
+    Ok(Json(InsightsResponse { accounts }))
+}
+
+/// GET /v1/patron/insights/{account_id}
+///
+/// Returns the weekly digest of one of the patron's active Steam accounts.
+pub(crate) async fn get_account_insights(
+    rate_limit_key: RateLimitKey,
+    State(app_state): State<AppState>,
+    session: PatronSession,
+    Path(AccountIdQuery { account_id }): Path<AccountIdQuery>,
+) -> Result<impl IntoResponse, APIError> {
+    apply_limits(&app_state, &rate_limit_key).await?;
+    let account_ids = active_account_ids(&app_state, &session).await?;
+    if !account_ids.contains(&account_id) {
+        return Err(APIError::status_msg(
+            StatusCode::NOT_FOUND,
+            "Account not found or does not belong to you",
+        ));
+    }
+    let insights =
+        account_insights(&app_state.ch_client_ro, account_id, current_period_end()).await?;
+    Ok(Json(insights))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn history(start_time: u32, player_score: f64) -> MMRHistory {
+        serde_json::from_value(serde_json::json!({
+            "account_id": 1,
+            "match_id": start_time,
+            "start_time": start_time,
+            "player_score": player_score,
+            "rank": 0,
+            "division": 0,
+            "division_tier": 0,
+        }))
+        .unwrap()
+    }
+
+    fn enemy(enemy_id: u32, wins: u64, matches_played: u64) -> EnemyStats {
+        serde_json::from_value(serde_json::json!({
+            "enemy_id": enemy_id,
+            "wins": wins,
+            "matches_played": matches_played,
+            "matches": [],
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_mmr_trend() {
+        let history = vec![history(10, 20.0), history(20, 22.0), history(30, 25.5)];
+        let trend = mmr_trend(&history, 15).unwrap();
+        assert_eq!(trend.start.map(|s| s.player_score), Some(20.0));
+        assert!((trend.end.player_score - 25.5).abs() < f64::EPSILON);
+        assert_eq!(trend.player_score_change, Some(5.5));
+        assert_eq!(trend.matches, 2);
+    }
+
+    #[test]
+    fn test_mmr_trend_without_matches() {
+        let history = vec![history(10, 20.0)];
+        assert_eq!(mmr_trend(&history, 15), None);
+        let trend = mmr_trend(&history, 5).unwrap();
+        assert_eq!(trend.start, None);
+        assert_eq!(trend.player_score_change, None);
+    }
+
+    #[test]
+    fn test_hero_pool() {
+        let row = |hero_id, matches, previous_matches| HeroRow {
+            hero_id,
+            matches,
+            wins: 0,
+            previous_matches,
+        };
+        let pool = hero_pool(&[row(1, 5, 3), row(2, 2, 0), row(3, 0, 4)]);
+        assert_eq!(
+            pool.heroes.iter().map(|h| h.hero_id).collect::<Vec<_>>(),
+            vec![1, 2]
+        );
+        assert_eq!(pool.new_heroes, vec![2]);
+        assert_eq!(pool.dropped_heroes, vec![3]);
+    }
+
+    #[test]
+    fn test_matchups() {
+        let stats = vec![
+            enemy(1, 3, 3),
+            enemy(2, 0, 2),
+            enemy(3, 1, 2),
+            enemy(4, 5, 5),
+            enemy(5, 1, 1),
+            enemy(6, 1, 4),
+            enemy(7, 2, 3),
+        ];
+        let (best, worst) = matchups(stats);
+        let ids = |s: &[EnemyStats]| s.iter().map(|e| e.enemy_id).collect::<Vec<_>>();
+        assert_eq!(ids(&best), vec![4, 1, 7]);
+        assert_eq!(ids(&worst), vec![2, 6, 3]);
+    }
+
+    #[test]
+    fn test_matchups_do_not_overlap() {
+        let (best, worst) = matchups(vec![enemy(1, 2, 2), enemy(2, 0, 2), enemy(3, 1, 2)]);
+        assert_eq!(best.len(), 3);
+        assert!(worst.is_empty());
+    }
+
+    #[test]
+    fn test_item_effectiveness() {
+        let row = |item_id, matches, wins| ItemRow {
+            item_id,
+            matches,
+            wins,
+            avg_buy_time_s: 600.0,
+        };
+        let items = item_effectiveness(&[row(1, 4, 3), row(2, 1, 1), row(3, 4, 1)], 5, 10);
+        assert_eq!(
+            items.iter().map(|i| i.item_id).collect::<Vec<_>>(),
+            vec![1, 3]
+        );
+        assert!((items[0].win_rate_difference - 0.25).abs() < f64::EPSILON);
+        assert!((items[1].win_rate_difference + 0.25).abs() < f64::EPSILON);
+    }
+}
@@ -6,12 +6,20 @@ use utoipa_axum::router::OpenApiRouter;
 use crate::context::AppState;
 use crate::middleware::cache::CacheControlMiddleware;
 
+pub(super) mod history;
+mod insights;
 mod status;
 mod steam_accounts;
 
 pub(super) fn router() -> OpenApiRouter<AppState> {
     OpenApiRouter::new()
         .route("/status", get(status::get_patron_status))
+        .route("/history", get(history::get_patron_history))
+        .route("/insights", get(insights::list_insights))
+        .route(
+            "/insights/{account_id}",
+            get(insights::get_account_insights),
+        )
         .route(
             "/steam-accounts",
             get(steam_accounts::list_steam_accounts).post(steam_accounts::add_steam_account),
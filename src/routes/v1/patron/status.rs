@@ -6,6 +6,7 @@ use serde::Serialize;
 
 use crate::context::AppState;
 use crate::error::APIError;
+use crate::services::membership::MembershipProviderKind;
 use crate::services::patreon::extractor::PatronSession;
 use crate::services::patreon::repository::PatronRepository;
 use crate::services::patreon::steam_accounts_repository::SteamAccountsRepository;
@@ -21,6 +22,8 @@ pub(crate) struct SteamAccountsSummary {
 /// Response for the patron status endpoint
 #[derive(Debug, Serialize)]
 pub(crate) struct PatronStatusResponse {
+    /// The platform the patron supports the project on
+    provider: MembershipProviderKind,
     tier_id: Option<String>,
     pledge_amount_cents: Option<i32>,
     total_slots: i32,
@@ -82,6 +85,7 @@ pub(crate) async fn get_patron_status(
     let available_slots = (total_slots - used_slots).max(0);
 
     Ok(Json(PatronStatusResponse {
+        provider: patron.provider,
         tier_id: patron.tier_id,
         pledge_amount_cents: patron.pledge_amount_cents,
         total_slots,
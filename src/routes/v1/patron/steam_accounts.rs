@@ -4,11 +4,15 @@ use axum::http::StatusCode;
 use axum::response::IntoResponse;
 use chrono::{DateTime, Duration, TimeDelta, Utc};
 use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use sqlx::{Postgres, Transaction};
 use uuid::Uuid;
 
 use crate::context::AppState;
 use crate::error::APIError;
+use crate::services::patreon::events::{PatronEventKind, PatronEventLog, PatronEventSource};
 use crate::services::patreon::extractor::PatronSession;
+use crate::services::patreon::forget_account_priorities;
 use crate::services::patreon::repository::PatronRepository;
 use crate::services::patreon::steam_accounts_repository::{
     SteamAccountsRepository, SteamAccountsRepositoryError,
@@ -151,13 +155,26 @@ pub(crate) async fn add_steam_account(
         }
 
         // Reactivate the soft-deleted account (sets deleted_at to NULL)
-        let reactivated = repo
-            .reactivate_account(deleted_account.id, session.patron_id)
-            .await
-            .map_err(|e| {
-                tracing::error!("Failed to reactivate account: {e}");
-                APIError::internal("Failed to reactivate Steam account")
-            })?;
+        let mut transaction = begin(&app_state).await?;
+        let reactivated = SteamAccountsRepository::reactivate_account(
+            &mut *transaction,
+            deleted_account.id,
+            session.patron_id,
+        )
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to reactivate account: {e}");
+            APIError::internal("Failed to reactivate Steam account")
+        })?;
+        commit_with_event(
+            transaction,
+            session.patron_id,
+            PatronEventKind::AccountReactivated,
+            reactivated.steam_id3,
+            Value::Null,
+        )
+        .await?;
+        forget_account_priorities(&[reactivated.steam_id3]).await;
 
         return Ok((
             StatusCode::CREATED,
@@ -198,13 +215,26 @@ pub(crate) async fn add_steam_account(
     }
 
     // Step 4: Insert new record
-    let account = repo
-        .add_steam_account(session.patron_id, request.steam_id3)
-        .await
-        .map_err(|e| {
-            tracing::error!("Failed to add steam account: {e}");
-            APIError::internal("Failed to add Steam account")
-        })?;
+    let mut transaction = begin(&app_state).await?;
+    let account = SteamAccountsRepository::add_steam_account(
+        &mut *transaction,
+        session.patron_id,
+        request.steam_id3,
+    )
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to add steam account: {e}");
+        APIError::internal("Failed to add Steam account")
+    })?;
+    commit_with_event(
+        transaction,
+        session.patron_id,
+        PatronEventKind::AccountAdded,
+        account.steam_id3,
+        Value::Null,
+    )
+    .await?;
+    forget_account_priorities(&[account.steam_id3]).await;
 
     // Return 201 Created with account details
     Ok((
@@ -322,20 +352,34 @@ pub(crate) async fn delete_steam_account(
     session: PatronSession,
     Path(account_id): Path<Uuid>,
 ) -> Result<impl IntoResponse, APIError> {
-    let repo = SteamAccountsRepository::new(app_state.pg_client.clone());
-
     // Soft delete the account (sets deleted_at to NOW())
     // This also verifies the account belongs to the authenticated patron
-    match repo.soft_delete_account(account_id, session.patron_id).await {
-        Ok(()) => Ok(Json(DeleteSteamAccountResponse {
-            message: "Steam account removed. The slot will be available for reuse after a 24-hour cooldown period.".to_string(),
-        })),
-        Err(SteamAccountsRepositoryError::AccountNotFound) => {
-            Err(APIError::status_msg(
-                StatusCode::NOT_FOUND,
-                "Account not found or does not belong to you",
-            ))
+    let mut transaction = begin(&app_state).await?;
+    match SteamAccountsRepository::soft_delete_account(
+        &mut *transaction,
+        account_id,
+        session.patron_id,
+    )
+    .await
+    {
+        Ok(steam_id3) => {
+            commit_with_event(
+                transaction,
+                session.patron_id,
+                PatronEventKind::AccountRemoved,
+                steam_id3,
+                Value::Null,
+            )
+            .await?;
+            forget_account_priorities(&[steam_id3]).await;
+            Ok(Json(DeleteSteamAccountResponse {
+                message: "Steam account removed. The slot will be available for reuse after a 24-hour cooldown period.".to_string(),
+            }))
         }
+        Err(SteamAccountsRepositoryError::AccountNotFound) => Err(APIError::status_msg(
+            StatusCode::NOT_FOUND,
+            "Account not found or does not belong to you",
+        )),
         Err(e) => {
             tracing::error!("Failed to delete steam account: {e}");
             Err(APIError::internal("Failed to remove Steam account"))
@@ -403,7 +447,8 @@ pub(crate) async fn replace_steam_account(
     }
 
     // Step 5: Hard delete the old record
-    repo.hard_delete_account(account_id, session.patron_id)
+    let mut transaction = begin(&app_state).await?;
+    SteamAccountsRepository::hard_delete_account(&mut *transaction, account_id, session.patron_id)
         .await
         .map_err(|e| {
             tracing::error!("Failed to hard delete old account: {e}");
@@ -411,13 +456,25 @@ pub(crate) async fn replace_steam_account(
         })?;
 
     // Step 6: Insert new account with the provided steam_id3
-    let new_account = repo
-        .add_steam_account(session.patron_id, request.steam_id3)
-        .await
-        .map_err(|e| {
-            tracing::error!("Failed to add new steam account: {e}");
-            APIError::internal("Failed to replace account")
-        })?;
+    let new_account = SteamAccountsRepository::add_steam_account(
+        &mut *transaction,
+        session.patron_id,
+        request.steam_id3,
+    )
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to add new steam account: {e}");
+        APIError::internal("Failed to replace account")
+    })?;
+    commit_with_event(
+        transaction,
+        session.patron_id,
+        PatronEventKind::AccountReplaced,
+        new_account.steam_id3,
+        json!({ "previous_steam_id3": account.steam_id3 }),
+    )
+    .await?;
+    forget_account_priorities(&[account.steam_id3, new_account.steam_id3]).await;
 
     // Return 200 OK with new account details
     Ok(Json(SteamAccountResponse {
@@ -525,19 +582,32 @@ pub(crate) async fn reactivate_steam_account(
     }
 
     // Step 5: Reactivate the account (sets deleted_at to NULL)
-    let reactivated = repo
-        .reactivate_account(account_id, session.patron_id)
-        .await
-        .map_err(|e| match e {
-            SteamAccountsRepositoryError::AccountNotFound => APIError::status_msg(
-                StatusCode::NOT_FOUND,
-                "Account not found or does not belong to you",
-            ),
-            SteamAccountsRepositoryError::Database(e) => {
-                tracing::error!("Failed to reactivate account: {e}");
-                APIError::internal("Failed to reactivate account")
-            }
-        })?;
+    let mut transaction = begin(&app_state).await?;
+    let reactivated = SteamAccountsRepository::reactivate_account(
+        &mut *transaction,
+        account_id,
+        session.patron_id,
+    )
+    .await
+    .map_err(|e| match e {
+        SteamAccountsRepositoryError::AccountNotFound => APIError::status_msg(
+            StatusCode::NOT_FOUND,
+            "Account not found or does not belong to you",
+        ),
+        SteamAccountsRepositoryError::Database(e) => {
+            tracing::error!("Failed to reactivate account: {e}");
+            APIError::internal("Failed to reactivate account")
+        }
+    })?;
+    commit_with_event(
+        transaction,
+        session.patron_id,
+        PatronEventKind::AccountReactivated,
+        reactivated.steam_id3,
+        Value::Null,
+    )
+    .await?;
+    forget_account_priorities(&[reactivated.steam_id3]).await;
 
     // Return 200 OK with reactivated account details
     Ok(Json(SteamAccountResponse {
@@ -547,3 +617,30 @@ pub(crate) async fn reactivate_steam_account(
         deleted_at: reactivated.deleted_at,
     }))
 }
+
+/// Starts the transaction of a change to the Steam accounts of a patron.
+async fn begin(app_state: &AppState) -> Result<Transaction<'static, Postgres>, APIError> {
+    app_state.pg_client.begin().await.map_err(|e| {
+        tracing::error!("Failed to start transaction: {e}");
+        APIError::internal("Failed to update Steam accounts")
+    })
+}
+
+/// Commits a change to the Steam accounts of a patron together with its event.
+async fn commit_with_event(
+    mut transaction: Transaction<'static, Postgres>,
+    patron_id: Uuid,
+    kind: PatronEventKind,
+    steam_id3: i64,
+    details: Value,
+) -> Result<(), APIError> {
+    let map_err = |e: sqlx::Error| {
+        tracing::error!("Failed to record patron event: {e}");
+        APIError::internal("Failed to update Steam accounts")
+    };
+    PatronEventLog::new(PatronEventSource::Patron)
+        .record(&mut *transaction, patron_id, kind, Some(steam_id3), details)
+        .await
+        .map_err(map_err)?;
+    transaction.commit().await.map_err(map_err)
+}
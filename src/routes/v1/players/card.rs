@@ -10,17 +10,14 @@ use serde::Serialize;
 use tracing::warn;
 use utoipa::ToSchema;
 use valveprotos::deadlock::{
-    CMsgCitadelProfileCard, CMsgClientToGcGetProfileCard, EgcCitadelClientMessages,
-    c_msg_citadel_profile_card,
+    CMsgCitadelProfileCard, CMsgClientToGcGetProfileCard, c_msg_citadel_profile_card,
 };
 
 use crate::context::AppState;
 use crate::error::APIResult;
 use crate::services::rate_limiter::extractor::RateLimitKey;
 use crate::services::steam::client::SteamClient;
-use crate::services::steam::types::{
-    SteamProxyQuery, SteamProxyRawResponse, SteamProxyResponse, SteamProxyResult,
-};
+use crate::services::steam::types::{SteamProxyRawResponse, SteamProxyResponse, SteamProxyResult};
 use crate::utils::types::AccountIdQuery;
 
 #[derive(Debug, Clone, Serialize, ToSchema)]
@@ -162,18 +159,7 @@ pub(crate) async fn fetch_player_card_raw(
         dev_access_hint: None,
         friend_access_hint: true.into(),
     };
-    steam_client
-        .call_steam_proxy_raw(SteamProxyQuery {
-            msg_type: EgcCitadelClientMessages::KEMsgClientToGcGetProfileCard,
-            msg,
-            in_all_groups: None,
-            in_any_groups: None,
-            cooldown_time: Duration::from_secs(10),
-            request_timeout: Duration::from_secs(2),
-            username: bot_username.into(),
-            soft_cooldown_millis: None,
-        })
-        .await
+    steam_client.gc(msg).username(bot_username).raw().await
 }
 
 pub(crate) async fn get_player_card(
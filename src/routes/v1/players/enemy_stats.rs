@@ -13,7 +13,7 @@ use crate::routes::v1::matches::types::GameMode;
 use crate::utils::types::AccountIdQuery;
 
 #[derive(Copy, Debug, Clone, Deserialize, IntoParams, Eq, PartialEq, Hash, Default)]
-pub(super) struct EnemyStatsQuery {
+pub(crate) struct EnemyStatsQuery {
     /// Filter matches based on their game mode. Valid values: `normal`, `street_brawl`. **Default:** `normal`.
     #[serde(default = "GameMode::default_option")]
     #[param(inline, default = "normal")]
@@ -44,11 +44,23 @@ pub(super) struct EnemyStatsQuery {
 pub struct EnemyStats {
     pub enemy_id: u32,
     /// The amount of matches won against the enemy.
-    wins: u64,
-    matches_played: u64,
+    pub(crate) wins: u64,
+    pub(crate) matches_played: u64,
     matches: Vec<u64>,
 }
 
+impl EnemyStatsQuery {
+    /// Creates a query for the matches started in the given time range.
+    pub(crate) fn between(min_unix_timestamp: i64, max_unix_timestamp: i64) -> Self {
+        Self {
+            game_mode: GameMode::default_option(),
+            min_unix_timestamp: Some(min_unix_timestamp),
+            max_unix_timestamp: Some(max_unix_timestamp),
+            ..Default::default()
+        }
+    }
+}
+
 fn build_query(account_id: u32, query: &EnemyStatsQuery) -> String {
     let mut history_filters = vec![];
     history_filters.push(format!("account_id = {account_id}"));
@@ -112,7 +124,7 @@ fn build_query(account_id: u32, query: &EnemyStatsQuery) -> String {
     )
 }
 
-async fn get_enemy_stats(
+pub(crate) async fn get_enemy_stats(
     ch_client: &clickhouse::Client,
     account_id: u32,
     query: EnemyStatsQuery,
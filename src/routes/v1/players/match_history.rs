@@ -13,8 +13,7 @@ use serde::{Deserialize, Serialize};
 use tracing::{debug, warn};
 use utoipa::{IntoParams, ToSchema};
 use valveprotos::deadlock::{
-    CMsgClientToGcGetMatchHistory, CMsgClientToGcGetMatchHistoryResponse, EgcCitadelClientMessages,
-    c_msg_client_to_gc_get_match_history_response,
+    CMsgClientToGcGetMatchHistory, c_msg_client_to_gc_get_match_history_response,
 };
 
 use crate::context::AppState;
@@ -22,7 +21,6 @@ use crate::error::{APIError, APIResult};
 use crate::services::rate_limiter::Quota;
 use crate::services::rate_limiter::extractor::RateLimitKey;
 use crate::services::steam::client::SteamClient;
-use crate::services::steam::types::SteamProxyQuery;
 use crate::utils::types::AccountIdQuery;
 
 const MAX_REFETCH_ITERATIONS: i32 = 100;
@@ -148,19 +146,7 @@ async fn fetch_match_history_raw(
         game_mode: None,
         match_mode: None,
     };
-    let response: CMsgClientToGcGetMatchHistoryResponse = steam_client
-        .call_steam_proxy(SteamProxyQuery {
-            msg_type: EgcCitadelClientMessages::KEMsgClientToGcGetMatchHistory,
-            msg,
-            in_all_groups: Some(vec!["GetMatchHistory".to_owned()]),
-            in_any_groups: None,
-            cooldown_time: Duration::from_secs(24 * 60 * 60 / 100),
-            request_timeout: Duration::from_secs(3),
-            username: None,
-            soft_cooldown_millis: None,
-        })
-        .await?
-        .msg;
+    let response = steam_client.gc(msg).await?.msg;
     if response.result.is_none_or(|r| {
         r != c_msg_client_to_gc_get_match_history_response::EResult::KEResultSuccess as i32
     }) {
@@ -26,12 +26,12 @@ pub(super) struct HeroMMRHistoryPath {
 
 #[derive(Debug, Clone, Row, Serialize, Deserialize, ToSchema)]
 pub struct MMRHistory {
-    account_id: u32,
+    pub(crate) account_id: u32,
     match_id: u64,
     /// Start time of the match
     pub start_time: u32,
     /// Player Score is the index for the rank array (internally used for the rank regression)
-    player_score: f64,
+    pub(crate) player_score: f64,
     /// The Player Rank (tier = first digits, subtier = last digit). See more: <https://assets.deadlock-api.com/v2/ranks>
     rank: u32,
     /// Extracted from the rank the division (rank // 10)
@@ -127,7 +127,7 @@ fn build_hero_mmr_history_query(account_id: u32, hero_id: u8) -> String {
     )
 }
 
-async fn get_mmr_history(
+pub(crate) async fn get_mmr_history(
     ch_client: &clickhouse::Client,
     account_id: u32,
 ) -> APIResult<Vec<MMRHistory>> {
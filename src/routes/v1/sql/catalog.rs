@@ -0,0 +1,589 @@
+use core::time::Duration;
+use std::collections::{BTreeMap, HashMap};
+
+use axum::Json;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum_extra::extract::Query;
+use cached::TimedCache;
+use cached::proc_macro::cached;
+use clickhouse::Row;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::context::AppState;
+use crate::error::{APIError, APIResult};
+use crate::routes::v1::sql::limits::{QueryLimits, QueryMode, QueryTier};
+use crate::routes::v1::sql::route::{SQLQueryError, fetch_list_tables, run_sql};
+use crate::services::rate_limiter::extractor::RateLimitKey;
+use crate::services::rate_limiter::{ApiKeyScope, Quota};
+
+/// Known joins between the tables, as `(from_table, to_table, columns, description)`.
+const RELATIONSHIPS: &[(&str, &str, &[&str], &str)] = &[
+    (
+        "match_player",
+        "match_info",
+        &["match_id"],
+        "Each match has one row per player in `match_player`.",
+    ),
+    (
+        "player_match_history",
+        "match_player",
+        &["account_id", "match_id"],
+        "The match history of a player. Contains matches without metadata, so not every row \
+         has a counterpart in `match_player`.",
+    ),
+    (
+        "player_match_history",
+        "match_info",
+        &["match_id"],
+        "Matches of the match history of a player, if their metadata is available.",
+    ),
+    (
+        "match_salts",
+        "match_info",
+        &["match_id"],
+        "Salts used to download the metadata and replay of a match.",
+    ),
+];
+
+#[derive(Debug, Clone, Deserialize, Row)]
+struct TableRow {
+    name: String,
+    engine: String,
+    comment: String,
+    total_rows: Option<u64>,
+}
+
+#[derive(Debug, Clone, Deserialize, Row)]
+struct ColumnRow {
+    table: String,
+    name: String,
+    r#type: String,
+    comment: String,
+}
+
+#[derive(Debug, Clone, Deserialize, Row)]
+struct DictionaryRow {
+    name: String,
+    key_names: Vec<String>,
+    attribute_names: Vec<String>,
+    attribute_types: Vec<String>,
+    source: String,
+    element_count: u64,
+    comment: String,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub(super) struct CatalogColumn {
+    name: String,
+    r#type: String,
+    comment: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub(super) struct CatalogTable {
+    name: String,
+    engine: String,
+    comment: Option<String>,
+    /// Estimated number of rows, if known.
+    estimated_rows: Option<u64>,
+    columns: Vec<CatalogColumn>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub(super) struct CatalogDictionary {
+    name: String,
+    comment: Option<String>,
+    /// The key columns, passed as last argument to `dictGet`.
+    keys: Vec<String>,
+    /// The attributes that can be looked up with `dictGet`.
+    attributes: Vec<CatalogColumn>,
+    /// Where the dictionary is loaded from, e.g. `ClickHouse: default.match_info`.
+    source: String,
+    /// Number of entries of the dictionary.
+    estimated_rows: u64,
+    /// Example usage of the dictionary.
+    example: String,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub(super) struct TableRelationship {
+    from_table: String,
+    to_table: String,
+    /// The columns to join on, they have the same name in both tables.
+    columns: Vec<String>,
+    description: String,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub(super) struct Catalog {
+    tables: Vec<CatalogTable>,
+    dictionaries: Vec<CatalogDictionary>,
+    relationships: Vec<TableRelationship>,
+}
+
+impl Catalog {
+    /// Names of the columns of a table that may be sampled, `None` if the table isn't in the
+    /// catalog.
+    fn sample_column_names(&self, table: &str) -> Option<Vec<String>> {
+        self.tables.iter().find(|t| t.name == table).map(|t| {
+            t.columns
+                .iter()
+                .filter(|c| !is_secret_column(&c.name))
+                .map(|c| c.name.clone())
+                .collect()
+        })
+    }
+}
+
+/// Whether a column holds salts or secrets, e.g. `match_salts.metadata_salt`, which are left out
+/// of sample rows.
+fn is_secret_column(name: &str) -> bool {
+    let name = name.to_lowercase();
+    ["salt", "secret"].iter().any(|s| name.contains(s))
+}
+
+fn empty_to_none(value: String) -> Option<String> {
+    (!value.is_empty()).then_some(value)
+}
+
+fn build_catalog(
+    tables: Vec<TableRow>,
+    columns: Vec<ColumnRow>,
+    dictionaries: Vec<DictionaryRow>,
+) -> Catalog {
+    let mut columns_by_table: HashMap<String, Vec<CatalogColumn>> = HashMap::new();
+    for column in columns {
+        columns_by_table
+            .entry(column.table)
+            .or_default()
+            .push(CatalogColumn {
+                name: column.name,
+                r#type: column.r#type,
+                comment: empty_to_none(column.comment),
+            });
+    }
+    let relationships = RELATIONSHIPS
+        .iter()
+        .filter(|(from, to, ..)| {
+            tables.iter().any(|t| t.name == *from) && tables.iter().any(|t| t.name == *to)
+        })
+        .map(
+            |&(from_table, to_table, columns, description)| TableRelationship {
+                from_table: from_table.to_owned(),
+                to_table: to_table.to_owned(),
+                columns: columns.iter().map(|&c| c.to_owned()).collect(),
+                description: description.to_owned(),
+            },
+        )
+        .collect();
+    let tables = tables
+        .into_iter()
+        .map(|table| CatalogTable {
+            columns: columns_by_table.remove(&table.name).unwrap_or_default(),
+            name: table.name,
+            engine: table.engine,
+            comment: empty_to_none(table.comment),
+            estimated_rows: table.total_rows,
+        })
+        .collect();
+    let dictionaries = dictionaries
+        .into_iter()
+        .map(|dictionary| CatalogDictionary {
+            example: format!(
+                "dictGet('{}', '{}', {})",
+                dictionary.name,
+                dictionary
+                    .attribute_names
+                    .first()
+                    .map_or("", String::as_str),
+                dictionary.key_names.join(", ")
+            ),
+            attributes: dictionary
+                .attribute_names
+                .into_iter()
+                .zip(dictionary.attribute_types)
+                .map(|(name, r#type)| CatalogColumn {
+                    name,
+                    r#type,
+                    comment: None,
+                })
+                .collect(),
+            name: dictionary.name,
+            comment: empty_to_none(dictionary.comment),
+            keys: dictionary.key_names,
+            source: dictionary.source,
+            estimated_rows: dictionary.element_count,
+        })
+        .collect();
+    Catalog {
+        tables,
+        dictionaries,
+        relationships,
+    }
+}
+
+#[cached(
+    ty = "TimedCache<u8, Catalog>",
+    create = "{ TimedCache::with_lifespan(std::time::Duration::from_secs(60 * 60)) }",
+    result = true,
+    convert = "{ 0 }",
+    sync_writes = "default"
+)]
+async fn fetch_catalog(ch_client: &clickhouse::Client) -> clickhouse::error::Result<Catalog> {
+    let table_names = fetch_list_tables(ch_client).await?;
+    let tables = ch_client
+        .query(
+            "
+            SELECT name, engine, comment, total_rows
+            FROM system.tables
+            WHERE database = 'default' AND has(?, name)
+            ORDER BY name
+        ",
+        )
+        .bind(&table_names)
+        .fetch_all::<TableRow>()
+        .await?;
+    let columns = ch_client
+        .query(
+            "
+            SELECT table, name, type, comment
+            FROM system.columns
+            WHERE database = 'default' AND has(?, table)
+            ORDER BY table, position
+        ",
+        )
+        .bind(&table_names)
+        .fetch_all::<ColumnRow>()
+        .await?;
+    let dictionaries = ch_client
+        .query(
+            "
+            SELECT
+                name,
+                `key.names`       AS key_names,
+                `attribute.names` AS attribute_names,
+                `attribute.types` AS attribute_types,
+                source,
+                element_count,
+                comment
+            FROM system.dictionaries
+            WHERE database = 'default'
+            ORDER BY name
+        ",
+        )
+        .fetch_all::<DictionaryRow>()
+        .await?;
+    Ok(build_catalog(tables, columns, dictionaries))
+}
+
+#[utoipa::path(
+    get,
+    path = "/catalog",
+    responses(
+        (status = OK, body = Catalog),
+        (status = INTERNAL_SERVER_ERROR, body = String)
+    ),
+    tags = ["SQL"],
+    summary = "Catalog",
+    description = "
+Returns a catalog of the database to help writing queries.
+
+Contains all tables with their column comments and estimated row counts, the dictionaries that can be used with `dictGet` and how the tables relate to each other.
+
+### Rate Limits:
+| Type | Limit |
+| ---- | ----- |
+| IP | 10req/min |
+| Key | - |
+| Global | 60req/min |
+    "
+)]
+pub(super) async fn catalog(
+    rate_limit_key: RateLimitKey,
+    State(state): State<AppState>,
+) -> APIResult<impl IntoResponse> {
+    if !state.config.clickhouse.allow_custom_queries {
+        return Err(APIError::status_msg(
+            StatusCode::FORBIDDEN,
+            "Custom queries are disabled",
+        ));
+    }
+
+    state
+        .rate_limit_client
+        .apply_limits(
+            &rate_limit_key,
+            "sql_catalog",
+            &[
+                Quota::ip_limit(10, Duration::from_mins(1)),
+                Quota::global_limit(60, Duration::from_mins(1)),
+            ],
+        )
+        .await?;
+
+    Ok(Json(fetch_catalog(&state.ch_client_restricted).await?))
+}
+
+fn default_sample_limit() -> u32 {
+    10
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub(super) struct SampleRowsPath {
+    /// The name of the table to fetch sample rows from.
+    table: String,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub(super) struct SampleRowsQuery {
+    /// The number of rows to return. Max 100.
+    #[serde(default = "default_sample_limit")]
+    #[param(default = "10")]
+    limit: u32,
+}
+
+/// Selects the given columns instead of `*`, which fails on tables with column-level grants,
+/// e.g. `match_salts`.
+fn sample_rows_query(table: &str, columns: &[String], limit: u32) -> String {
+    let columns = columns
+        .iter()
+        .map(|c| format!("`{}`", c.replace('`', "\\`")))
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!("SELECT {columns} FROM {table} LIMIT {limit}")
+}
+
+#[cached(
+    ty = "TimedCache<(String, u32), Vec<serde_json::Value>>",
+    create = "{ TimedCache::with_lifespan(std::time::Duration::from_secs(60 * 60)) }",
+    result = true,
+    convert = r#"{ (table.to_owned(), limit) }"#,
+    sync_writes = "by_key",
+    key = "(String, u32)"
+)]
+async fn fetch_sample_rows(
+    ch_client: &clickhouse::Client,
+    table: &str,
+    columns: &[String],
+    limit: u32,
+) -> Result<Vec<serde_json::Value>, SQLQueryError> {
+    run_sql(
+        ch_client,
+        &sample_rows_query(table, columns, limit),
+        &BTreeMap::new(),
+        QueryLimits::new(QueryTier::Standard, QueryMode::Sync),
+    )
+    .await
+}
+
+#[utoipa::path(
+    get,
+    path = "/tables/{table}/sample",
+    params(SampleRowsPath, SampleRowsQuery),
+    responses(
+        (status = OK, body = Vec<serde_json::Value>),
+        (status = BAD_REQUEST, description = "Invalid limit"),
+        (status = FORBIDDEN, description = "API key is missing the `sql` scope"),
+        (status = NOT_FOUND, description = "Table not found"),
+        (status = TOO_MANY_REQUESTS, description = "Rate limit exceeded"),
+        (status = INTERNAL_SERVER_ERROR, body = String)
+    ),
+    security(("api_key_header" = ["sql"]), ("api_key_query" = ["sql"])),
+    tags = ["SQL"],
+    summary = "Table Sample Rows",
+    description = "
+Returns some rows of a table, to get an idea of its contents. Columns holding salts or secrets, like `match_salts.metadata_salt`, are left out.
+
+Requires an API key with the `sql` scope.
+
+### Rate Limits:
+| Type | Limit |
+| ---- | ----- |
+| IP | API-Key ONLY |
+| Key | 10req/min |
+| Global | 60req/min |
+    "
+)]
+pub(super) async fn sample_rows(
+    rate_limit_key: RateLimitKey,
+    Path(SampleRowsPath { table }): Path<SampleRowsPath>,
+    Query(SampleRowsQuery { limit }): Query<SampleRowsQuery>,
+    State(state): State<AppState>,
+) -> APIResult<impl IntoResponse> {
+    if !state.config.clickhouse.allow_custom_queries {
+        return Err(APIError::status_msg(
+            StatusCode::FORBIDDEN,
+            "Custom queries are disabled",
+        ));
+    }
+
+    state
+        .rate_limit_client
+        .require_scope(&rate_limit_key, ApiKeyScope::Sql)
+        .await?;
+    state
+        .rate_limit_client
+        .apply_limits(
+            &rate_limit_key,
+            "sql_sample_rows",
+            &[
+                Quota::key_limit(10, Duration::from_mins(1)),
+                Quota::global_limit(60, Duration::from_mins(1)),
+            ],
+        )
+        .await?;
+
+    if !(1..=100).contains(&limit) {
+        return Err(APIError::status_msg(
+            StatusCode::BAD_REQUEST,
+            "limit must be between 1 and 100",
+        ));
+    }
+    // Only tables and columns of the catalog are interpolated into the query, the catalog only
+    // contains the columns the restricted user is allowed to see
+    let catalog = fetch_catalog(&state.ch_client_restricted).await?;
+    let Some(columns) = catalog
+        .sample_column_names(&table)
+        .filter(|columns| !columns.is_empty())
+    else {
+        return Err(APIError::status_msg(
+            StatusCode::NOT_FOUND,
+            format!("Table {table} not found"),
+        ));
+    };
+
+    fetch_sample_rows(&state.ch_client_restricted, &table, &columns, limit)
+        .await
+        .map(Json)
+        .map_err(|sql_error| {
+            warn!("Failed to fetch sample rows of {table}: {sql_error}");
+            APIError::internal(format!("Failed to fetch sample rows of {table}"))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(name: &str) -> TableRow {
+        TableRow {
+            name: name.to_owned(),
+            engine: "MergeTree".to_owned(),
+            comment: String::new(),
+            total_rows: Some(100),
+        }
+    }
+
+    fn column(table: &str, name: &str, comment: &str) -> ColumnRow {
+        ColumnRow {
+            table: table.to_owned(),
+            name: name.to_owned(),
+            r#type: "UInt64".to_owned(),
+            comment: comment.to_owned(),
+        }
+    }
+
+    #[test]
+    fn test_sample_column_names_leave_out_secrets() {
+        let catalog = build_catalog(
+            vec![table("match_salts")],
+            vec![
+                column("match_salts", "match_id", ""),
+                column("match_salts", "metadata_salt", ""),
+                column("match_salts", "replay_salt", ""),
+                column("match_salts", "webhook_secret", ""),
+            ],
+            vec![],
+        );
+        assert_eq!(
+            catalog.sample_column_names("match_salts"),
+            Some(vec!["match_id".to_owned()])
+        );
+        assert_eq!(catalog.sample_column_names("match_info"), None);
+    }
+
+    #[test]
+    fn test_build_catalog_groups_columns() {
+        let catalog = build_catalog(
+            vec![table("match_info"), table("items")],
+            vec![
+                column("match_info", "match_id", "ID of the match"),
+                column("match_info", "start_time", ""),
+                column("items", "id", ""),
+            ],
+            vec![],
+        );
+        assert_eq!(catalog.tables.len(), 2);
+        let match_info = &catalog.tables[0];
+        assert_eq!(match_info.name, "match_info");
+        assert_eq!(match_info.columns.len(), 2);
+        assert_eq!(
+            match_info.columns[0].comment.as_deref(),
+            Some("ID of the match")
+        );
+        assert_eq!(match_info.columns[1].comment, None);
+        assert_eq!(catalog.tables[1].columns.len(), 1);
+    }
+
+    #[test]
+    fn test_build_catalog_only_includes_relationships_of_known_tables() {
+        let catalog = build_catalog(
+            vec![
+                table("match_info"),
+                table("match_player"),
+                table("match_salts"),
+            ],
+            vec![],
+            vec![],
+        );
+        let relationships: Vec<(&str, &str)> = catalog
+            .relationships
+            .iter()
+            .map(|r| (r.from_table.as_str(), r.to_table.as_str()))
+            .collect();
+        assert_eq!(
+            relationships,
+            vec![
+                ("match_player", "match_info"),
+                ("match_salts", "match_info")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_build_catalog_dictionary_example() {
+        let catalog = build_catalog(
+            vec![],
+            vec![],
+            vec![DictionaryRow {
+                name: "match_info_dict".to_owned(),
+                key_names: vec!["match_id".to_owned()],
+                attribute_names: vec!["start_time".to_owned(), "average_badge_team0".to_owned()],
+                attribute_types: vec!["DateTime".to_owned(), "Nullable(UInt32)".to_owned()],
+                source: "ClickHouse: default.match_info".to_owned(),
+                element_count: 100,
+                comment: String::new(),
+            }],
+        );
+        let dictionary = &catalog.dictionaries[0];
+        assert_eq!(
+            dictionary.example,
+            "dictGet('match_info_dict', 'start_time', match_id)"
+        );
+        assert_eq!(dictionary.attributes.len(), 2);
+        assert_eq!(dictionary.attributes[1].r#type, "Nullable(UInt32)");
+    }
+
+    #[test]
+    fn test_sample_rows_query_selects_columns() {
+        let columns = vec!["match_id".to_owned(), "metadata_salt".to_owned()];
+        assert_eq!(
+            sample_rows_query("match_salts", &columns, 10),
+            "SELECT `match_id`, `metadata_salt` FROM match_salts LIMIT 10"
+        );
+    }
+}
@@ -0,0 +1,346 @@
+use core::time::Duration;
+
+use axum::Json;
+use axum::body::Body;
+use axum::extract::{Path, State};
+use axum::http::{StatusCode, header};
+use axum::response::IntoResponse;
+use chrono::{DateTime, Utc};
+use object_store::ObjectStoreExt;
+use serde::{Deserialize, Serialize};
+use strum::{Display, EnumString};
+use tracing::{debug, warn};
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+use crate::context::AppState;
+use crate::error::{APIError, APIResult};
+use crate::routes::v1::sql::route::{fetch_list_tables, sanitize_query};
+use crate::routes::v1::sql::saved::require_owner;
+use crate::routes::v1::sql::validation::validate_query;
+use crate::services::rate_limiter::Quota;
+use crate::services::rate_limiter::extractor::RateLimitKey;
+use crate::services::sql_jobs::result_path;
+
+/// Maximum number of queued and running jobs per API key
+const MAX_ACTIVE_JOBS: i64 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema, Display, EnumString)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub(super) enum SqlJobStatus {
+    /// The job waits for a worker.
+    Queued,
+    /// The query is running.
+    Running,
+    /// The result can be downloaded from `/v1/sql/jobs/{id}/result`.
+    Succeeded,
+    /// The query failed, see `error`.
+    Failed,
+}
+
+struct SqlJobRow {
+    id: Uuid,
+    query: String,
+    status: String,
+    error: Option<String>,
+    row_count: Option<i64>,
+    created_at: DateTime<Utc>,
+    started_at: Option<DateTime<Utc>>,
+    finished_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub(super) struct SqlJob {
+    id: Uuid,
+    /// The query as it is executed, including the enforced row limit.
+    query: String,
+    status: SqlJobStatus,
+    /// Why the job failed.
+    error: Option<String>,
+    /// Number of rows of the result, once the job succeeded.
+    row_count: Option<i64>,
+    created_at: DateTime<Utc>,
+    started_at: Option<DateTime<Utc>>,
+    finished_at: Option<DateTime<Utc>>,
+}
+
+impl From<SqlJobRow> for SqlJob {
+    fn from(row: SqlJobRow) -> Self {
+        Self {
+            id: row.id,
+            query: row.query,
+            status: row.status.parse().unwrap_or(SqlJobStatus::Failed),
+            error: row.error,
+            row_count: row.row_count,
+            created_at: row.created_at,
+            started_at: row.started_at,
+            finished_at: row.finished_at,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub(super) struct SubmitSqlJobRequest {
+    /// The SQL query to execute. It must follow the Clickhouse SQL syntax.
+    query: String,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub(super) struct SqlJobPath {
+    /// The ID of the job.
+    id: Uuid,
+}
+
+fn sql_job_not_found(id: Uuid) -> APIError {
+    APIError::status_msg(StatusCode::NOT_FOUND, format!("Job {id} not found"))
+}
+
+async fn fetch_sql_job(state: &AppState, id: Uuid, api_key: Uuid) -> APIResult<SqlJobRow> {
+    sqlx::query_as!(
+        SqlJobRow,
+        r#"
+        SELECT id, query, status, error, row_count, created_at, started_at, finished_at
+        FROM sql_jobs
+        WHERE id = $1 AND api_key = $2
+        "#,
+        id,
+        api_key
+    )
+    .fetch_optional(&state.pg_client)
+    .await?
+    .ok_or_else(|| sql_job_not_found(id))
+}
+
+#[utoipa::path(
+    get,
+    path = "/jobs",
+    responses(
+        (status = OK, body = [SqlJob]),
+        (status = FORBIDDEN, description = "API key is missing the `sql` scope"),
+        (status = INTERNAL_SERVER_ERROR, description = "Fetching the jobs failed")
+    ),
+    security(("api_key_header" = ["sql"]), ("api_key_query" = ["sql"])),
+    tags = ["SQL"],
+    summary = "List Query Jobs",
+    description = "
+Lists the 100 most recent query jobs of your API key.
+
+Requires an API key with the `sql` scope.
+
+### Rate Limits:
+| Type | Limit |
+| ---- | ----- |
+| IP | API-Key ONLY |
+| Key | - |
+| Global | - |
+    "
+)]
+pub(super) async fn list_sql_jobs(
+    rate_limit_key: RateLimitKey,
+    State(state): State<AppState>,
+) -> APIResult<impl IntoResponse> {
+    let api_key = require_owner(&state, &rate_limit_key).await?;
+    let jobs = sqlx::query_as!(
+        SqlJobRow,
+        r#"
+        SELECT id, query, status, error, row_count, created_at, started_at, finished_at
+        FROM sql_jobs
+        WHERE api_key = $1
+        ORDER BY created_at DESC
+        LIMIT 100
+        "#,
+        api_key
+    )
+    .fetch_all(&state.pg_client)
+    .await?;
+    Ok(Json(jobs.into_iter().map(SqlJob::from).collect::<Vec<_>>()))
+}
+
+#[utoipa::path(
+    post,
+    path = "/jobs",
+    request_body = SubmitSqlJobRequest,
+    responses(
+        (status = ACCEPTED, body = SqlJob),
+        (status = BAD_REQUEST, description = "Invalid query"),
+        (status = FORBIDDEN, description = "API key is missing the `sql` scope"),
+        (status = TOO_MANY_REQUESTS, description = "Rate limit exceeded or too many active jobs"),
+        (status = INTERNAL_SERVER_ERROR, description = "Submitting the job failed")
+    ),
+    security(("api_key_header" = ["sql"]), ("api_key_query" = ["sql"])),
+    tags = ["SQL"],
+    summary = "Submit Query Job",
+    description = "
+Submits a SQL query to run in the background, for queries that take longer than `/v1/sql` allows.
+
+The same restrictions as for `/v1/sql` apply. Poll the job with `/v1/sql/jobs/{id}` and download the result from `/v1/sql/jobs/{id}/result` once it succeeded.
+
+Jobs run read-only with limits depending on the tier of your API key:
+| Tier | Max Execution Time | Max Rows to Read | Max Memory Usage |
+| ---- | ------------------ | ---------------- | ---------------- |
+| Standard | 5min | 10B | 8 GiB |
+| Patron | 15min | 50B | 16 GiB |
+| Data Access | 30min | 200B | 32 GiB |
+
+At most 3 jobs per API key can be queued or running at the same time.
+
+Requires an API key with the `sql` scope.
+
+### Rate Limits:
+| Type | Limit |
+| ---- | ----- |
+| IP | API-Key ONLY |
+| Key | 30req/h |
+| Global | - |
+    "
+)]
+pub(super) async fn submit_sql_job(
+    rate_limit_key: RateLimitKey,
+    State(state): State<AppState>,
+    Json(request): Json<SubmitSqlJobRequest>,
+) -> APIResult<impl IntoResponse> {
+    let api_key = require_owner(&state, &rate_limit_key).await?;
+    state
+        .rate_limit_client
+        .apply_limits(
+            &rate_limit_key,
+            "sql_jobs",
+            &[Quota::key_limit(30, Duration::from_hours(1))],
+        )
+        .await?;
+
+    let query = sanitize_query(&request.query);
+    let tables = fetch_list_tables(&state.ch_client_restricted).await?;
+    let query = validate_query(&query, &tables)
+        .map_err(|msg| APIError::status_msg(StatusCode::BAD_REQUEST, msg))?;
+
+    let active_jobs = sqlx::query_scalar!(
+        r#"
+        SELECT COUNT(*) AS "count!"
+        FROM sql_jobs
+        WHERE api_key = $1 AND status IN ('queued', 'running')
+        "#,
+        api_key
+    )
+    .fetch_one(&state.pg_client)
+    .await?;
+    if active_jobs >= MAX_ACTIVE_JOBS {
+        return Err(APIError::status_msg(
+            StatusCode::TOO_MANY_REQUESTS,
+            format!("At most {MAX_ACTIVE_JOBS} jobs can be queued or running at the same time"),
+        ));
+    }
+
+    let job = sqlx::query_as!(
+        SqlJobRow,
+        r#"
+        INSERT INTO sql_jobs (api_key, query)
+        VALUES ($1, $2)
+        RETURNING id, query, status, error, row_count, created_at, started_at, finished_at
+        "#,
+        api_key,
+        query
+    )
+    .fetch_one(&state.pg_client)
+    .await?;
+    debug!("Submitted SQL job {}: {}", job.id, job.query);
+    Ok((StatusCode::ACCEPTED, Json(SqlJob::from(job))))
+}
+
+#[utoipa::path(
+    get,
+    path = "/jobs/{id}",
+    params(SqlJobPath),
+    responses(
+        (status = OK, body = SqlJob),
+        (status = FORBIDDEN, description = "API key is missing the `sql` scope"),
+        (status = NOT_FOUND, description = "Job not found"),
+        (status = INTERNAL_SERVER_ERROR, description = "Fetching the job failed")
+    ),
+    security(("api_key_header" = ["sql"]), ("api_key_query" = ["sql"])),
+    tags = ["SQL"],
+    summary = "Get Query Job",
+    description = "
+Returns the status of a query job of your API key.
+
+Requires an API key with the `sql` scope.
+
+### Rate Limits:
+| Type | Limit |
+| ---- | ----- |
+| IP | API-Key ONLY |
+| Key | - |
+| Global | - |
+    "
+)]
+pub(super) async fn get_sql_job(
+    rate_limit_key: RateLimitKey,
+    Path(SqlJobPath { id }): Path<SqlJobPath>,
+    State(state): State<AppState>,
+) -> APIResult<impl IntoResponse> {
+    let api_key = require_owner(&state, &rate_limit_key).await?;
+    fetch_sql_job(&state, id, api_key)
+        .await
+        .map(SqlJob::from)
+        .map(Json)
+}
+
+#[utoipa::path(
+    get,
+    path = "/jobs/{id}/result",
+    params(SqlJobPath),
+    responses(
+        (status = OK, description = "The result as newline delimited JSON, one object per row.", content_type = "application/x-ndjson", body = String),
+        (status = FORBIDDEN, description = "API key is missing the `sql` scope"),
+        (status = NOT_FOUND, description = "Job or result not found"),
+        (status = CONFLICT, description = "The job has not succeeded"),
+        (status = INTERNAL_SERVER_ERROR, description = "Fetching the result failed")
+    ),
+    security(("api_key_header" = ["sql"]), ("api_key_query" = ["sql"])),
+    tags = ["SQL"],
+    summary = "Download Query Job Result",
+    description = "
+Downloads the result of a succeeded query job of your API key as newline delimited JSON.
+
+Requires an API key with the `sql` scope.
+
+### Rate Limits:
+| Type | Limit |
+| ---- | ----- |
+| IP | API-Key ONLY |
+| Key | - |
+| Global | - |
+    "
+)]
+pub(super) async fn download_sql_job_result(
+    rate_limit_key: RateLimitKey,
+    Path(SqlJobPath { id }): Path<SqlJobPath>,
+    State(state): State<AppState>,
+) -> APIResult<impl IntoResponse> {
+    let api_key = require_owner(&state, &rate_limit_key).await?;
+    let job = SqlJob::from(fetch_sql_job(&state, id, api_key).await?);
+    if job.status != SqlJobStatus::Succeeded {
+        return Err(APIError::status_msg(
+            StatusCode::CONFLICT,
+            format!("Job {id} is {}", job.status),
+        ));
+    }
+    let result = match state.s3_client.get(&result_path(id)).await {
+        Ok(result) => result,
+        Err(object_store::Error::NotFound { .. }) => {
+            return Err(APIError::status_msg(
+                StatusCode::NOT_FOUND,
+                format!("Result of job {id} is no longer available"),
+            ));
+        }
+        Err(e) => {
+            warn!("Failed to fetch result of SQL job {id}: {e}");
+            return Err(APIError::internal("Failed to fetch the job result"));
+        }
+    };
+    Ok((
+        [(header::CONTENT_TYPE, "application/x-ndjson")],
+        Body::from_stream(result.into_stream()),
+    ))
+}
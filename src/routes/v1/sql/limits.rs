@@ -0,0 +1,146 @@
+use core::time::Duration;
+
+use cached::TimedCache;
+use cached::proc_macro::cached;
+use clickhouse::query::Query;
+use sqlx::{Pool, Postgres};
+use uuid::Uuid;
+
+const GIB: u64 = 1024 * 1024 * 1024;
+
+/// Tier of an API key, deciding how many resources its custom queries may use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum QueryTier {
+    Standard,
+    /// The key is linked to an active patron.
+    Patron,
+    /// The key has data access.
+    DataAccess,
+}
+
+/// How a custom query is executed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum QueryMode {
+    /// The query runs while the client waits for the response.
+    Sync,
+    /// The query runs as a background job.
+    Async,
+}
+
+/// `ClickHouse` settings enforced on a custom query.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct QueryLimits {
+    pub(crate) max_execution_time: Duration,
+    pub(crate) max_rows_to_read: u64,
+    pub(crate) max_memory_usage: u64,
+}
+
+impl QueryLimits {
+    pub(crate) const fn new(tier: QueryTier, mode: QueryMode) -> Self {
+        let (secs, max_rows_to_read, max_memory_usage) = match (tier, mode) {
+            (QueryTier::Standard, QueryMode::Sync) => (20, 1_000_000_000, 4 * GIB),
+            (QueryTier::Standard, QueryMode::Async) => (300, 10_000_000_000, 8 * GIB),
+            (QueryTier::Patron, QueryMode::Sync) => (30, 5_000_000_000, 8 * GIB),
+            (QueryTier::Patron, QueryMode::Async) => (900, 50_000_000_000, 16 * GIB),
+            (QueryTier::DataAccess, QueryMode::Sync) => (60, 20_000_000_000, 16 * GIB),
+            (QueryTier::DataAccess, QueryMode::Async) => (1800, 200_000_000_000, 32 * GIB),
+        };
+        Self {
+            max_execution_time: Duration::from_secs(secs),
+            max_rows_to_read,
+            max_memory_usage,
+        }
+    }
+
+    /// Applies the limits to a query and makes it read-only.
+    ///
+    /// The limits can only be changed in read-only mode because the restricted user marks them
+    /// as `CHANGEABLE_IN_READONLY`, up to the `MAX` of its constraints.
+    pub(crate) fn apply(self, query: Query) -> Query {
+        query
+            .with_option("readonly", "1")
+            .with_option(
+                "max_execution_time",
+                self.max_execution_time.as_secs().to_string(),
+            )
+            .with_option("max_rows_to_read", self.max_rows_to_read.to_string())
+            .with_option("max_memory_usage", self.max_memory_usage.to_string())
+    }
+}
+
+/// Returns the query tier of an API key, requests without a key get the standard tier.
+pub(crate) async fn fetch_query_tier(
+    pg_client: &Pool<Postgres>,
+    api_key: Option<Uuid>,
+) -> sqlx::Result<QueryTier> {
+    match api_key {
+        Some(api_key) => fetch_api_key_tier(pg_client, api_key).await,
+        None => Ok(QueryTier::Standard),
+    }
+}
+
+#[cached(
+    ty = "TimedCache<Uuid, QueryTier>",
+    create = "{ TimedCache::with_lifespan(Duration::from_mins(5)) }",
+    result = true,
+    convert = "{ api_key }",
+    sync_writes = "by_key",
+    key = "Uuid"
+)]
+async fn fetch_api_key_tier(pg_client: &Pool<Postgres>, api_key: Uuid) -> sqlx::Result<QueryTier> {
+    let row = sqlx::query!(
+        r#"
+        SELECT k.data_access, COALESCE(p.is_active, FALSE) AS "is_patron!"
+        FROM api_keys k
+        LEFT JOIN patrons p ON p.id = k.patron_id
+        WHERE k.key = $1
+        "#,
+        api_key
+    )
+    .fetch_optional(pg_client)
+    .await?;
+    Ok(match row {
+        Some(row) if row.data_access => QueryTier::DataAccess,
+        Some(row) if row.is_patron => QueryTier::Patron,
+        _ => QueryTier::Standard,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    #[rstest]
+    #[case(QueryTier::Standard, QueryTier::Patron)]
+    #[case(QueryTier::Patron, QueryTier::DataAccess)]
+    fn test_higher_tiers_get_higher_limits(#[case] lower: QueryTier, #[case] higher: QueryTier) {
+        for mode in [QueryMode::Sync, QueryMode::Async] {
+            let lower = QueryLimits::new(lower, mode);
+            let higher = QueryLimits::new(higher, mode);
+            assert!(lower.max_execution_time < higher.max_execution_time);
+            assert!(lower.max_rows_to_read < higher.max_rows_to_read);
+            assert!(lower.max_memory_usage <= higher.max_memory_usage);
+        }
+    }
+
+    #[rstest]
+    fn test_async_gets_higher_limits(
+        #[values(QueryTier::Standard, QueryTier::Patron, QueryTier::DataAccess)] tier: QueryTier,
+    ) {
+        let sync = QueryLimits::new(tier, QueryMode::Sync);
+        let r#async = QueryLimits::new(tier, QueryMode::Async);
+        assert!(sync.max_execution_time < r#async.max_execution_time);
+        assert!(sync.max_rows_to_read < r#async.max_rows_to_read);
+        assert!(sync.max_memory_usage < r#async.max_memory_usage);
+    }
+
+    #[test]
+    fn test_standard_sync_limits() {
+        let limits = QueryLimits::new(QueryTier::Standard, QueryMode::Sync);
+        assert_eq!(limits.max_execution_time, Duration::from_secs(20));
+        assert_eq!(limits.max_rows_to_read, 1_000_000_000);
+        assert_eq!(limits.max_memory_usage, 4 * GIB);
+    }
+}
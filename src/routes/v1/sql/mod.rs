@@ -1,4 +1,9 @@
+mod catalog;
+mod jobs;
+pub(crate) mod limits;
 pub mod route;
+mod saved;
+mod validation;
 
 use utoipa::OpenApi;
 use utoipa_axum::router::OpenApiRouter;
@@ -9,13 +14,31 @@ use crate::context::AppState;
 #[derive(OpenApi)]
 #[openapi(tags((name = "SQL", description = "
 Database exploration endpoints for direct SQL access.
-Provides functionality to execute custom SQL queries with rate limiting protection, list available tables, and inspect table schemas.
+Provides functionality to execute custom SQL queries with rate limiting protection, list available tables, inspect table schemas and sample rows, and browse a catalog of tables, dictionaries and their relationships.
+Queries can be saved with typed parameters, their results are cached and they can be shared via a public link.
+Long running queries can be submitted as jobs, their results are downloaded once they are done.
 ")))]
 struct ApiDoc;
 
 pub(super) fn router() -> OpenApiRouter<AppState> {
     OpenApiRouter::with_openapi(ApiDoc::openapi())
-        .routes(routes!(route::sql))
+        .routes(routes!(route::sql, route::sql_post))
         .routes(routes!(route::list_tables))
         .routes(routes!(route::table_schema))
+        .routes(routes!(catalog::catalog))
+        .routes(routes!(catalog::sample_rows))
+        .routes(routes!(
+            saved::list_saved_queries,
+            saved::create_saved_query
+        ))
+        .routes(routes!(
+            saved::get_saved_query,
+            saved::update_saved_query,
+            saved::delete_saved_query
+        ))
+        .routes(routes!(saved::execute_saved))
+        .routes(routes!(saved::execute_shared))
+        .routes(routes!(jobs::list_sql_jobs, jobs::submit_sql_job))
+        .routes(routes!(jobs::get_sql_job))
+        .routes(routes!(jobs::download_sql_job_result))
 }
@@ -1,86 +1,84 @@
 use core::time::Duration;
-use std::sync::LazyLock;
+use std::collections::BTreeMap;
 
 use axum::Json;
 use axum::extract::{Path, State};
-use axum::http::StatusCode;
-use axum::response::IntoResponse;
+use axum::http::{StatusCode, header};
+use axum::response::{IntoResponse, Response};
 use axum_extra::extract::Query;
 use cached::TimedCache;
 use cached::proc_macro::cached;
 use clickhouse::query::BytesCursor;
-use regex::Regex;
 use serde::{Deserialize, Serialize};
 use tokio::io::{AsyncBufReadExt, Lines};
 use tracing::{debug, warn};
-use utoipa::IntoParams;
+use utoipa::{IntoParams, ToSchema};
 
 use crate::context::AppState;
 use crate::error::{APIError, APIResult};
-use crate::services::rate_limiter::Quota;
+use crate::routes::v1::sql::limits::{QueryLimits, QueryMode, fetch_query_tier};
+use crate::routes::v1::sql::saved::{parse_parameters, validate_params};
+use crate::routes::v1::sql::validation::validate_query;
 use crate::services::rate_limiter::extractor::RateLimitKey;
-
-static SYSTEM_TABLE_RE: LazyLock<Regex> =
-    LazyLock::new(|| Regex::new(r"(?i)\bsystem\s*\.\s*\w+").unwrap());
-
-static BLOCKED_FUNCTION_RE: LazyLock<Regex> = LazyLock::new(|| {
-    // Matches dangerous ClickHouse table functions with optional whitespace before '('
-    Regex::new(
-        r"(?i)\b(url|file|remote|remoteSecure|input|cluster|clusterAllReplicas|mysql|postgresql|s3|s3Cluster|hdfs|jdbc|odbc|executable|mongo|sqlite|azureBlobStorage)\s*\(",
-    )
-    .unwrap()
-});
-
-static BLOCK_COMMENT_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"/\*[\s\S]*?\*/").unwrap());
-
-static LINE_COMMENT_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"--[^\n]*").unwrap());
-
-static INTO_OUTFILE_RE: LazyLock<Regex> =
-    LazyLock::new(|| Regex::new(r"(?i)\bINTO\s+OUTFILE\b").unwrap());
-
-/// Normalizes a SQL query by stripping comments and collapsing whitespace.
-/// The normalized form is used both for validation AND sent to `ClickHouse`,
-/// ensuring no divergence between what we validate and what executes.
-fn normalize_query(query: &str) -> String {
-    let without_block = BLOCK_COMMENT_RE.replace_all(query, " ");
-    let without_line = LINE_COMMENT_RE.replace_all(&without_block, " ");
-    without_line
-        .split_whitespace()
-        .collect::<Vec<_>>()
-        .join(" ")
+use crate::services::rate_limiter::{ApiKeyScope, Quota};
+
+/// Strips surrounding whitespace and trailing statement separators of a user-supplied SQL query.
+///
+/// The query is otherwise left as is, comments and further statements are rejected by
+/// [`validate_query`], which sees exactly what is executed.
+pub(super) fn sanitize_query(query: &str) -> String {
+    query
+        .trim()
+        .trim_end_matches([';', ' ', '\t', '\n', '\r'])
+        .to_owned()
 }
 
-/// Validates that a user-supplied SQL query is safe to execute.
-fn validate_query(query: &str) -> Result<(), &'static str> {
-    if query.is_empty() {
-        return Err("Query cannot be empty");
-    }
-
-    // Must start with SELECT or WITH (for CTEs)
-    let upper = query.to_uppercase();
-    if !upper.starts_with("SELECT") && !upper.starts_with("WITH") {
-        return Err("Only SELECT queries are allowed");
-    }
-
-    if SYSTEM_TABLE_RE.is_match(query) {
-        return Err("Access to system tables is not allowed");
-    }
+#[derive(Debug, Deserialize, Serialize, IntoParams)]
+pub(super) struct SQLQuery {
+    /// The SQL query to execute. It must follow the Clickhouse SQL syntax.
+    query: String,
+}
 
-    if BLOCKED_FUNCTION_RE.is_match(query) {
-        return Err("Query contains a blocked function");
-    }
+/// Format of the result of a custom query.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ToSchema)]
+pub(super) enum SQLResultFormat {
+    /// A JSON array of objects, one per row.
+    #[default]
+    #[serde(rename = "JSON")]
+    Json,
+    /// One JSON object per line.
+    #[serde(rename = "JSONEachRow")]
+    JsonEachRow,
+    #[serde(rename = "CSV")]
+    Csv,
+    /// Tab separated values with a header row.
+    #[serde(rename = "TSVWithNames")]
+    TsvWithNames,
+}
 
-    if INTO_OUTFILE_RE.is_match(query) {
-        return Err("INTO OUTFILE is not allowed");
+impl SQLResultFormat {
+    /// The `ClickHouse` output format and content type, `None` for a JSON array.
+    fn clickhouse_format(self) -> Option<(&'static str, &'static str)> {
+        match self {
+            Self::Json => None,
+            Self::JsonEachRow => Some(("JSONEachRow", "application/x-ndjson")),
+            Self::Csv => Some(("CSV", "text/csv")),
+            Self::TsvWithNames => Some(("TSVWithNames", "text/tab-separated-values")),
+        }
     }
-
-    Ok(())
 }
 
-#[derive(Debug, Deserialize, Serialize, IntoParams)]
-pub(super) struct SQLQuery {
+#[derive(Debug, Deserialize, ToSchema)]
+pub(super) struct SQLQueryBody {
     /// The SQL query to execute. It must follow the Clickhouse SQL syntax.
+    /// Parameters are declared as `{name:Type}`, e.g. `{hero_id:UInt32}`.
     query: String,
+    /// Values of the parameters declared in the query.
+    #[serde(default)]
+    #[schema(value_type = Object)]
+    params: BTreeMap<String, serde_json::Value>,
+    #[serde(default)]
+    format: SQLResultFormat,
 }
 
 #[derive(Debug, Deserialize, IntoParams)]
@@ -97,7 +95,7 @@ pub struct TableSchemaRow {
 }
 
 #[derive(thiserror::Error, Debug)]
-enum SQLQueryError {
+pub(super) enum SQLQueryError {
     #[error("Failed to execute query: {0}")]
     Query(#[from] clickhouse::error::Error),
     #[error("Failed to parse query result: {0}")]
@@ -112,17 +110,32 @@ enum SQLQueryError {
     params(SQLQuery),
     responses(
         (status = OK, body = String),
+        (status = FORBIDDEN, description = "API key is missing the `sql` scope"),
         (status = INTERNAL_SERVER_ERROR, body = String)
     ),
+    security(("api_key_header" = ["sql"]), ("api_key_query" = ["sql"])),
     tags = ["SQL"],
     summary = "Query",
     description = "
 Executes a SQL query on the database.
 
+Only a single `SELECT` query on the tables listed by `/v1/sql/tables` is allowed, using common functions. `SETTINGS` and `FORMAT` clauses are not allowed and results are limited to 100000 rows.
+
+Queries run read-only with limits depending on the tier of your API key:
+| Tier | Max Execution Time | Max Rows to Read | Max Memory Usage |
+| ---- | ------------------ | ---------------- | ---------------- |
+| Standard | 20s | 1B | 4 GiB |
+| Patron | 30s | 5B | 8 GiB |
+| Data Access | 60s | 20B | 16 GiB |
+
+For longer running queries use `/v1/sql/jobs`.
+
+Requires an API key with the `sql` scope.
+
 ### Rate Limits:
 | Type | Limit |
 | ---- | ----- |
-| IP | 5req/min, 50req/hr |
+| IP | API-Key ONLY |
 | Key | 10req/min |
 | Global | 30req/min |
     "
@@ -132,53 +145,195 @@ pub(super) async fn sql(
     Query(query): Query<SQLQuery>,
     State(state): State<AppState>,
 ) -> APIResult<impl IntoResponse> {
-    if !state.config.clickhouse.allow_custom_queries {
-        return Err(APIError::status_msg(
-            StatusCode::FORBIDDEN,
-            "Custom queries are disabled",
-        ));
+    let (query, limits) = prepare_custom_query(&state, &rate_limit_key, &query.query).await?;
+    run_sql(
+        &state.ch_client_restricted,
+        &query,
+        &BTreeMap::new(),
+        limits,
+    )
+    .await
+    .map(Json)
+    .map_err(query_execution_failed)
+}
+
+#[utoipa::path(
+    post,
+    path = "/",
+    request_body = SQLQueryBody,
+    responses(
+        (status = OK, description = "The result in the requested format."),
+        (status = BAD_REQUEST, description = "Invalid query or parameters, or the query failed."),
+        (status = FORBIDDEN, description = "API key is missing the `sql` scope"),
+        (status = INTERNAL_SERVER_ERROR, body = String)
+    ),
+    security(("api_key_header" = ["sql"]), ("api_key_query" = ["sql"])),
+    tags = ["SQL"],
+    summary = "Query (POST)",
+    description = "
+Executes a SQL query on the database, like `GET /v1/sql`, but with the query in the JSON body.
+
+Parameters are declared in the query as `{name:Type}`, e.g. `SELECT * FROM match_info WHERE match_id = {match_id:UInt64}`, and their values are passed in `params`. They are bound by ClickHouse on the server side, so they don't have to be escaped. Parameters can only be values of numeric, string, date, `Bool` or `UUID` types, optionally wrapped in `Array` or `Nullable`.
+
+The result is returned as JSON array by default, or as `JSONEachRow`, `CSV` or `TSVWithNames` depending on `format`.
+
+The same restrictions and limits as for `GET /v1/sql` apply.
+
+Requires an API key with the `sql` scope.
+
+### Rate Limits:
+| Type | Limit |
+| ---- | ----- |
+| IP | API-Key ONLY |
+| Key | 10req/min |
+| Global | 30req/min |
+    "
+)]
+pub(super) async fn sql_post(
+    rate_limit_key: RateLimitKey,
+    State(state): State<AppState>,
+    Json(body): Json<SQLQueryBody>,
+) -> APIResult<Response> {
+    let (query, limits) = prepare_custom_query(&state, &rate_limit_key, &body.query).await?;
+    let params: BTreeMap<String, String> = body
+        .params
+        .into_iter()
+        .map(|(name, value)| (name, param_value(value)))
+        .collect();
+    let parameters = parse_parameters(&query)
+        .map_err(|msg| APIError::status_msg(StatusCode::BAD_REQUEST, msg))?;
+    validate_params(&parameters, &params)
+        .map_err(|msg| APIError::status_msg(StatusCode::BAD_REQUEST, msg))?;
+
+    let Some((format, content_type)) = body.format.clickhouse_format() else {
+        return run_sql(&state.ch_client_restricted, &query, &params, limits)
+            .await
+            .map(|result| Json(result).into_response())
+            .map_err(query_execution_failed);
+    };
+    let mut ch_query = limits.apply(state.ch_client_restricted.query(&query));
+    for (name, value) in &params {
+        ch_query = ch_query.param(name, value);
     }
+    let result = ch_query
+        .fetch_bytes(format)
+        .map_err(|e| query_execution_failed(e.into()))?
+        .collect()
+        .await
+        .map_err(|e| query_execution_failed(e.into()))?;
+    Ok(([(header::CONTENT_TYPE, content_type)], result).into_response())
+}
 
+/// Applies the limits of custom queries, shared by everything executing a query.
+pub(super) async fn apply_sql_limits(
+    state: &AppState,
+    rate_limit_key: &RateLimitKey,
+) -> APIResult<()> {
     state
         .rate_limit_client
         .apply_limits(
-            &rate_limit_key,
+            rate_limit_key,
             "sql",
             &[
-                Quota::ip_limit(5, Duration::from_mins(1)),
-                Quota::ip_limit(50, Duration::from_hours(1)),
                 Quota::key_limit(10, Duration::from_mins(1)),
                 Quota::global_limit(30, Duration::from_mins(1)),
             ],
         )
         .await?;
+    Ok(())
+}
+
+/// Checks access and rate limits, and validates a custom query.
+///
+/// Returns the query to execute and the limits of the API key.
+async fn prepare_custom_query(
+    state: &AppState,
+    rate_limit_key: &RateLimitKey,
+    query: &str,
+) -> APIResult<(String, QueryLimits)> {
+    if !state.config.clickhouse.allow_custom_queries {
+        return Err(APIError::status_msg(
+            StatusCode::FORBIDDEN,
+            "Custom queries are disabled",
+        ));
+    }
+
+    state
+        .rate_limit_client
+        .require_scope(rate_limit_key, ApiKeyScope::Sql)
+        .await?;
+    apply_sql_limits(state, rate_limit_key).await?;
 
-    let query = query.query;
-    let query = query.trim().replace(';', "");
-    let query = normalize_query(&query);
+    let query = sanitize_query(query);
 
-    validate_query(&query).map_err(|msg| APIError::status_msg(StatusCode::BAD_REQUEST, msg))?;
+    let tables = fetch_list_tables(&state.ch_client_restricted).await?;
+    let query = validate_query(&query, &tables)
+        .map_err(|msg| APIError::status_msg(StatusCode::BAD_REQUEST, msg))?;
 
     debug!("CUSTOM QUERY: {query}");
 
-    run_sql(&state.ch_client_restricted, &query)
-        .await
-        .map(Json)
-        .map_err(|sql_error| {
-            warn!("Failed to execute query: {sql_error}");
-            APIError::status_msg(
-                StatusCode::BAD_REQUEST,
-                "Query execution failed. Check your SQL syntax and try again.",
-            )
-        })
+    let tier = fetch_query_tier(&state.pg_client, rate_limit_key.api_key).await?;
+    Ok((query, QueryLimits::new(tier, QueryMode::Sync)))
+}
+
+fn query_execution_failed(sql_error: SQLQueryError) -> APIError {
+    warn!("Failed to execute query: {sql_error}");
+    APIError::status_msg(
+        StatusCode::BAD_REQUEST,
+        "Query execution failed. Check your SQL syntax and try again.",
+    )
+}
+
+/// Converts a JSON parameter value to the text format of `ClickHouse` query parameters.
+///
+/// Strings are passed as is, arrays and objects as `ClickHouse` literals (see [`param_literal`])
+/// and other values like numbers in their JSON form.
+fn param_value(value: serde_json::Value) -> String {
+    match value {
+        serde_json::Value::String(value) => value,
+        value @ (serde_json::Value::Array(_) | serde_json::Value::Object(_)) => {
+            param_literal(&value)
+        }
+        value => value.to_string(),
+    }
+}
+
+/// Formats a JSON value as a `ClickHouse` literal, e.g. `['a','b']`, `[1,2]` or `{'a':1}`.
+fn param_literal(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::Null => "NULL".to_owned(),
+        serde_json::Value::Bool(_) | serde_json::Value::Number(_) => value.to_string(),
+        serde_json::Value::String(value) => string_literal(value),
+        serde_json::Value::Array(values) => {
+            let values = values.iter().map(param_literal).collect::<Vec<_>>();
+            format!("[{}]", values.join(","))
+        }
+        serde_json::Value::Object(entries) => {
+            let entries = entries
+                .iter()
+                .map(|(key, value)| format!("{}:{}", string_literal(key), param_literal(value)))
+                .collect::<Vec<_>>();
+            format!("{{{}}}", entries.join(","))
+        }
+    }
+}
+
+fn string_literal(value: &str) -> String {
+    format!("'{}'", value.replace('\\', "\\\\").replace('\'', "\\'"))
 }
 
-async fn run_sql(
+/// Runs a query with the given limits, binding `params` to its `{name:Type}` query parameters.
+pub(super) async fn run_sql(
     ch_client: &clickhouse::Client,
     query: &str,
+    params: &BTreeMap<String, String>,
+    limits: QueryLimits,
 ) -> Result<Vec<serde_json::Value>, SQLQueryError> {
-    let mut lines: Lines<BytesCursor> = ch_client
-        .query(query)
+    let mut ch_query = limits.apply(ch_client.query(query));
+    for (name, value) in params {
+        ch_query = ch_query.param(name, value);
+    }
+    let mut lines: Lines<BytesCursor> = ch_query
         .fetch_bytes("JSONEachRow")
         .map(AsyncBufReadExt::lines)?;
     let mut parsed_result: Vec<serde_json::Value> = vec![];
@@ -242,7 +397,7 @@ pub(super) async fn list_tables(
     convert = "{ 0 }",
     sync_writes = "default"
 )]
-async fn fetch_list_tables(
+pub(super) async fn fetch_list_tables(
     ch_client: &clickhouse::Client,
 ) -> clickhouse::error::Result<Vec<String>> {
     ch_client
@@ -341,3 +496,57 @@ async fn fetch_table_schema(
         .fetch_all()
         .await
 }
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+    use serde_json::json;
+
+    use super::*;
+
+    #[rstest]
+    #[case(json!("Team0"), "Team0")]
+    #[case(json!(15), "15")]
+    #[case(json!(1.5), "1.5")]
+    #[case(json!(true), "true")]
+    #[case(json!(null), "null")]
+    #[case(json!([1, 2, 3]), "[1,2,3]")]
+    #[case(json!(["Team0", "Team1"]), "['Team0','Team1']")]
+    #[case(json!(["it's", "a\\b"]), r"['it\'s','a\\b']")]
+    #[case(json!([[1, 2], [], [null]]), "[[1,2],[],[NULL]]")]
+    #[case(json!({"a": 1, "b": [true]}), "{'a':1,'b':[true]}")]
+    fn test_param_value(#[case] value: serde_json::Value, #[case] expected: &str) {
+        assert_eq!(param_value(value), expected);
+    }
+
+    #[rstest]
+    #[case("  SELECT 1;\n", "SELECT 1")]
+    #[case("SELECT 1 ; ;", "SELECT 1")]
+    #[case("SELECT 'a--b', ';', '/* */'", "SELECT 'a--b', ';', '/* */'")]
+    #[case("SELECT 'a  b'", "SELECT 'a  b'")]
+    #[case("SELECT 1; DROP TABLE match_info", "SELECT 1; DROP TABLE match_info")]
+    fn test_sanitize_query(#[case] query: &str, #[case] expected: &str) {
+        assert_eq!(sanitize_query(query), expected);
+    }
+
+    #[test]
+    fn test_sql_query_body_defaults() {
+        let body: SQLQueryBody = serde_json::from_str(r#"{"query": "SELECT 1"}"#).unwrap();
+        assert!(body.params.is_empty());
+        assert_eq!(body.format, SQLResultFormat::Json);
+        assert_eq!(body.format.clickhouse_format(), None);
+    }
+
+    #[rstest]
+    #[case("JSONEachRow", "JSONEachRow")]
+    #[case("CSV", "CSV")]
+    #[case("TSVWithNames", "TSVWithNames")]
+    fn test_sql_result_format(#[case] format: &str, #[case] expected: &str) {
+        let body: SQLQueryBody =
+            serde_json::from_value(json!({"query": "SELECT 1", "format": format})).unwrap();
+        assert_eq!(
+            body.format.clickhouse_format().map(|(f, _)| f),
+            Some(expected)
+        );
+    }
+}
@@ -0,0 +1,803 @@
+use core::time::Duration;
+use std::collections::BTreeMap;
+
+use axum::Json;
+use axum::extract::{Path, State};
+use axum::http::{StatusCode, header};
+use axum::response::{IntoResponse, Response};
+use axum_extra::extract::Query;
+use chrono::{DateTime, Utc};
+use md5::{Digest, Md5};
+use redis::AsyncCommands;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+use crate::context::AppState;
+use crate::error::{APIError, APIResult};
+use crate::routes::v1::sql::limits::{QueryLimits, QueryMode, QueryTier, fetch_query_tier};
+use crate::routes::v1::sql::route::{apply_sql_limits, fetch_list_tables, run_sql, sanitize_query};
+use crate::routes::v1::sql::validation::{QUERY_PARAMETER_RE, validate_query};
+use crate::services::rate_limiter::extractor::RateLimitKey;
+use crate::services::rate_limiter::{ApiKeyScope, Quota};
+
+const DEFAULT_CACHE_TTL_SECS: i32 = 5 * 60;
+const MAX_CACHE_TTL_SECS: i32 = 24 * 60 * 60;
+/// Public queries can be executed by anyone, so their results have to be cached
+const MIN_PUBLIC_CACHE_TTL_SECS: i32 = 60;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub(super) struct QueryParameter {
+    /// The name of the parameter, passed as query parameter when executing the query.
+    name: String,
+    /// The `ClickHouse` type of the parameter, e.g. `UInt32`.
+    r#type: String,
+}
+
+/// Extracts the typed query parameters of a query.
+pub(super) fn parse_parameters(query: &str) -> Result<Vec<QueryParameter>, String> {
+    let mut parameters: Vec<QueryParameter> = vec![];
+    for capture in QUERY_PARAMETER_RE.captures_iter(query) {
+        let parameter = QueryParameter {
+            name: capture[1].to_owned(),
+            r#type: capture[2].to_owned(),
+        };
+        match parameters.iter().find(|p| p.name == parameter.name) {
+            Some(existing) if existing.r#type != parameter.r#type => {
+                return Err(format!(
+                    "Parameter {} is declared with different types",
+                    parameter.name
+                ));
+            }
+            Some(_) => {}
+            None => parameters.push(parameter),
+        }
+    }
+    Ok(parameters)
+}
+
+/// Checks that exactly the declared parameters are provided.
+pub(super) fn validate_params(
+    parameters: &[QueryParameter],
+    params: &BTreeMap<String, String>,
+) -> Result<(), String> {
+    if let Some(missing) = parameters.iter().find(|p| !params.contains_key(&p.name)) {
+        return Err(format!("Missing parameter: {}", missing.name));
+    }
+    if let Some(unknown) = params
+        .keys()
+        .find(|name| !parameters.iter().any(|p| &p.name == *name))
+    {
+        return Err(format!("Unknown parameter: {unknown}"));
+    }
+    Ok(())
+}
+
+fn cache_key(id: Uuid, updated_at: DateTime<Utc>, params: &BTreeMap<String, String>) -> String {
+    let mut hasher = Md5::new();
+    for (name, value) in params {
+        hasher.update(name);
+        hasher.update([0]);
+        hasher.update(value);
+        hasher.update([0]);
+    }
+    format!(
+        "saved-query:{id}:{}:{}",
+        updated_at.timestamp(),
+        hex::encode(hasher.finalize())
+    )
+}
+
+/// Sanitizes and validates a query before it is saved.
+async fn prepare_query(state: &AppState, query: &str) -> APIResult<String> {
+    let query = sanitize_query(query);
+    let tables = fetch_list_tables(&state.ch_client_restricted).await?;
+    validate_query(&query, &tables)
+        .map_err(|msg| APIError::status_msg(StatusCode::BAD_REQUEST, msg))?;
+    parse_parameters(&query).map_err(|msg| APIError::status_msg(StatusCode::BAD_REQUEST, msg))?;
+    Ok(query)
+}
+
+fn validate_cache_ttl(cache_ttl_secs: i32, public: bool) -> APIResult<()> {
+    if !(0..=MAX_CACHE_TTL_SECS).contains(&cache_ttl_secs) {
+        return Err(APIError::status_msg(
+            StatusCode::BAD_REQUEST,
+            format!("cache_ttl_secs must be between 0 and {MAX_CACHE_TTL_SECS}"),
+        ));
+    }
+    if public && cache_ttl_secs < MIN_PUBLIC_CACHE_TTL_SECS {
+        return Err(APIError::status_msg(
+            StatusCode::BAD_REQUEST,
+            format!(
+                "cache_ttl_secs of public queries must be at least {MIN_PUBLIC_CACHE_TTL_SECS}"
+            ),
+        ));
+    }
+    Ok(())
+}
+
+fn map_constraint_error(e: sqlx::Error) -> APIError {
+    match e {
+        sqlx::Error::Database(e) if e.is_unique_violation() => APIError::status_msg(
+            StatusCode::CONFLICT,
+            "A saved query with this name already exists",
+        ),
+        e => e.into(),
+    }
+}
+
+fn saved_query_not_found(id: Uuid) -> APIError {
+    APIError::status_msg(StatusCode::NOT_FOUND, format!("Saved query {id} not found"))
+}
+
+struct SavedQueryRow {
+    id: Uuid,
+    api_key: Uuid,
+    name: String,
+    query: String,
+    cache_ttl_secs: i32,
+    share_token: Option<Uuid>,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub(super) struct SavedQuery {
+    id: Uuid,
+    name: String,
+    query: String,
+    /// The typed parameters of the query, declared as `{name:Type}` in the query.
+    parameters: Vec<QueryParameter>,
+    /// How long results are cached, in seconds.
+    cache_ttl_secs: i32,
+    /// Token of the public share link `/v1/sql/shared/{share_token}`, if the query is public.
+    share_token: Option<Uuid>,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+}
+
+impl From<SavedQueryRow> for SavedQuery {
+    fn from(row: SavedQueryRow) -> Self {
+        Self {
+            parameters: parse_parameters(&row.query).unwrap_or_default(),
+            id: row.id,
+            name: row.name,
+            query: row.query,
+            cache_ttl_secs: row.cache_ttl_secs,
+            share_token: row.share_token,
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+        }
+    }
+}
+
+fn default_cache_ttl_secs() -> i32 {
+    DEFAULT_CACHE_TTL_SECS
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub(super) struct CreateSavedQueryRequest {
+    /// Unique name of the query.
+    name: String,
+    /// The SQL query. Parameters are declared as `{name:Type}`, e.g. `{hero_id:UInt32}`.
+    query: String,
+    /// How long results are cached, in seconds. Between 0 and 86400, and at least 60 for public
+    /// queries.
+    #[serde(default = "default_cache_ttl_secs")]
+    cache_ttl_secs: i32,
+    /// Whether to create a public share link for the query.
+    #[serde(default)]
+    public: bool,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub(super) struct UpdateSavedQueryRequest {
+    name: Option<String>,
+    query: Option<String>,
+    cache_ttl_secs: Option<i32>,
+    /// Creates or revokes the public share link.
+    public: Option<bool>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub(super) struct SavedQueryPath {
+    /// The ID of the saved query.
+    id: Uuid,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub(super) struct SharedQueryPath {
+    /// The share token of the saved query.
+    share_token: Uuid,
+}
+
+/// Checks that custom queries are enabled and returns the API key of the caller.
+pub(super) async fn require_owner(
+    state: &AppState,
+    rate_limit_key: &RateLimitKey,
+) -> APIResult<Uuid> {
+    if !state.config.clickhouse.allow_custom_queries {
+        return Err(APIError::status_msg(
+            StatusCode::FORBIDDEN,
+            "Custom queries are disabled",
+        ));
+    }
+    state
+        .rate_limit_client
+        .require_scope(rate_limit_key, ApiKeyScope::Sql)
+        .await?;
+    rate_limit_key
+        .api_key
+        .ok_or_else(|| APIError::status_msg(StatusCode::FORBIDDEN, "API key is required"))
+}
+
+async fn fetch_saved_query(state: &AppState, id: Uuid, api_key: Uuid) -> APIResult<SavedQueryRow> {
+    sqlx::query_as!(
+        SavedQueryRow,
+        r#"
+        SELECT id, api_key, name, query, cache_ttl_secs, share_token, created_at, updated_at
+        FROM saved_queries
+        WHERE id = $1 AND api_key = $2
+        "#,
+        id,
+        api_key
+    )
+    .fetch_optional(&state.pg_client)
+    .await?
+    .ok_or_else(|| saved_query_not_found(id))
+}
+
+/// Executes a saved query, serving the result from the cache if possible.
+///
+/// Executions that miss the cache count towards the `/v1/sql` limits of `rate_limit_key`.
+async fn execute_saved_query(
+    state: &AppState,
+    rate_limit_key: &RateLimitKey,
+    saved_query: &SavedQueryRow,
+    mut params: BTreeMap<String, String>,
+    limits: QueryLimits,
+) -> APIResult<Response> {
+    // The API key may be passed as query parameter
+    params.remove("api_key");
+    let parameters = parse_parameters(&saved_query.query)
+        .map_err(|msg| APIError::status_msg(StatusCode::BAD_REQUEST, msg))?;
+    validate_params(&parameters, &params)
+        .map_err(|msg| APIError::status_msg(StatusCode::BAD_REQUEST, msg))?;
+
+    let key = cache_key(saved_query.id, saved_query.updated_at, &params);
+    let mut redis_client = state.redis_client.clone();
+    if saved_query.cache_ttl_secs > 0 {
+        match redis_client.get::<_, Option<String>>(&key).await {
+            Ok(Some(cached)) => {
+                return Ok(([(header::CONTENT_TYPE, "application/json")], cached).into_response());
+            }
+            Ok(None) => {}
+            Err(e) => warn!("Failed to read cached saved query result: {e}"),
+        }
+    }
+
+    apply_sql_limits(state, rate_limit_key).await?;
+
+    // The allowed tables may have changed since the query was saved
+    let tables = fetch_list_tables(&state.ch_client_restricted).await?;
+    let query = validate_query(&saved_query.query, &tables)
+        .map_err(|msg| APIError::status_msg(StatusCode::BAD_REQUEST, msg))?;
+    debug!("SAVED QUERY {}: {query}", saved_query.id);
+    let result = run_sql(&state.ch_client_restricted, &query, &params, limits)
+        .await
+        .map_err(|sql_error| {
+            warn!("Failed to execute saved query: {sql_error}");
+            APIError::status_msg(
+                StatusCode::BAD_REQUEST,
+                "Query execution failed. Check your SQL syntax and parameters and try again.",
+            )
+        })?;
+    let body = serde_json::to_string(&result)?;
+
+    if let Ok(ttl) = u64::try_from(saved_query.cache_ttl_secs)
+        && ttl > 0
+        && let Err(e) = redis_client.set_ex::<_, _, ()>(&key, &body, ttl).await
+    {
+        warn!("Failed to cache saved query result: {e}");
+    }
+    Ok(([(header::CONTENT_TYPE, "application/json")], body).into_response())
+}
+
+#[utoipa::path(
+    get,
+    path = "/saved",
+    responses(
+        (status = OK, body = [SavedQuery]),
+        (status = FORBIDDEN, description = "API key is missing the `sql` scope"),
+        (status = INTERNAL_SERVER_ERROR, description = "Fetching the saved queries failed")
+    ),
+    security(("api_key_header" = ["sql"]), ("api_key_query" = ["sql"])),
+    tags = ["SQL"],
+    summary = "List Saved Queries",
+    description = "
+Lists the saved queries of your API key.
+
+Requires an API key with the `sql` scope.
+
+### Rate Limits:
+| Type | Limit |
+| ---- | ----- |
+| IP | API-Key ONLY |
+| Key | - |
+| Global | - |
+    "
+)]
+pub(super) async fn list_saved_queries(
+    rate_limit_key: RateLimitKey,
+    State(state): State<AppState>,
+) -> APIResult<impl IntoResponse> {
+    let api_key = require_owner(&state, &rate_limit_key).await?;
+    let saved_queries = sqlx::query_as!(
+        SavedQueryRow,
+        r#"
+        SELECT id, api_key, name, query, cache_ttl_secs, share_token, created_at, updated_at
+        FROM saved_queries
+        WHERE api_key = $1
+        ORDER BY name
+        "#,
+        api_key
+    )
+    .fetch_all(&state.pg_client)
+    .await?;
+    Ok(Json(
+        saved_queries
+            .into_iter()
+            .map(SavedQuery::from)
+            .collect::<Vec<_>>(),
+    ))
+}
+
+#[utoipa::path(
+    post,
+    path = "/saved",
+    request_body = CreateSavedQueryRequest,
+    responses(
+        (status = CREATED, body = SavedQuery),
+        (status = BAD_REQUEST, description = "The query or its parameters are invalid."),
+        (status = FORBIDDEN, description = "API key is missing the `sql` scope"),
+        (status = CONFLICT, description = "A saved query with this name already exists"),
+        (status = INTERNAL_SERVER_ERROR, description = "Saving the query failed")
+    ),
+    security(("api_key_header" = ["sql"]), ("api_key_query" = ["sql"])),
+    tags = ["SQL"],
+    summary = "Save Query",
+    description = r#"
+Saves a named SQL query, which can then be executed by its ID.
+
+Queries can declare typed parameters using the ClickHouse query parameter syntax, e.g. `SELECT * FROM match_player WHERE hero_id = {hero_id:UInt32}`.
+
+Results are cached for `cache_ttl_secs` seconds. If `public` is set, a share token is created, which allows anyone to execute the query via `/v1/sql/shared/{share_token}`.
+
+Requires an API key with the `sql` scope.
+
+### Rate Limits:
+| Type | Limit |
+| ---- | ----- |
+| IP | API-Key ONLY |
+| Key | 10req/min |
+| Global | - |
+    "#
+)]
+pub(super) async fn create_saved_query(
+    rate_limit_key: RateLimitKey,
+    State(state): State<AppState>,
+    Json(request): Json<CreateSavedQueryRequest>,
+) -> APIResult<impl IntoResponse> {
+    let api_key = require_owner(&state, &rate_limit_key).await?;
+    state
+        .rate_limit_client
+        .apply_limits(
+            &rate_limit_key,
+            "sql_saved_write",
+            &[Quota::key_limit(10, Duration::from_mins(1))],
+        )
+        .await?;
+    let query = prepare_query(&state, &request.query).await?;
+    validate_cache_ttl(request.cache_ttl_secs, request.public)?;
+
+    let saved_query = sqlx::query_as!(
+        SavedQueryRow,
+        r#"
+        INSERT INTO saved_queries (api_key, name, query, cache_ttl_secs, share_token)
+        VALUES ($1, $2, $3, $4, CASE WHEN $5 THEN gen_random_uuid() END)
+        RETURNING id, api_key, name, query, cache_ttl_secs, share_token, created_at, updated_at
+        "#,
+        api_key,
+        request.name,
+        query,
+        request.cache_ttl_secs,
+        request.public
+    )
+    .fetch_one(&state.pg_client)
+    .await
+    .map_err(map_constraint_error)?;
+    Ok((StatusCode::CREATED, Json(SavedQuery::from(saved_query))))
+}
+
+#[utoipa::path(
+    get,
+    path = "/saved/{id}",
+    params(SavedQueryPath),
+    responses(
+        (status = OK, body = SavedQuery),
+        (status = FORBIDDEN, description = "API key is missing the `sql` scope"),
+        (status = NOT_FOUND, description = "Saved query not found"),
+        (status = INTERNAL_SERVER_ERROR, description = "Fetching the saved query failed")
+    ),
+    security(("api_key_header" = ["sql"]), ("api_key_query" = ["sql"])),
+    tags = ["SQL"],
+    summary = "Get Saved Query",
+    description = "
+Returns a saved query of your API key.
+
+Requires an API key with the `sql` scope.
+
+### Rate Limits:
+| Type | Limit |
+| ---- | ----- |
+| IP | API-Key ONLY |
+| Key | - |
+| Global | - |
+    "
+)]
+pub(super) async fn get_saved_query(
+    rate_limit_key: RateLimitKey,
+    Path(SavedQueryPath { id }): Path<SavedQueryPath>,
+    State(state): State<AppState>,
+) -> APIResult<impl IntoResponse> {
+    let api_key = require_owner(&state, &rate_limit_key).await?;
+    let saved_query = fetch_saved_query(&state, id, api_key).await?;
+    Ok(Json(SavedQuery::from(saved_query)))
+}
+
+#[utoipa::path(
+    patch,
+    path = "/saved/{id}",
+    params(SavedQueryPath),
+    request_body = UpdateSavedQueryRequest,
+    responses(
+        (status = OK, body = SavedQuery),
+        (status = BAD_REQUEST, description = "The query or its parameters are invalid."),
+        (status = FORBIDDEN, description = "API key is missing the `sql` scope"),
+        (status = NOT_FOUND, description = "Saved query not found"),
+        (status = CONFLICT, description = "A saved query with this name already exists"),
+        (status = INTERNAL_SERVER_ERROR, description = "Updating the saved query failed")
+    ),
+    security(("api_key_header" = ["sql"]), ("api_key_query" = ["sql"])),
+    tags = ["SQL"],
+    summary = "Update Saved Query",
+    description = "
+Updates a saved query of your API key. Cached results of the previous version are not served anymore.
+
+Setting `public` to `false` revokes the share link, setting it to `true` again creates a new one.
+
+Requires an API key with the `sql` scope.
+
+### Rate Limits:
+| Type | Limit |
+| ---- | ----- |
+| IP | API-Key ONLY |
+| Key | 10req/min |
+| Global | - |
+    "
+)]
+pub(super) async fn update_saved_query(
+    rate_limit_key: RateLimitKey,
+    Path(SavedQueryPath { id }): Path<SavedQueryPath>,
+    State(state): State<AppState>,
+    Json(request): Json<UpdateSavedQueryRequest>,
+) -> APIResult<impl IntoResponse> {
+    let api_key = require_owner(&state, &rate_limit_key).await?;
+    state
+        .rate_limit_client
+        .apply_limits(
+            &rate_limit_key,
+            "sql_saved_write",
+            &[Quota::key_limit(10, Duration::from_mins(1))],
+        )
+        .await?;
+    let query = match &request.query {
+        Some(query) => Some(prepare_query(&state, query).await?),
+        None => None,
+    };
+    let existing = fetch_saved_query(&state, id, api_key).await?;
+    validate_cache_ttl(
+        request.cache_ttl_secs.unwrap_or(existing.cache_ttl_secs),
+        request.public.unwrap_or(existing.share_token.is_some()),
+    )?;
+
+    let saved_query = sqlx::query_as!(
+        SavedQueryRow,
+        r#"
+        UPDATE saved_queries
+        SET name           = COALESCE($3, name),
+            query          = COALESCE($4, query),
+            cache_ttl_secs = COALESCE($5, cache_ttl_secs),
+            share_token    = CASE
+                                 WHEN $6::boolean IS NULL THEN share_token
+                                 WHEN $6 THEN COALESCE(share_token, gen_random_uuid())
+                             END,
+            updated_at     = current_timestamp
+        WHERE id = $1 AND api_key = $2
+        RETURNING id, api_key, name, query, cache_ttl_secs, share_token, created_at, updated_at
+        "#,
+        id,
+        api_key,
+        request.name,
+        query,
+        request.cache_ttl_secs,
+        request.public
+    )
+    .fetch_optional(&state.pg_client)
+    .await
+    .map_err(map_constraint_error)?
+    .ok_or_else(|| saved_query_not_found(id))?;
+    Ok(Json(SavedQuery::from(saved_query)))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/saved/{id}",
+    params(SavedQueryPath),
+    responses(
+        (status = NO_CONTENT, description = "Saved query deleted"),
+        (status = FORBIDDEN, description = "API key is missing the `sql` scope"),
+        (status = NOT_FOUND, description = "Saved query not found"),
+        (status = INTERNAL_SERVER_ERROR, description = "Deleting the saved query failed")
+    ),
+    security(("api_key_header" = ["sql"]), ("api_key_query" = ["sql"])),
+    tags = ["SQL"],
+    summary = "Delete Saved Query",
+    description = "
+Deletes a saved query of your API key, including its share link.
+
+Requires an API key with the `sql` scope.
+
+### Rate Limits:
+| Type | Limit |
+| ---- | ----- |
+| IP | API-Key ONLY |
+| Key | - |
+| Global | - |
+    "
+)]
+pub(super) async fn delete_saved_query(
+    rate_limit_key: RateLimitKey,
+    Path(SavedQueryPath { id }): Path<SavedQueryPath>,
+    State(state): State<AppState>,
+) -> APIResult<impl IntoResponse> {
+    let api_key = require_owner(&state, &rate_limit_key).await?;
+    let result = sqlx::query!(
+        "DELETE FROM saved_queries WHERE id = $1 AND api_key = $2",
+        id,
+        api_key
+    )
+    .execute(&state.pg_client)
+    .await?;
+    if result.rows_affected() == 0 {
+        return Err(saved_query_not_found(id));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    get,
+    path = "/saved/{id}/execute",
+    params(SavedQueryPath),
+    responses(
+        (status = OK, body = Vec<serde_json::Value>),
+        (status = BAD_REQUEST, description = "Missing or invalid parameters, or the query failed."),
+        (status = FORBIDDEN, description = "API key is missing the `sql` scope"),
+        (status = NOT_FOUND, description = "Saved query not found"),
+        (status = TOO_MANY_REQUESTS, description = "Rate limit exceeded"),
+        (status = INTERNAL_SERVER_ERROR, description = "Executing the saved query failed")
+    ),
+    security(("api_key_header" = ["sql"]), ("api_key_query" = ["sql"])),
+    tags = ["SQL"],
+    summary = "Execute Saved Query",
+    description = "
+Executes a saved query of your API key.
+
+The parameters of the query are passed as query parameters, e.g. `?hero_id=15`. Results are cached for the `cache_ttl_secs` of the query.
+
+Requires an API key with the `sql` scope.
+
+### Rate Limits:
+| Type | Limit |
+| ---- | ----- |
+| IP | API-Key ONLY |
+| Key | 60req/min, executions missing the cache also count towards the `/v1/sql` limits |
+| Global | Executions missing the cache count towards the `/v1/sql` limits |
+    "
+)]
+pub(super) async fn execute_saved(
+    rate_limit_key: RateLimitKey,
+    Path(SavedQueryPath { id }): Path<SavedQueryPath>,
+    Query(params): Query<BTreeMap<String, String>>,
+    State(state): State<AppState>,
+) -> APIResult<impl IntoResponse> {
+    let api_key = require_owner(&state, &rate_limit_key).await?;
+    state
+        .rate_limit_client
+        .apply_limits(
+            &rate_limit_key,
+            "sql_saved",
+            &[Quota::key_limit(60, Duration::from_mins(1))],
+        )
+        .await?;
+    let saved_query = fetch_saved_query(&state, id, api_key).await?;
+    let tier = fetch_query_tier(&state.pg_client, Some(api_key)).await?;
+    execute_saved_query(
+        &state,
+        &rate_limit_key,
+        &saved_query,
+        params,
+        QueryLimits::new(tier, QueryMode::Sync),
+    )
+    .await
+}
+
+#[utoipa::path(
+    get,
+    path = "/shared/{share_token}",
+    params(SharedQueryPath),
+    responses(
+        (status = OK, body = Vec<serde_json::Value>),
+        (status = BAD_REQUEST, description = "Missing or invalid parameters, or the query failed."),
+        (status = FORBIDDEN, description = "The API key that shared the query is missing the `sql` scope"),
+        (status = NOT_FOUND, description = "Shared query not found"),
+        (status = TOO_MANY_REQUESTS, description = "Rate limit exceeded"),
+        (status = INTERNAL_SERVER_ERROR, description = "Executing the shared query failed")
+    ),
+    tags = ["SQL"],
+    summary = "Execute Shared Query",
+    description = "
+Executes a publicly shared saved query. No API key is required, so the link can be embedded in dashboards.
+
+The parameters of the query are passed as query parameters, e.g. `?hero_id=15`. Only the declared parameters are accepted, the query itself can't be changed.
+
+Results are cached for at least 60 seconds. Executions missing the cache count towards the `/v1/sql` limits of the API key that shared the query, the link stops working if the key loses the `sql` scope.
+
+### Rate Limits:
+| Type | Limit |
+| ---- | ----- |
+| IP | 60req/min |
+| Key | Executions missing the cache count towards the `/v1/sql` limits of the owner |
+| Global | Executions missing the cache count towards the `/v1/sql` limits |
+    "
+)]
+pub(super) async fn execute_shared(
+    rate_limit_key: RateLimitKey,
+    Path(SharedQueryPath { share_token }): Path<SharedQueryPath>,
+    Query(params): Query<BTreeMap<String, String>>,
+    State(state): State<AppState>,
+) -> APIResult<impl IntoResponse> {
+    if !state.config.clickhouse.allow_custom_queries {
+        return Err(APIError::status_msg(
+            StatusCode::FORBIDDEN,
+            "Custom queries are disabled",
+        ));
+    }
+    state
+        .rate_limit_client
+        .apply_limits(
+            &rate_limit_key,
+            "sql_shared",
+            &[Quota::ip_limit(60, Duration::from_mins(1))],
+        )
+        .await?;
+    let mut saved_query = sqlx::query_as!(
+        SavedQueryRow,
+        r#"
+        SELECT id, api_key, name, query, cache_ttl_secs, share_token, created_at, updated_at
+        FROM saved_queries
+        WHERE share_token = $1
+        "#,
+        share_token
+    )
+    .fetch_optional(&state.pg_client)
+    .await?
+    .ok_or_else(|| APIError::status_msg(StatusCode::NOT_FOUND, "Shared query not found"))?;
+    let owner_key = rate_limit_key.on_behalf_of(saved_query.api_key);
+    state
+        .rate_limit_client
+        .require_scope(&owner_key, ApiKeyScope::Sql)
+        .await?;
+    // Queries shared before the minimum TTL was introduced may not be cached
+    saved_query.cache_ttl_secs = saved_query.cache_ttl_secs.max(MIN_PUBLIC_CACHE_TTL_SECS);
+    // Shared queries are executed anonymously, so they get the standard limits
+    execute_saved_query(
+        &state,
+        &owner_key,
+        &saved_query,
+        params,
+        QueryLimits::new(QueryTier::Standard, QueryMode::Sync),
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    fn parameter(name: &str, r#type: &str) -> QueryParameter {
+        QueryParameter {
+            name: name.to_owned(),
+            r#type: r#type.to_owned(),
+        }
+    }
+
+    #[rstest]
+    #[case("SELECT 1", vec![])]
+    #[case(
+        "SELECT * FROM match_player WHERE hero_id = {hero_id:UInt32}",
+        vec![parameter("hero_id", "UInt32")]
+    )]
+    #[case(
+        "SELECT * FROM match_info WHERE start_time > { min_time : DateTime } AND match_id IN {ids:Array(UInt64)} AND start_time > {min_time:DateTime}",
+        vec![parameter("min_time", "DateTime"), parameter("ids", "Array(UInt64)")]
+    )]
+    fn test_parse_parameters(#[case] query: &str, #[case] expected: Vec<QueryParameter>) {
+        assert_eq!(parse_parameters(query).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_parse_parameters_conflicting_types() {
+        assert!(parse_parameters("SELECT {a:UInt32}, {a:String}").is_err());
+    }
+
+    #[test]
+    fn test_validate_params() {
+        let parameters = vec![parameter("hero_id", "UInt32")];
+        let params = |pairs: &[(&str, &str)]| {
+            pairs
+                .iter()
+                .map(|&(k, v)| (k.to_owned(), v.to_owned()))
+                .collect::<BTreeMap<_, _>>()
+        };
+        assert!(validate_params(&parameters, &params(&[("hero_id", "15")])).is_ok());
+        assert!(validate_params(&parameters, &params(&[])).is_err());
+        assert!(
+            validate_params(&parameters, &params(&[("hero_id", "15"), ("other", "1")])).is_err()
+        );
+    }
+
+    #[rstest]
+    #[case(0, false, true)]
+    #[case(0, true, false)]
+    #[case(59, true, false)]
+    #[case(60, true, true)]
+    #[case(MAX_CACHE_TTL_SECS, true, true)]
+    #[case(MAX_CACHE_TTL_SECS + 1, false, false)]
+    #[case(-1, false, false)]
+    fn test_validate_cache_ttl(
+        #[case] cache_ttl_secs: i32,
+        #[case] public: bool,
+        #[case] valid: bool,
+    ) {
+        assert_eq!(validate_cache_ttl(cache_ttl_secs, public).is_ok(), valid);
+    }
+
+    #[test]
+    fn test_cache_key() {
+        let id = Uuid::nil();
+        let updated_at = Utc::now();
+        let mut params = BTreeMap::new();
+        params.insert("hero_id".to_owned(), "15".to_owned());
+        let key = cache_key(id, updated_at, &params);
+        assert_eq!(key, cache_key(id, updated_at, &params));
+        assert_ne!(key, cache_key(id, updated_at, &BTreeMap::new()));
+        assert_ne!(
+            key,
+            cache_key(id, updated_at + chrono::TimeDelta::seconds(1), &params)
+        );
+    }
+}
@@ -0,0 +1,705 @@
+use core::ops::ControlFlow;
+use std::collections::HashSet;
+use std::sync::LazyLock;
+
+use regex::Regex;
+use sqlparser::ast::{
+    Expr, LimitClause, ObjectName, Query, SetExpr, Statement, TableAlias, TableFactor, Visit,
+    Visitor,
+};
+use sqlparser::dialect::ClickHouseDialect;
+use sqlparser::keywords::Keyword;
+use sqlparser::parser::Parser;
+use sqlparser::tokenizer::{Token, Tokenizer, Whitespace};
+
+/// Matches `ClickHouse` query parameters, e.g. `{hero_id:UInt32}`
+pub(super) static QUERY_PARAMETER_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\{\s*([A-Za-z_]\w*)\s*:\s*([^{}]+?)\s*\}").unwrap());
+
+/// Types query parameters may have, optionally wrapped in `Array` or `Nullable`.
+///
+/// Parameters may only be literal values, types like `Identifier` would let them change the
+/// query itself, e.g. the table it reads from.
+const ALLOWED_PARAMETER_TYPES: &[&str] = &[
+    "bool",
+    "date",
+    "date32",
+    "datetime",
+    "datetime64",
+    "decimal",
+    "decimal32",
+    "decimal64",
+    "decimal128",
+    "decimal256",
+    "fixedstring",
+    "float32",
+    "float64",
+    "int8",
+    "int16",
+    "int32",
+    "int64",
+    "int128",
+    "int256",
+    "string",
+    "uint8",
+    "uint16",
+    "uint32",
+    "uint64",
+    "uint128",
+    "uint256",
+    "uuid",
+];
+
+/// Maximum number of rows a custom query may return
+const MAX_ROWS: u64 = 100_000;
+
+/// Functions that may be called, compared case-insensitively after stripping aggregate
+/// combinators
+const ALLOWED_FUNCTIONS: &[&str] = &[
+    // Aggregate and window functions
+    "any",
+    "anyheavy",
+    "anylast",
+    "argmax",
+    "argmin",
+    "avg",
+    "avgweighted",
+    "corr",
+    "count",
+    "covarpop",
+    "covarsamp",
+    "cume_dist",
+    "dense_rank",
+    "entropy",
+    "first_value",
+    "grouparray",
+    "grouparraysorted",
+    "groupbitand",
+    "groupbitor",
+    "groupbitxor",
+    "groupuniqarray",
+    "histogram",
+    "lag",
+    "laginframe",
+    "last_value",
+    "lead",
+    "leadinframe",
+    "max",
+    "median",
+    "medianexact",
+    "min",
+    "nth_value",
+    "ntile",
+    "percent_rank",
+    "quantile",
+    "quantileexact",
+    "quantiles",
+    "quantilesexact",
+    "quantilestdigest",
+    "quantiletdigest",
+    "quantiletiming",
+    "rank",
+    "row_number",
+    "simplelinearregression",
+    "stddevpop",
+    "stddevsamp",
+    "sum",
+    "summap",
+    "sumwithoverflow",
+    "topk",
+    "topkweighted",
+    "uniq",
+    "uniqcombined",
+    "uniqexact",
+    "uniqhll12",
+    "varpop",
+    "varsamp",
+    // Conditional, logical and comparison functions
+    "and",
+    "assumenotnull",
+    "coalesce",
+    "equals",
+    "greater",
+    "greaterorequals",
+    "greatest",
+    "if",
+    "ifnull",
+    "in",
+    "least",
+    "less",
+    "lessorequals",
+    "multiif",
+    "not",
+    "notequals",
+    "notin",
+    "nullif",
+    "or",
+    "xor",
+    // Mathematical functions
+    "abs",
+    "cbrt",
+    "ceil",
+    "ceiling",
+    "clamp",
+    "cos",
+    "divide",
+    "dotproduct",
+    "e",
+    "exp",
+    "exp10",
+    "exp2",
+    "floor",
+    "intdiv",
+    "intdivorzero",
+    "ln",
+    "log",
+    "log10",
+    "log2",
+    "minus",
+    "mod",
+    "modulo",
+    "multiply",
+    "negate",
+    "pi",
+    "plus",
+    "pow",
+    "power",
+    "round",
+    "roundbankers",
+    "rounddown",
+    "sign",
+    "sin",
+    "sqrt",
+    "tan",
+    "trunc",
+    // String functions
+    "ascii",
+    "base64decode",
+    "base64encode",
+    "char",
+    "concat",
+    "concatwithseparator",
+    "empty",
+    "endswith",
+    "extract",
+    "extractall",
+    "format",
+    "formatreadablequantity",
+    "formatreadablesize",
+    "formatreadabletimedelta",
+    "hex",
+    "ilike",
+    "left",
+    "leftpad",
+    "length",
+    "lengthutf8",
+    "like",
+    "lower",
+    "ltrim",
+    "match",
+    "notempty",
+    "notlike",
+    "position",
+    "positioncaseinsensitive",
+    "repeat",
+    "replace",
+    "replaceall",
+    "replaceone",
+    "replaceregexpall",
+    "replaceregexpone",
+    "reverse",
+    "right",
+    "rightpad",
+    "rtrim",
+    "splitbychar",
+    "splitbyregexp",
+    "splitbystring",
+    "startswith",
+    "substr",
+    "substring",
+    "trim",
+    "trimboth",
+    "trimleft",
+    "trimright",
+    "unhex",
+    "upper",
+    // Date and time functions
+    "adddays",
+    "addhours",
+    "addminutes",
+    "addmonths",
+    "addseconds",
+    "addweeks",
+    "addyears",
+    "age",
+    "date_diff",
+    "date_trunc",
+    "dateadd",
+    "datediff",
+    "datesub",
+    "datetrunc",
+    "day",
+    "dayofmonth",
+    "dayofweek",
+    "dayofyear",
+    "formatdatetime",
+    "fromunixtimestamp",
+    "hour",
+    "minute",
+    "month",
+    "now",
+    "parsedatetimebesteffort",
+    "parsedatetimebesteffortornull",
+    "quarter",
+    "second",
+    "subtractdays",
+    "subtracthours",
+    "subtractminutes",
+    "subtractmonths",
+    "subtractseconds",
+    "subtractweeks",
+    "subtractyears",
+    "timeslot",
+    "today",
+    "week",
+    "year",
+    "yesterday",
+    // Array, map and tuple functions
+    "countequal",
+    "has",
+    "hasall",
+    "hasany",
+    "indexof",
+    "range",
+    "tuple",
+    "tupleelement",
+];
+
+/// Function families that may be called, e.g. `toUInt32` or `arrayMap`
+const ALLOWED_FUNCTION_PREFIXES: &[&str] = &[
+    "array",
+    "bit",
+    "dictget",
+    "is",
+    "json",
+    "map",
+    "to",
+    "visitparam",
+];
+
+/// Aggregate function combinators, e.g. `countIf` or `uniqMerge`
+const AGGREGATE_COMBINATORS: &[&str] = &[
+    "if",
+    "array",
+    "map",
+    "merge",
+    "state",
+    "ornull",
+    "ordefault",
+    "distinct",
+    "foreach",
+    "resample",
+];
+
+fn is_allowed_function(name: &str) -> bool {
+    let mut name = name.to_lowercase();
+    loop {
+        if ALLOWED_FUNCTIONS.contains(&name.as_str())
+            || ALLOWED_FUNCTION_PREFIXES
+                .iter()
+                .any(|prefix| name.starts_with(prefix))
+        {
+            return true;
+        }
+        let Some(base) = AGGREGATE_COMBINATORS.iter().find_map(|combinator| {
+            name.strip_suffix(combinator)
+                .filter(|base| !base.is_empty())
+                .map(ToOwned::to_owned)
+        }) else {
+            return false;
+        };
+        name = base;
+    }
+}
+
+fn is_allowed_parameter_type(r#type: &str) -> bool {
+    let r#type = r#type.trim().to_lowercase();
+    if let Some(inner) = ["array(", "nullable("].iter().find_map(|wrapper| {
+        r#type
+            .strip_prefix(wrapper)
+            .and_then(|inner| inner.strip_suffix(')'))
+    }) {
+        return is_allowed_parameter_type(inner);
+    }
+    // Arguments like the precision of `Decimal(10, 2)` don't change the kind of the type
+    let base = r#type
+        .split_once('(')
+        .map_or(r#type.as_str(), |(base, _)| base);
+    ALLOWED_PARAMETER_TYPES.contains(&base.trim_end())
+}
+
+/// Splits a possibly qualified name into its unquoted parts.
+fn name_parts(name: &ObjectName) -> Vec<String> {
+    name.0
+        .iter()
+        .map(|part| {
+            part.as_ident()
+                .map_or_else(|| part.to_string(), |ident| ident.value.clone())
+        })
+        .collect()
+}
+
+/// `sqlparser` doesn't know `ARRAY JOIN`, it parses `t ARRAY JOIN a` as a join of `t AS ARRAY`
+/// and `a`.
+fn is_array_join_alias(alias: Option<&TableAlias>) -> bool {
+    alias.is_some_and(|alias| {
+        alias.name.quote_style.is_none() && alias.name.value.eq_ignore_ascii_case("array")
+    })
+}
+
+/// Walks the AST and checks all table references, table expressions and function calls.
+struct QueryValidator<'a> {
+    tables: &'a [String],
+    /// Names of the common table expressions, which can be referenced like tables
+    ctes: HashSet<String>,
+}
+
+impl Visitor for QueryValidator<'_> {
+    type Break = String;
+
+    fn pre_visit_query(&mut self, query: &Query) -> ControlFlow<Self::Break> {
+        if let Some(settings) = &query.settings {
+            let settings = settings.iter().map(ToString::to_string).collect::<Vec<_>>();
+            return ControlFlow::Break(format!(
+                "SETTINGS clause is not allowed: SETTINGS {}",
+                settings.join(", ")
+            ));
+        }
+        if let Some(format_clause) = &query.format_clause {
+            return ControlFlow::Break(format!("FORMAT clause is not allowed: {format_clause}"));
+        }
+        if let Some(with) = &query.with {
+            self.ctes.extend(
+                with.cte_tables
+                    .iter()
+                    .map(|cte| cte.alias.name.value.clone()),
+            );
+        }
+        ControlFlow::Continue(())
+    }
+
+    fn pre_visit_relation(&mut self, relation: &ObjectName) -> ControlFlow<Self::Break> {
+        let allowed = match name_parts(relation).as_slice() {
+            [table] => self.ctes.contains(table) || self.tables.contains(table),
+            [database, table] => database == "default" && self.tables.contains(table),
+            _ => false,
+        };
+        if allowed {
+            ControlFlow::Continue(())
+        } else {
+            ControlFlow::Break(format!("Access to table {relation} is not allowed"))
+        }
+    }
+
+    fn pre_visit_table_factor(&mut self, table_factor: &TableFactor) -> ControlFlow<Self::Break> {
+        match table_factor {
+            TableFactor::Table { alias, .. } | TableFactor::Derived { alias, .. }
+                if is_array_join_alias(alias.as_ref()) =>
+            {
+                ControlFlow::Break(
+                    "ARRAY JOIN is not supported, use the arrayJoin function instead".to_owned(),
+                )
+            }
+            TableFactor::Table {
+                name,
+                args: Some(_),
+                ..
+            } => ControlFlow::Break(format!("Table function {name} is not allowed")),
+            TableFactor::Table { .. }
+            | TableFactor::Derived { .. }
+            | TableFactor::NestedJoin { .. } => ControlFlow::Continue(()),
+            other => ControlFlow::Break(format!("Table expression {other} is not allowed")),
+        }
+    }
+
+    fn pre_visit_expr(&mut self, expr: &Expr) -> ControlFlow<Self::Break> {
+        let Expr::Function(function) = expr else {
+            return ControlFlow::Continue(());
+        };
+        match name_parts(&function.name).as_slice() {
+            [name] if is_allowed_function(name) => ControlFlow::Continue(()),
+            _ => ControlFlow::Break(format!("Function {} is not allowed", function.name)),
+        }
+    }
+}
+
+/// Caps the number of returned rows at [`MAX_ROWS`].
+///
+/// Queries without a `LIMIT` get one appended. Queries with a larger or non-literal `LIMIT`, or
+/// with set operations, where an appended `LIMIT` would only apply to the last `SELECT`, are
+/// wrapped into an outer query with a `LIMIT`.
+fn enforce_row_limit(query: &str, ast: &Query) -> String {
+    let is_select = matches!(ast.body.as_ref(), SetExpr::Select(_));
+    let limit = match &ast.limit_clause {
+        None => None,
+        Some(LimitClause::LimitOffset {
+            limit, limit_by, ..
+        }) if limit_by.is_empty() => limit.as_ref(),
+        // A `LIMIT n BY` limits the rows per group, the total can still be limited
+        Some(LimitClause::LimitOffset { .. }) if is_select => {
+            return format!("{query} LIMIT {MAX_ROWS}");
+        }
+        Some(LimitClause::LimitOffset { .. }) => {
+            return format!("SELECT * FROM ({query}) LIMIT {MAX_ROWS}");
+        }
+        Some(LimitClause::OffsetCommaLimit { limit, .. }) => Some(limit),
+    };
+    match limit.map(|limit| limit.to_string().parse::<u64>()) {
+        None if is_select => format!("{query} LIMIT {MAX_ROWS}"),
+        Some(Ok(limit)) if limit <= MAX_ROWS => query.to_owned(),
+        _ => format!("SELECT * FROM ({query}) LIMIT {MAX_ROWS}"),
+    }
+}
+
+/// Tokenizes a query for [`round_trips`], ignoring whitespace and the case of unquoted words.
+fn normalized_tokens(query: &str) -> Result<Vec<Token>, String> {
+    let tokens = Tokenizer::new(&ClickHouseDialect {}, query)
+        .tokenize()
+        .map_err(|e| format!("Failed to parse query: {e}"))?;
+    let mut normalized = Vec::with_capacity(tokens.len());
+    for token in tokens {
+        match token {
+            Token::Whitespace(Whitespace::Space | Whitespace::Newline | Whitespace::Tab) => {}
+            Token::Whitespace(_) => return Err("Comments are not allowed".to_owned()),
+            Token::Word(mut word) => {
+                if word.quote_style.is_none() {
+                    word.value = word.value.to_lowercase();
+                }
+                word.keyword = Keyword::NoKeyword;
+                normalized.push(Token::Word(word));
+            }
+            token => normalized.push(token),
+        }
+    }
+    Ok(normalized)
+}
+
+/// Checks that the query consists of exactly the tokens of the rendered AST.
+///
+/// The query is executed as written, as `sqlparser` renders some `ClickHouse` types differently
+/// (e.g. `String` as `STRING`). Anything the parser dropped or interpreted differently, like
+/// comments, changes the tokens, so `ClickHouse` only executes what was validated.
+fn round_trips(query: &str, ast: &Query) -> Result<bool, String> {
+    Ok(normalized_tokens(query)? == normalized_tokens(&ast.to_string())?)
+}
+
+/// Validates that a user-supplied SQL query is safe to execute and returns the query to execute.
+///
+/// The query is parsed, all referenced tables have to be in `tables` and all called functions
+/// have to be allowed. The query has to round-trip through the parser and is executed as written,
+/// except for the row limit.
+pub(super) fn validate_query(query: &str, tables: &[String]) -> Result<String, String> {
+    if query.is_empty() {
+        return Err("Query cannot be empty".to_owned());
+    }
+
+    if let Some(parameter) = QUERY_PARAMETER_RE
+        .captures_iter(query)
+        .find(|parameter| !is_allowed_parameter_type(&parameter[2]))
+    {
+        return Err(format!(
+            "Parameter {} has unsupported type {}",
+            &parameter[1], &parameter[2]
+        ));
+    }
+
+    // sqlparser doesn't know query parameters, so they are parsed as placeholders
+    let mut index = 0;
+    let parseable = QUERY_PARAMETER_RE.replace_all(query, |_: &regex::Captures<'_>| {
+        index += 1;
+        format!("$__param{index}__")
+    });
+    let statements = Parser::parse_sql(&ClickHouseDialect {}, &parseable)
+        .map_err(|e| format!("Failed to parse query: {e}"))?;
+    let [Statement::Query(ast)] = statements.as_slice() else {
+        return Err("Only a single SELECT query is allowed".to_owned());
+    };
+
+    let mut validator = QueryValidator {
+        tables,
+        ctes: HashSet::new(),
+    };
+    if let ControlFlow::Break(error) = ast.visit(&mut validator) {
+        return Err(error);
+    }
+    if !round_trips(&parseable, ast)? {
+        return Err("Query contains syntax that cannot be validated".to_owned());
+    }
+    Ok(enforce_row_limit(query, ast))
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    fn tables() -> Vec<String> {
+        vec!["match_info".to_owned(), "match_player".to_owned()]
+    }
+
+    #[rstest]
+    #[case("SELECT 1", "SELECT 1 LIMIT 100000")]
+    #[case(
+        "SELECT match_id FROM match_info LIMIT 10",
+        "SELECT match_id FROM match_info LIMIT 10"
+    )]
+    #[case(
+        "SELECT match_id FROM match_info LIMIT 10 OFFSET 20",
+        "SELECT match_id FROM match_info LIMIT 10 OFFSET 20"
+    )]
+    #[case(
+        "SELECT match_id FROM match_info LIMIT 1000000",
+        "SELECT * FROM (SELECT match_id FROM match_info LIMIT 1000000) LIMIT 100000"
+    )]
+    #[case(
+        "SELECT match_id FROM match_info LIMIT 5, 1000000",
+        "SELECT * FROM (SELECT match_id FROM match_info LIMIT 5, 1000000) LIMIT 100000"
+    )]
+    #[case(
+        "SELECT hero_id, account_id FROM match_player LIMIT 1 BY hero_id",
+        "SELECT hero_id, account_id FROM match_player LIMIT 1 BY hero_id LIMIT 100000"
+    )]
+    #[case(
+        "SELECT 1 UNION ALL SELECT 2",
+        "SELECT * FROM (SELECT 1 UNION ALL SELECT 2) LIMIT 100000"
+    )]
+    #[case(
+        "SELECT hero_id FROM match_player WHERE account_id = {account_id:UInt32} LIMIT {limit:UInt32}",
+        "SELECT * FROM (SELECT hero_id FROM match_player WHERE account_id = {account_id:UInt32} LIMIT {limit:UInt32}) LIMIT 100000"
+    )]
+    #[case(
+        "WITH heroes AS (SELECT hero_id FROM default.match_player) SELECT countIf(hero_id > 1), toStartOfDay(now()), arrayMap(x -> x + 1, [1, 2]) FROM heroes LIMIT 1",
+        "WITH heroes AS (SELECT hero_id FROM default.match_player) SELECT countIf(hero_id > 1), toStartOfDay(now()), arrayMap(x -> x + 1, [1, 2]) FROM heroes LIMIT 1"
+    )]
+    #[case(
+        "SELECT uniqExactIf(account_id, hero_id = 1) FROM match_player WHERE match_id IN (SELECT match_id FROM match_info) LIMIT 1",
+        "SELECT uniqExactIf(account_id, hero_id = 1) FROM match_player WHERE match_id IN (SELECT match_id FROM match_info) LIMIT 1"
+    )]
+    #[case(
+        "select CAST(hero_id AS String), account_id::Int64 from match_player where hero_id != 1",
+        "select CAST(hero_id AS String), account_id::Int64 from match_player where hero_id != 1 LIMIT 100000"
+    )]
+    #[case(
+        "SELECT 'a--b', ';', '/* */' FROM match_info",
+        "SELECT 'a--b', ';', '/* */' FROM match_info LIMIT 100000"
+    )]
+    fn test_validate_query(#[case] query: &str, #[case] expected: &str) {
+        assert_eq!(validate_query(query, &tables()).unwrap(), expected);
+    }
+
+    #[rstest]
+    #[case("", "Query cannot be empty")]
+    #[case("DROP TABLE match_info", "Only a single SELECT query is allowed")]
+    #[case(
+        "INSERT INTO match_info (match_id) VALUES (1)",
+        "Only a single SELECT query is allowed"
+    )]
+    #[case(
+        "SELECT * FROM system.tables",
+        "Access to table system.tables is not allowed"
+    )]
+    #[case(
+        "SELECT * FROM other_db.match_info",
+        "Access to table other_db.match_info is not allowed"
+    )]
+    #[case(
+        "SELECT * FROM match_salts",
+        "Access to table match_salts is not allowed"
+    )]
+    #[case(
+        "SELECT * FROM match_info WHERE match_id IN (SELECT 1 FROM system.users)",
+        "Access to table system.users is not allowed"
+    )]
+    #[case(
+        "SELECT * FROM url('http://example.com', CSV)",
+        "Table function url is not allowed"
+    )]
+    #[case("SELECT * FROM numbers(10)", "Table function numbers is not allowed")]
+    #[case("SELECT file('/etc/passwd')", "Function file is not allowed")]
+    #[case("SELECT currentUser()", "Function currentUser is not allowed")]
+    #[case(
+        "SELECT count() FROM match_info SETTINGS max_threads = 100",
+        "SETTINGS clause is not allowed: SETTINGS max_threads = 100"
+    )]
+    #[case(
+        "SELECT match_id FROM match_info ARRAY JOIN players",
+        "ARRAY JOIN is not supported, use the arrayJoin function instead"
+    )]
+    #[case(
+        "SELECT match_id FROM match_info -- comment\nWHERE match_id = 1",
+        "Comments are not allowed"
+    )]
+    #[case(
+        "SELECT match_id /* comment */ FROM match_info",
+        "Comments are not allowed"
+    )]
+    #[case(
+        "SELECT match_id FROM match_info; DROP TABLE match_info",
+        "Only a single SELECT query is allowed"
+    )]
+    #[case(
+        "SELECT * FROM {table:Identifier}",
+        "Parameter table has unsupported type Identifier"
+    )]
+    #[case(
+        "SELECT {column: identifier} FROM match_info",
+        "Parameter column has unsupported type identifier"
+    )]
+    #[case(
+        "SELECT * FROM match_info WHERE match_id IN {ids:Array(Identifier)}",
+        "Parameter ids has unsupported type Array(Identifier)"
+    )]
+    fn test_validate_query_rejected(#[case] query: &str, #[case] expected: &str) {
+        assert_eq!(validate_query(query, &tables()).unwrap_err(), expected);
+    }
+
+    #[rstest]
+    #[case("SELECT 1 FORMAT JSON")]
+    #[case("SELECT 1 INTO OUTFILE 'file'")]
+    fn test_validate_query_unparseable(#[case] query: &str) {
+        assert!(
+            validate_query(query, &tables())
+                .unwrap_err()
+                .starts_with("Failed to parse query")
+        );
+    }
+
+    #[rstest]
+    #[case("UInt32", true)]
+    #[case("String", true)]
+    #[case("Array(UInt64)", true)]
+    #[case("Nullable(DateTime64(3, 'UTC'))", true)]
+    #[case("Decimal(10, 2)", true)]
+    #[case("Identifier", false)]
+    #[case("Array(Identifier)", false)]
+    #[case("Nullable(Identifier", false)]
+    fn test_is_allowed_parameter_type(#[case] r#type: &str, #[case] expected: bool) {
+        assert_eq!(is_allowed_parameter_type(r#type), expected);
+    }
+
+    #[rstest]
+    #[case("count", true)]
+    #[case("countIf", true)]
+    #[case("uniqExactIfMerge", true)]
+    #[case("toUInt32", true)]
+    #[case("arrayMap", true)]
+    #[case("If", true)]
+    #[case("file", false)]
+    #[case("url", false)]
+    #[case("currentUser", false)]
+    #[case("fileIf", false)]
+    fn test_is_allowed_function(#[case] name: &str, #[case] expected: bool) {
+        assert_eq!(is_allowed_function(name), expected);
+    }
+}
@@ -0,0 +1,490 @@
+use std::collections::{HashMap, HashSet};
+
+use itertools::Itertools;
+use uuid::Uuid;
+
+use crate::routes::v1::tournaments::types::Bracket;
+
+/// Where the team of a series slot comes from
+///
+/// Planned series refer to other series by their index, stored series by their id.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum SlotSource<T> {
+    /// The team is known from the start.
+    Team(Uuid),
+    /// The winner of another series.
+    Winner(T),
+    /// The loser of another series.
+    Loser(T),
+    /// The slot stays empty, the other team gets a bye.
+    Empty,
+}
+
+/// A series of a bracket before it is stored
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct SeriesPlan {
+    pub(super) bracket: Bracket,
+    pub(super) round: u32,
+    pub(super) position: u32,
+    pub(super) slots: [SlotSource<usize>; 2],
+}
+
+/// A stored series, with the recorded winner if it was played
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct SeriesNode {
+    pub(super) id: Uuid,
+    pub(super) slots: [SlotSource<Uuid>; 2],
+    pub(super) winner_id: Option<Uuid>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum SlotState {
+    Team(Uuid),
+    /// The series the team comes from is not decided yet.
+    Pending,
+    /// The slot stays empty.
+    Empty,
+}
+
+impl SlotState {
+    pub(super) fn team(self) -> Option<Uuid> {
+        match self {
+            Self::Team(team) => Some(team),
+            Self::Pending | Self::Empty => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Outcome {
+    /// At least one team is not known yet.
+    Waiting,
+    /// Both teams are known and the series can be played.
+    Ready,
+    /// The series was played, or skipped because of a bye.
+    Decided {
+        winner: Option<Uuid>,
+        loser: Option<Uuid>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct ResolvedSeries {
+    pub(super) teams: [SlotState; 2],
+    pub(super) outcome: Outcome,
+}
+
+/// Returns the seeds of the first round slots of a bracket, pairing the best seeds with the worst
+/// ones, so the best seeds meet last.
+fn seed_order(size: usize) -> Vec<usize> {
+    let mut order = vec![0];
+    while order.len() < size {
+        let len = order.len() * 2;
+        order = order
+            .iter()
+            .flat_map(|&seed| [seed, len - 1 - seed])
+            .collect();
+    }
+    order
+}
+
+/// Appends a round to the plan and returns the indices of its series.
+fn push_round(
+    plan: &mut Vec<SeriesPlan>,
+    bracket: Bracket,
+    round: u32,
+    slots: impl IntoIterator<Item = [SlotSource<usize>; 2]>,
+) -> Vec<usize> {
+    (0..)
+        .zip(slots)
+        .map(|(position, slots)| {
+            plan.push(SeriesPlan {
+                bracket,
+                round,
+                position,
+                slots,
+            });
+            plan.len() - 1
+        })
+        .collect()
+}
+
+/// Plans the winners bracket and returns the series of every round.
+///
+/// The bracket is filled up to a power of two with empty slots, which are given to the best seeds.
+fn winners_bracket(teams: &[Uuid], plan: &mut Vec<SeriesPlan>) -> Vec<Vec<usize>> {
+    let team = |seed: usize| {
+        teams
+            .get(seed)
+            .map_or(SlotSource::Empty, |&t| SlotSource::Team(t))
+    };
+    let first_round = seed_order(teams.len().next_power_of_two())
+        .chunks(2)
+        .map(|pair| [team(pair[0]), team(pair[1])])
+        .collect_vec();
+    let mut rounds = vec![push_round(plan, Bracket::Winners, 1, first_round)];
+    let mut round = 1;
+    while let Some(previous) = rounds.last().filter(|r| r.len() > 1) {
+        round += 1;
+        let slots = previous
+            .chunks(2)
+            .map(|pair| [SlotSource::Winner(pair[0]), SlotSource::Winner(pair[1])])
+            .collect_vec();
+        rounds.push(push_round(plan, Bracket::Winners, round, slots));
+    }
+    rounds
+}
+
+/// Plans a single elimination bracket for the teams, ordered by their seed.
+pub(super) fn single_elimination(teams: &[Uuid]) -> Vec<SeriesPlan> {
+    let mut plan = vec![];
+    winners_bracket(teams, &mut plan);
+    plan
+}
+
+/// Plans a double elimination bracket for the teams, ordered by their seed.
+///
+/// The losers bracket alternates between rounds in which its survivors meet the teams dropping
+/// from the winners bracket, and rounds in which the survivors play each other. The grand final is
+/// a single series between the winners of both brackets.
+pub(super) fn double_elimination(teams: &[Uuid]) -> Vec<SeriesPlan> {
+    let mut plan = vec![];
+    let winners = winners_bracket(teams, &mut plan);
+    let mut losers: Vec<Vec<usize>> = vec![];
+    if let Some(first_round) = winners.first().filter(|_| winners.len() > 1) {
+        let slots = first_round
+            .chunks(2)
+            .map(|pair| [SlotSource::Loser(pair[0]), SlotSource::Loser(pair[1])])
+            .collect_vec();
+        losers.push(push_round(&mut plan, Bracket::Losers, 1, slots));
+        let mut round = 1;
+        for (i, dropping) in winners.iter().enumerate().skip(1) {
+            let survivors = losers.last().map(Vec::as_slice).unwrap_or_default();
+            // Drop the losers in reverse order every other round, to avoid immediate rematches
+            let dropping = if i % 2 == 1 {
+                dropping.iter().rev().copied().collect_vec()
+            } else {
+                dropping.clone()
+            };
+            let slots = survivors
+                .iter()
+                .zip(dropping)
+                .map(|(&survivor, loser)| [SlotSource::Winner(survivor), SlotSource::Loser(loser)])
+                .collect_vec();
+            round += 1;
+            losers.push(push_round(&mut plan, Bracket::Losers, round, slots));
+
+            let survivors = losers.last().map(Vec::as_slice).unwrap_or_default();
+            if survivors.len() > 1 {
+                let slots = survivors
+                    .chunks(2)
+                    .map(|pair| [SlotSource::Winner(pair[0]), SlotSource::Winner(pair[1])])
+                    .collect_vec();
+                round += 1;
+                losers.push(push_round(&mut plan, Bracket::Losers, round, slots));
+            }
+        }
+    }
+
+    let winners_final = winners.last().and_then(|r| r.first()).copied();
+    let losers_final = losers.last().and_then(|r| r.first()).copied();
+    if let Some(winners_final) = winners_final {
+        // Without a losers bracket the loser of the winners final gets a second chance right away
+        let challenger = losers_final.map_or(SlotSource::Loser(winners_final), SlotSource::Winner);
+        push_round(
+            &mut plan,
+            Bracket::GrandFinal,
+            1,
+            [[SlotSource::Winner(winners_final), challenger]],
+        );
+    }
+    plan
+}
+
+/// Plans the first Swiss round, pairing the top half of the seeds with the bottom half.
+///
+/// With an odd number of teams, the lowest seed gets a bye.
+pub(super) fn swiss_first_round(teams: &[Uuid]) -> Vec<SeriesPlan> {
+    let (top, bottom) = teams.split_at(teams.len() / 2);
+    let mut slots = top
+        .iter()
+        .zip(bottom)
+        .map(|(&a, &b)| [SlotSource::Team(a), SlotSource::Team(b)])
+        .collect_vec();
+    if let Some(&bye) = bottom.get(top.len()) {
+        slots.push([SlotSource::Team(bye), SlotSource::Empty]);
+    }
+    let mut plan = vec![];
+    push_round(&mut plan, Bracket::Swiss, 1, slots);
+    plan
+}
+
+/// Plans a following Swiss round for the teams, ordered by their standing.
+///
+/// Every team is paired with the best ranked team it did not play yet. With an odd number of
+/// teams, the lowest ranked team that did not have a bye yet gets one.
+pub(super) fn swiss_round(
+    round: u32,
+    ranked: &[Uuid],
+    played: &HashSet<(Uuid, Uuid)>,
+    byes: &HashSet<Uuid>,
+) -> Vec<SeriesPlan> {
+    let mut remaining = ranked.to_vec();
+    let bye = (remaining.len() % 2 == 1).then(|| {
+        let index = remaining
+            .iter()
+            .rposition(|t| !byes.contains(t))
+            .unwrap_or(remaining.len() - 1);
+        remaining.remove(index)
+    });
+    let mut slots = vec![];
+    while !remaining.is_empty() {
+        let team = remaining.remove(0);
+        let opponent = remaining
+            .iter()
+            .position(|&o| !played.contains(&(team, o)) && !played.contains(&(o, team)))
+            .unwrap_or(0);
+        let opponent = remaining.remove(opponent);
+        slots.push([SlotSource::Team(team), SlotSource::Team(opponent)]);
+    }
+    if let Some(bye) = bye {
+        slots.push([SlotSource::Team(bye), SlotSource::Empty]);
+    }
+    let mut plan = vec![];
+    push_round(&mut plan, Bracket::Swiss, round, slots);
+    plan
+}
+
+fn resolve_slot(
+    source: SlotSource<Uuid>,
+    resolved: &HashMap<Uuid, ResolvedSeries>,
+) -> Option<SlotState> {
+    let decided = |id: Uuid, takes_winner: bool| {
+        resolved.get(&id).map(|r| match r.outcome {
+            Outcome::Decided {
+                winner: Some(team), ..
+            } if takes_winner => SlotState::Team(team),
+            Outcome::Decided {
+                loser: Some(team), ..
+            } if !takes_winner => SlotState::Team(team),
+            Outcome::Decided { .. } => SlotState::Empty,
+            Outcome::Waiting | Outcome::Ready => SlotState::Pending,
+        })
+    };
+    match source {
+        SlotSource::Team(team) => Some(SlotState::Team(team)),
+        SlotSource::Empty => Some(SlotState::Empty),
+        SlotSource::Winner(id) => decided(id, true),
+        SlotSource::Loser(id) => decided(id, false),
+    }
+}
+
+/// Resolves the teams and outcome of every series, advancing teams with a bye.
+///
+/// Series whose sources are missing are left out.
+pub(super) fn resolve(series: &[SeriesNode]) -> HashMap<Uuid, ResolvedSeries> {
+    let mut resolved = HashMap::with_capacity(series.len());
+    let mut progress = true;
+    while progress {
+        progress = false;
+        for node in series {
+            if resolved.contains_key(&node.id) {
+                continue;
+            }
+            let (Some(first), Some(second)) = (
+                resolve_slot(node.slots[0], &resolved),
+                resolve_slot(node.slots[1], &resolved),
+            ) else {
+                continue;
+            };
+            let outcome = match (first, second) {
+                (SlotState::Team(a), SlotState::Team(b)) => match node.winner_id {
+                    Some(winner) => Outcome::Decided {
+                        winner: Some(winner),
+                        loser: Some(if winner == a { b } else { a }),
+                    },
+                    None => Outcome::Ready,
+                },
+                (SlotState::Team(team), SlotState::Empty)
+                | (SlotState::Empty, SlotState::Team(team)) => Outcome::Decided {
+                    winner: Some(team),
+                    loser: None,
+                },
+                (SlotState::Empty, SlotState::Empty) => Outcome::Decided {
+                    winner: None,
+                    loser: None,
+                },
+                _ => Outcome::Waiting,
+            };
+            resolved.insert(
+                node.id,
+                ResolvedSeries {
+                    teams: [first, second],
+                    outcome,
+                },
+            );
+            progress = true;
+        }
+    }
+    resolved
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    fn teams(count: usize) -> Vec<Uuid> {
+        (0..count).map(|_| Uuid::new_v4()).collect()
+    }
+
+    /// Stores a plan, assigning an id to every series.
+    fn store(plan: &[SeriesPlan]) -> Vec<SeriesNode> {
+        let ids = plan.iter().map(|_| Uuid::new_v4()).collect_vec();
+        let source = |s: SlotSource<usize>| match s {
+            SlotSource::Team(team) => SlotSource::Team(team),
+            SlotSource::Winner(i) => SlotSource::Winner(ids[i]),
+            SlotSource::Loser(i) => SlotSource::Loser(ids[i]),
+            SlotSource::Empty => SlotSource::Empty,
+        };
+        plan.iter()
+            .zip(&ids)
+            .map(|(p, &id)| SeriesNode {
+                id,
+                slots: p.slots.map(source),
+                winner_id: None,
+            })
+            .collect()
+    }
+
+    /// Plays every ready series, letting the better seed win, until the bracket is decided.
+    fn play_out(series: &mut [SeriesNode], teams: &[Uuid]) -> HashMap<Uuid, ResolvedSeries> {
+        let seed = |team: &Uuid| teams.iter().position(|t| t == team);
+        loop {
+            let resolved = resolve(series);
+            let mut played = false;
+            for node in series.iter_mut() {
+                let r = resolved[&node.id];
+                if r.outcome == Outcome::Ready {
+                    node.winner_id = r.teams.iter().filter_map(|t| t.team()).min_by_key(seed);
+                    played = true;
+                }
+            }
+            if !played {
+                return resolved;
+            }
+        }
+    }
+
+    #[test]
+    fn test_seed_order() {
+        assert_eq!(seed_order(1), vec![0]);
+        assert_eq!(seed_order(4), vec![0, 3, 1, 2]);
+        assert_eq!(seed_order(8), vec![0, 7, 3, 4, 1, 6, 2, 5]);
+    }
+
+    #[rstest]
+    #[case(2, 1)]
+    #[case(4, 3)]
+    #[case(5, 7)]
+    #[case(8, 7)]
+    fn test_single_elimination_size(#[case] count: usize, #[case] expected: usize) {
+        assert_eq!(single_elimination(&teams(count)).len(), expected);
+    }
+
+    #[rstest]
+    #[case(2, 2)]
+    #[case(4, 6)]
+    #[case(8, 14)]
+    #[case(16, 30)]
+    fn test_double_elimination_size(#[case] count: usize, #[case] expected: usize) {
+        assert_eq!(double_elimination(&teams(count)).len(), expected);
+    }
+
+    #[test]
+    fn test_single_elimination_byes() {
+        let teams = teams(3);
+        let series = store(&single_elimination(&teams));
+        let resolved = resolve(&series);
+        // The top seed gets a bye and waits in the final
+        assert_eq!(
+            resolved[&series[0].id].outcome,
+            Outcome::Decided {
+                winner: Some(teams[0]),
+                loser: None
+            }
+        );
+        assert_eq!(resolved[&series[1].id].outcome, Outcome::Ready);
+        assert_eq!(
+            resolved[&series[2].id].teams,
+            [SlotState::Team(teams[0]), SlotState::Pending]
+        );
+    }
+
+    #[rstest]
+    #[case(2)]
+    #[case(3)]
+    #[case(5)]
+    #[case(8)]
+    #[case(13)]
+    fn test_double_elimination_plays_out(#[case] count: usize) {
+        let teams = teams(count);
+        let mut series = store(&double_elimination(&teams));
+        let resolved = play_out(&mut series, &teams);
+        assert!(
+            resolved
+                .values()
+                .all(|r| matches!(r.outcome, Outcome::Decided { .. }))
+        );
+        // The top seed wins every series, the second seed reaches the grand final
+        let grand_final = resolved[&series.last().expect("grand final").id];
+        assert_eq!(
+            grand_final.teams,
+            [SlotState::Team(teams[0]), SlotState::Team(teams[1])]
+        );
+        // Every team but the champion loses exactly twice
+        let losses = resolved
+            .values()
+            .filter_map(|r| match r.outcome {
+                Outcome::Decided { loser, .. } => loser,
+                _ => None,
+            })
+            .counts();
+        assert_eq!(losses.get(&teams[0]), None);
+        assert!(teams[1..].iter().all(|t| losses.get(t) == Some(&2)));
+    }
+
+    #[test]
+    fn test_swiss_first_round() {
+        let teams = teams(5);
+        let plan = swiss_first_round(&teams);
+        let slots = plan.iter().map(|p| p.slots).collect_vec();
+        assert_eq!(
+            slots,
+            vec![
+                [SlotSource::Team(teams[0]), SlotSource::Team(teams[2])],
+                [SlotSource::Team(teams[1]), SlotSource::Team(teams[3])],
+                [SlotSource::Team(teams[4]), SlotSource::Empty],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_swiss_round_avoids_rematches_and_repeated_byes() {
+        let teams = teams(5);
+        let played = HashSet::from([(teams[0], teams[1]), (teams[2], teams[3])]);
+        let byes = HashSet::from([teams[4]]);
+        let plan = swiss_round(2, &teams, &played, &byes);
+        let slots = plan.iter().map(|p| p.slots).collect_vec();
+        assert_eq!(
+            slots,
+            vec![
+                [SlotSource::Team(teams[0]), SlotSource::Team(teams[2])],
+                [SlotSource::Team(teams[1]), SlotSource::Team(teams[4])],
+                [SlotSource::Team(teams[3]), SlotSource::Empty],
+            ]
+        );
+        assert!(plan.iter().all(|p| p.round == 2));
+    }
+}
@@ -0,0 +1,255 @@
+use core::time::Duration;
+
+use axum::Json;
+use axum::extract::rejection::JsonRejection;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use sqlx::{Pool, Postgres};
+use tracing::{error, warn};
+use uuid::Uuid;
+
+use crate::context::AppState;
+use crate::error::{APIError, APIResult};
+use crate::routes::v1::matches::custom::create::{
+    CreateCustomRequest, CreateCustomResponse, create_lobby, discard_lobby,
+};
+use crate::routes::v1::tournaments::bracket::Outcome;
+use crate::routes::v1::tournaments::repository::{fetch_series, lock_tournament, resolve_series};
+use crate::routes::v1::tournaments::types::{TournamentSeriesPath, TournamentStatus};
+use crate::services::rate_limiter::extractor::RateLimitKey;
+use crate::services::rate_limiter::{ApiKeyScope, Quota};
+
+/// Time a series stays reserved while its lobby is created, in case the request never ends it.
+const LOBBY_RESERVATION_SECS: f64 = 5. * 60.;
+
+#[utoipa::path(
+    post,
+    path = "/{tournament_id}/series/{series_id}/lobby",
+    params(TournamentSeriesPath),
+    request_body = CreateCustomRequest,
+    responses(
+        (status = 200, description = "Successfully created the lobby.", body = CreateCustomResponse),
+        (status = BAD_REQUEST, description = "Provided parameters are invalid."),
+        (status = FORBIDDEN, description = "API key is missing the `custom_matches` scope"),
+        (status = NOT_FOUND, description = "Tournament or series not found"),
+        (status = CONFLICT, description = "The series can't be played or a lobby of it is still active"),
+        (status = TOO_MANY_REQUESTS, description = "Rate limit exceeded"),
+        (status = INTERNAL_SERVER_ERROR, description = "Creating the lobby failed")
+    ),
+    security(("api_key_header" = ["custom_matches"]), ("api_key_query" = ["custom_matches"])),
+    tags = ["Tournaments"],
+    summary = "Create Series Lobby",
+    description = "
+This endpoint creates a custom match lobby for the next game of a series, the same way as `POST /v1/matches/custom/create` and with the same settings.
+
+The lobby is managed with the custom match endpoints: players join with the party code, and the match is started with the ready and start endpoints.
+Once the metadata of the match is available, the winner of the game is recorded and the bracket is updated.
+The winning team is identified by its players, so players have to play on the same side as the rest of their team.
+
+A new lobby can only be created once the previous lobby of the series finished or expired.
+
+Requires an API key with the `custom_matches` scope.
+
+### Rate Limits:
+| Type | Limit |
+| ---- | ----- |
+| IP | API-Key ONLY |
+| Key | 100req/30min |
+| Global | 1000req/h |
+"
+)]
+pub(super) async fn create_series_lobby(
+    Path(TournamentSeriesPath {
+        tournament_id,
+        series_id,
+    }): Path<TournamentSeriesPath>,
+    rate_limit_key: RateLimitKey,
+    State(mut state): State<AppState>,
+    payload: Result<Json<CreateCustomRequest>, JsonRejection>,
+) -> APIResult<impl IntoResponse> {
+    state
+        .rate_limit_client
+        .require_scope(&rate_limit_key, ApiKeyScope::CustomMatches)
+        .await?;
+    state
+        .rate_limit_client
+        .apply_limits(
+            &rate_limit_key,
+            "tournament_lobby",
+            &[
+                Quota::key_limit(100, Duration::from_mins(30)),
+                Quota::global_limit(1000, Duration::from_hours(1)),
+            ],
+        )
+        .await?;
+    let payload = match payload {
+        Ok(Json(p)) => Some(p),
+        Err(JsonRejection::MissingJsonContentType(_)) => None,
+        Err(rejection) => {
+            return Err(APIError::status_msg(
+                rejection.status(),
+                format!("Invalid request body: {rejection}"),
+            ));
+        }
+    };
+
+    // The lobby is created outside of the lock of the tournament, as it takes a while and would
+    // block recording results. The reservation keeps other lobbies of the series out meanwhile.
+    reserve_series(
+        &state.pg_client,
+        tournament_id,
+        series_id,
+        rate_limit_key.api_key,
+    )
+    .await?;
+    let lobby = match create_lobby(&mut state, rate_limit_key.api_key, payload).await {
+        Ok(lobby) => lobby,
+        Err(e) => {
+            release_series(&state.pg_client, series_id).await;
+            return Err(e);
+        }
+    };
+    if let Err(e) = store_game(&state.pg_client, lobby.party_id, series_id).await {
+        error!("Failed to store game of tournament series {series_id}: {e}");
+        if let Err(e) = discard_lobby(&mut state, lobby.party_id).await {
+            error!("Failed to discard lobby {}: {e}", lobby.party_id);
+        }
+        release_series(&state.pg_client, series_id).await;
+        return Err(e.into());
+    }
+    Ok(Json(CreateCustomResponse::from(lobby)))
+}
+
+/// Checks that a lobby can be created for the series, and reserves the series while it is
+/// created.
+async fn reserve_series(
+    pg_client: &Pool<Postgres>,
+    tournament_id: Uuid,
+    series_id: Uuid,
+    api_key: Option<Uuid>,
+) -> APIResult<()> {
+    let mut transaction = pg_client.begin().await?;
+    let tournament = lock_tournament(&mut *transaction, tournament_id, api_key)
+        .await?
+        .ok_or_else(|| {
+            APIError::status_msg(
+                StatusCode::NOT_FOUND,
+                format!("Tournament {tournament_id} not found"),
+            )
+        })?;
+    if tournament.status != TournamentStatus::Running.to_string() {
+        return Err(APIError::status_msg(
+            StatusCode::CONFLICT,
+            format!("Tournament {tournament_id} is {}", tournament.status),
+        ));
+    }
+    let series = fetch_series(&mut *transaction, tournament_id).await?;
+    let outcome = resolve_series(&series)
+        .get(&series_id)
+        .map(|r| r.outcome)
+        .ok_or_else(|| {
+            APIError::status_msg(
+                StatusCode::NOT_FOUND,
+                format!("Series {series_id} not found"),
+            )
+        })?;
+    match outcome {
+        Outcome::Ready => {}
+        Outcome::Waiting => {
+            return Err(APIError::status_msg(
+                StatusCode::CONFLICT,
+                format!("The teams of series {series_id} are not known yet"),
+            ));
+        }
+        Outcome::Decided { .. } => {
+            return Err(APIError::status_msg(
+                StatusCode::CONFLICT,
+                format!("Series {series_id} is already finished"),
+            ));
+        }
+    }
+    let active = sqlx::query_scalar!(
+        r#"
+        SELECT EXISTS (
+            SELECT 1
+            FROM tournament_games g
+            JOIN custom_matches c ON c.party_id = g.party_id
+            WHERE g.series_id = $1 AND c.status IN ('created', 'filled', 'started')
+        ) OR EXISTS (
+            SELECT 1
+            FROM tournament_series
+            WHERE id = $1 AND lobby_reserved_until > now()
+        ) AS "active!"
+        "#,
+        series_id
+    )
+    .fetch_one(&mut *transaction)
+    .await?;
+    if active {
+        return Err(APIError::status_msg(
+            StatusCode::CONFLICT,
+            format!("A lobby of series {series_id} is still active"),
+        ));
+    }
+    sqlx::query!(
+        r#"
+        UPDATE tournament_series
+        SET lobby_reserved_until = now() + make_interval(secs => $2)
+        WHERE id = $1
+        "#,
+        series_id,
+        LOBBY_RESERVATION_SECS
+    )
+    .execute(&mut *transaction)
+    .await?;
+    transaction.commit().await?;
+    Ok(())
+}
+
+/// Stores the created lobby as the next game of the series, and ends its reservation.
+async fn store_game(
+    pg_client: &Pool<Postgres>,
+    party_id: u64,
+    series_id: Uuid,
+) -> sqlx::Result<()> {
+    let mut transaction = pg_client.begin().await?;
+    sqlx::query!(
+        r#"
+        INSERT INTO tournament_games (party_id, series_id)
+        VALUES ($1, $2)
+        "#,
+        party_id.cast_signed(),
+        series_id
+    )
+    .execute(&mut *transaction)
+    .await?;
+    sqlx::query!(
+        r#"
+        UPDATE tournament_series
+        SET lobby_reserved_until = NULL
+        WHERE id = $1
+        "#,
+        series_id
+    )
+    .execute(&mut *transaction)
+    .await?;
+    transaction.commit().await
+}
+
+/// Ends the reservation of a series whose lobby could not be created.
+async fn release_series(pg_client: &Pool<Postgres>, series_id: Uuid) {
+    let result = sqlx::query!(
+        r#"
+        UPDATE tournament_series
+        SET lobby_reserved_until = NULL
+        WHERE id = $1
+        "#,
+        series_id
+    )
+    .execute(pg_client)
+    .await;
+    if let Err(e) = result {
+        warn!("Failed to release the lobby reservation of series {series_id}: {e}");
+    }
+}
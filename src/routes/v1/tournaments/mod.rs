@@ -0,0 +1,29 @@
+mod bracket;
+mod lobbies;
+mod repository;
+pub mod results;
+mod route;
+mod standings;
+mod types;
+
+use utoipa::OpenApi;
+use utoipa_axum::router::OpenApiRouter;
+use utoipa_axum::routes;
+
+use crate::context::AppState;
+
+#[derive(OpenApi)]
+#[openapi(tags((name = "Tournaments", description = "
+Endpoints for running tournaments on top of custom matches.
+Tournaments are single elimination, double elimination or Swiss brackets of teams, whose series are played in custom match lobbies.
+Winners are recorded automatically from the match metadata, and the standings of every tournament are available.
+")))]
+struct ApiDoc;
+
+pub(super) fn router() -> OpenApiRouter<AppState> {
+    OpenApiRouter::with_openapi(ApiDoc::openapi())
+        .routes(routes!(route::list_tournaments, route::create_tournament))
+        .routes(routes!(route::get_tournament))
+        .routes(routes!(standings::get_standings))
+        .routes(routes!(lobbies::create_series_lobby))
+}
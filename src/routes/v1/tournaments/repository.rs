@@ -0,0 +1,278 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use sqlx::PgConnection;
+use uuid::Uuid;
+
+use crate::routes::v1::tournaments::bracket::{
+    ResolvedSeries, SeriesNode, SeriesPlan, SlotSource, resolve,
+};
+use crate::routes::v1::tournaments::types::{
+    Bracket, Tournament, TournamentFormat, TournamentGame, TournamentStatus, TournamentTeam,
+};
+
+pub(super) struct TournamentRow {
+    pub(super) id: Uuid,
+    pub(super) name: String,
+    pub(super) format: String,
+    pub(super) best_of: i32,
+    pub(super) swiss_rounds: Option<i32>,
+    pub(super) status: String,
+    pub(super) created_at: DateTime<Utc>,
+    pub(super) finished_at: Option<DateTime<Utc>>,
+}
+
+impl TournamentRow {
+    pub(super) fn format(&self) -> TournamentFormat {
+        self.format
+            .parse()
+            .unwrap_or(TournamentFormat::SingleElimination)
+    }
+}
+
+impl From<TournamentRow> for Tournament {
+    fn from(row: TournamentRow) -> Self {
+        Self {
+            id: row.id,
+            format: row.format(),
+            name: row.name,
+            best_of: row.best_of.cast_unsigned(),
+            swiss_rounds: row.swiss_rounds.map(i32::cast_unsigned),
+            status: row.status.parse().unwrap_or(TournamentStatus::Running),
+            created_at: row.created_at,
+            finished_at: row.finished_at,
+        }
+    }
+}
+
+pub(super) struct TeamRow {
+    pub(super) id: Uuid,
+    pub(super) seed: i32,
+    pub(super) name: String,
+    pub(super) account_ids: Vec<i64>,
+}
+
+impl TeamRow {
+    pub(super) fn account_ids(&self) -> impl Iterator<Item = u32> {
+        self.account_ids
+            .iter()
+            .filter_map(|&a| u32::try_from(a).ok())
+    }
+}
+
+impl From<TeamRow> for TournamentTeam {
+    fn from(row: TeamRow) -> Self {
+        Self {
+            id: row.id,
+            seed: row.seed.cast_unsigned(),
+            account_ids: row.account_ids().collect(),
+            name: row.name,
+        }
+    }
+}
+
+pub(super) struct SeriesRow {
+    pub(super) id: Uuid,
+    pub(super) bracket: String,
+    pub(super) round: i32,
+    pub(super) position: i32,
+    pub(super) team1_id: Option<Uuid>,
+    pub(super) team1_source_id: Option<Uuid>,
+    pub(super) team1_source_loser: bool,
+    pub(super) team2_id: Option<Uuid>,
+    pub(super) team2_source_id: Option<Uuid>,
+    pub(super) team2_source_loser: bool,
+    pub(super) team1_wins: i32,
+    pub(super) team2_wins: i32,
+    pub(super) winner_id: Option<Uuid>,
+}
+
+fn slot_source(team_id: Option<Uuid>, source_id: Option<Uuid>, loser: bool) -> SlotSource<Uuid> {
+    match (team_id, source_id) {
+        (Some(team_id), _) => SlotSource::Team(team_id),
+        (None, Some(source_id)) if loser => SlotSource::Loser(source_id),
+        (None, Some(source_id)) => SlotSource::Winner(source_id),
+        (None, None) => SlotSource::Empty,
+    }
+}
+
+impl SeriesRow {
+    pub(super) fn bracket(&self) -> Bracket {
+        self.bracket.parse().unwrap_or(Bracket::Winners)
+    }
+
+    pub(super) fn node(&self) -> SeriesNode {
+        SeriesNode {
+            id: self.id,
+            slots: [
+                slot_source(self.team1_id, self.team1_source_id, self.team1_source_loser),
+                slot_source(self.team2_id, self.team2_source_id, self.team2_source_loser),
+            ],
+            winner_id: self.winner_id,
+        }
+    }
+}
+
+/// Resolves the teams and outcomes of the series of a tournament.
+pub(super) fn resolve_series(series: &[SeriesRow]) -> HashMap<Uuid, ResolvedSeries> {
+    resolve(&series.iter().map(SeriesRow::node).collect::<Vec<_>>())
+}
+
+pub(super) struct GameRow {
+    pub(super) party_id: i64,
+    pub(super) series_id: Uuid,
+    pub(super) match_id: Option<i64>,
+    pub(super) winner_id: Option<Uuid>,
+    pub(super) created_at: DateTime<Utc>,
+    pub(super) finished_at: Option<DateTime<Utc>>,
+}
+
+impl From<GameRow> for TournamentGame {
+    fn from(row: GameRow) -> Self {
+        Self {
+            party_id: row.party_id.cast_unsigned().to_string(),
+            match_id: row.match_id.map(i64::cast_unsigned),
+            winner_id: row.winner_id,
+            created_at: row.created_at,
+            finished_at: row.finished_at,
+        }
+    }
+}
+
+/// Fetches a tournament, of the API key if one is given.
+pub(super) async fn fetch_tournament(
+    conn: &mut PgConnection,
+    id: Uuid,
+    api_key: Option<Uuid>,
+) -> sqlx::Result<Option<TournamentRow>> {
+    sqlx::query_as!(
+        TournamentRow,
+        r#"
+        SELECT id, name, format, best_of, swiss_rounds, status, created_at, finished_at
+        FROM tournaments
+        WHERE id = $1 AND ($2::uuid IS NULL OR api_key = $2)
+        "#,
+        id,
+        api_key
+    )
+    .fetch_optional(conn)
+    .await
+}
+
+/// Fetches a tournament and locks it, so its bracket is only updated by one transaction at a time.
+pub(super) async fn lock_tournament(
+    conn: &mut PgConnection,
+    id: Uuid,
+    api_key: Option<Uuid>,
+) -> sqlx::Result<Option<TournamentRow>> {
+    sqlx::query_as!(
+        TournamentRow,
+        r#"
+        SELECT id, name, format, best_of, swiss_rounds, status, created_at, finished_at
+        FROM tournaments
+        WHERE id = $1 AND ($2::uuid IS NULL OR api_key = $2)
+        FOR UPDATE
+        "#,
+        id,
+        api_key
+    )
+    .fetch_optional(conn)
+    .await
+}
+
+pub(super) async fn fetch_teams(
+    conn: &mut PgConnection,
+    tournament_id: Uuid,
+) -> sqlx::Result<Vec<TeamRow>> {
+    sqlx::query_as!(
+        TeamRow,
+        r#"
+        SELECT id, seed, name, account_ids
+        FROM tournament_teams
+        WHERE tournament_id = $1
+        ORDER BY seed
+        "#,
+        tournament_id
+    )
+    .fetch_all(conn)
+    .await
+}
+
+pub(super) async fn fetch_series(
+    conn: &mut PgConnection,
+    tournament_id: Uuid,
+) -> sqlx::Result<Vec<SeriesRow>> {
+    sqlx::query_as!(
+        SeriesRow,
+        r#"
+        SELECT id, bracket, round, position, team1_id, team1_source_id, team1_source_loser,
+               team2_id, team2_source_id, team2_source_loser, team1_wins, team2_wins, winner_id
+        FROM tournament_series
+        WHERE tournament_id = $1
+        ORDER BY bracket = 'grand_final', bracket = 'losers', round, position
+        "#,
+        tournament_id
+    )
+    .fetch_all(conn)
+    .await
+}
+
+pub(super) async fn fetch_games(
+    conn: &mut PgConnection,
+    tournament_id: Uuid,
+) -> sqlx::Result<Vec<GameRow>> {
+    sqlx::query_as!(
+        GameRow,
+        r#"
+        SELECT g.party_id, g.series_id, g.match_id, g.winner_id, g.created_at, g.finished_at
+        FROM tournament_games g
+        JOIN tournament_series s ON s.id = g.series_id
+        WHERE s.tournament_id = $1
+        ORDER BY g.created_at
+        "#,
+        tournament_id
+    )
+    .fetch_all(conn)
+    .await
+}
+
+/// Stores the planned series, assigning ids to them in order.
+pub(super) async fn insert_series(
+    conn: &mut PgConnection,
+    tournament_id: Uuid,
+    plan: &[SeriesPlan],
+) -> sqlx::Result<()> {
+    let ids = plan.iter().map(|_| Uuid::new_v4()).collect::<Vec<_>>();
+    let columns = |source: SlotSource<usize>| match source {
+        SlotSource::Team(team_id) => (Some(team_id), None, false),
+        SlotSource::Winner(i) => (None, Some(ids[i]), false),
+        SlotSource::Loser(i) => (None, Some(ids[i]), true),
+        SlotSource::Empty => (None, None, false),
+    };
+    for (series, &id) in plan.iter().zip(&ids) {
+        let (team1_id, team1_source_id, team1_source_loser) = columns(series.slots[0]);
+        let (team2_id, team2_source_id, team2_source_loser) = columns(series.slots[1]);
+        sqlx::query!(
+            r#"
+            INSERT INTO tournament_series (id, tournament_id, bracket, round, position, team1_id,
+                                           team1_source_id, team1_source_loser, team2_id,
+                                           team2_source_id, team2_source_loser)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+            "#,
+            id,
+            tournament_id,
+            series.bracket.to_string(),
+            series.round.cast_signed(),
+            series.position.cast_signed(),
+            team1_id,
+            team1_source_id,
+            team1_source_loser,
+            team2_id,
+            team2_source_id,
+            team2_source_loser
+        )
+        .execute(&mut *conn)
+        .await?;
+    }
+    Ok(())
+}
@@ -0,0 +1,271 @@
+use core::cmp::Ordering;
+use std::collections::HashSet;
+
+use itertools::Itertools;
+use sqlx::PgConnection;
+use tracing::{info, warn};
+use uuid::Uuid;
+use valveprotos::deadlock::CMsgMatchMetaDataContents;
+use valveprotos::deadlock::c_msg_match_meta_data_contents::EMatchOutcome;
+
+use crate::routes::v1::tournaments::bracket::{Outcome, swiss_round};
+use crate::routes::v1::tournaments::repository::{
+    SeriesRow, TeamRow, TournamentRow, fetch_series, fetch_teams, insert_series, lock_tournament,
+    resolve_series,
+};
+use crate::routes::v1::tournaments::standings::standings;
+use crate::routes::v1::tournaments::types::TournamentFormat;
+
+struct PendingGame {
+    series_id: Uuid,
+    tournament_id: Uuid,
+}
+
+/// Returns which of the two teams won the match, by the players on the winning side.
+///
+/// Draws, and matches in which neither team has more players on the winning side, have no winner.
+fn winning_team(metadata: &CMsgMatchMetaDataContents, teams: [&HashSet<u32>; 2]) -> Option<usize> {
+    let info = metadata.match_info.as_ref()?;
+    if info.match_outcome() != EMatchOutcome::KEOutcomeTeamWin {
+        return None;
+    }
+    let winners = info
+        .players
+        .iter()
+        .filter(|p| p.team() == info.winning_team())
+        .map(|p| p.account_id())
+        .collect_vec();
+    let players = |team: &HashSet<u32>| winners.iter().filter(|a| team.contains(a)).count();
+    match players(teams[0]).cmp(&players(teams[1])) {
+        Ordering::Greater => Some(0),
+        Ordering::Less => Some(1),
+        Ordering::Equal => None,
+    }
+}
+
+/// Records the result of a finished custom match, if it was played for a tournament series.
+///
+/// The series is decided once a team won the majority of its games. Once every series is decided,
+/// the next Swiss round is paired or the tournament is finished.
+pub async fn record_tournament_game(
+    conn: &mut PgConnection,
+    party_id: u64,
+    match_id: u64,
+    metadata: &CMsgMatchMetaDataContents,
+) -> sqlx::Result<()> {
+    let game = sqlx::query_as!(
+        PendingGame,
+        r#"
+        SELECT g.series_id, s.tournament_id
+        FROM tournament_games g
+        JOIN tournament_series s ON s.id = g.series_id
+        WHERE g.party_id = $1 AND g.finished_at IS NULL
+        "#,
+        party_id.cast_signed()
+    )
+    .fetch_optional(&mut *conn)
+    .await?;
+    let Some(game) = game else {
+        return Ok(());
+    };
+    let Some(tournament) = lock_tournament(conn, game.tournament_id, None).await? else {
+        return Ok(());
+    };
+    let teams = fetch_teams(conn, tournament.id).await?;
+    let mut series = fetch_series(conn, tournament.id).await?;
+    let resolved = resolve_series(&series);
+    let (Some(row), Some(r)) = (
+        series.iter_mut().find(|s| s.id == game.series_id),
+        resolved.get(&game.series_id),
+    ) else {
+        return Ok(());
+    };
+
+    let winner = match r.teams.map(|t| t.team()) {
+        [Some(team1), Some(team2)] => {
+            let accounts = |id: Uuid| {
+                teams
+                    .iter()
+                    .find(|t| t.id == id)
+                    .map(|t| t.account_ids().collect::<HashSet<_>>())
+                    .unwrap_or_default()
+            };
+            winning_team(metadata, [&accounts(team1), &accounts(team2)]).map(|i| [team1, team2][i])
+        }
+        _ => None,
+    };
+    sqlx::query!(
+        r#"
+        UPDATE tournament_games
+        SET match_id = $2, winner_id = $3, finished_at = now()
+        WHERE party_id = $1
+        "#,
+        party_id.cast_signed(),
+        match_id.cast_signed(),
+        winner
+    )
+    .execute(&mut *conn)
+    .await?;
+    let Some(winner) = winner else {
+        warn!(
+            "No winner of tournament series {} in match {match_id}",
+            row.id
+        );
+        return Ok(());
+    };
+    // The series was decided by an earlier game
+    if r.outcome != Outcome::Ready {
+        return Ok(());
+    }
+
+    if r.teams[0].team() == Some(winner) {
+        row.team1_wins += 1;
+    } else {
+        row.team2_wins += 1;
+    }
+    if row.team1_wins.max(row.team2_wins) > tournament.best_of / 2 {
+        row.winner_id = Some(winner);
+    }
+    sqlx::query!(
+        r#"
+        UPDATE tournament_series
+        SET team1_wins = $2, team2_wins = $3, winner_id = $4,
+            finished_at = CASE WHEN $4::uuid IS NULL THEN NULL ELSE now() END
+        WHERE id = $1
+        "#,
+        row.id,
+        row.team1_wins,
+        row.team2_wins,
+        row.winner_id
+    )
+    .execute(&mut *conn)
+    .await?;
+    if row.winner_id.is_some() {
+        info!("Team {winner} won tournament series {}", row.id);
+        advance_tournament(conn, &tournament, &teams, &series).await?;
+    }
+    Ok(())
+}
+
+/// Pairs the next Swiss round or finishes the tournament, once every series is decided.
+async fn advance_tournament(
+    conn: &mut PgConnection,
+    tournament: &TournamentRow,
+    teams: &[TeamRow],
+    series: &[SeriesRow],
+) -> sqlx::Result<()> {
+    let resolved = resolve_series(series);
+    if resolved
+        .values()
+        .any(|r| !matches!(r.outcome, Outcome::Decided { .. }))
+    {
+        return Ok(());
+    }
+    let format = tournament.format();
+    let round = series.iter().map(|s| s.round).max().unwrap_or_default();
+    if format == TournamentFormat::Swiss && round < tournament.swiss_rounds.unwrap_or_default() {
+        let ranked = standings(format, teams, series, &resolved)
+            .into_iter()
+            .map(|s| s.team_id)
+            .collect_vec();
+        let played = resolved
+            .values()
+            .filter_map(|r| Some((r.teams[0].team()?, r.teams[1].team()?)))
+            .collect();
+        let byes = resolved
+            .values()
+            .filter_map(|r| match r.outcome {
+                Outcome::Decided {
+                    winner,
+                    loser: None,
+                } => winner,
+                _ => None,
+            })
+            .collect();
+        let plan = swiss_round((round + 1).cast_unsigned(), &ranked, &played, &byes);
+        insert_series(conn, tournament.id, &plan).await?;
+        info!("Paired round {} of tournament {}", round + 1, tournament.id);
+        return Ok(());
+    }
+    sqlx::query!(
+        r#"
+        UPDATE tournaments
+        SET status = 'finished', finished_at = now()
+        WHERE id = $1
+        "#,
+        tournament.id
+    )
+    .execute(&mut *conn)
+    .await?;
+    info!("Tournament {} finished", tournament.id);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use valveprotos::deadlock::ECitadelLobbyTeam;
+    use valveprotos::deadlock::c_msg_match_meta_data_contents::{MatchInfo, Players};
+
+    use super::*;
+
+    fn metadata(
+        outcome: EMatchOutcome,
+        players: &[(u32, ECitadelLobbyTeam)],
+    ) -> CMsgMatchMetaDataContents {
+        CMsgMatchMetaDataContents {
+            match_info: Some(MatchInfo {
+                match_outcome: Some(outcome as i32),
+                winning_team: Some(ECitadelLobbyTeam::KECitadelLobbyTeamTeam1 as i32),
+                players: players
+                    .iter()
+                    .map(|&(account_id, team)| Players {
+                        account_id: Some(account_id),
+                        team: Some(team as i32),
+                        ..Default::default()
+                    })
+                    .collect(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_winning_team() {
+        let team0 = ECitadelLobbyTeam::KECitadelLobbyTeamTeam0;
+        let team1 = ECitadelLobbyTeam::KECitadelLobbyTeamTeam1;
+        let metadata = metadata(
+            EMatchOutcome::KEOutcomeTeamWin,
+            &[(1, team0), (2, team0), (3, team1), (4, team1)],
+        );
+        let a = HashSet::from([1, 2]);
+        let b = HashSet::from([3, 4]);
+        assert_eq!(winning_team(&metadata, [&a, &b]), Some(1));
+        assert_eq!(winning_team(&metadata, [&b, &a]), Some(0));
+    }
+
+    #[test]
+    fn test_winning_team_with_mixed_sides() {
+        let team0 = ECitadelLobbyTeam::KECitadelLobbyTeamTeam0;
+        let team1 = ECitadelLobbyTeam::KECitadelLobbyTeamTeam1;
+        // A substitute plays for the first team, which still has the majority
+        let metadata = metadata(
+            EMatchOutcome::KEOutcomeTeamWin,
+            &[(1, team1), (2, team1), (9, team1), (3, team0), (4, team0)],
+        );
+        let a = HashSet::from([1, 2]);
+        let b = HashSet::from([3, 4]);
+        assert_eq!(winning_team(&metadata, [&a, &b]), Some(0));
+    }
+
+    #[test]
+    fn test_winning_team_of_draw() {
+        let metadata = metadata(
+            EMatchOutcome::KEOutcomeMatchDraw,
+            &[(1, ECitadelLobbyTeam::KECitadelLobbyTeamTeam1)],
+        );
+        let a = HashSet::from([1]);
+        let b = HashSet::from([2]);
+        assert_eq!(winning_team(&metadata, [&a, &b]), None);
+    }
+}
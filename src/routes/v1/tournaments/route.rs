@@ -0,0 +1,434 @@
+use core::time::Duration;
+
+use axum::Json;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use itertools::Itertools;
+use sqlx::PgConnection;
+
+use crate::context::AppState;
+use crate::error::{APIError, APIResult};
+use crate::routes::v1::tournaments::bracket::{
+    Outcome, double_elimination, single_elimination, swiss_first_round,
+};
+use crate::routes::v1::tournaments::repository::{
+    TournamentRow, fetch_games, fetch_series, fetch_teams, fetch_tournament, insert_series,
+    resolve_series,
+};
+use crate::routes::v1::tournaments::types::{
+    CreateTournamentRequest, SeriesStatus, Tournament, TournamentFormat, TournamentGame,
+    TournamentIdPath, TournamentSeries, TournamentTeam, TournamentWithBracket,
+};
+use crate::services::rate_limiter::extractor::RateLimitKey;
+use crate::services::rate_limiter::{ApiKeyScope, Quota};
+
+const MAX_NAME_LENGTH: usize = 100;
+const MAX_TEAMS: usize = 64;
+const MAX_TEAM_SIZE: usize = 6;
+
+/// Returns the default number of Swiss rounds, enough for a single undefeated team.
+fn default_swiss_rounds(teams: usize) -> u32 {
+    teams.next_power_of_two().trailing_zeros()
+}
+
+fn validate_tournament(request: &CreateTournamentRequest) -> Result<(), String> {
+    if request.name.trim().is_empty() || request.name.len() > MAX_NAME_LENGTH {
+        return Err(format!(
+            "Name must be between 1 and {MAX_NAME_LENGTH} characters long"
+        ));
+    }
+    if !(2..=MAX_TEAMS).contains(&request.teams.len()) {
+        return Err(format!(
+            "A tournament needs between 2 and {MAX_TEAMS} teams"
+        ));
+    }
+    if ![1, 3, 5, 7].contains(&request.best_of) {
+        return Err("best_of must be 1, 3, 5 or 7".to_owned());
+    }
+    match (request.format, request.swiss_rounds) {
+        (TournamentFormat::Swiss, Some(rounds))
+            if rounds == 0 || rounds as usize >= request.teams.len() =>
+        {
+            return Err(format!(
+                "swiss_rounds must be between 1 and {}",
+                request.teams.len() - 1
+            ));
+        }
+        (TournamentFormat::SingleElimination | TournamentFormat::DoubleElimination, Some(_)) => {
+            return Err("swiss_rounds is only supported for Swiss tournaments".to_owned());
+        }
+        _ => {}
+    }
+    for team in &request.teams {
+        if team.name.trim().is_empty() || team.name.len() > MAX_NAME_LENGTH {
+            return Err(format!(
+                "Team names must be between 1 and {MAX_NAME_LENGTH} characters long"
+            ));
+        }
+        if !(1..=MAX_TEAM_SIZE).contains(&team.account_ids.len()) {
+            return Err(format!(
+                "Team {} must have between 1 and {MAX_TEAM_SIZE} players",
+                team.name
+            ));
+        }
+    }
+    if let Some(name) = request
+        .teams
+        .iter()
+        .map(|t| t.name.as_str())
+        .duplicates()
+        .next()
+    {
+        return Err(format!("Team {name} is listed more than once"));
+    }
+    if let Some(account_id) = request
+        .teams
+        .iter()
+        .flat_map(|t| &t.account_ids)
+        .duplicates()
+        .next()
+    {
+        return Err(format!("Account {account_id} is in more than one team"));
+    }
+    Ok(())
+}
+
+/// Loads the teams and series of a tournament, with the state of every series.
+pub(super) async fn load_bracket(
+    conn: &mut PgConnection,
+    tournament: TournamentRow,
+) -> sqlx::Result<TournamentWithBracket> {
+    let teams = fetch_teams(conn, tournament.id).await?;
+    let series = fetch_series(conn, tournament.id).await?;
+    let mut games = fetch_games(conn, tournament.id)
+        .await?
+        .into_iter()
+        .into_group_map_by(|g| g.series_id);
+    let resolved = resolve_series(&series);
+    let series = series
+        .into_iter()
+        .filter_map(|row| {
+            let r = resolved.get(&row.id)?;
+            let (status, winner_id) = match r.outcome {
+                Outcome::Waiting => (SeriesStatus::Waiting, None),
+                Outcome::Ready => (SeriesStatus::Ready, None),
+                Outcome::Decided { winner, .. } => (SeriesStatus::Finished, winner),
+            };
+            Some(TournamentSeries {
+                id: row.id,
+                bracket: row.bracket(),
+                round: row.round.cast_unsigned(),
+                position: row.position.cast_unsigned(),
+                status,
+                team1_id: r.teams[0].team(),
+                team2_id: r.teams[1].team(),
+                team1_wins: row.team1_wins.cast_unsigned(),
+                team2_wins: row.team2_wins.cast_unsigned(),
+                winner_id,
+                games: games
+                    .remove(&row.id)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(TournamentGame::from)
+                    .collect(),
+            })
+        })
+        .collect();
+    Ok(TournamentWithBracket {
+        tournament: tournament.into(),
+        teams: teams.into_iter().map(TournamentTeam::from).collect(),
+        series,
+    })
+}
+
+#[utoipa::path(
+    post,
+    path = "/",
+    request_body = CreateTournamentRequest,
+    responses(
+        (status = 200, description = "Successfully created the tournament.", body = TournamentWithBracket),
+        (status = BAD_REQUEST, description = "Provided parameters are invalid."),
+        (status = FORBIDDEN, description = "API key is missing the `custom_matches` scope"),
+        (status = TOO_MANY_REQUESTS, description = "Rate limit exceeded"),
+        (status = INTERNAL_SERVER_ERROR, description = "Creating the tournament failed")
+    ),
+    security(("api_key_header" = ["custom_matches"]), ("api_key_query" = ["custom_matches"])),
+    tags = ["Tournaments"],
+    summary = "Create Tournament",
+    description = "
+This endpoint creates a tournament and generates its bracket.
+
+**Formats:**
+- `single_elimination`: Teams are eliminated after losing a series.
+- `double_elimination`: Teams drop into the losers bracket after their first lost series and are eliminated after their second. The grand final is a single series, without a bracket reset.
+- `swiss`: Teams play a fixed number of rounds against teams with a similar record. The next round is paired once every series of the current round is finished.
+
+Teams are seeded in the order they are listed. Elimination brackets are filled up with byes, which are given to the best seeds.
+
+Once both teams of a series are known, lobbies for its games are created with `POST /v1/tournaments/{tournament_id}/series/{series_id}/lobby`.
+
+Requires an API key with the `custom_matches` scope.
+
+### Rate Limits:
+| Type | Limit |
+| ---- | ----- |
+| IP | API-Key ONLY |
+| Key | 20req/h |
+| Global | 200req/h |
+"
+)]
+pub(super) async fn create_tournament(
+    rate_limit_key: RateLimitKey,
+    State(state): State<AppState>,
+    Json(request): Json<CreateTournamentRequest>,
+) -> APIResult<impl IntoResponse> {
+    state
+        .rate_limit_client
+        .require_scope(&rate_limit_key, ApiKeyScope::CustomMatches)
+        .await?;
+    state
+        .rate_limit_client
+        .apply_limits(
+            &rate_limit_key,
+            "create_tournament",
+            &[
+                Quota::key_limit(20, Duration::from_hours(1)),
+                Quota::global_limit(200, Duration::from_hours(1)),
+            ],
+        )
+        .await?;
+    validate_tournament(&request)
+        .map_err(|msg| APIError::status_msg(StatusCode::BAD_REQUEST, msg))?;
+    let swiss_rounds = (request.format == TournamentFormat::Swiss).then(|| {
+        request
+            .swiss_rounds
+            .unwrap_or_else(|| default_swiss_rounds(request.teams.len()))
+    });
+
+    let mut transaction = state.pg_client.begin().await?;
+    let tournament = sqlx::query_as!(
+        TournamentRow,
+        r#"
+        INSERT INTO tournaments (api_key, name, format, best_of, swiss_rounds)
+        VALUES ($1, $2, $3, $4, $5)
+        RETURNING id, name, format, best_of, swiss_rounds, status, created_at, finished_at
+        "#,
+        rate_limit_key.api_key,
+        request.name.trim(),
+        request.format.to_string(),
+        request.best_of.cast_signed(),
+        swiss_rounds.map(u32::cast_signed)
+    )
+    .fetch_one(&mut *transaction)
+    .await?;
+    let mut team_ids = Vec::with_capacity(request.teams.len());
+    for (seed, team) in (1..).zip(&request.teams) {
+        let id = sqlx::query_scalar!(
+            r#"
+            INSERT INTO tournament_teams (tournament_id, seed, name, account_ids)
+            VALUES ($1, $2, $3, $4)
+            RETURNING id
+            "#,
+            tournament.id,
+            seed,
+            team.name.trim(),
+            &team
+                .account_ids
+                .iter()
+                .copied()
+                .map(i64::from)
+                .collect_vec()
+        )
+        .fetch_one(&mut *transaction)
+        .await?;
+        team_ids.push(id);
+    }
+    let plan = match request.format {
+        TournamentFormat::SingleElimination => single_elimination(&team_ids),
+        TournamentFormat::DoubleElimination => double_elimination(&team_ids),
+        TournamentFormat::Swiss => swiss_first_round(&team_ids),
+    };
+    insert_series(&mut *transaction, tournament.id, &plan).await?;
+    let tournament = load_bracket(&mut *transaction, tournament).await?;
+    transaction.commit().await?;
+    Ok(Json(tournament))
+}
+
+#[utoipa::path(
+    get,
+    path = "/",
+    responses(
+        (status = 200, description = "Successfully fetched the tournaments.", body = [Tournament]),
+        (status = FORBIDDEN, description = "API key is missing the `custom_matches` scope"),
+        (status = INTERNAL_SERVER_ERROR, description = "Fetching the tournaments failed")
+    ),
+    security(("api_key_header" = ["custom_matches"]), ("api_key_query" = ["custom_matches"])),
+    tags = ["Tournaments"],
+    summary = "List Tournaments",
+    description = "
+This endpoint lists the 100 most recent tournaments created with your API key.
+
+Requires an API key with the `custom_matches` scope.
+
+### Rate Limits:
+| Type | Limit |
+| ---- | ----- |
+| IP | API-Key ONLY |
+| Key | - |
+| Global | - |
+"
+)]
+pub(super) async fn list_tournaments(
+    rate_limit_key: RateLimitKey,
+    State(state): State<AppState>,
+) -> APIResult<impl IntoResponse> {
+    state
+        .rate_limit_client
+        .require_scope(&rate_limit_key, ApiKeyScope::CustomMatches)
+        .await?;
+    let tournaments = sqlx::query_as!(
+        TournamentRow,
+        r#"
+        SELECT id, name, format, best_of, swiss_rounds, status, created_at, finished_at
+        FROM tournaments
+        WHERE api_key = $1
+        ORDER BY created_at DESC
+        LIMIT 100
+        "#,
+        rate_limit_key.api_key
+    )
+    .fetch_all(&state.pg_client)
+    .await?;
+    Ok(Json(
+        tournaments
+            .into_iter()
+            .map(Tournament::from)
+            .collect::<Vec<_>>(),
+    ))
+}
+
+#[utoipa::path(
+    get,
+    path = "/{tournament_id}",
+    params(TournamentIdPath),
+    responses(
+        (status = 200, description = "Successfully fetched the tournament.", body = TournamentWithBracket),
+        (status = FORBIDDEN, description = "API key is missing the `custom_matches` scope"),
+        (status = NOT_FOUND, description = "Tournament not found"),
+        (status = INTERNAL_SERVER_ERROR, description = "Fetching the tournament failed")
+    ),
+    security(("api_key_header" = ["custom_matches"]), ("api_key_query" = ["custom_matches"])),
+    tags = ["Tournaments"],
+    summary = "Get Tournament",
+    description = "
+This endpoint returns a tournament with its teams and every series of its bracket, including the lobbies created for their games.
+
+Winners of games are recorded automatically once the metadata of the match is available, the first team winning the majority of the games wins the series.
+
+Requires an API key with the `custom_matches` scope.
+
+### Rate Limits:
+| Type | Limit |
+| ---- | ----- |
+| IP | API-Key ONLY |
+| Key | - |
+| Global | - |
+"
+)]
+pub(super) async fn get_tournament(
+    Path(TournamentIdPath { tournament_id }): Path<TournamentIdPath>,
+    rate_limit_key: RateLimitKey,
+    State(state): State<AppState>,
+) -> APIResult<impl IntoResponse> {
+    state
+        .rate_limit_client
+        .require_scope(&rate_limit_key, ApiKeyScope::CustomMatches)
+        .await?;
+    let mut conn = state.pg_client.acquire().await?;
+    let tournament = fetch_tournament(&mut conn, tournament_id, rate_limit_key.api_key)
+        .await?
+        .ok_or_else(|| {
+            APIError::status_msg(
+                StatusCode::NOT_FOUND,
+                format!("Tournament {tournament_id} not found"),
+            )
+        })?;
+    Ok(Json(load_bracket(&mut conn, tournament).await?))
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+    use crate::routes::v1::tournaments::types::CreateTournamentTeam;
+
+    fn request(format: TournamentFormat, teams: &[&[u32]]) -> CreateTournamentRequest {
+        CreateTournamentRequest {
+            name: "Cup".to_owned(),
+            format,
+            best_of: 3,
+            swiss_rounds: None,
+            teams: (1..)
+                .zip(teams)
+                .map(|(i, account_ids)| CreateTournamentTeam {
+                    name: format!("Team {i}"),
+                    account_ids: account_ids.to_vec(),
+                })
+                .collect(),
+        }
+    }
+
+    #[rstest]
+    #[case(2, 1)]
+    #[case(5, 3)]
+    #[case(8, 3)]
+    #[case(9, 4)]
+    fn test_default_swiss_rounds(#[case] teams: usize, #[case] expected: u32) {
+        assert_eq!(default_swiss_rounds(teams), expected);
+    }
+
+    #[test]
+    fn test_validate_tournament() {
+        let valid = request(TournamentFormat::SingleElimination, &[&[1, 2], &[3, 4]]);
+        assert!(validate_tournament(&valid).is_ok());
+    }
+
+    #[test]
+    fn test_validate_tournament_rejects_shared_accounts() {
+        let invalid = request(TournamentFormat::SingleElimination, &[&[1, 2], &[2, 3]]);
+        assert_eq!(
+            validate_tournament(&invalid),
+            Err("Account 2 is in more than one team".to_owned())
+        );
+    }
+
+    #[test]
+    fn test_validate_tournament_rejects_single_team() {
+        let invalid = request(TournamentFormat::DoubleElimination, &[&[1]]);
+        assert!(validate_tournament(&invalid).is_err());
+    }
+
+    #[rstest]
+    #[case(TournamentFormat::Swiss, Some(2), true)]
+    #[case(TournamentFormat::Swiss, Some(3), false)]
+    #[case(TournamentFormat::Swiss, Some(0), false)]
+    #[case(TournamentFormat::SingleElimination, Some(2), false)]
+    fn test_validate_swiss_rounds(
+        #[case] format: TournamentFormat,
+        #[case] swiss_rounds: Option<u32>,
+        #[case] valid: bool,
+    ) {
+        let mut request = request(format, &[&[1], &[2], &[3]]);
+        request.swiss_rounds = swiss_rounds;
+        assert_eq!(validate_tournament(&request).is_ok(), valid);
+    }
+
+    #[test]
+    fn test_validate_best_of() {
+        let mut request = request(TournamentFormat::Swiss, &[&[1], &[2]]);
+        request.best_of = 2;
+        assert!(validate_tournament(&request).is_err());
+    }
+}
@@ -0,0 +1,307 @@
+use std::collections::{HashMap, HashSet};
+
+use axum::Json;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use serde::Serialize;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::context::AppState;
+use crate::error::{APIError, APIResult};
+use crate::routes::v1::tournaments::bracket::{Outcome, ResolvedSeries};
+use crate::routes::v1::tournaments::repository::{
+    SeriesRow, TeamRow, fetch_series, fetch_teams, fetch_tournament, resolve_series,
+};
+use crate::routes::v1::tournaments::types::{Bracket, TournamentFormat, TournamentIdPath};
+use crate::services::rate_limiter::ApiKeyScope;
+use crate::services::rate_limiter::extractor::RateLimitKey;
+
+#[derive(Debug, Serialize, ToSchema)]
+pub(super) struct TournamentStanding {
+    /// The rank of the team, starting at 1.
+    pub(super) rank: u32,
+    pub(super) team_id: Uuid,
+    pub(super) name: String,
+    pub(super) seed: u32,
+    /// The won series, including byes.
+    pub(super) series_wins: u32,
+    pub(super) series_losses: u32,
+    pub(super) game_wins: u32,
+    pub(super) game_losses: u32,
+    /// Whether the team was eliminated, never set in Swiss tournaments.
+    pub(super) eliminated: bool,
+    #[serde(skip)]
+    eliminated_in: Option<(bool, i32)>,
+}
+
+impl TournamentStanding {
+    fn game_difference(&self) -> i64 {
+        i64::from(self.game_wins) - i64::from(self.game_losses)
+    }
+}
+
+/// Computes the standings of the teams.
+///
+/// Swiss tournaments are ranked by won series, then by the game difference. Elimination
+/// tournaments are ranked by how far teams made it, eliminated teams in the grand final or a later
+/// round of their bracket ranking higher.
+pub(super) fn standings(
+    format: TournamentFormat,
+    teams: &[TeamRow],
+    series: &[SeriesRow],
+    resolved: &HashMap<Uuid, ResolvedSeries>,
+) -> Vec<TournamentStanding> {
+    let mut standings: HashMap<Uuid, TournamentStanding> = teams
+        .iter()
+        .map(|t| {
+            let standing = TournamentStanding {
+                rank: 0,
+                team_id: t.id,
+                name: t.name.clone(),
+                seed: t.seed.cast_unsigned(),
+                series_wins: 0,
+                series_losses: 0,
+                game_wins: 0,
+                game_losses: 0,
+                eliminated: false,
+                eliminated_in: None,
+            };
+            (t.id, standing)
+        })
+        .collect();
+    // Losers of these series drop into the losers bracket instead of being eliminated
+    let dropping: HashSet<Uuid> = series
+        .iter()
+        .flat_map(|s| {
+            [
+                s.team1_source_id.filter(|_| s.team1_source_loser),
+                s.team2_source_id.filter(|_| s.team2_source_loser),
+            ]
+        })
+        .flatten()
+        .collect();
+
+    for row in series {
+        let Some(r) = resolved.get(&row.id) else {
+            continue;
+        };
+        let games = [
+            row.team1_wins.cast_unsigned(),
+            row.team2_wins.cast_unsigned(),
+        ];
+        for (i, team) in r.teams.iter().enumerate() {
+            if let Some(s) = team.team().and_then(|t| standings.get_mut(&t)) {
+                s.game_wins += games[i];
+                s.game_losses += games[1 - i];
+            }
+        }
+        let Outcome::Decided { winner, loser } = r.outcome else {
+            continue;
+        };
+        if let Some(s) = winner.and_then(|t| standings.get_mut(&t)) {
+            s.series_wins += 1;
+        }
+        if let Some(s) = loser.and_then(|t| standings.get_mut(&t)) {
+            s.series_losses += 1;
+            if format != TournamentFormat::Swiss && !dropping.contains(&row.id) {
+                s.eliminated = true;
+                s.eliminated_in = Some((row.bracket() == Bracket::GrandFinal, row.round));
+            }
+        }
+    }
+
+    let mut standings = standings.into_values().collect::<Vec<_>>();
+    if format == TournamentFormat::Swiss {
+        standings.sort_by(|a, b| {
+            b.series_wins
+                .cmp(&a.series_wins)
+                .then(b.game_difference().cmp(&a.game_difference()))
+                .then(a.seed.cmp(&b.seed))
+        });
+    } else {
+        standings.sort_by(|a, b| {
+            a.eliminated
+                .cmp(&b.eliminated)
+                .then(b.eliminated_in.cmp(&a.eliminated_in))
+                .then(b.series_wins.cmp(&a.series_wins))
+                .then(a.seed.cmp(&b.seed))
+        });
+    }
+    for (rank, standing) in (1..).zip(&mut standings) {
+        standing.rank = rank;
+    }
+    standings
+}
+
+#[utoipa::path(
+    get,
+    path = "/{tournament_id}/standings",
+    params(TournamentIdPath),
+    responses(
+        (status = 200, description = "Successfully fetched the standings.", body = [TournamentStanding]),
+        (status = FORBIDDEN, description = "API key is missing the `custom_matches` scope"),
+        (status = NOT_FOUND, description = "Tournament not found"),
+        (status = INTERNAL_SERVER_ERROR, description = "Fetching the standings failed")
+    ),
+    security(("api_key_header" = ["custom_matches"]), ("api_key_query" = ["custom_matches"])),
+    tags = ["Tournaments"],
+    summary = "Get Standings",
+    description = "
+This endpoint returns the standings of the teams of a tournament.
+
+Swiss tournaments are ranked by won series, then by the difference of won and lost games.
+Elimination tournaments are ranked by how far the teams made it, teams that are still in the tournament rank first.
+
+Requires an API key with the `custom_matches` scope.
+
+### Rate Limits:
+| Type | Limit |
+| ---- | ----- |
+| IP | API-Key ONLY |
+| Key | - |
+| Global | - |
+"
+)]
+pub(super) async fn get_standings(
+    Path(TournamentIdPath { tournament_id }): Path<TournamentIdPath>,
+    rate_limit_key: RateLimitKey,
+    State(state): State<AppState>,
+) -> APIResult<impl IntoResponse> {
+    state
+        .rate_limit_client
+        .require_scope(&rate_limit_key, ApiKeyScope::CustomMatches)
+        .await?;
+    let mut conn = state.pg_client.acquire().await?;
+    let tournament = fetch_tournament(&mut conn, tournament_id, rate_limit_key.api_key)
+        .await?
+        .ok_or_else(|| {
+            APIError::status_msg(
+                StatusCode::NOT_FOUND,
+                format!("Tournament {tournament_id} not found"),
+            )
+        })?;
+    let teams = fetch_teams(&mut conn, tournament_id).await?;
+    let series = fetch_series(&mut conn, tournament_id).await?;
+    let resolved = resolve_series(&series);
+    Ok(Json(standings(
+        tournament.format(),
+        &teams,
+        &series,
+        &resolved,
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::routes::v1::tournaments::bracket::{SeriesPlan, SlotSource, single_elimination};
+
+    fn team(seed: i32) -> TeamRow {
+        TeamRow {
+            id: Uuid::new_v4(),
+            seed,
+            name: format!("Team {seed}"),
+            account_ids: vec![],
+        }
+    }
+
+    fn series_rows(plan: &[SeriesPlan]) -> Vec<SeriesRow> {
+        let ids = plan.iter().map(|_| Uuid::new_v4()).collect::<Vec<_>>();
+        let columns = |source: SlotSource<usize>| match source {
+            SlotSource::Team(team_id) => (Some(team_id), None, false),
+            SlotSource::Winner(i) => (None, Some(ids[i]), false),
+            SlotSource::Loser(i) => (None, Some(ids[i]), true),
+            SlotSource::Empty => (None, None, false),
+        };
+        plan.iter()
+            .zip(&ids)
+            .map(|(p, &id)| {
+                let (team1_id, team1_source_id, team1_source_loser) = columns(p.slots[0]);
+                let (team2_id, team2_source_id, team2_source_loser) = columns(p.slots[1]);
+                SeriesRow {
+                    id,
+                    bracket: p.bracket.to_string(),
+                    round: p.round.cast_signed(),
+                    position: p.position.cast_signed(),
+                    team1_id,
+                    team1_source_id,
+                    team1_source_loser,
+                    team2_id,
+                    team2_source_id,
+                    team2_source_loser,
+                    team1_wins: 0,
+                    team2_wins: 0,
+                    winner_id: None,
+                }
+            })
+            .collect()
+    }
+
+    /// Records a 2-1 series win of the given team.
+    fn win(series: &mut SeriesRow, resolved: &HashMap<Uuid, ResolvedSeries>, winner: Uuid) {
+        let first = resolved[&series.id].teams[0].team() == Some(winner);
+        (series.team1_wins, series.team2_wins) = if first { (2, 1) } else { (1, 2) };
+        series.winner_id = Some(winner);
+    }
+
+    #[test]
+    fn test_single_elimination_standings() {
+        let teams = (1..=4).map(team).collect::<Vec<_>>();
+        let ids = teams.iter().map(|t| t.id).collect::<Vec<_>>();
+        let mut series = series_rows(&single_elimination(&ids));
+        // Seed 4 upsets seed 1, seed 2 beats seed 3 and wins the final
+        let resolved = resolve_series(&series);
+        win(&mut series[0], &resolved, ids[3]);
+        win(&mut series[1], &resolved, ids[1]);
+        let resolved = resolve_series(&series);
+        win(&mut series[2], &resolved, ids[1]);
+        let resolved = resolve_series(&series);
+
+        let standings = standings(
+            TournamentFormat::SingleElimination,
+            &teams,
+            &series,
+            &resolved,
+        );
+        let order = standings.iter().map(|s| s.team_id).collect::<Vec<_>>();
+        assert_eq!(order, vec![ids[1], ids[3], ids[0], ids[2]]);
+        assert!(!standings[0].eliminated);
+        assert!(standings[1..].iter().all(|s| s.eliminated));
+        assert_eq!(standings[0].series_wins, 2);
+        assert_eq!((standings[0].game_wins, standings[0].game_losses), (4, 2));
+        assert_eq!(standings[3].rank, 4);
+    }
+
+    #[test]
+    fn test_swiss_standings_use_game_difference() {
+        let teams = (1..=4).map(team).collect::<Vec<_>>();
+        let ids = teams.iter().map(|t| t.id).collect::<Vec<_>>();
+        let plan = vec![
+            SeriesPlan {
+                bracket: Bracket::Swiss,
+                round: 1,
+                position: 0,
+                slots: [SlotSource::Team(ids[0]), SlotSource::Team(ids[2])],
+            },
+            SeriesPlan {
+                bracket: Bracket::Swiss,
+                round: 1,
+                position: 1,
+                slots: [SlotSource::Team(ids[1]), SlotSource::Team(ids[3])],
+            },
+        ];
+        let mut series = series_rows(&plan);
+        let resolved = resolve_series(&series);
+        win(&mut series[0], &resolved, ids[2]);
+        series[1].team1_wins = 2;
+        series[1].winner_id = Some(ids[1]);
+        let resolved = resolve_series(&series);
+
+        let standings = standings(TournamentFormat::Swiss, &teams, &series, &resolved);
+        let order = standings.iter().map(|s| s.team_id).collect::<Vec<_>>();
+        assert_eq!(order, vec![ids[1], ids[2], ids[0], ids[3]]);
+        assert!(standings.iter().all(|s| !s.eliminated));
+    }
+}
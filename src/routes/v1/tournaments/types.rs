@@ -0,0 +1,158 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use strum::{Display, EnumString};
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema, Display, EnumString,
+)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub(super) enum TournamentFormat {
+    /// Teams are eliminated after losing one series.
+    SingleElimination,
+    /// Teams drop into a losers bracket after losing their first series, and are eliminated after
+    /// losing two.
+    DoubleElimination,
+    /// Every round teams play an opponent with a similar record, nobody is eliminated.
+    Swiss,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema, Display, EnumString)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub(super) enum TournamentStatus {
+    /// Series are still being played.
+    Running,
+    /// Every series was played.
+    Finished,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema, Display, EnumString)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub(super) enum Bracket {
+    /// The bracket of elimination tournaments teams start in.
+    Winners,
+    /// The bracket of double elimination tournaments for teams that lost once.
+    Losers,
+    /// The series between the winners of both brackets of a double elimination tournament.
+    GrandFinal,
+    /// A round of a Swiss tournament.
+    Swiss,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub(super) enum SeriesStatus {
+    /// At least one of the teams is not known yet.
+    Waiting,
+    /// Both teams are known, lobbies can be created for the series.
+    Ready,
+    /// The series has a winner, or was skipped as a team got a bye.
+    Finished,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub(super) struct CreateTournamentTeam {
+    pub(super) name: String,
+    /// The account ids of the players of the team.
+    pub(super) account_ids: Vec<u32>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub(super) struct CreateTournamentRequest {
+    pub(super) name: String,
+    pub(super) format: TournamentFormat,
+    /// The number of games of a series, the first team winning the majority wins the series.
+    #[serde(default = "default_best_of")]
+    #[schema(default = 1, minimum = 1, maximum = 7)]
+    pub(super) best_of: u32,
+    /// The number of rounds of a Swiss tournament, defaults to enough rounds for a single
+    /// undefeated team.
+    #[serde(default)]
+    pub(super) swiss_rounds: Option<u32>,
+    /// The teams, ordered by their seed.
+    pub(super) teams: Vec<CreateTournamentTeam>,
+}
+
+fn default_best_of() -> u32 {
+    1
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub(super) struct TournamentIdPath {
+    /// The ID of the tournament.
+    pub(super) tournament_id: Uuid,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub(super) struct TournamentSeriesPath {
+    /// The ID of the tournament.
+    pub(super) tournament_id: Uuid,
+    /// The ID of the series.
+    pub(super) series_id: Uuid,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub(super) struct Tournament {
+    pub(super) id: Uuid,
+    pub(super) name: String,
+    pub(super) format: TournamentFormat,
+    pub(super) best_of: u32,
+    /// The number of rounds, for Swiss tournaments.
+    pub(super) swiss_rounds: Option<u32>,
+    pub(super) status: TournamentStatus,
+    pub(super) created_at: DateTime<Utc>,
+    pub(super) finished_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub(super) struct TournamentTeam {
+    pub(super) id: Uuid,
+    /// The seed of the team, starting at 1.
+    pub(super) seed: u32,
+    pub(super) name: String,
+    pub(super) account_ids: Vec<u32>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub(super) struct TournamentGame {
+    pub(super) party_id: String,
+    /// The id of the match, once it finished.
+    pub(super) match_id: Option<u64>,
+    /// The team that won the game, once it finished.
+    pub(super) winner_id: Option<Uuid>,
+    pub(super) created_at: DateTime<Utc>,
+    pub(super) finished_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub(super) struct TournamentSeries {
+    pub(super) id: Uuid,
+    pub(super) bracket: Bracket,
+    /// The round of the series within its bracket, starting at 1.
+    pub(super) round: u32,
+    /// The position of the series within its round, starting at 0.
+    pub(super) position: u32,
+    pub(super) status: SeriesStatus,
+    /// The first team, once it is known.
+    pub(super) team1_id: Option<Uuid>,
+    /// The second team, once it is known.
+    pub(super) team2_id: Option<Uuid>,
+    pub(super) team1_wins: u32,
+    pub(super) team2_wins: u32,
+    /// The winner of the series, a team with a bye wins without playing.
+    pub(super) winner_id: Option<Uuid>,
+    /// The lobbies created for the games of the series.
+    pub(super) games: Vec<TournamentGame>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub(super) struct TournamentWithBracket {
+    #[serde(flatten)]
+    pub(super) tournament: Tournament,
+    pub(super) teams: Vec<TournamentTeam>,
+    pub(super) series: Vec<TournamentSeries>,
+}
@@ -0,0 +1,419 @@
+use core::net::SocketAddr;
+use core::time::Duration;
+use std::sync::Arc;
+use std::time::Instant;
+
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use sha2::Sha256;
+use sqlx::{PgExecutor, Pool, Postgres};
+use tokio::time::sleep;
+use tracing::{debug, info, warn};
+use url::{Host, Url};
+use uuid::Uuid;
+
+use crate::utils::net::is_public_ip;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Interval for checking for due deliveries while there are none
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Deliveries claimed at once
+const BATCH_SIZE: i64 = 20;
+
+/// Timeout of a single delivery attempt
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Deliveries failing this many times are moved to the dead-letter queue.
+const MAX_ATTEMPTS: i32 = 10;
+
+/// Delay before the first retry, doubled with every failed attempt
+const BASE_RETRY_DELAY_SECS: f64 = 30.;
+
+/// Upper bound of the delay between two attempts
+const MAX_RETRY_DELAY_SECS: f64 = 60. * 60.;
+
+/// Claimed deliveries are retried after this time if their attempt was lost.
+const CLAIM_TIMEOUT_SECS: f64 = 5. * 60.;
+
+/// Deliveries older than this are removed, together with their attempts.
+const RETENTION_SECS: f64 = 7. * 24. * 60. * 60.;
+
+/// Signs a callback body with the callback secret.
+///
+/// The signature is the hex encoded HMAC-SHA256 of `{timestamp}.{body}`, so receivers can reject
+/// replayed requests by their timestamp.
+pub(crate) fn sign(secret: &str, timestamp: i64, body: &str) -> String {
+    let mut mac = HmacSha256::new_from_slice(secret.as_bytes())
+        .unwrap_or_else(|_| unreachable!("HMAC accepts keys of any length"));
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body.as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/// Checks that a callback URL can only reach public HTTP(S) receivers.
+///
+/// Hosts that are names are checked when they are resolved, by [`PublicResolver`].
+pub(crate) fn validate_callback_url(url: &Url) -> Result<(), String> {
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err("Callback url must be a http or https url".to_owned());
+    }
+    let is_public = match url.host() {
+        None => false,
+        Some(Host::Domain(_)) => true,
+        Some(Host::Ipv4(ip)) => is_public_ip(ip.into()),
+        Some(Host::Ipv6(ip)) => is_public_ip(ip.into()),
+    };
+    if is_public {
+        Ok(())
+    } else {
+        Err("Callback url must point to a public address".to_owned())
+    }
+}
+
+/// Resolves the hosts of callback URLs, dropping addresses that aren't publicly routable.
+///
+/// Requests connect to the checked addresses, so a host can't resolve to a public address during
+/// validation and to an internal one afterwards.
+struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> = tokio::net::lookup_host((name.as_str(), 0))
+                .await?
+                .filter(|addr| is_public_ip(addr.ip()))
+                .collect();
+            if addrs.is_empty() {
+                return Err(format!("{} has no public address", name.as_str()).into());
+            }
+            let addrs: Addrs = Box::new(addrs.into_iter());
+            Ok(addrs)
+        })
+    }
+}
+
+/// Delay before the next attempt, after the given number of failed attempts.
+fn retry_delay_secs(attempts: i32) -> f64 {
+    let exponent = attempts.saturating_sub(1).clamp(0, 16);
+    (BASE_RETRY_DELAY_SECS * 2_f64.powi(exponent)).min(MAX_RETRY_DELAY_SECS)
+}
+
+/// Adds a callback with a JSON body to the outbox, it is delivered by the
+/// [`CallbackDeliveryWorker`].
+pub(crate) async fn enqueue_callback<'e>(
+    executor: impl PgExecutor<'e>,
+    api_key: Uuid,
+    party_id: u64,
+    event: &str,
+    url: &str,
+    secret: &str,
+    body: &str,
+) -> sqlx::Result<Uuid> {
+    let id = sqlx::query_scalar!(
+        r#"
+        INSERT INTO callback_deliveries (api_key, party_id, event, url, secret, body)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        RETURNING id
+        "#,
+        api_key,
+        party_id.cast_signed(),
+        event,
+        url,
+        secret,
+        body
+    )
+    .fetch_one(executor)
+    .await?;
+    debug!("Enqueued {event} callback {id} of party {party_id}");
+    Ok(id)
+}
+
+struct ClaimedDelivery {
+    id: Uuid,
+    event: String,
+    url: String,
+    secret: String,
+    body: String,
+    attempts: i32,
+    attempts_before_retry: i32,
+}
+
+impl ClaimedDelivery {
+    /// Attempts made since the delivery was last retried from the dead-letter queue
+    fn attempts_since_retry(&self) -> i32 {
+        self.attempts - self.attempts_before_retry
+    }
+}
+
+/// Result of a single delivery attempt
+struct Attempt {
+    status_code: Option<i32>,
+    error: Option<String>,
+    duration_ms: i32,
+}
+
+impl Attempt {
+    fn is_success(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+/// Worker delivering queued callbacks
+///
+/// Deliveries are claimed from Postgres with `SKIP LOCKED`, so every instance can run a worker.
+/// Failed deliveries are retried with exponential backoff, after [`MAX_ATTEMPTS`] they are moved to
+/// the dead-letter queue, from where they can be retried manually.
+///
+/// Callbacks only reach public addresses and don't follow redirects, so they can't be used to
+/// send requests to internal services.
+pub(crate) struct CallbackDeliveryWorker {
+    pg_client: Pool<Postgres>,
+    http_client: reqwest::Client,
+}
+
+impl CallbackDeliveryWorker {
+    pub(crate) fn new(pg_client: Pool<Postgres>) -> reqwest::Result<Self> {
+        let http_client = reqwest::Client::builder()
+            .dns_resolver(Arc::new(PublicResolver))
+            .redirect(reqwest::redirect::Policy::none())
+            .no_proxy()
+            .build()?;
+        Ok(Self {
+            pg_client,
+            http_client,
+        })
+    }
+
+    /// Start the background worker task
+    pub(crate) fn start_background_worker(self: Arc<Self>) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            info!("Callback delivery worker started");
+            loop {
+                match self.claim_deliveries().await {
+                    Ok(deliveries) if !deliveries.is_empty() => {
+                        futures::future::join_all(deliveries.into_iter().map(|d| self.deliver(d)))
+                            .await;
+                    }
+                    Ok(_) => {
+                        if let Err(e) = self.remove_old_deliveries().await {
+                            warn!("Failed to remove old callback deliveries: {e}");
+                        }
+                        sleep(POLL_INTERVAL).await;
+                    }
+                    Err(e) => {
+                        warn!("Failed to claim callback deliveries: {e}");
+                        sleep(POLL_INTERVAL).await;
+                    }
+                }
+            }
+        })
+    }
+
+    /// Claims due deliveries, pushing their next attempt back in case this attempt gets lost.
+    async fn claim_deliveries(&self) -> sqlx::Result<Vec<ClaimedDelivery>> {
+        sqlx::query_as!(
+            ClaimedDelivery,
+            r#"
+            UPDATE callback_deliveries
+            SET attempts = attempts + 1, next_attempt_at = now() + make_interval(secs => $2)
+            WHERE id IN (
+                SELECT id
+                FROM callback_deliveries
+                WHERE status = 'pending' AND next_attempt_at <= now()
+                ORDER BY next_attempt_at
+                LIMIT $1
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING id, event, url, secret, body, attempts, attempts_before_retry
+            "#,
+            BATCH_SIZE,
+            CLAIM_TIMEOUT_SECS
+        )
+        .fetch_all(&self.pg_client)
+        .await
+    }
+
+    async fn send(&self, delivery: &ClaimedDelivery) -> Attempt {
+        if let Err(e) = Url::parse(&delivery.url)
+            .map_err(|e| e.to_string())
+            .and_then(|url| validate_callback_url(&url))
+        {
+            return Attempt {
+                status_code: None,
+                error: Some(format!("Invalid callback url: {e}")),
+                duration_ms: 0,
+            };
+        }
+        let timestamp = Utc::now().timestamp();
+        let signature = sign(&delivery.secret, timestamp, &delivery.body);
+        let start = Instant::now();
+        let response = self
+            .http_client
+            .post(&delivery.url)
+            .timeout(REQUEST_TIMEOUT)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header("X-Callback-Id", delivery.id.to_string())
+            .header("X-Callback-Event", &delivery.event)
+            .header("X-Callback-Timestamp", timestamp)
+            .header("X-Callback-Signature", format!("sha256={signature}"))
+            .body(delivery.body.clone())
+            .send()
+            .await;
+        let duration_ms = i32::try_from(start.elapsed().as_millis()).unwrap_or(i32::MAX);
+        match response {
+            Ok(response) if response.status().is_success() => Attempt {
+                status_code: Some(response.status().as_u16().into()),
+                error: None,
+                duration_ms,
+            },
+            // The response body isn't stored, as it would be readable through the API
+            Ok(response) => Attempt {
+                status_code: Some(response.status().as_u16().into()),
+                error: Some(format!("Receiver responded with {}", response.status())),
+                duration_ms,
+            },
+            Err(e) => Attempt {
+                status_code: None,
+                error: Some(format!("Request failed: {e}")),
+                duration_ms,
+            },
+        }
+    }
+
+    async fn deliver(&self, delivery: ClaimedDelivery) {
+        let attempt = self.send(&delivery).await;
+        if let Err(e) = self.record_attempt(&delivery, &attempt).await {
+            warn!(
+                "Failed to record attempt {} of callback {}: {e}",
+                delivery.attempts, delivery.id
+            );
+        }
+    }
+
+    async fn record_attempt(
+        &self,
+        delivery: &ClaimedDelivery,
+        attempt: &Attempt,
+    ) -> sqlx::Result<()> {
+        let mut transaction = self.pg_client.begin().await?;
+        sqlx::query!(
+            r#"
+            INSERT INTO callback_delivery_attempts (delivery_id, attempt, status_code, error, duration_ms)
+            VALUES ($1, $2, $3, $4, $5)
+            "#,
+            delivery.id,
+            delivery.attempts,
+            attempt.status_code,
+            attempt.error,
+            attempt.duration_ms
+        )
+        .execute(&mut *transaction)
+        .await?;
+        if attempt.is_success() {
+            debug!("Delivered callback {}", delivery.id);
+            sqlx::query!(
+                r#"
+                UPDATE callback_deliveries
+                SET status = 'delivered', delivered_at = now()
+                WHERE id = $1
+                "#,
+                delivery.id
+            )
+            .execute(&mut *transaction)
+            .await?;
+        } else if delivery.attempts_since_retry() >= MAX_ATTEMPTS {
+            warn!(
+                "Moving callback {} to the dead-letter queue after {} attempts",
+                delivery.id,
+                delivery.attempts_since_retry()
+            );
+            sqlx::query!(
+                r#"
+                UPDATE callback_deliveries
+                SET status = 'dead'
+                WHERE id = $1
+                "#,
+                delivery.id
+            )
+            .execute(&mut *transaction)
+            .await?;
+        } else {
+            sqlx::query!(
+                r#"
+                UPDATE callback_deliveries
+                SET next_attempt_at = now() + make_interval(secs => $2)
+                WHERE id = $1
+                "#,
+                delivery.id,
+                retry_delay_secs(delivery.attempts_since_retry())
+            )
+            .execute(&mut *transaction)
+            .await?;
+        }
+        transaction.commit().await
+    }
+
+    async fn remove_old_deliveries(&self) -> sqlx::Result<()> {
+        sqlx::query!(
+            r#"
+            DELETE FROM callback_deliveries
+            WHERE created_at < now() - make_interval(secs => $1)
+            "#,
+            RETENTION_SECS
+        )
+        .execute(&self.pg_client)
+        .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    #[test]
+    fn test_sign() {
+        let signature = sign("secret", 1_700_000_000, r#"{"match_id":1}"#);
+        let mut mac = HmacSha256::new_from_slice(b"secret").expect("valid key");
+        mac.update(br#"1700000000.{"match_id":1}"#);
+        assert_eq!(signature, hex::encode(mac.finalize().into_bytes()));
+    }
+
+    #[test]
+    fn test_sign_depends_on_timestamp() {
+        assert_ne!(
+            sign("secret", 1_700_000_000, "body"),
+            sign("secret", 1_700_000_001, "body")
+        );
+    }
+
+    #[rstest]
+    #[case("https://example.com/callback", true)]
+    #[case("http://1.1.1.1:8080/callback", true)]
+    #[case("ftp://example.com/callback", false)]
+    #[case("file:///etc/passwd", false)]
+    #[case("http://127.0.0.1/callback", false)]
+    #[case("http://169.254.169.254/latest/meta-data", false)]
+    #[case("http://[::1]/callback", false)]
+    #[case("http://[::ffff:10.0.0.1]/callback", false)]
+    fn test_validate_callback_url(#[case] url: &str, #[case] valid: bool) {
+        let url = Url::parse(url).unwrap();
+        assert_eq!(validate_callback_url(&url).is_ok(), valid);
+    }
+
+    #[rstest]
+    #[case(1, 30.)]
+    #[case(2, 60.)]
+    #[case(5, 480.)]
+    #[case(8, 3600.)]
+    #[case(i32::MAX, 3600.)]
+    fn test_retry_delay_secs(#[case] attempts: i32, #[case] expected: f64) {
+        assert!((retry_delay_secs(attempts) - expected).abs() < f64::EPSILON);
+    }
+}
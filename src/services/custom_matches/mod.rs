@@ -0,0 +1,400 @@
+use core::time::Duration;
+use std::sync::Arc;
+
+use md5::{Digest, Md5};
+use object_store::aws::AmazonS3;
+use prost::Message;
+use redis::AsyncCommands;
+use redis::aio::MultiplexedConnection;
+use serde::Serialize;
+use sqlx::{PgConnection, Pool, Postgres};
+use tokio::time::interval;
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+use valveprotos::deadlock::cso_citadel_party::EPlayerType;
+use valveprotos::deadlock::{CMsgMatchMetaDataContents, CsoCitadelParty};
+
+use crate::error::APIResult;
+use crate::routes::v1::matches::custom::party::{CustomPartyState, get_party_state};
+use crate::routes::v1::matches::metadata::fetch_ingested_match_metadata;
+use crate::routes::v1::tournaments::results::record_tournament_game;
+use crate::services::callbacks::enqueue_callback;
+
+/// Interval for checking the state of active lobbies
+const TRACK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Roster size of a lobby that has no minimum roster size set
+const DEFAULT_ROSTER_SIZE: u32 = 12;
+
+/// Lobbies that did not start after this time were left by the bot.
+const LOBBY_TIMEOUT_SECS: f64 = 30. * 60.;
+
+/// Matches without metadata after this time will never get any.
+const MATCH_TIMEOUT_SECS: f64 = 24. * 60. * 60.;
+
+struct ActiveCustomMatch {
+    party_id: i64,
+    status: String,
+    match_id: Option<i64>,
+}
+
+struct CustomMatchCallback {
+    api_key: Uuid,
+    callback_url: Option<String>,
+    callback_secret: Option<String>,
+}
+
+impl CustomMatchCallback {
+    /// Queues a callback to `{callback_url}{path}`, if a callback url was provided.
+    async fn enqueue(
+        &self,
+        conn: &mut PgConnection,
+        party_id: u64,
+        event: &str,
+        path: &str,
+        body: &impl Serialize,
+    ) -> APIResult<()> {
+        let (Some(callback_url), Some(callback_secret)) =
+            (&self.callback_url, &self.callback_secret)
+        else {
+            return Ok(());
+        };
+        let url = if path.is_empty() {
+            callback_url.clone()
+        } else {
+            format!("{}{path}", callback_url.trim_end_matches('/'))
+        };
+        enqueue_callback(
+            conn,
+            self.api_key,
+            party_id,
+            event,
+            &url,
+            callback_secret,
+            &serde_json::to_string(body)?,
+        )
+        .await?;
+        Ok(())
+    }
+}
+
+#[derive(Serialize)]
+struct CustomMatchStart {
+    party_id: String,
+    match_id: u64,
+}
+
+#[derive(Serialize)]
+struct CustomMatchResult<'a> {
+    party_id: String,
+    match_id: u64,
+    metadata: &'a CMsgMatchMetaDataContents,
+}
+
+/// Returns whether enough players joined the lobby to start the match.
+fn is_filled(party: &CsoCitadelParty) -> bool {
+    let min_roster_size = party
+        .private_lobby_settings
+        .as_ref()
+        .and_then(|s| s.min_roster_size)
+        .filter(|&s| s > 0)
+        .unwrap_or(DEFAULT_ROSTER_SIZE);
+    let players = party
+        .members
+        .iter()
+        .filter(|m| m.player_type() != EPlayerType::KEPlayerTypeSpectator)
+        .count();
+    players >= min_roster_size as usize
+}
+
+/// Digest of the lobby settings, to notice changes of them.
+fn settings_digest(party: &CsoCitadelParty) -> String {
+    let settings = party
+        .private_lobby_settings
+        .as_ref()
+        .map(Message::encode_to_vec)
+        .unwrap_or_default();
+    hex::encode(Md5::digest(settings))
+}
+
+/// Background task tracking the lifecycle of custom match lobbies
+///
+/// Lobbies move from `created` to `filled` once enough players joined, to `started` once the bot
+/// stored the match id, and to `finished` once the metadata of the match was ingested. Every
+/// transition is a conditional update, so every instance can run a tracker.
+///
+/// The callbacks of a lobby are queued in the transaction of the change they report, so each is
+/// sent once.
+pub(crate) struct CustomMatchTracker {
+    pg_client: Pool<Postgres>,
+    redis_client: MultiplexedConnection,
+    ch_client: clickhouse::Client,
+    s3_client: AmazonS3,
+}
+
+impl CustomMatchTracker {
+    pub(crate) fn new(
+        pg_client: Pool<Postgres>,
+        redis_client: MultiplexedConnection,
+        ch_client: clickhouse::Client,
+        s3_client: AmazonS3,
+    ) -> Self {
+        Self {
+            pg_client,
+            redis_client,
+            ch_client,
+            s3_client,
+        }
+    }
+
+    /// Start the background tracking task
+    pub(crate) fn start_background_tracking(self: Arc<Self>) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut interval = interval(TRACK_INTERVAL);
+            info!("Custom match tracker started");
+            loop {
+                interval.tick().await;
+                if let Err(e) = self.track().await {
+                    warn!("Failed to track custom matches: {e}");
+                }
+            }
+        })
+    }
+
+    async fn track(&self) -> APIResult<()> {
+        self.expire_matches().await?;
+        let active = sqlx::query_as!(
+            ActiveCustomMatch,
+            r#"
+            SELECT party_id, status, match_id
+            FROM custom_matches
+            WHERE status IN ('created', 'filled', 'started')
+            "#
+        )
+        .fetch_all(&self.pg_client)
+        .await?;
+
+        let mut started = vec![];
+        for custom_match in active {
+            let party_id = custom_match.party_id.cast_unsigned();
+            match (custom_match.status.as_str(), custom_match.match_id) {
+                ("started", Some(match_id)) => started.push((party_id, match_id.cast_unsigned())),
+                _ => {
+                    if let Err(e) = self.track_lobby(party_id).await {
+                        warn!("Failed to track custom match lobby {party_id}: {e}");
+                    }
+                }
+            }
+        }
+        if started.is_empty() {
+            return Ok(());
+        }
+
+        let match_ids = started.iter().map(|&(_, m)| m).collect::<Vec<_>>();
+        let ingested: Vec<u64> = self
+            .ch_client
+            .query("SELECT match_id FROM match_info WHERE match_id IN ?")
+            .bind(&match_ids)
+            .fetch_all()
+            .await?;
+        for (party_id, match_id) in started {
+            if ingested.contains(&match_id)
+                && let Err(e) = self.finish_match(party_id, match_id).await
+            {
+                warn!("Failed to finish custom match {match_id} of party {party_id}: {e}");
+            }
+        }
+        Ok(())
+    }
+
+    /// Moves a lobby to `started` once the bot stored its match id, or to `filled` once enough
+    /// players joined, and reports changes of its settings.
+    async fn track_lobby(&self, party_id: u64) -> APIResult<()> {
+        let mut redis_client = self.redis_client.clone();
+        let match_id: Option<u64> = redis_client.get(format!("{party_id}:match-id")).await?;
+        if let Some(match_id) = match_id {
+            return self.start_match(party_id, match_id).await;
+        }
+
+        // The party state is missing until the bot received the first update of the lobby
+        let Ok(party) = get_party_state(&mut redis_client, party_id).await else {
+            return Ok(());
+        };
+        self.track_settings(party_id, &party).await?;
+        if is_filled(&party) {
+            sqlx::query!(
+                r#"
+                UPDATE custom_matches
+                SET status = 'filled', filled_at = now()
+                WHERE party_id = $1 AND status = 'created'
+                "#,
+                party_id.cast_signed()
+            )
+            .execute(&self.pg_client)
+            .await?;
+        }
+        Ok(())
+    }
+
+    /// Moves a lobby to `started` and queues the start for the callback url.
+    async fn start_match(&self, party_id: u64, match_id: u64) -> APIResult<()> {
+        let mut transaction = self.pg_client.begin().await?;
+        let started = sqlx::query_as!(
+            CustomMatchCallback,
+            r#"
+            UPDATE custom_matches
+            SET status = 'started', match_id = $2, started_at = now(), filled_at = COALESCE(filled_at, now())
+            WHERE party_id = $1 AND status IN ('created', 'filled')
+            RETURNING api_key, callback_url, callback_secret
+            "#,
+            party_id.cast_signed(),
+            match_id.cast_signed()
+        )
+        .fetch_optional(&mut *transaction)
+        .await?;
+        // Another instance started the match already
+        let Some(started) = started else {
+            return Ok(());
+        };
+        debug!("Custom match {match_id} of party {party_id} started");
+
+        let start = CustomMatchStart {
+            party_id: party_id.to_string(),
+            match_id,
+        };
+        started
+            .enqueue(&mut transaction, party_id, "start", "", &start)
+            .await?;
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    /// Queues the party state for the callback url when the settings of the lobby changed.
+    async fn track_settings(&self, party_id: u64, party: &CsoCitadelParty) -> APIResult<()> {
+        let mut transaction = self.pg_client.begin().await?;
+        let changed = sqlx::query_as!(
+            CustomMatchCallback,
+            r#"
+            UPDATE custom_matches
+            SET settings_digest = $2
+            WHERE party_id = $1 AND settings_digest IS DISTINCT FROM $2
+            RETURNING api_key, callback_url, callback_secret
+            "#,
+            party_id.cast_signed(),
+            settings_digest(party)
+        )
+        .fetch_optional(&mut *transaction)
+        .await?;
+        // The settings didn't change, or another instance reported the change already
+        let Some(changed) = changed else {
+            return Ok(());
+        };
+
+        let state = CustomPartyState::from(party.clone());
+        changed
+            .enqueue(&mut transaction, party_id, "settings", "/settings", &state)
+            .await?;
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    /// Moves a match to `finished`, queues its result for the callback url and records it for its
+    /// tournament.
+    async fn finish_match(&self, party_id: u64, match_id: u64) -> APIResult<()> {
+        let metadata = fetch_ingested_match_metadata(&self.s3_client, match_id).await?;
+        let mut transaction = self.pg_client.begin().await?;
+        let finished = sqlx::query_as!(
+            CustomMatchCallback,
+            r#"
+            UPDATE custom_matches
+            SET status = 'finished', finished_at = now()
+            WHERE party_id = $1 AND status = 'started'
+            RETURNING api_key, callback_url, callback_secret
+            "#,
+            party_id.cast_signed()
+        )
+        .fetch_optional(&mut *transaction)
+        .await?;
+        // Another instance finished the match already
+        let Some(finished) = finished else {
+            return Ok(());
+        };
+        info!("Custom match {match_id} of party {party_id} finished");
+
+        let result = CustomMatchResult {
+            party_id: party_id.to_string(),
+            match_id,
+            metadata: &metadata,
+        };
+        finished
+            .enqueue(&mut transaction, party_id, "result", "/result", &result)
+            .await?;
+        record_tournament_game(&mut *transaction, party_id, match_id, &metadata).await?;
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    /// Marks lobbies as expired that never started, and matches that never got metadata.
+    async fn expire_matches(&self) -> sqlx::Result<()> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE custom_matches
+            SET status = 'expired'
+            WHERE (status IN ('created', 'filled') AND created_at < now() - make_interval(secs => $1))
+               OR (status = 'started' AND started_at < now() - make_interval(secs => $2))
+            "#,
+            LOBBY_TIMEOUT_SECS,
+            MATCH_TIMEOUT_SECS
+        )
+        .execute(&self.pg_client)
+        .await?;
+        if result.rows_affected() > 0 {
+            debug!("Expired {} custom matches", result.rows_affected());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+    use valveprotos::deadlock::cso_citadel_party::{Member, PrivateLobbySettings};
+
+    use super::*;
+
+    fn party(players: u32, spectators: u32, min_roster_size: Option<u32>) -> CsoCitadelParty {
+        let member = |player_type: EPlayerType| Member {
+            player_type: Some(player_type as i32),
+            ..Default::default()
+        };
+        CsoCitadelParty {
+            members: (0..players)
+                .map(|_| member(EPlayerType::KEPlayerTypePlayer))
+                .chain((0..spectators).map(|_| member(EPlayerType::KEPlayerTypeSpectator)))
+                .collect(),
+            private_lobby_settings: Some(PrivateLobbySettings {
+                min_roster_size,
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_settings_digest() {
+        let digest = settings_digest(&party(2, 1, Some(2)));
+        assert_eq!(digest, settings_digest(&party(3, 0, Some(2))));
+        assert_ne!(digest, settings_digest(&party(2, 1, Some(4))));
+        assert_ne!(digest, settings_digest(&CsoCitadelParty::default()));
+    }
+
+    #[rstest]
+    #[case(party(12, 1, None), true)]
+    #[case(party(11, 2, None), false)]
+    #[case(party(2, 1, Some(2)), true)]
+    #[case(party(1, 1, Some(2)), false)]
+    #[case(party(12, 0, Some(0)), true)]
+    fn test_is_filled(#[case] party: CsoCitadelParty, #[case] expected: bool) {
+        assert_eq!(is_filled(&party), expected);
+    }
+}
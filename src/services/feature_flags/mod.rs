@@ -0,0 +1,179 @@
+mod types;
+
+use core::time::Duration;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use sqlx::{PgConnection, Pool, Postgres};
+use tokio::sync::RwLock;
+use tokio::time::interval;
+use tracing::{info, warn};
+pub(crate) use types::{RouteAccess, RouteFlag};
+use uuid::Uuid;
+
+use crate::services::rate_limiter::extractor::RateLimitKey;
+
+const RELOAD_INTERVAL_SECS: u64 = 30;
+
+/// Route feature flags stored in Postgres.
+///
+/// Flags are kept in memory and reloaded periodically, so changes made by other instances are
+/// picked up without a redeploy.
+pub(crate) struct FeatureFlags {
+    routes: RwLock<HashMap<String, RouteFlag>>,
+    pg_client: Pool<Postgres>,
+}
+
+impl FeatureFlags {
+    pub(crate) fn new(pg_client: Pool<Postgres>) -> Self {
+        Self {
+            routes: RwLock::new(HashMap::new()),
+            pg_client,
+        }
+    }
+
+    /// Returns how the route may be accessed, or `None` if there is no flag for it.
+    pub(crate) async fn evaluate(
+        &self,
+        path: &str,
+        rate_limit_key: &RateLimitKey,
+    ) -> Option<RouteAccess> {
+        self.routes
+            .read()
+            .await
+            .get(path)
+            .map(|flag| flag.evaluate(rate_limit_key.api_key, &rate_limit_key.client_id()))
+    }
+
+    /// Start the background reload task
+    pub(crate) fn start_background_reload(self: Arc<Self>) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut interval = interval(Duration::from_secs(RELOAD_INTERVAL_SECS));
+            info!("Feature flags background reload task started");
+
+            loop {
+                interval.tick().await;
+                if let Err(e) = self.reload().await {
+                    warn!("Failed to reload feature flags: {e}");
+                }
+            }
+        })
+    }
+
+    /// Replaces the in-memory flags with the current state of the database
+    pub(crate) async fn reload(&self) -> sqlx::Result<()> {
+        let routes = self.fetch_all().await?;
+        *self.routes.write().await = routes
+            .into_iter()
+            .map(|flag| (flag.path.clone(), flag))
+            .collect();
+        Ok(())
+    }
+
+    pub(crate) async fn fetch_all(&self) -> sqlx::Result<Vec<RouteFlag>> {
+        let flags = sqlx::query!(
+            r#"
+            SELECT path, enabled, rollout_percent, degraded
+            FROM feature_flags
+            ORDER BY path
+            "#
+        )
+        .fetch_all(&self.pg_client)
+        .await?;
+        let overrides = sqlx::query!(
+            r#"
+            SELECT path, key, enabled
+            FROM feature_flag_overrides
+            "#
+        )
+        .fetch_all(&self.pg_client)
+        .await?;
+
+        let mut overrides_by_path: HashMap<String, HashMap<Uuid, bool>> = HashMap::new();
+        for row in overrides {
+            overrides_by_path
+                .entry(row.path)
+                .or_default()
+                .insert(row.key, row.enabled);
+        }
+        Ok(flags
+            .into_iter()
+            .map(|row| RouteFlag {
+                overrides: overrides_by_path.remove(&row.path).unwrap_or_default(),
+                path: row.path,
+                enabled: row.enabled,
+                rollout_percent: u8::try_from(row.rollout_percent).unwrap_or(100),
+                degraded: row.degraded,
+            })
+            .collect())
+    }
+
+    /// Creates or updates the flag for a route, keeping its overrides
+    ///
+    /// Changes are picked up by the next [`reload`](Self::reload), after they were committed.
+    pub(crate) async fn upsert(conn: &mut PgConnection, flag: &RouteFlag) -> sqlx::Result<()> {
+        sqlx::query!(
+            r#"
+            INSERT INTO feature_flags (path, enabled, rollout_percent, degraded)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (path) DO UPDATE SET
+                enabled = EXCLUDED.enabled,
+                rollout_percent = EXCLUDED.rollout_percent,
+                degraded = EXCLUDED.degraded,
+                updated_at = NOW()
+            "#,
+            flag.path,
+            flag.enabled,
+            i16::from(flag.rollout_percent),
+            flag.degraded,
+        )
+        .execute(conn)
+        .await?;
+        Ok(())
+    }
+
+    /// Deletes the flag for a route, including its overrides. Returns whether a flag existed.
+    pub(crate) async fn delete(conn: &mut PgConnection, path: &str) -> sqlx::Result<bool> {
+        let result = sqlx::query!("DELETE FROM feature_flags WHERE path = $1", path)
+            .execute(conn)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub(crate) async fn set_override(
+        conn: &mut PgConnection,
+        path: &str,
+        api_key: Uuid,
+        enabled: bool,
+    ) -> sqlx::Result<()> {
+        sqlx::query!(
+            r#"
+            INSERT INTO feature_flag_overrides (path, key, enabled)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (path, key) DO UPDATE SET enabled = EXCLUDED.enabled
+            "#,
+            path,
+            api_key,
+            enabled,
+        )
+        .execute(conn)
+        .await?;
+        Ok(())
+    }
+
+    /// Removes an API key override. Returns whether an override existed.
+    pub(crate) async fn delete_override(
+        conn: &mut PgConnection,
+        path: &str,
+        api_key: Uuid,
+    ) -> sqlx::Result<bool> {
+        let result = sqlx::query!(
+            "DELETE FROM feature_flag_overrides WHERE path = $1 AND key = $2",
+            path,
+            api_key
+        )
+        .execute(conn)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+}
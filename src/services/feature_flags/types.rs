@@ -0,0 +1,122 @@
+use core::hash::{Hash, Hasher};
+use std::collections::HashMap;
+use std::hash::DefaultHasher;
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Feature flag for a single route, keyed by its matched path (e.g. `/v1/sql`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct RouteFlag {
+    pub(crate) path: String,
+    /// Whether the route is enabled at all
+    pub(crate) enabled: bool,
+    /// Percentage of clients (0-100) the route is enabled for
+    pub(crate) rollout_percent: u8,
+    /// Serve the last known response instead of calling the handler, and reject writes
+    pub(crate) degraded: bool,
+    /// Per API key overrides of `enabled` and `rollout_percent`
+    #[serde(default)]
+    pub(crate) overrides: HashMap<Uuid, bool>,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub(crate) enum RouteAccess {
+    Enabled,
+    Disabled,
+    Degraded,
+}
+
+impl RouteFlag {
+    pub(crate) fn evaluate(&self, api_key: Option<Uuid>, client_id: &str) -> RouteAccess {
+        if self.degraded {
+            return RouteAccess::Degraded;
+        }
+        if let Some(enabled) = api_key.and_then(|k| self.overrides.get(&k)) {
+            return if *enabled {
+                RouteAccess::Enabled
+            } else {
+                RouteAccess::Disabled
+            };
+        }
+        if self.enabled && rollout_bucket(&self.path, client_id) < self.rollout_percent {
+            RouteAccess::Enabled
+        } else {
+            RouteAccess::Disabled
+        }
+    }
+}
+
+/// Deterministically assigns a client to a bucket in `0..100`, so a client keeps seeing the same
+/// result for a route while its rollout percentage is unchanged.
+fn rollout_bucket(path: &str, client_id: &str) -> u8 {
+    let mut hasher = DefaultHasher::new();
+    path.hash(&mut hasher);
+    client_id.hash(&mut hasher);
+    #[allow(clippy::cast_possible_truncation)]
+    let bucket = (hasher.finish() % 100) as u8;
+    bucket
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    fn flag(enabled: bool, rollout_percent: u8, degraded: bool) -> RouteFlag {
+        RouteFlag {
+            path: "/v1/sql".to_owned(),
+            enabled,
+            rollout_percent,
+            degraded,
+            overrides: HashMap::new(),
+        }
+    }
+
+    #[rstest]
+    #[case(flag(true, 100, false), RouteAccess::Enabled)]
+    #[case(flag(false, 100, false), RouteAccess::Disabled)]
+    #[case(flag(true, 0, false), RouteAccess::Disabled)]
+    #[case(flag(true, 100, true), RouteAccess::Degraded)]
+    #[case(flag(false, 0, true), RouteAccess::Degraded)]
+    fn test_evaluate(#[case] flag: RouteFlag, #[case] expected: RouteAccess) {
+        assert_eq!(flag.evaluate(None, "1.2.3.4"), expected);
+    }
+
+    #[rstest]
+    #[case(false, true, RouteAccess::Enabled)]
+    #[case(true, false, RouteAccess::Disabled)]
+    fn test_evaluate_override(
+        #[case] enabled: bool,
+        #[case] override_enabled: bool,
+        #[case] expected: RouteAccess,
+    ) {
+        let api_key = Uuid::new_v4();
+        let mut flag = flag(enabled, 100, false);
+        flag.overrides.insert(api_key, override_enabled);
+
+        assert_eq!(flag.evaluate(Some(api_key), &api_key.to_string()), expected);
+        assert_eq!(
+            flag.evaluate(Some(Uuid::new_v4()), "other"),
+            flag.evaluate(None, "other")
+        );
+    }
+
+    #[test]
+    fn test_rollout_is_stable_and_proportional() {
+        let flag = flag(true, 30, false);
+        let enabled = (0..10_000)
+            .filter(|i| flag.evaluate(None, &i.to_string()) == RouteAccess::Enabled)
+            .count();
+        assert!((2_500..3_500).contains(&enabled), "enabled for {enabled}");
+
+        for i in 0..100 {
+            let client_id = i.to_string();
+            assert_eq!(
+                flag.evaluate(None, &client_id),
+                flag.evaluate(None, &client_id)
+            );
+        }
+    }
+}
@@ -0,0 +1,405 @@
+use std::collections::HashMap;
+
+use cached::TimedCache;
+use cached::proc_macro::cached;
+use chrono::{DateTime, Utc};
+use clickhouse::Row;
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use crate::error::APIResult;
+use crate::routes::v1::leaderboard::types::{Leaderboard, LeaderboardRegion};
+use crate::routes::v1::players::mmr::batch::get_mmr;
+
+const NAME_WEIGHT: f64 = 0.4;
+const RANK_WEIGHT: f64 = 0.4;
+const ACTIVITY_WEIGHT: f64 = 0.2;
+/// Rank score of candidates without MMR history
+const UNKNOWN_RANK_SCORE: f64 = 0.3;
+/// Mappings with at least this confidence are persisted
+const MIN_PERSIST_CONFIDENCE: f64 = 0.8;
+/// How long a persisted mapping is trusted without rescoring
+const MAPPING_TTL_DAYS: u32 = 30;
+
+/// The resolved account of a leaderboard entry
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct AccountResolution {
+    pub(crate) account_id: Option<u32>,
+    pub(crate) confidence: Option<f64>,
+    /// All candidates, the most likely first
+    pub(crate) possible_account_ids: Vec<u32>,
+}
+
+/// A confirmed mapping of a leaderboard name to an account
+#[derive(Debug, Clone, Row, Serialize, Deserialize)]
+struct AccountMapping {
+    region: u8,
+    account_name: String,
+    account_id: u32,
+    confidence: f64,
+    #[serde(with = "clickhouse::serde::chrono::datetime")]
+    updated_at: DateTime<Utc>,
+}
+
+/// An account that used the name of a leaderboard entry
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Candidate {
+    account_id: u32,
+    /// Whether the account still uses the name
+    current_name: bool,
+    /// Latest MMR, as index into the rank array
+    player_score: Option<f64>,
+    /// Start time of the latest match
+    last_match: Option<u32>,
+}
+
+/// Converts a badge level into the index of the rank array, like the MMR player score.
+fn badge_index(badge_level: u32) -> f64 {
+    f64::from((badge_level / 10).saturating_sub(1) * 6 + badge_level % 10)
+}
+
+fn rank_score(badge_level: Option<u32>, player_score: Option<f64>) -> f64 {
+    match (badge_level, player_score) {
+        (Some(badge_level), Some(player_score)) => {
+            (1.0 - (badge_index(badge_level) - player_score).abs() / 6.0).max(0.0)
+        }
+        _ => UNKNOWN_RANK_SCORE,
+    }
+}
+
+fn activity_score(last_match: Option<u32>, now: DateTime<Utc>) -> f64 {
+    let Some(last_match) = last_match else {
+        return 0.0;
+    };
+    match (now.timestamp() - i64::from(last_match)) / (24 * 60 * 60) {
+        ..=14 => 1.0,
+        ..=30 => 0.5,
+        _ => 0.0,
+    }
+}
+
+impl Candidate {
+    fn score(&self, badge_level: Option<u32>, now: DateTime<Utc>) -> f64 {
+        let name_score = if self.current_name { 1.0 } else { 0.5 };
+        NAME_WEIGHT * name_score
+            + RANK_WEIGHT * rank_score(badge_level, self.player_score)
+            + ACTIVITY_WEIGHT * activity_score(self.last_match, now)
+    }
+}
+
+/// Picks the best scoring candidate.
+///
+/// The confidence is the best score, weighted by its share of all scores, so a single perfect
+/// candidate has a confidence of 1 and two equally likely candidates have at most 0.5.
+fn resolve(
+    candidates: &[Candidate],
+    badge_level: Option<u32>,
+    now: DateTime<Utc>,
+) -> AccountResolution {
+    let scored = candidates
+        .iter()
+        .map(|c| (c.account_id, c.score(badge_level, now)))
+        .sorted_by(|a, b| b.1.total_cmp(&a.1))
+        .collect_vec();
+    let total: f64 = scored.iter().map(|(_, score)| score).sum();
+    let best = scored
+        .first()
+        .filter(|_| total > 0.0)
+        .map(|&(account_id, score)| (account_id, score * score / total));
+    AccountResolution {
+        account_id: best.map(|(account_id, _)| account_id),
+        confidence: best.map(|(_, confidence)| confidence),
+        possible_account_ids: scored
+            .into_iter()
+            .map(|(account_id, _)| account_id)
+            .collect(),
+    }
+}
+
+async fn fetch_mappings(
+    ch_client: &clickhouse::Client,
+    region: LeaderboardRegion,
+    names: &[&str],
+) -> clickhouse::error::Result<Vec<AccountMapping>> {
+    let query = format!(
+        "
+        SELECT ?fields
+        FROM leaderboard_account_ids FINAL
+        WHERE region = {} AND has(?, account_name)
+            AND updated_at > now() - INTERVAL {MAPPING_TTL_DAYS} DAY
+        ",
+        region as u8
+    );
+    debug!(?query);
+    ch_client.query(&query).bind(names).fetch_all().await
+}
+
+async fn fetch_candidates(
+    ch_client: &clickhouse::Client,
+    names: &[&str],
+) -> APIResult<HashMap<String, Vec<Candidate>>> {
+    #[derive(Deserialize, Row)]
+    struct CHResponse {
+        name: String,
+        account_id: u32,
+        current_name: bool,
+    }
+
+    let query = "
+        WITH
+            ? AS names,
+            current_names AS (
+                SELECT account_id, argMax(personaname, last_updated) AS latest_name
+                FROM steam_profiles
+                WHERE account_id IN (SELECT account_id FROM steam_profiles WHERE has(names, personaname))
+                GROUP BY account_id
+            )
+        SELECT DISTINCT
+            assumeNotNull(personaname) AS name,
+            account_id,
+            name = assumeNotNull(latest_name) AS current_name
+        FROM steam_profiles
+        INNER JOIN current_names USING account_id
+        WHERE has(names, personaname)
+    ";
+    let rows = ch_client
+        .query(query)
+        .bind(names)
+        .fetch_all::<CHResponse>()
+        .await?;
+    let account_ids = rows.iter().map(|r| r.account_id).unique().collect_vec();
+    let mmr = if account_ids.is_empty() {
+        HashMap::new()
+    } else {
+        get_mmr(ch_client, &account_ids, None)
+            .await?
+            .into_iter()
+            .map(|m| (m.account_id, m))
+            .collect()
+    };
+    Ok(rows
+        .into_iter()
+        .map(|row| {
+            let mmr = mmr.get(&row.account_id);
+            let candidate = Candidate {
+                account_id: row.account_id,
+                current_name: row.current_name,
+                player_score: mmr.map(|m| m.player_score),
+                last_match: mmr.map(|m| m.start_time),
+            };
+            (row.name, candidate)
+        })
+        .into_group_map())
+}
+
+#[cached(
+    ty = "TimedCache<(LeaderboardRegion, Option<u32>), HashMap<String, AccountResolution>>",
+    create = "{ TimedCache::with_lifespan(std::time::Duration::from_secs(10 * 60)) }",
+    result = true,
+    convert = "{ (region, hero_id) }",
+    sync_writes = "by_key",
+    key = "(LeaderboardRegion, Option<u32>)"
+)]
+async fn resolve_leaderboard(
+    ch_client: &clickhouse::Client,
+    ch_client_ro: &clickhouse::Client,
+    region: LeaderboardRegion,
+    hero_id: Option<u32>,
+    leaderboard: &Leaderboard,
+) -> APIResult<HashMap<String, AccountResolution>> {
+    debug!("Resolving account ids of leaderboard {region:?} {hero_id:?}");
+    let entries = leaderboard
+        .entries
+        .iter()
+        .filter_map(|e| Some((e.account_name.as_deref()?, e.badge_level)))
+        .collect_vec();
+    let names = entries.iter().map(|&(name, _)| name).collect_vec();
+
+    let mut resolutions: HashMap<String, AccountResolution> =
+        fetch_mappings(ch_client_ro, region, &names)
+            .await?
+            .into_iter()
+            .map(|m| {
+                let resolution = AccountResolution {
+                    account_id: Some(m.account_id),
+                    confidence: Some(m.confidence),
+                    possible_account_ids: vec![m.account_id],
+                };
+                (m.account_name, resolution)
+            })
+            .collect();
+
+    let unresolved = names
+        .iter()
+        .copied()
+        .filter(|name| !resolutions.contains_key(*name))
+        .collect_vec();
+    if unresolved.is_empty() {
+        return Ok(resolutions);
+    }
+    let candidates = fetch_candidates(ch_client_ro, &unresolved).await?;
+
+    let now = Utc::now();
+    let mut confirmed = vec![];
+    for (name, badge_level) in entries {
+        if resolutions.contains_key(name) {
+            continue;
+        }
+        let Some(candidates) = candidates.get(name) else {
+            continue;
+        };
+        let resolution = resolve(candidates, badge_level, now);
+        if let (Some(account_id), Some(confidence)) = (resolution.account_id, resolution.confidence)
+            && confidence >= MIN_PERSIST_CONFIDENCE
+        {
+            confirmed.push(AccountMapping {
+                region: region as u8,
+                account_name: name.to_owned(),
+                account_id,
+                confidence,
+                updated_at: now,
+            });
+        }
+        resolutions.insert(name.to_owned(), resolution);
+    }
+
+    if !confirmed.is_empty() {
+        let mut inserter = ch_client
+            .insert::<AccountMapping>("leaderboard_account_ids")
+            .await?;
+        for mapping in &confirmed {
+            inserter.write(mapping).await?;
+        }
+        inserter.end().await?;
+    }
+    Ok(resolutions)
+}
+
+/// Resolves the account IDs of all leaderboard entries.
+///
+/// Candidates are all accounts that used the name of an entry, scored by whether they still use
+/// it, how well their MMR matches the badge level and how recently they played. Confident
+/// mappings are persisted, so later lookups don't depend on the name history anymore.
+pub(crate) async fn resolve_account_ids(
+    ch_client: &clickhouse::Client,
+    ch_client_ro: &clickhouse::Client,
+    region: LeaderboardRegion,
+    hero_id: Option<u32>,
+    leaderboard: &mut Leaderboard,
+) -> APIResult<()> {
+    let resolutions =
+        resolve_leaderboard(ch_client, ch_client_ro, region, hero_id, leaderboard).await?;
+    for entry in &mut leaderboard.entries {
+        let Some(resolution) = entry
+            .account_name
+            .as_ref()
+            .and_then(|name| resolutions.get(name))
+        else {
+            continue;
+        };
+        entry.account_id = resolution.account_id;
+        entry.account_id_confidence = resolution.confidence;
+        entry
+            .possible_account_ids
+            .clone_from(&resolution.possible_account_ids);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeDelta;
+    use rstest::rstest;
+
+    use super::*;
+
+    fn candidate(
+        account_id: u32,
+        current_name: bool,
+        player_score: Option<f64>,
+        days_since_last_match: Option<i64>,
+    ) -> Candidate {
+        let now = Utc::now();
+        Candidate {
+            account_id,
+            current_name,
+            player_score,
+            last_match: days_since_last_match
+                .map(|d| u32::try_from((now - TimeDelta::days(d)).timestamp()).unwrap()),
+        }
+    }
+
+    #[rstest]
+    #[case(11, 1.0)]
+    #[case(16, 6.0)]
+    #[case(116, 66.0)]
+    #[case(0, 0.0)]
+    fn test_badge_index(#[case] badge_level: u32, #[case] expected: f64) {
+        assert!((badge_index(badge_level) - expected).abs() < f64::EPSILON);
+    }
+
+    #[rstest]
+    #[case(Some(116), Some(66.0), 1.0)]
+    #[case(Some(116), Some(63.0), 0.5)]
+    #[case(Some(116), Some(50.0), 0.0)]
+    #[case(Some(116), None, UNKNOWN_RANK_SCORE)]
+    #[case(None, Some(66.0), UNKNOWN_RANK_SCORE)]
+    fn test_rank_score(
+        #[case] badge_level: Option<u32>,
+        #[case] player_score: Option<f64>,
+        #[case] expected: f64,
+    ) {
+        assert!((rank_score(badge_level, player_score) - expected).abs() < f64::EPSILON);
+    }
+
+    #[rstest]
+    #[case(Some(1), 1.0)]
+    #[case(Some(20), 0.5)]
+    #[case(Some(60), 0.0)]
+    #[case(None, 0.0)]
+    fn test_activity_score(#[case] days_since_last_match: Option<i64>, #[case] expected: f64) {
+        let last_match = candidate(1, true, None, days_since_last_match).last_match;
+        assert!((activity_score(last_match, Utc::now()) - expected).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn test_resolve_single_match() {
+        let candidates = [candidate(1, true, Some(66.0), Some(1))];
+        let resolution = resolve(&candidates, Some(116), Utc::now());
+        assert_eq!(resolution.account_id, Some(1));
+        assert!((resolution.confidence.unwrap() - 1.0).abs() < 1e-9);
+        assert_eq!(resolution.possible_account_ids, vec![1]);
+    }
+
+    #[test]
+    fn test_resolve_prefers_consistent_candidate() {
+        let candidates = [
+            candidate(1, false, Some(20.0), None),
+            candidate(2, true, Some(65.0), Some(2)),
+            candidate(3, true, None, Some(100)),
+        ];
+        let resolution = resolve(&candidates, Some(116), Utc::now());
+        assert_eq!(resolution.account_id, Some(2));
+        assert_eq!(resolution.possible_account_ids, vec![2, 3, 1]);
+        assert!(resolution.confidence.unwrap() < MIN_PERSIST_CONFIDENCE);
+    }
+
+    #[test]
+    fn test_resolve_ambiguous() {
+        let candidates = [
+            candidate(1, true, Some(66.0), Some(1)),
+            candidate(2, true, Some(66.0), Some(1)),
+        ];
+        let resolution = resolve(&candidates, Some(116), Utc::now());
+        assert!((resolution.confidence.unwrap() - 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_resolve_no_candidates() {
+        assert_eq!(
+            resolve(&[], Some(116), Utc::now()),
+            AccountResolution::default()
+        );
+    }
+}
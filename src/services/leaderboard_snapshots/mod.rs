@@ -0,0 +1,149 @@
+use core::time::Duration;
+use std::sync::Arc;
+
+use chrono::{DateTime, DurationRound, TimeDelta, Utc};
+use redis::aio::MultiplexedConnection;
+use redis::{AsyncCommands, ExistenceCheck, SetExpiry, SetOptions};
+use strum::IntoEnumIterator;
+use tokio::time::interval;
+use tracing::{debug, info, warn};
+use valveprotos::deadlock::CMsgClientToGcGetLeaderboardResponse;
+
+use crate::error::APIResult;
+use crate::routes::v1::leaderboard::route::fetch_leaderboard_raw;
+use crate::routes::v1::leaderboard::types::{
+    Leaderboard, LeaderboardRegion, LeaderboardSnapshotRow,
+};
+use crate::services::assets::client::AssetsClient;
+use crate::services::steam::client::SteamClient;
+use crate::services::steam::types::SteamProxyResponse;
+
+/// Valve updates the leaderboards once per hour
+const SNAPSHOT_INTERVAL: Duration = Duration::from_hours(1);
+
+/// Hourly job that stores every leaderboard in `ClickHouse`
+///
+/// Snapshots the overall and all hero leaderboards of every region, so the history of a
+/// leaderboard can be queried later on.
+pub(crate) struct LeaderboardSnapshotJob {
+    steam_client: SteamClient,
+    assets_client: AssetsClient,
+    ch_client: clickhouse::Client,
+    redis_client: MultiplexedConnection,
+}
+
+impl LeaderboardSnapshotJob {
+    pub(crate) fn new(
+        steam_client: SteamClient,
+        assets_client: AssetsClient,
+        ch_client: clickhouse::Client,
+        redis_client: MultiplexedConnection,
+    ) -> Self {
+        Self {
+            steam_client,
+            assets_client,
+            ch_client,
+            redis_client,
+        }
+    }
+
+    /// Start the background snapshot task
+    pub(crate) fn start_background_snapshots(self: Arc<Self>) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut interval = interval(SNAPSHOT_INTERVAL);
+            info!("Leaderboard snapshot task started");
+            loop {
+                interval.tick().await;
+                self.snapshot_all().await;
+            }
+        })
+    }
+
+    async fn snapshot_all(&self) {
+        let snapshot_time = Utc::now()
+            .duration_trunc(TimeDelta::hours(1))
+            .unwrap_or_else(|_| Utc::now());
+        if !self.acquire_snapshot_lock(snapshot_time).await {
+            debug!("Leaderboard snapshot for {snapshot_time} is already taken");
+            return;
+        }
+
+        let hero_ids = match self.assets_client.fetch_heroes().await {
+            Ok(heroes) => heroes.into_iter().map(|h| h.id).collect(),
+            Err(e) => {
+                warn!("Failed to fetch heroes, only snapshotting overall leaderboards: {e}");
+                vec![]
+            }
+        };
+        let boards: Vec<Option<u32>> = core::iter::once(None)
+            .chain(hero_ids.into_iter().map(Some))
+            .collect();
+
+        let mut entries = 0;
+        let mut failures = 0;
+        for region in LeaderboardRegion::iter() {
+            for &hero_id in &boards {
+                match self.snapshot(snapshot_time, region, hero_id).await {
+                    Ok(count) => entries += count,
+                    Err(e) => {
+                        warn!("Failed to snapshot leaderboard {region:?} {hero_id:?}: {e}");
+                        failures += 1;
+                    }
+                }
+            }
+        }
+        info!(
+            "Leaderboard snapshot for {snapshot_time} done: {entries} entries, {failures} failed \
+             leaderboards"
+        );
+    }
+
+    /// Ensures that only one instance takes the snapshot of an hour.
+    ///
+    /// If Redis is unavailable, the snapshot is taken anyway.
+    async fn acquire_snapshot_lock(&self, snapshot_time: DateTime<Utc>) -> bool {
+        let options = SetOptions::default()
+            .conditional_set(ExistenceCheck::NX)
+            .with_expiration(SetExpiry::EX(SNAPSHOT_INTERVAL.as_secs()));
+        let result: redis::RedisResult<Option<String>> = self
+            .redis_client
+            .clone()
+            .set_options(
+                format!("leaderboard-snapshot:{}", snapshot_time.timestamp()),
+                1,
+                options,
+            )
+            .await;
+        match result {
+            Ok(acquired) => acquired.is_some(),
+            Err(e) => {
+                warn!("Failed to acquire leaderboard snapshot lock: {e}");
+                true
+            }
+        }
+    }
+
+    async fn snapshot(
+        &self,
+        snapshot_time: DateTime<Utc>,
+        region: LeaderboardRegion,
+        hero_id: Option<u32>,
+    ) -> APIResult<usize> {
+        let raw_leaderboard = fetch_leaderboard_raw(&self.steam_client, region, hero_id).await?;
+        let proto_leaderboard: SteamProxyResponse<CMsgClientToGcGetLeaderboardResponse> =
+            raw_leaderboard.try_into()?;
+        let leaderboard: Leaderboard = proto_leaderboard.msg.try_into()?;
+        let rows =
+            LeaderboardSnapshotRow::from_leaderboard(snapshot_time, region, hero_id, leaderboard);
+
+        let mut inserter = self
+            .ch_client
+            .insert::<LeaderboardSnapshotRow>("leaderboard_snapshots")
+            .await?;
+        for row in &rows {
+            inserter.write(row).await?;
+        }
+        inserter.end().await?;
+        Ok(rows.len())
+    }
+}
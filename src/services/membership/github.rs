@@ -0,0 +1,500 @@
+use axum::http::HeaderMap;
+use axum::http::header::{ACCEPT, USER_AGENT};
+use chrono::{Duration, Utc};
+use futures::FutureExt;
+use futures::future::BoxFuture;
+use serde::Deserialize;
+use serde_json::json;
+use tracing::{info, warn};
+
+use super::webhook_worker::dedupe_key;
+use super::{
+    MemberIdentity, MembershipError, MembershipProvider, MembershipProviderKind, MembershipResult,
+    MembershipStatus, ProviderTokens, WebhookDelivery, WebhookError, header, verify_hmac_sha256,
+};
+use crate::context::GitHubSponsorsConfig;
+
+const AUTHORIZE_ENDPOINT: &str = "https://github.com/login/oauth/authorize";
+const TOKEN_ENDPOINT: &str = "https://github.com/login/oauth/access_token";
+const USER_ENDPOINT: &str = "https://api.github.com/user";
+const GRAPHQL_ENDPOINT: &str = "https://api.github.com/graphql";
+
+/// GitHub requires a user agent on API requests
+const API_USER_AGENT: &str = "deadlock-api";
+
+/// GitHub OAuth scopes required for this application
+const GITHUB_SCOPES: &str = "read:user user:email";
+
+/// Fetches the active sponsorship of the token owner for the sponsorable user
+const SPONSORSHIP_QUERY: &str = "query($login: String!) { user(login: $login) { \
+    sponsorshipForViewerAsSponsor(activeOnly: true) { isActive tier { id monthlyPriceInCents \
+    isOneTimePayment } } } }";
+
+/// Build the GitHub OAuth authorization URL
+fn build_github_auth_url(client_id: &str, redirect_uri: &str, state: &str) -> String {
+    let encoded_redirect_uri = urlencoding::encode(redirect_uri);
+    let encoded_scopes = urlencoding::encode(GITHUB_SCOPES);
+
+    format!(
+        "{AUTHORIZE_ENDPOINT}?client_id={client_id}&redirect_uri={encoded_redirect_uri}&scope={encoded_scopes}&state={state}"
+    )
+}
+
+/// Maps a sponsorship tier to a membership.
+///
+/// Tiers are priced monthly, so the price is the pledge. One-time sponsorships don't grant
+/// recurring perks.
+fn tier_membership(
+    tier_id: &str,
+    monthly_price_in_cents: i32,
+    is_one_time: bool,
+) -> MembershipStatus {
+    if is_one_time {
+        return MembershipStatus::none();
+    }
+    MembershipStatus {
+        tier_id: Some(tier_id.to_owned()),
+        pledge_amount_cents: Some(monthly_price_in_cents),
+        is_active: true,
+    }
+}
+
+/// Response from the GitHub OAuth token endpoint, errors are returned with status 200
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    access_token: Option<String>,
+    refresh_token: Option<String>,
+    /// Only set if token expiration is enabled for the app
+    expires_in: Option<i64>,
+    error_description: Option<String>,
+}
+
+impl TryFrom<TokenResponse> for ProviderTokens {
+    type Error = MembershipError;
+
+    fn try_from(response: TokenResponse) -> Result<Self, Self::Error> {
+        let Some(access_token) = response.access_token else {
+            return Err(MembershipError::InvalidResponse(
+                response
+                    .error_description
+                    .unwrap_or_else(|| "missing access token".to_owned()),
+            ));
+        };
+        Ok(Self {
+            access_token,
+            refresh_token: response.refresh_token,
+            expires_at: response
+                .expires_in
+                .map(|expires_in| Utc::now() + Duration::seconds(expires_in)),
+        })
+    }
+}
+
+/// A GitHub user or organization
+#[derive(Debug, Deserialize)]
+struct GitHubAccount {
+    id: u64,
+    login: String,
+    email: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GraphQlResponse {
+    data: Option<SponsorshipData>,
+    #[serde(default)]
+    errors: Vec<GraphQlError>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GraphQlError {
+    message: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct SponsorshipData {
+    user: Option<SponsorableUser>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SponsorableUser {
+    sponsorship_for_viewer_as_sponsor: Option<Sponsorship>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Sponsorship {
+    is_active: bool,
+    tier: Option<SponsorsTier>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SponsorsTier {
+    id: String,
+    monthly_price_in_cents: i32,
+    is_one_time_payment: bool,
+}
+
+/// Payload of the `sponsorship` webhook event
+#[derive(Debug, Deserialize)]
+struct SponsorshipWebhook {
+    action: String,
+    sponsorship: WebhookSponsorship,
+}
+
+#[derive(Debug, Deserialize)]
+struct WebhookSponsorship {
+    sponsor: GitHubAccount,
+    sponsorable: GitHubAccount,
+    tier: WebhookTier,
+}
+
+#[derive(Debug, Deserialize)]
+struct WebhookTier {
+    node_id: String,
+    monthly_price_in_cents: i32,
+    is_one_time: bool,
+}
+
+impl SponsorshipWebhook {
+    /// Whether the action changes the membership now, pending changes are followed by another
+    /// event once they take effect.
+    fn changes_membership(&self) -> bool {
+        matches!(
+            self.action.as_str(),
+            "created" | "cancelled" | "tier_changed"
+        )
+    }
+
+    fn membership(&self) -> MembershipStatus {
+        if self.action == "cancelled" {
+            return MembershipStatus::none();
+        }
+        let tier = &self.sponsorship.tier;
+        tier_membership(&tier.node_id, tier.monthly_price_in_cents, tier.is_one_time)
+    }
+}
+
+/// GitHub Sponsors, supporters sponsor the configured user
+pub(crate) struct GitHubSponsorsProvider {
+    http_client: reqwest::Client,
+    config: GitHubSponsorsConfig,
+}
+
+impl GitHubSponsorsProvider {
+    pub(crate) fn new(http_client: reqwest::Client, config: GitHubSponsorsConfig) -> Self {
+        Self {
+            http_client,
+            config,
+        }
+    }
+
+    async fn request_tokens(&self, params: &[(&str, &str)]) -> MembershipResult<ProviderTokens> {
+        self.http_client
+            .post(TOKEN_ENDPOINT)
+            .header(ACCEPT, "application/json")
+            .form(params)
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<TokenResponse>()
+            .await?
+            .try_into()
+    }
+}
+
+impl MembershipProvider for GitHubSponsorsProvider {
+    fn kind(&self) -> MembershipProviderKind {
+        MembershipProviderKind::GitHub
+    }
+
+    fn authorize_url(&self, state: &str) -> String {
+        build_github_auth_url(&self.config.client_id, &self.config.redirect_uri, state)
+    }
+
+    fn frontend_redirect_url(&self) -> &str {
+        &self.config.frontend_redirect_url
+    }
+
+    fn exchange_code<'a>(
+        &'a self,
+        code: &'a str,
+    ) -> BoxFuture<'a, MembershipResult<ProviderTokens>> {
+        async move {
+            self.request_tokens(&[
+                ("client_id", &self.config.client_id),
+                ("client_secret", &self.config.client_secret),
+                ("code", code),
+                ("redirect_uri", &self.config.redirect_uri),
+            ])
+            .await
+        }
+        .boxed()
+    }
+
+    fn refresh_tokens<'a>(
+        &'a self,
+        refresh_token: &'a str,
+    ) -> BoxFuture<'a, MembershipResult<ProviderTokens>> {
+        async move {
+            self.request_tokens(&[
+                ("client_id", &self.config.client_id),
+                ("client_secret", &self.config.client_secret),
+                ("grant_type", "refresh_token"),
+                ("refresh_token", refresh_token),
+            ])
+            .await
+        }
+        .boxed()
+    }
+
+    fn get_identity<'a>(
+        &'a self,
+        access_token: &'a str,
+    ) -> BoxFuture<'a, MembershipResult<MemberIdentity>> {
+        async move {
+            let user = self
+                .http_client
+                .get(USER_ENDPOINT)
+                .bearer_auth(access_token)
+                .header(USER_AGENT, API_USER_AGENT)
+                .send()
+                .await?
+                .error_for_status()?
+                .json::<GitHubAccount>()
+                .await?;
+            Ok(MemberIdentity {
+                id: user.id.to_string(),
+                email: user.email,
+            })
+        }
+        .boxed()
+    }
+
+    fn get_membership<'a>(
+        &'a self,
+        access_token: &'a str,
+    ) -> BoxFuture<'a, MembershipResult<MembershipStatus>> {
+        async move {
+            let response = self
+                .http_client
+                .post(GRAPHQL_ENDPOINT)
+                .bearer_auth(access_token)
+                .header(USER_AGENT, API_USER_AGENT)
+                .json(&json!({
+                    "query": SPONSORSHIP_QUERY,
+                    "variables": { "login": self.config.sponsorable_login },
+                }))
+                .send()
+                .await?
+                .error_for_status()?
+                .json::<GraphQlResponse>()
+                .await?;
+            if let Some(error) = response.errors.into_iter().next() {
+                return Err(MembershipError::InvalidResponse(error.message));
+            }
+            let sponsorship = response
+                .data
+                .and_then(|d| d.user)
+                .and_then(|u| u.sponsorship_for_viewer_as_sponsor);
+            Ok(match sponsorship {
+                Some(Sponsorship {
+                    is_active: true,
+                    tier: Some(tier),
+                }) => tier_membership(
+                    &tier.id,
+                    tier.monthly_price_in_cents,
+                    tier.is_one_time_payment,
+                ),
+                _ => MembershipStatus::none(),
+            })
+        }
+        .boxed()
+    }
+
+    fn verify_webhook(
+        &self,
+        headers: &HeaderMap,
+        body: &[u8],
+    ) -> Result<Option<WebhookDelivery>, WebhookError> {
+        let signature = header(headers, "X-Hub-Signature-256")?;
+        let event_header = header(headers, "X-GitHub-Event")?;
+        let delivery_id = header(headers, "X-GitHub-Delivery")?;
+
+        let Some(signature) = signature.strip_prefix("sha256=") else {
+            return Err(WebhookError::InvalidSignature);
+        };
+        if !verify_hmac_sha256(body, &self.config.webhook_secret, signature) {
+            return Err(WebhookError::InvalidSignature);
+        }
+
+        if event_header != "sponsorship" {
+            info!("GitHub webhook: ignoring {event_header} event");
+            return Ok(None);
+        }
+
+        let payload: SponsorshipWebhook = serde_json::from_slice(body)
+            .map_err(|e| WebhookError::InvalidPayload(e.to_string()))?;
+
+        if !payload
+            .sponsorship
+            .sponsorable
+            .login
+            .eq_ignore_ascii_case(&self.config.sponsorable_login)
+        {
+            warn!("GitHub webhook: sponsorable mismatch, ignoring");
+            return Ok(None);
+        }
+
+        if !payload.changes_membership() {
+            info!("GitHub webhook: ignoring {} sponsorship", payload.action);
+            return Ok(None);
+        }
+
+        // Redeliveries keep the delivery ID
+        let event = format!("sponsorship:{}", payload.action);
+        Ok(Some(WebhookDelivery {
+            dedupe_key: dedupe_key(&event, delivery_id),
+            event,
+            provider_user_id: payload.sponsorship.sponsor.id.to_string(),
+        }))
+    }
+
+    fn webhook_membership(&self, _event: &str, body: &str) -> MembershipResult<MembershipStatus> {
+        let payload: SponsorshipWebhook = serde_json::from_str(body)
+            .map_err(|e| MembershipError::InvalidPayload(e.to_string()))?;
+        Ok(payload.membership())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::HeaderValue;
+    use hmac::{Hmac, Mac};
+    use sha2::Sha256;
+
+    use super::*;
+
+    const WEBHOOK_BODY: &str = r#"{
+        "action": "tier_changed",
+        "sponsorship": {
+            "sponsor": {"id": 123, "login": "supporter"},
+            "sponsorable": {"id": 1, "login": "Maintainer"},
+            "tier": {"node_id": "ST_1", "monthly_price_in_cents": 600, "is_one_time": false}
+        }
+    }"#;
+
+    fn provider() -> GitHubSponsorsProvider {
+        GitHubSponsorsProvider::new(
+            reqwest::Client::new(),
+            GitHubSponsorsConfig {
+                client_id: "test_client_id".to_owned(),
+                sponsorable_login: "maintainer".to_owned(),
+                webhook_secret: "secret".to_owned(),
+                ..Default::default()
+            },
+        )
+    }
+
+    fn webhook_headers(event: &'static str, body: &[u8], secret: &str) -> HeaderMap {
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+        mac.update(body);
+        let signature = format!("sha256={}", hex::encode(mac.finalize().into_bytes()));
+
+        let mut headers = HeaderMap::new();
+        headers.insert("X-GitHub-Event", HeaderValue::from_static(event));
+        headers.insert("X-GitHub-Delivery", HeaderValue::from_static("delivery-1"));
+        headers.insert(
+            "X-Hub-Signature-256",
+            HeaderValue::from_str(&signature).unwrap(),
+        );
+        headers
+    }
+
+    #[test]
+    fn test_build_github_auth_url() {
+        let url = build_github_auth_url("client", "https://example.com/callback", "state_123");
+
+        assert!(url.starts_with("https://github.com/login/oauth/authorize?"));
+        assert!(url.contains("client_id=client"));
+        assert!(url.contains("redirect_uri=https%3A%2F%2Fexample.com%2Fcallback"));
+        assert!(url.contains("scope=read%3Auser%20user%3Aemail"));
+        assert!(url.contains("state=state_123"));
+    }
+
+    #[test]
+    fn test_tier_membership() {
+        assert_eq!(
+            tier_membership("ST_1", 500, false),
+            MembershipStatus {
+                tier_id: Some("ST_1".to_owned()),
+                pledge_amount_cents: Some(500),
+                is_active: true,
+            }
+        );
+        assert_eq!(
+            tier_membership("ST_2", 2500, true),
+            MembershipStatus::none()
+        );
+    }
+
+    #[test]
+    fn test_token_error_response() {
+        let response: TokenResponse = serde_json::from_str(
+            r#"{"error": "bad_verification_code", "error_description": "The code is invalid."}"#,
+        )
+        .unwrap();
+        assert!(matches!(
+            ProviderTokens::try_from(response),
+            Err(MembershipError::InvalidResponse(e)) if e == "The code is invalid."
+        ));
+    }
+
+    #[test]
+    fn test_verify_webhook() {
+        let provider = provider();
+        let body = WEBHOOK_BODY.as_bytes();
+
+        let delivery = provider
+            .verify_webhook(&webhook_headers("sponsorship", body, "secret"), body)
+            .unwrap()
+            .unwrap();
+        assert_eq!(delivery.event, "sponsorship:tier_changed");
+        assert_eq!(delivery.provider_user_id, "123");
+        assert_eq!(
+            delivery.dedupe_key,
+            dedupe_key("sponsorship:tier_changed", "delivery-1")
+        );
+
+        assert!(matches!(
+            provider.verify_webhook(&webhook_headers("sponsorship", body, "wrong"), body),
+            Err(WebhookError::InvalidSignature)
+        ));
+        assert!(
+            provider
+                .verify_webhook(&webhook_headers("ping", body, "secret"), body)
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_webhook_membership() {
+        let provider = provider();
+        assert_eq!(
+            provider
+                .webhook_membership("sponsorship:tier_changed", WEBHOOK_BODY)
+                .unwrap(),
+            tier_membership("ST_1", 600, false)
+        );
+
+        let cancelled = WEBHOOK_BODY.replace("tier_changed", "cancelled");
+        assert_eq!(
+            provider
+                .webhook_membership("sponsorship:cancelled", &cancelled)
+                .unwrap(),
+            MembershipStatus::none()
+        );
+    }
+}
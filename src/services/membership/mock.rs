@@ -0,0 +1,237 @@
+use axum::http::HeaderMap;
+use chrono::{Duration, Utc};
+use futures::FutureExt;
+use futures::future::BoxFuture;
+use serde::Deserialize;
+
+use super::webhook_worker::dedupe_key;
+use super::{
+    MemberIdentity, MembershipError, MembershipProvider, MembershipProviderKind, MembershipResult,
+    MembershipStatus, ProviderTokens, WebhookDelivery, WebhookError, header, verify_hmac_sha256,
+};
+use crate::context::MockMembershipConfig;
+
+/// Member logged in by the mock authorization URL
+const DEFAULT_MEMBER: &str = "mock-user:500";
+
+/// Tier ID of every mock membership
+const MOCK_TIER_ID: &str = "mock";
+
+/// A member of the mock provider.
+///
+/// Authorization codes and tokens are the member itself, encoded as `{user_id}:{pledge}`, so any
+/// member can log in by calling the callback with a matching code.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+struct MockMember {
+    user_id: String,
+    pledge_amount_cents: i32,
+}
+
+impl MockMember {
+    fn parse(token: &str) -> MembershipResult<Self> {
+        token
+            .split_once(':')
+            .and_then(|(user_id, pledge)| {
+                Some(Self {
+                    user_id: user_id.to_owned(),
+                    pledge_amount_cents: pledge.parse().ok()?,
+                })
+            })
+            .filter(|m| !m.user_id.is_empty())
+            .ok_or_else(|| MembershipError::InvalidResponse(format!("Invalid mock token {token}")))
+    }
+
+    fn membership(&self) -> MembershipStatus {
+        if self.pledge_amount_cents <= 0 {
+            return MembershipStatus::none();
+        }
+        MembershipStatus {
+            tier_id: Some(MOCK_TIER_ID.to_owned()),
+            pledge_amount_cents: Some(self.pledge_amount_cents),
+            is_active: true,
+        }
+    }
+}
+
+/// Offline provider for testing the membership flows without a real platform
+pub(crate) struct MockProvider {
+    config: MockMembershipConfig,
+}
+
+impl MockProvider {
+    pub(crate) fn new(config: MockMembershipConfig) -> Self {
+        Self { config }
+    }
+
+    fn tokens(code: &str) -> MembershipResult<ProviderTokens> {
+        MockMember::parse(code)?;
+        Ok(ProviderTokens {
+            access_token: code.to_owned(),
+            refresh_token: Some(code.to_owned()),
+            expires_at: Some(Utc::now() + Duration::days(1)),
+        })
+    }
+}
+
+impl MembershipProvider for MockProvider {
+    fn kind(&self) -> MembershipProviderKind {
+        MembershipProviderKind::Mock
+    }
+
+    /// Skips the authorization and redirects to the callback directly.
+    fn authorize_url(&self, state: &str) -> String {
+        format!(
+            "{}?code={}&state={state}",
+            self.config.redirect_uri,
+            urlencoding::encode(DEFAULT_MEMBER)
+        )
+    }
+
+    fn frontend_redirect_url(&self) -> &str {
+        &self.config.frontend_redirect_url
+    }
+
+    fn exchange_code<'a>(
+        &'a self,
+        code: &'a str,
+    ) -> BoxFuture<'a, MembershipResult<ProviderTokens>> {
+        async move { Self::tokens(code) }.boxed()
+    }
+
+    fn refresh_tokens<'a>(
+        &'a self,
+        refresh_token: &'a str,
+    ) -> BoxFuture<'a, MembershipResult<ProviderTokens>> {
+        async move { Self::tokens(refresh_token) }.boxed()
+    }
+
+    fn get_identity<'a>(
+        &'a self,
+        access_token: &'a str,
+    ) -> BoxFuture<'a, MembershipResult<MemberIdentity>> {
+        async move {
+            Ok(MemberIdentity {
+                id: MockMember::parse(access_token)?.user_id,
+                email: None,
+            })
+        }
+        .boxed()
+    }
+
+    fn get_membership<'a>(
+        &'a self,
+        access_token: &'a str,
+    ) -> BoxFuture<'a, MembershipResult<MembershipStatus>> {
+        async move { Ok(MockMember::parse(access_token)?.membership()) }.boxed()
+    }
+
+    fn verify_webhook(
+        &self,
+        headers: &HeaderMap,
+        body: &[u8],
+    ) -> Result<Option<WebhookDelivery>, WebhookError> {
+        let signature = header(headers, "X-Mock-Signature")?;
+        let event = header(headers, "X-Mock-Event")?;
+
+        if !verify_hmac_sha256(body, &self.config.webhook_secret, signature) {
+            return Err(WebhookError::InvalidSignature);
+        }
+
+        let member: MockMember = serde_json::from_slice(body)
+            .map_err(|e| WebhookError::InvalidPayload(e.to_string()))?;
+
+        Ok(Some(WebhookDelivery {
+            event: event.to_owned(),
+            dedupe_key: dedupe_key(event, signature),
+            provider_user_id: member.user_id,
+        }))
+    }
+
+    fn webhook_membership(&self, _event: &str, body: &str) -> MembershipResult<MembershipStatus> {
+        let member: MockMember = serde_json::from_str(body)
+            .map_err(|e| MembershipError::InvalidPayload(e.to_string()))?;
+        Ok(member.membership())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::HeaderValue;
+    use hmac::{Hmac, Mac};
+    use sha2::Sha256;
+
+    use super::*;
+
+    fn provider() -> MockProvider {
+        MockProvider::new(MockMembershipConfig {
+            enabled: true,
+            redirect_uri: "http://localhost:8080/v1/auth/mock/callback".to_owned(),
+            frontend_redirect_url: "http://localhost:3000".to_owned(),
+            webhook_secret: "secret".to_owned(),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_authorize_url_redirects_to_callback() {
+        assert_eq!(
+            provider().authorize_url("state_123"),
+            "http://localhost:8080/v1/auth/mock/callback?code=mock-user%3A500&state=state_123"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_login_flow() {
+        let provider = provider();
+        let tokens = provider.exchange_code("alice:900").await.unwrap();
+        let identity = provider.get_identity(&tokens.access_token).await.unwrap();
+        let membership = provider.get_membership(&tokens.access_token).await.unwrap();
+
+        assert_eq!(identity.id, "alice");
+        assert_eq!(
+            membership,
+            MembershipStatus {
+                tier_id: Some("mock".to_owned()),
+                pledge_amount_cents: Some(900),
+                is_active: true,
+            }
+        );
+        assert_eq!(
+            provider.get_membership("bob:0").await.unwrap(),
+            MembershipStatus::none()
+        );
+        assert!(provider.exchange_code("invalid").await.is_err());
+        assert!(provider.exchange_code(":500").await.is_err());
+    }
+
+    #[test]
+    fn test_webhook() {
+        let provider = provider();
+        let body = br#"{"user_id": "alice", "pledge_amount_cents": 0}"#;
+        let mut mac = Hmac::<Sha256>::new_from_slice(b"secret").unwrap();
+        mac.update(body);
+        let signature = hex::encode(mac.finalize().into_bytes());
+
+        let mut headers = HeaderMap::new();
+        headers.insert("X-Mock-Event", HeaderValue::from_static("cancel"));
+        headers.insert(
+            "X-Mock-Signature",
+            HeaderValue::from_str(&signature).unwrap(),
+        );
+
+        let delivery = provider.verify_webhook(&headers, body).unwrap().unwrap();
+        assert_eq!(delivery.provider_user_id, "alice");
+        assert_eq!(
+            provider
+                .webhook_membership(&delivery.event, core::str::from_utf8(body).unwrap())
+                .unwrap(),
+            MembershipStatus::none()
+        );
+
+        headers.insert("X-Mock-Signature", HeaderValue::from_static("00"));
+        assert!(matches!(
+            provider.verify_webhook(&headers, body),
+            Err(WebhookError::InvalidSignature)
+        ));
+    }
+}
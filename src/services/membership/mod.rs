@@ -0,0 +1,286 @@
+pub(crate) mod github;
+pub(crate) mod mock;
+pub(crate) mod patreon;
+pub(crate) mod verification_job;
+pub(crate) mod webhook_worker;
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use axum::http::HeaderMap;
+use chrono::{DateTime, Utc};
+use futures::future::BoxFuture;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use strum::{Display, EnumString};
+use thiserror::Error;
+use tracing::{error, warn};
+
+use crate::context::Config;
+use crate::services::membership::github::GitHubSponsorsProvider;
+use crate::services::membership::mock::MockProvider;
+use crate::services::membership::patreon::PatreonProvider;
+
+/// Platforms supporters can back the project on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Display, EnumString)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub(crate) enum MembershipProviderKind {
+    Patreon,
+    #[serde(rename = "github")]
+    #[strum(serialize = "github")]
+    GitHub,
+    Mock,
+}
+
+/// Error type for membership provider API calls
+#[derive(Debug, Error)]
+pub(crate) enum MembershipError {
+    #[error("HTTP request failed: {0}")]
+    Request(#[from] reqwest::Error),
+    #[error("Invalid response from provider: {0}")]
+    InvalidResponse(String),
+    #[error("Invalid webhook payload: {0}")]
+    InvalidPayload(String),
+}
+
+pub(crate) type MembershipResult<T> = Result<T, MembershipError>;
+
+/// Error type for webhook verification
+#[derive(Debug, Error)]
+pub(crate) enum WebhookError {
+    #[error("Missing or invalid {0} header")]
+    MissingHeader(&'static str),
+    #[error("Invalid signature")]
+    InvalidSignature,
+    #[error("Invalid payload: {0}")]
+    InvalidPayload(String),
+}
+
+/// OAuth tokens of a supporter
+#[derive(Debug, Clone)]
+pub(crate) struct ProviderTokens {
+    pub(crate) access_token: String,
+    /// Not every provider issues refresh tokens
+    pub(crate) refresh_token: Option<String>,
+    /// `None` if the access token doesn't expire
+    pub(crate) expires_at: Option<DateTime<Utc>>,
+}
+
+/// Identity of a supporter on a provider
+#[derive(Debug, Clone)]
+pub(crate) struct MemberIdentity {
+    /// User ID on the provider
+    pub(crate) id: String,
+    pub(crate) email: Option<String>,
+}
+
+/// Membership of a supporter, normalized across providers
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct MembershipStatus {
+    /// Provider specific tier ID
+    pub(crate) tier_id: Option<String>,
+    /// Monthly pledge in cents, the Steam account slots are calculated from it
+    pub(crate) pledge_amount_cents: Option<i32>,
+    pub(crate) is_active: bool,
+}
+
+impl MembershipStatus {
+    /// The membership of a user not supporting the project
+    pub(crate) fn none() -> Self {
+        Self {
+            tier_id: None,
+            pledge_amount_cents: Some(0),
+            is_active: false,
+        }
+    }
+}
+
+/// A verified webhook delivery, stored until the webhook worker applies it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct WebhookDelivery {
+    pub(crate) event: String,
+    /// Identifies redeliveries of the same event
+    pub(crate) dedupe_key: String,
+    /// User ID of the supporter on the provider
+    pub(crate) provider_user_id: String,
+}
+
+/// A platform supporters can back the project on
+///
+/// Providers handle the OAuth login, the verification of their webhooks and the mapping of their
+/// tiers to a monthly pledge, from which the Steam account slots are calculated. Prioritization
+/// and API key perks only depend on the patron record, so they work the same for every provider.
+pub(crate) trait MembershipProvider: Send + Sync {
+    fn kind(&self) -> MembershipProviderKind;
+
+    /// The URL the login redirects to, `state` is passed back to the callback.
+    fn authorize_url(&self, state: &str) -> String;
+
+    /// The frontend URL the callback redirects to.
+    fn frontend_redirect_url(&self) -> &str;
+
+    /// Exchanges the authorization code of the callback for tokens.
+    fn exchange_code<'a>(
+        &'a self,
+        code: &'a str,
+    ) -> BoxFuture<'a, MembershipResult<ProviderTokens>>;
+
+    /// Refreshes expiring tokens.
+    fn refresh_tokens<'a>(
+        &'a self,
+        refresh_token: &'a str,
+    ) -> BoxFuture<'a, MembershipResult<ProviderTokens>>;
+
+    /// Fetches the identity of the token owner.
+    fn get_identity<'a>(
+        &'a self,
+        access_token: &'a str,
+    ) -> BoxFuture<'a, MembershipResult<MemberIdentity>>;
+
+    /// Fetches the current membership of the token owner.
+    fn get_membership<'a>(
+        &'a self,
+        access_token: &'a str,
+    ) -> BoxFuture<'a, MembershipResult<MembershipStatus>>;
+
+    /// Verifies a webhook delivery.
+    ///
+    /// Returns `None` for events not affecting a membership, they are acknowledged without being
+    /// stored.
+    fn verify_webhook(
+        &self,
+        headers: &HeaderMap,
+        body: &[u8],
+    ) -> Result<Option<WebhookDelivery>, WebhookError>;
+
+    /// Reads the membership from the body of a stored webhook event.
+    fn webhook_membership(&self, event: &str, body: &str) -> MembershipResult<MembershipStatus>;
+}
+
+/// The configured membership providers
+#[derive(Clone, Default)]
+pub(crate) struct MembershipProviders {
+    providers: HashMap<MembershipProviderKind, Arc<dyn MembershipProvider>>,
+}
+
+impl MembershipProviders {
+    pub(crate) fn from_config(config: &Config, http_client: &reqwest::Client) -> Self {
+        let mut providers = Self::default();
+        providers.register(PatreonProvider::new(
+            http_client.clone(),
+            config.patreon.clone(),
+        ));
+        if config.github_sponsors.is_enabled() {
+            providers.register(GitHubSponsorsProvider::new(
+                http_client.clone(),
+                config.github_sponsors.clone(),
+            ));
+        } else if !config.github_sponsors.client_id.is_empty() {
+            error!("GitHub Sponsors has no webhook secret, not registering it");
+        }
+        let mock = &config.mock_membership;
+        if mock.enabled && !cfg!(debug_assertions) && !mock.not_production {
+            error!(
+                "Mock membership provider needs MOCK_MEMBERSHIP_NOT_PRODUCTION in release builds, not registering it"
+            );
+        } else if mock.enabled && mock.webhook_secret.is_empty() {
+            error!("Mock membership provider has no webhook secret, not registering it");
+        } else if mock.enabled {
+            warn!("Mock membership provider is enabled, its logins are not verified");
+            providers.register(MockProvider::new(mock.clone()));
+        }
+        providers
+    }
+
+    pub(crate) fn register(&mut self, provider: impl MembershipProvider + 'static) {
+        self.providers.insert(provider.kind(), Arc::new(provider));
+    }
+
+    pub(crate) fn get(&self, kind: MembershipProviderKind) -> Option<Arc<dyn MembershipProvider>> {
+        self.providers.get(&kind).cloned()
+    }
+
+    /// Looks up a configured provider by its name, as used in the auth routes.
+    pub(crate) fn by_name(&self, name: &str) -> Option<Arc<dyn MembershipProvider>> {
+        name.parse().ok().and_then(|kind| self.get(kind))
+    }
+}
+
+/// Verifies a hex encoded HMAC-SHA256 signature of a webhook body.
+///
+/// Signatures are never valid without a secret, anyone could sign with an empty key.
+pub(crate) fn verify_hmac_sha256(body: &[u8], secret: &str, signature_hex: &str) -> bool {
+    if secret.is_empty() {
+        return false;
+    }
+    let Ok(mut mac) = Hmac::<Sha256>::new_from_slice(secret.as_bytes()) else {
+        return false;
+    };
+    mac.update(body);
+    let Ok(expected) = hex::decode(signature_hex) else {
+        return false;
+    };
+    mac.verify_slice(&expected).is_ok()
+}
+
+/// The header value of a webhook delivery.
+pub(crate) fn header<'a>(
+    headers: &'a HeaderMap,
+    name: &'static str,
+) -> Result<&'a str, WebhookError> {
+    headers
+        .get(name)
+        .and_then(|v| v.to_str().ok())
+        .ok_or(WebhookError::MissingHeader(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+    use crate::context::MockMembershipConfig;
+
+    #[rstest]
+    #[case(MembershipProviderKind::Patreon, "patreon")]
+    #[case(MembershipProviderKind::GitHub, "github")]
+    #[case(MembershipProviderKind::Mock, "mock")]
+    fn test_provider_names(#[case] kind: MembershipProviderKind, #[case] name: &str) {
+        assert_eq!(kind.to_string(), name);
+        assert_eq!(name.parse::<MembershipProviderKind>().ok(), Some(kind));
+        assert_eq!(serde_json::to_value(kind).unwrap(), name);
+    }
+
+    #[test]
+    fn test_registry_only_returns_registered_providers() {
+        let mut providers = MembershipProviders::default();
+        providers.register(MockProvider::new(MockMembershipConfig::default()));
+
+        assert!(providers.by_name("mock").is_some());
+        assert!(providers.by_name("patreon").is_none());
+        assert!(providers.by_name("unknown").is_none());
+    }
+
+    #[test]
+    fn test_verify_hmac_sha256() {
+        let body = b"test body content";
+        let mut mac = Hmac::<Sha256>::new_from_slice(b"secret").unwrap();
+        mac.update(body);
+        let signature = hex::encode(mac.finalize().into_bytes());
+
+        assert!(verify_hmac_sha256(body, "secret", &signature));
+        assert!(!verify_hmac_sha256(body, "other", &signature));
+        assert!(!verify_hmac_sha256(body, "secret", "not_valid_hex_zz"));
+    }
+
+    #[test]
+    fn test_verify_hmac_sha256_rejects_empty_secret() {
+        let body = b"test body content";
+        let mac = Hmac::<Sha256>::new_from_slice(b"").unwrap();
+        let signature = hex::encode(mac.chain_update(body).finalize().into_bytes());
+
+        assert!(!verify_hmac_sha256(body, "", &signature));
+    }
+}
@@ -0,0 +1,319 @@
+use axum::http::HeaderMap;
+use chrono::{Duration, Utc};
+use futures::FutureExt;
+use futures::future::BoxFuture;
+use hmac::{Hmac, Mac};
+use md5::Md5;
+use tracing::{info, warn};
+
+use super::webhook_worker::dedupe_key;
+use super::{
+    MemberIdentity, MembershipError, MembershipProvider, MembershipProviderKind, MembershipResult,
+    MembershipStatus, ProviderTokens, WebhookDelivery, WebhookError, header,
+};
+use crate::context::PatreonConfig;
+use crate::services::patreon::client::PatreonClient;
+use crate::services::patreon::types::{Membership, PatreonError, TokenResponse};
+use crate::services::patreon::webhook_types::{PatreonWebhookEvent, WebhookPayload};
+
+type HmacMd5 = Hmac<Md5>;
+
+/// Patreon OAuth scopes required for this application
+const PATREON_SCOPES: &str = "identity identity[email] campaigns.members";
+
+/// Build the Patreon OAuth authorization URL
+fn build_patreon_auth_url(client_id: &str, redirect_uri: &str, state: &str) -> String {
+    let encoded_redirect_uri = urlencoding::encode(redirect_uri);
+    let encoded_scopes = urlencoding::encode(PATREON_SCOPES);
+
+    format!(
+        "https://www.patreon.com/oauth2/authorize?response_type=code&client_id={client_id}&redirect_uri={encoded_redirect_uri}&scope={encoded_scopes}&state={state}"
+    )
+}
+
+/// Verify the HMAC-MD5 signature from the `X-Patreon-Signature` header.
+fn verify_signature(body: &[u8], secret: &str, signature_hex: &str) -> bool {
+    if secret.is_empty() {
+        return false;
+    }
+    let Ok(mut mac) = HmacMd5::new_from_slice(secret.as_bytes()) else {
+        return false;
+    };
+    mac.update(body);
+    let Ok(expected) = hex::decode(signature_hex) else {
+        return false;
+    };
+    mac.verify_slice(&expected).is_ok()
+}
+
+impl From<PatreonError> for MembershipError {
+    fn from(error: PatreonError) -> Self {
+        match error {
+            PatreonError::Request(e) => Self::Request(e),
+            PatreonError::InvalidResponse(e) => Self::InvalidResponse(e),
+        }
+    }
+}
+
+impl From<TokenResponse> for ProviderTokens {
+    fn from(response: TokenResponse) -> Self {
+        Self {
+            access_token: response.access_token,
+            refresh_token: Some(response.refresh_token),
+            expires_at: Some(Utc::now() + Duration::seconds(response.expires_in)),
+        }
+    }
+}
+
+impl From<Option<Membership>> for MembershipStatus {
+    fn from(membership: Option<Membership>) -> Self {
+        match membership {
+            Some(m) => Self {
+                tier_id: m.tier_id,
+                pledge_amount_cents: Some(m.pledge_amount_cents),
+                is_active: m.patron_status.as_deref() == Some("active_patron"),
+            },
+            None => Self::none(),
+        }
+    }
+}
+
+/// Patreon, supporters pledge to the campaign
+pub(crate) struct PatreonProvider {
+    client: PatreonClient,
+    config: PatreonConfig,
+}
+
+impl PatreonProvider {
+    pub(crate) fn new(http_client: reqwest::Client, config: PatreonConfig) -> Self {
+        Self {
+            client: PatreonClient::new(
+                http_client,
+                config.client_id.clone(),
+                config.client_secret.clone(),
+                config.redirect_uri.clone(),
+            ),
+            config,
+        }
+    }
+}
+
+impl MembershipProvider for PatreonProvider {
+    fn kind(&self) -> MembershipProviderKind {
+        MembershipProviderKind::Patreon
+    }
+
+    fn authorize_url(&self, state: &str) -> String {
+        build_patreon_auth_url(&self.config.client_id, &self.config.redirect_uri, state)
+    }
+
+    fn frontend_redirect_url(&self) -> &str {
+        &self.config.frontend_redirect_url
+    }
+
+    fn exchange_code<'a>(
+        &'a self,
+        code: &'a str,
+    ) -> BoxFuture<'a, MembershipResult<ProviderTokens>> {
+        async move { Ok(self.client.exchange_code(code).await?.into()) }.boxed()
+    }
+
+    fn refresh_tokens<'a>(
+        &'a self,
+        refresh_token: &'a str,
+    ) -> BoxFuture<'a, MembershipResult<ProviderTokens>> {
+        async move { Ok(self.client.refresh_token(refresh_token).await?.into()) }.boxed()
+    }
+
+    fn get_identity<'a>(
+        &'a self,
+        access_token: &'a str,
+    ) -> BoxFuture<'a, MembershipResult<MemberIdentity>> {
+        async move {
+            let identity = self.client.get_identity(access_token).await?;
+            Ok(MemberIdentity {
+                id: identity.id,
+                email: identity.email,
+            })
+        }
+        .boxed()
+    }
+
+    fn get_membership<'a>(
+        &'a self,
+        access_token: &'a str,
+    ) -> BoxFuture<'a, MembershipResult<MembershipStatus>> {
+        async move { Ok(self.client.get_membership(access_token).await?.into()) }.boxed()
+    }
+
+    fn verify_webhook(
+        &self,
+        headers: &HeaderMap,
+        body: &[u8],
+    ) -> Result<Option<WebhookDelivery>, WebhookError> {
+        let signature = header(headers, "X-Patreon-Signature")?;
+        let event_header = header(headers, "X-Patreon-Event")?;
+
+        if !verify_signature(body, &self.config.webhook_secret, signature) {
+            return Err(WebhookError::InvalidSignature);
+        }
+
+        // Acknowledge unrecognized events
+        if PatreonWebhookEvent::from_header(event_header).is_none() {
+            info!("Patreon webhook: ignoring unrecognized event: {event_header}");
+            return Ok(None);
+        }
+
+        let payload: WebhookPayload = serde_json::from_slice(body)
+            .map_err(|e| WebhookError::InvalidPayload(e.to_string()))?;
+
+        if !payload.is_campaign(&self.config.campaign_id) {
+            warn!("Patreon webhook: campaign ID mismatch, ignoring");
+            return Ok(None);
+        }
+
+        let Some(patreon_user_id) = payload.patreon_user_id() else {
+            return Err(WebhookError::InvalidPayload(
+                "missing user relationship".to_owned(),
+            ));
+        };
+
+        Ok(Some(WebhookDelivery {
+            event: event_header.to_owned(),
+            dedupe_key: dedupe_key(event_header, signature),
+            provider_user_id: patreon_user_id.to_owned(),
+        }))
+    }
+
+    fn webhook_membership(&self, _event: &str, body: &str) -> MembershipResult<MembershipStatus> {
+        let payload: WebhookPayload = serde_json::from_str(body)
+            .map_err(|e| MembershipError::InvalidPayload(e.to_string()))?;
+        Ok(payload.membership())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::HeaderValue;
+
+    use super::*;
+
+    fn provider() -> PatreonProvider {
+        PatreonProvider::new(
+            reqwest::Client::new(),
+            PatreonConfig {
+                client_id: "test_client_id".to_owned(),
+                client_secret: "secret".to_owned(),
+                redirect_uri: "https://example.com/callback".to_owned(),
+                frontend_redirect_url: "https://example.com".to_owned(),
+                campaign_id: "42".to_owned(),
+                webhook_secret: "webhook_secret_123".to_owned(),
+            },
+        )
+    }
+
+    fn sign(body: &[u8], secret: &str) -> String {
+        let mut mac = HmacMd5::new_from_slice(secret.as_bytes()).unwrap();
+        mac.update(body);
+        hex::encode(mac.finalize().into_bytes())
+    }
+
+    fn webhook_headers(event: &'static str, signature: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert("X-Patreon-Event", HeaderValue::from_static(event));
+        headers.insert(
+            "X-Patreon-Signature",
+            HeaderValue::from_str(signature).unwrap(),
+        );
+        headers
+    }
+
+    #[test]
+    fn test_build_patreon_auth_url() {
+        let url = build_patreon_auth_url(
+            "test_client_id",
+            "https://example.com/callback",
+            "test_state_123",
+        );
+
+        assert!(url.starts_with("https://www.patreon.com/oauth2/authorize?"));
+        assert!(url.contains("response_type=code"));
+        assert!(url.contains("client_id=test_client_id"));
+        assert!(url.contains("redirect_uri=https%3A%2F%2Fexample.com%2Fcallback"));
+        assert!(url.contains("scope=identity%20identity%5Bemail%5D%20campaigns.members"));
+        assert!(url.contains("state=test_state_123"));
+    }
+
+    #[test]
+    fn test_verify_signature_valid() {
+        let body = b"test body content";
+        let secret = "webhook_secret_123";
+        assert!(verify_signature(body, secret, &sign(body, secret)));
+    }
+
+    #[test]
+    fn test_verify_signature_invalid() {
+        let body = b"test body content";
+        let secret = "webhook_secret_123";
+        let wrong_signature = "0000000000000000000000000000000f";
+
+        assert!(!verify_signature(body, secret, wrong_signature));
+    }
+
+    #[test]
+    fn test_verify_signature_bad_hex() {
+        let body = b"test body content";
+        let secret = "webhook_secret_123";
+
+        assert!(!verify_signature(body, secret, "not_valid_hex_zz"));
+    }
+
+    #[test]
+    fn test_membership_status_from_patreon_membership() {
+        let membership = Membership {
+            tier_id: Some("9".to_owned()),
+            pledge_amount_cents: 500,
+            patron_status: Some("active_patron".to_owned()),
+        };
+        assert_eq!(
+            MembershipStatus::from(Some(membership)),
+            MembershipStatus {
+                tier_id: Some("9".to_owned()),
+                pledge_amount_cents: Some(500),
+                is_active: true,
+            }
+        );
+        assert_eq!(MembershipStatus::from(None), MembershipStatus::none());
+    }
+
+    #[test]
+    fn test_verify_webhook() {
+        let provider = provider();
+        let body =
+            br#"{"data": {"attributes": {"patron_status": "active_patron"}, "relationships": {
+            "user": {"data": {"id": "123"}}, "campaign": {"data": {"id": "42"}}}}}"#;
+        let signature = sign(body, "webhook_secret_123");
+
+        let delivery = provider
+            .verify_webhook(&webhook_headers("members:update", &signature), body)
+            .unwrap()
+            .unwrap();
+        assert_eq!(delivery.event, "members:update");
+        assert_eq!(delivery.provider_user_id, "123");
+        assert_eq!(
+            delivery.dedupe_key,
+            dedupe_key("members:update", &signature)
+        );
+
+        assert!(matches!(
+            provider.verify_webhook(&webhook_headers("members:update", "00"), body),
+            Err(WebhookError::InvalidSignature)
+        ));
+        assert!(
+            provider
+                .verify_webhook(&webhook_headers("posts:publish", &signature), body)
+                .unwrap()
+                .is_none()
+        );
+    }
+}
@@ -3,16 +3,19 @@ use core::time::Duration;
 use std::sync::Arc;
 
 use chrono::{TimeDelta, Utc};
+use serde::Serialize;
+use serde_json::json;
 use sqlx::{Pool, Postgres};
 use tokio::sync::Mutex;
 use tokio::time::interval;
 use tracing::{error, info, warn};
 
-use super::client::PatreonClient;
-use super::membership::{handle_downgrade_or_cancellation, handle_reactivation};
-use super::repository::PatronRepository;
-use super::steam_accounts_repository::SteamAccountsRepository;
-use super::types::Patron;
+use super::{MembershipProvider, MembershipProviderKind, MembershipProviders, MembershipStatus};
+use crate::services::patreon::events::{PatronEventKind, PatronEventLog, PatronEventSource};
+use crate::services::patreon::membership::{handle_downgrade_or_cancellation, handle_reactivation};
+use crate::services::patreon::repository::{PatronRepository, PatronRepositoryResult};
+use crate::services::patreon::steam_accounts_repository::SteamAccountsRepository;
+use crate::services::patreon::types::Patron;
 
 /// Interval between verification runs (1 hour)
 const VERIFICATION_INTERVAL_SECS: u64 = 60 * 60;
@@ -20,56 +23,74 @@ const VERIFICATION_INTERVAL_SECS: u64 = 60 * 60;
 /// Patron queued for retry after API error
 struct RetryPatron {
     id: uuid::Uuid,
-    patreon_user_id: String,
+    provider: MembershipProviderKind,
+    provider_user_id: String,
     access_token: String,
     slot_override: Option<i32>,
 }
 
-/// Interval for retry on Patreon API errors (30 minutes)
+/// Patron waiting in the retry queue, without its credentials
+#[derive(Debug, Serialize)]
+pub(crate) struct QueuedPatron {
+    id: uuid::Uuid,
+    provider: MembershipProviderKind,
+    provider_user_id: String,
+}
+
+/// Interval for retry on provider API errors (30 minutes)
 const RETRY_INTERVAL_SECS: u64 = 30 * 60;
 
-/// Hourly verification job for Patreon tokens and membership sync
+/// Clears the running flag when a verification run ends, even if it panics
+struct RunningGuard<'a>(&'a AtomicBool);
+
+impl Drop for RunningGuard<'_> {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::Release);
+    }
+}
+
+/// Hourly verification job for membership provider tokens and membership sync
 ///
 /// This job runs once per hour and:
 /// 1. Fetches all patrons with stored tokens
-/// 2. For each patron with an expired token, refreshes it using the Patreon API
+/// 2. For each patron with an expiring token, refreshes it using their provider's API
 /// 3. Updates the stored tokens on successful refresh
 /// 4. Fetches current membership status and updates patron record
 /// 5. Handles patron downgrades by soft-deleting excess Steam accounts
 /// 6. Handles patron cancellations by soft-deleting all Steam accounts
 /// 7. Logs refresh failures for later re-authentication
 /// 8. Retries failed API calls after 30 minutes
-pub(crate) struct PatreonVerificationJob {
+pub(crate) struct MembershipVerificationJob {
+    pg_client: Pool<Postgres>,
     patron_repository: PatronRepository,
     steam_accounts_repository: SteamAccountsRepository,
-    patreon_client: PatreonClient,
+    events: PatronEventLog,
+    providers: MembershipProviders,
     shutdown: Arc<AtomicBool>,
+    /// Whether a verification run is currently in progress
+    running: AtomicBool,
     /// Patrons that need retry due to API errors
     retry_queue: Arc<Mutex<Vec<RetryPatron>>>,
 }
 
-impl PatreonVerificationJob {
+impl MembershipVerificationJob {
     pub(crate) fn new(
         pg_client: Pool<Postgres>,
         encryption_key: String,
-        patreon_client_id: String,
-        patreon_client_secret: String,
-        patreon_redirect_uri: String,
+        providers: MembershipProviders,
     ) -> Self {
         let patron_repository = PatronRepository::new(pg_client.clone(), encryption_key);
-        let steam_accounts_repository = SteamAccountsRepository::new(pg_client);
-        let patreon_client = PatreonClient::new(
-            reqwest::Client::new(),
-            patreon_client_id,
-            patreon_client_secret,
-            patreon_redirect_uri,
-        );
+        let steam_accounts_repository = SteamAccountsRepository::new(pg_client.clone());
+        let events = PatronEventLog::new(PatronEventSource::Verification);
 
         Self {
+            pg_client,
             patron_repository,
             steam_accounts_repository,
-            patreon_client,
+            events,
+            providers,
             shutdown: Arc::new(AtomicBool::new(false)),
+            running: AtomicBool::new(false),
             retry_queue: Arc::new(Mutex::new(Vec::new())),
         }
     }
@@ -80,27 +101,27 @@ impl PatreonVerificationJob {
         let job = Arc::clone(&self);
         tokio::spawn(async move {
             let mut interval = interval(Duration::from_secs(VERIFICATION_INTERVAL_SECS));
-            info!("Patreon verification job started (runs every hour)");
+            info!("Membership verification job started (runs every hour)");
 
             loop {
                 interval.tick().await;
 
                 if job.shutdown.load(Ordering::Relaxed) {
-                    info!("Patreon verification job shutting down");
+                    info!("Membership verification job shutting down");
                     break;
                 }
 
                 job.run_verification().await;
             }
 
-            info!("Patreon verification job stopped");
+            info!("Membership verification job stopped");
         });
 
         // Start the retry task (runs every 30 minutes)
         let job = Arc::clone(&self);
         tokio::spawn(async move {
             let mut interval = interval(Duration::from_secs(RETRY_INTERVAL_SECS));
-            info!("Patreon retry job started (checks every 30 minutes)");
+            info!("Membership retry job started (checks every 30 minutes)");
 
             loop {
                 interval.tick().await;
@@ -120,9 +141,37 @@ impl PatreonVerificationJob {
         self.shutdown.store(true, Ordering::Relaxed);
     }
 
-    /// Run the verification process for all patrons
-    async fn run_verification(&self) {
-        info!("Starting hourly Patreon verification (tokens + membership)");
+    /// Whether a verification run is currently in progress
+    pub(crate) fn is_running(&self) -> bool {
+        self.running.load(Ordering::Relaxed)
+    }
+
+    /// Patrons currently waiting for a retry after a provider API error
+    pub(crate) async fn queued_patrons(&self) -> Vec<QueuedPatron> {
+        self.retry_queue
+            .lock()
+            .await
+            .iter()
+            .map(|p| QueuedPatron {
+                id: p.id,
+                provider: p.provider,
+                provider_user_id: p.provider_user_id.clone(),
+            })
+            .collect()
+    }
+
+    /// Run the verification process for all patrons, unless a run is already in progress
+    pub(crate) async fn run_verification(&self) {
+        if self.running.swap(true, Ordering::AcqRel) {
+            warn!("Membership verification is already running, skipping");
+            return;
+        }
+        let _running = RunningGuard(&self.running);
+        self.verify_all_patrons().await;
+    }
+
+    async fn verify_all_patrons(&self) {
+        info!("Starting hourly membership verification (tokens + membership)");
 
         // Fetch all patrons with stored tokens
         let patrons = match self.patron_repository.get_all_patrons_with_tokens().await {
@@ -166,24 +215,34 @@ impl PatreonVerificationJob {
         let Some(ref access_token) = patron.access_token else {
             warn!(
                 "Patron {} has no access token, skipping",
-                patron.patreon_user_id
+                patron.provider_user_id
             );
             return VerificationResult::Skipped;
         };
 
-        // Refresh token if needed and get the current access token
-        let (token_refreshed, current_access_token) = match self.maybe_refresh_token(patron).await {
-            TokenRefreshResult::Refreshed(new_token) => (true, new_token),
-            TokenRefreshResult::NotNeeded => (false, access_token.clone()),
-            TokenRefreshResult::Skipped => return VerificationResult::Skipped,
-            TokenRefreshResult::Failed => return VerificationResult::Failed,
+        let Some(provider) = self.providers.get(patron.provider) else {
+            warn!(
+                "Provider {} of patron {} is not configured, skipping",
+                patron.provider, patron.provider_user_id
+            );
+            return VerificationResult::Skipped;
         };
 
-        // Sync membership status from Patreon API
+        // Refresh token if needed and get the current access token
+        let (token_refreshed, current_access_token) =
+            match self.maybe_refresh_token(provider.as_ref(), patron).await {
+                TokenRefreshResult::Refreshed(new_token) => (true, new_token),
+                TokenRefreshResult::NotNeeded => (false, access_token.clone()),
+                TokenRefreshResult::Skipped => return VerificationResult::Skipped,
+                TokenRefreshResult::Failed => return VerificationResult::Failed,
+            };
+
+        // Sync membership status from the provider API
         match self
             .sync_membership(
+                provider.as_ref(),
                 patron.id,
-                &patron.patreon_user_id,
+                &patron.provider_user_id,
                 &current_access_token,
                 patron.slot_override,
             )
@@ -197,24 +256,24 @@ impl PatreonVerificationJob {
                 }
             }
             MembershipSyncResult::ApiError => {
-                self.queue_for_retry(
-                    patron.id,
-                    &patron.patreon_user_id,
-                    &current_access_token,
-                    patron.slot_override,
-                )
-                .await;
+                self.queue_for_retry(patron, &current_access_token).await;
                 VerificationResult::ApiError
             }
             MembershipSyncResult::DbError => VerificationResult::Failed,
         }
     }
 
-    /// Refresh token if expired or expiring soon
-    async fn maybe_refresh_token(&self, patron: &Patron) -> TokenRefreshResult {
-        let needs_refresh = patron
-            .token_expires_at
-            .is_none_or(|expires_at| expires_at <= Utc::now() + TimeDelta::hours(1));
+    /// Refresh token if expired or expiring soon, tokens without an expiration are kept
+    async fn maybe_refresh_token(
+        &self,
+        provider: &dyn MembershipProvider,
+        patron: &Patron,
+    ) -> TokenRefreshResult {
+        // Tokens with an unknown expiry are refreshed if the provider issued a refresh token
+        let needs_refresh = match patron.token_expires_at {
+            Some(expires_at) => expires_at <= Utc::now() + TimeDelta::hours(1),
+            None => patron.refresh_token.is_some(),
+        };
 
         if !needs_refresh {
             return TokenRefreshResult::NotNeeded;
@@ -223,90 +282,81 @@ impl PatreonVerificationJob {
         let Some(ref refresh_token) = patron.refresh_token else {
             warn!(
                 "Patron {} has no refresh token, skipping",
-                patron.patreon_user_id
+                patron.provider_user_id
             );
             return TokenRefreshResult::Skipped;
         };
 
         info!(
             "Refreshing token for patron {} (expires: {:?})",
-            patron.patreon_user_id, patron.token_expires_at
+            patron.provider_user_id, patron.token_expires_at
         );
 
-        match self.patreon_client.refresh_token(refresh_token).await {
-            Ok(token_response) => {
-                let new_expires_at = Utc::now() + TimeDelta::seconds(token_response.expires_in);
-
+        match provider.refresh_tokens(refresh_token).await {
+            Ok(tokens) => {
                 if let Err(e) = self
                     .patron_repository
-                    .update_patron_tokens(
-                        patron.id,
-                        &token_response.access_token,
-                        &token_response.refresh_token,
-                        new_expires_at,
-                    )
+                    .update_patron_tokens(patron.id, &tokens)
                     .await
                 {
                     error!(
                         "Failed to update tokens for patron {}: {e}",
-                        patron.patreon_user_id
+                        patron.provider_user_id
                     );
                     return TokenRefreshResult::Failed;
                 }
 
                 info!(
                     "Successfully refreshed token for patron {}",
-                    patron.patreon_user_id
+                    patron.provider_user_id
                 );
-                TokenRefreshResult::Refreshed(token_response.access_token)
+                TokenRefreshResult::Refreshed(tokens.access_token)
             }
             Err(e) => {
                 error!(
                     "Failed to refresh token for patron {} (will need to re-authenticate): {e}",
-                    patron.patreon_user_id
+                    patron.provider_user_id
                 );
                 TokenRefreshResult::Failed
             }
         }
     }
 
-    /// Sync membership status from Patreon API and update the database.
+    /// Sync membership status from the provider API and update the database.
     /// Also handles patron downgrades and cancellations by soft-deleting excess accounts.
     async fn sync_membership(
         &self,
+        provider: &dyn MembershipProvider,
         patron_id: uuid::Uuid,
-        patreon_user_id: &str,
+        provider_user_id: &str,
         access_token: &str,
         slot_override: Option<i32>,
     ) -> MembershipSyncResult {
-        match self.patreon_client.get_membership(access_token).await {
-            Ok(membership) => {
-                let (tier_id, pledge_amount_cents, is_active) =
-                    Self::extract_membership_data(membership);
-
+        match provider.get_membership(access_token).await {
+            Ok(MembershipStatus {
+                tier_id,
+                pledge_amount_cents,
+                is_active,
+            }) => {
                 if let Err(e) = self
-                    .patron_repository
-                    .update_patron_membership(
-                        patron_id,
-                        tier_id.clone(),
-                        pledge_amount_cents,
-                        is_active,
-                    )
+                    .update_membership(patron_id, tier_id, pledge_amount_cents, is_active)
                     .await
                 {
-                    error!("Failed to update membership for patron {patreon_user_id}: {e}");
+                    error!("Failed to update membership for patron {provider_user_id}: {e}");
                     return MembershipSyncResult::DbError;
                 }
 
                 info!(
-                    "Successfully synced membership for patron {patreon_user_id} (active: {is_active})"
+                    "Successfully synced membership for patron {provider_user_id} (active: {is_active})"
                 );
 
                 // Handle downgrade/cancellation
                 if let Err(e) = handle_downgrade_or_cancellation(
+                    &self.pg_client,
                     &self.steam_accounts_repository,
+                    self.events,
                     patron_id,
-                    patreon_user_id,
+                    provider_user_id,
                     pledge_amount_cents,
                     is_active,
                     slot_override,
@@ -314,62 +364,86 @@ impl PatreonVerificationJob {
                 .await
                 {
                     error!(
-                        "Failed to handle downgrade/cancellation for patron {patreon_user_id}: {e}"
+                        "Failed to handle downgrade/cancellation for patron {provider_user_id}: {e}"
                     );
                     // Don't return DbError here as the membership sync itself succeeded
                 }
 
                 // Handle reactivation (re-subscribe)
                 if let Err(e) = handle_reactivation(
+                    &self.pg_client,
                     &self.steam_accounts_repository,
+                    self.events,
                     patron_id,
-                    patreon_user_id,
+                    provider_user_id,
                     pledge_amount_cents,
                     is_active,
                     slot_override,
                 )
                 .await
                 {
-                    error!("Failed to handle reactivation for patron {patreon_user_id}: {e}");
+                    error!("Failed to handle reactivation for patron {provider_user_id}: {e}");
                 }
 
                 MembershipSyncResult::Success
             }
             Err(e) => {
-                warn!("Patreon API error for patron {patreon_user_id}, queuing for retry: {e}");
+                warn!(
+                    "{} API error for patron {provider_user_id}, queuing for retry: {e}",
+                    provider.kind()
+                );
                 MembershipSyncResult::ApiError
             }
         }
     }
 
-    /// Extract membership data from Patreon API response
-    fn extract_membership_data(
-        membership: Option<super::types::Membership>,
-    ) -> (Option<String>, Option<i32>, bool) {
-        match membership {
-            Some(m) => (
-                m.tier_id,
-                Some(m.pledge_amount_cents),
-                m.patron_status.as_deref() == Some("active_patron"),
-            ),
-            None => (None, Some(0), false),
+    /// Updates the membership of a patron, and records it in the same transaction if it changed.
+    ///
+    /// Only changes are recorded, the job syncs every patron each hour.
+    async fn update_membership(
+        &self,
+        patron_id: uuid::Uuid,
+        tier_id: Option<String>,
+        pledge_amount_cents: Option<i32>,
+        is_active: bool,
+    ) -> PatronRepositoryResult<()> {
+        let mut transaction = self.pg_client.begin().await?;
+        let changed = PatronRepository::update_patron_membership(
+            &mut *transaction,
+            patron_id,
+            tier_id.clone(),
+            pledge_amount_cents,
+            is_active,
+        )
+        .await?;
+        if changed {
+            self.events
+                .record(
+                    &mut *transaction,
+                    patron_id,
+                    PatronEventKind::MembershipUpdated,
+                    None,
+                    json!({
+                        "tier_id": tier_id,
+                        "pledge_amount_cents": pledge_amount_cents,
+                        "is_active": is_active,
+                    }),
+                )
+                .await?;
         }
+        transaction.commit().await?;
+        Ok(())
     }
 
-    /// Queue a patron for retry after Patreon API error
-    async fn queue_for_retry(
-        &self,
-        patron_id: uuid::Uuid,
-        patreon_user_id: &str,
-        access_token: &str,
-        slot_override: Option<i32>,
-    ) {
+    /// Queue a patron for retry after provider API error
+    async fn queue_for_retry(&self, patron: &Patron, access_token: &str) {
         let mut queue = self.retry_queue.lock().await;
         queue.push(RetryPatron {
-            id: patron_id,
-            patreon_user_id: patreon_user_id.to_string(),
+            id: patron.id,
+            provider: patron.provider,
+            provider_user_id: patron.provider_user_id.clone(),
             access_token: access_token.to_string(),
-            slot_override,
+            slot_override: patron.slot_override,
         });
     }
 
@@ -393,10 +467,14 @@ impl PatreonVerificationJob {
         let mut requeued_count = 0;
 
         for retry_patron in patrons_to_retry {
+            let Some(provider) = self.providers.get(retry_patron.provider) else {
+                continue;
+            };
             match self
                 .sync_membership(
+                    provider.as_ref(),
                     retry_patron.id,
-                    &retry_patron.patreon_user_id,
+                    &retry_patron.provider_user_id,
                     &retry_patron.access_token,
                     retry_patron.slot_override,
                 )
@@ -405,14 +483,14 @@ impl PatreonVerificationJob {
                 MembershipSyncResult::Success => {
                     info!(
                         "Retry successful: synced membership for patron {}",
-                        retry_patron.patreon_user_id
+                        retry_patron.provider_user_id
                     );
                     success_count += 1;
                 }
                 MembershipSyncResult::ApiError => {
                     warn!(
                         "Retry failed for patron {}, will try again",
-                        retry_patron.patreon_user_id
+                        retry_patron.provider_user_id
                     );
                     let mut queue = self.retry_queue.lock().await;
                     queue.push(retry_patron);
@@ -451,3 +529,20 @@ enum MembershipSyncResult {
     ApiError,
     DbError,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_running_guard_resets_on_panic() {
+        let running = AtomicBool::new(true);
+        let result = std::panic::catch_unwind(|| {
+            let _running = RunningGuard(&running);
+            panic!("verification failed");
+        });
+
+        assert!(result.is_err());
+        assert!(!running.load(Ordering::Acquire));
+    }
+}
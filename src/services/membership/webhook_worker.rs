@@ -0,0 +1,479 @@
+use core::time::Duration;
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::json;
+use sqlx::{PgConnection, Pool, Postgres};
+use strum::Display;
+use tokio::time::sleep;
+use tracing::{debug, info, warn};
+
+use super::{MembershipProviderKind, MembershipProviders, WebhookDelivery};
+use crate::services::patreon::events::{PatronEventKind, PatronEventLog, PatronEventSource};
+use crate::services::patreon::membership::{handle_downgrade_or_cancellation, handle_reactivation};
+use crate::services::patreon::repository::PatronRepository;
+use crate::services::patreon::steam_accounts_repository::SteamAccountsRepository;
+
+/// Interval for checking for pending events while there are none
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Events claimed at once, at most one per patron
+const BATCH_SIZE: i64 = 20;
+
+/// Events failing this many times are marked as failed, they can be replayed manually.
+const MAX_ATTEMPTS: i32 = 5;
+
+/// Delay before the first retry, doubled with every failed attempt
+const BASE_RETRY_DELAY_SECS: f64 = 30.;
+
+/// Upper bound of the delay between two attempts
+const MAX_RETRY_DELAY_SECS: f64 = 60. * 60.;
+
+/// Claimed events are retried after this time if their attempt was lost.
+const CLAIM_TIMEOUT_SECS: f64 = 5. * 60.;
+
+/// Bodies of events older than this are removed, they contain personal data of the supporter.
+/// Events without a body can't be replayed anymore.
+const BODY_RETENTION_SECS: f64 = 30. * 24. * 60. * 60.;
+
+/// Processing status of a stored webhook event
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Display)]
+#[strum(serialize_all = "snake_case")]
+enum WebhookEventStatus {
+    /// Waiting to be processed, or for a retry
+    Pending,
+    /// The membership change was applied
+    Processed,
+    /// The event doesn't belong to a known patron
+    Ignored,
+    /// A later event of the patron was applied already
+    Superseded,
+    /// Processing failed [`MAX_ATTEMPTS`] times
+    Failed,
+}
+
+/// Delay before the next attempt, after the given number of failed attempts.
+fn retry_delay_secs(attempts: i32) -> f64 {
+    let exponent = attempts.saturating_sub(1).clamp(0, 16);
+    (BASE_RETRY_DELAY_SECS * 2_f64.powi(exponent)).min(MAX_RETRY_DELAY_SECS)
+}
+
+/// The key identifying redeliveries of the same webhook event.
+///
+/// `delivery` is the same for identical deliveries, e.g. the HMAC signature of the body.
+pub(crate) fn dedupe_key(event: &str, delivery: &str) -> String {
+    format!("{event}:{}", delivery.to_ascii_lowercase())
+}
+
+/// Stores a verified webhook event, it is processed by the [`MembershipWebhookWorker`].
+///
+/// Returns `false` if an event with the same dedupe key was already stored.
+pub(crate) async fn store_webhook_event(
+    pg_client: &Pool<Postgres>,
+    provider: MembershipProviderKind,
+    delivery: &WebhookDelivery,
+    body: &str,
+) -> sqlx::Result<bool> {
+    let result = sqlx::query!(
+        r#"
+        INSERT INTO membership_webhook_events (provider, dedupe_key, event, provider_user_id, body)
+        VALUES ($1, $2, $3, $4, $5)
+        ON CONFLICT (provider, dedupe_key) DO NOTHING
+        "#,
+        provider.to_string(),
+        delivery.dedupe_key,
+        delivery.event,
+        delivery.provider_user_id,
+        body
+    )
+    .execute(pg_client)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// A stored webhook event, without its body
+#[derive(Debug, Serialize)]
+pub(crate) struct WebhookEventEntry {
+    id: i64,
+    provider: String,
+    event: String,
+    provider_user_id: String,
+    status: String,
+    attempts: i32,
+    last_error: Option<String>,
+    received_at: DateTime<Utc>,
+    processed_at: Option<DateTime<Utc>>,
+}
+
+/// Lists the stored webhook events, newest first.
+pub(crate) async fn list_webhook_events(
+    pg_client: &Pool<Postgres>,
+    provider: Option<&str>,
+    provider_user_id: Option<&str>,
+    status: Option<&str>,
+    before_id: Option<i64>,
+    limit: i64,
+) -> sqlx::Result<Vec<WebhookEventEntry>> {
+    sqlx::query_as!(
+        WebhookEventEntry,
+        r#"
+        SELECT id, provider, event, provider_user_id, status, attempts, last_error, received_at,
+            processed_at
+        FROM membership_webhook_events
+        WHERE ($1::text IS NULL OR provider = $1)
+          AND ($2::text IS NULL OR provider_user_id = $2)
+          AND ($3::text IS NULL OR status = $3)
+          AND ($4::bigint IS NULL OR id < $4)
+        ORDER BY id DESC
+        LIMIT $5
+        "#,
+        provider,
+        provider_user_id,
+        status,
+        before_id,
+        limit
+    )
+    .fetch_all(pg_client)
+    .await
+}
+
+/// Queues stored events for processing again, e.g. after a bug fix.
+///
+/// Every event received from `from_id` or `since` on is replayed, not only failed ones, so the
+/// latest event of a patron is applied again. Events older than the last applied event of their
+/// patron are superseded, and events whose body was removed after the retention period are not
+/// replayed. Returns the number of replayed events.
+pub(crate) async fn replay_webhook_events(
+    conn: &mut PgConnection,
+    from_id: Option<i64>,
+    since: Option<DateTime<Utc>>,
+    provider: Option<&str>,
+    provider_user_id: Option<&str>,
+) -> sqlx::Result<u64> {
+    let result = sqlx::query!(
+        r#"
+        UPDATE membership_webhook_events
+        SET status = 'pending', attempts = 0, next_attempt_at = now(), last_error = NULL,
+            processed_at = NULL
+        WHERE status <> 'pending' AND body IS NOT NULL
+          AND ($1::bigint IS NULL OR id >= $1)
+          AND ($2::timestamptz IS NULL OR received_at >= $2)
+          AND ($3::text IS NULL OR provider = $3)
+          AND ($4::text IS NULL OR provider_user_id = $4)
+        "#,
+        from_id,
+        since,
+        provider,
+        provider_user_id
+    )
+    .execute(conn)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+struct ClaimedEvent {
+    id: i64,
+    provider: String,
+    event: String,
+    provider_user_id: String,
+    body: String,
+    attempts: i32,
+}
+
+/// Worker applying stored membership provider webhook events
+///
+/// Only the oldest pending event of a patron can be claimed, so the events of a patron are applied
+/// in the order they were received, also with workers running on every instance. Failed events are
+/// retried with exponential backoff and block the later events of their patron until they are
+/// processed or marked as failed after [`MAX_ATTEMPTS`].
+///
+/// The providers don't send when a change happened, so the last applied event of every patron is
+/// stored and events older than it are superseded instead of applied, e.g. failed events that are
+/// replayed after a later event was applied.
+pub(crate) struct MembershipWebhookWorker {
+    pg_client: Pool<Postgres>,
+    providers: MembershipProviders,
+    patron_repository: PatronRepository,
+    steam_accounts_repository: SteamAccountsRepository,
+    events: PatronEventLog,
+}
+
+impl MembershipWebhookWorker {
+    pub(crate) fn new(
+        pg_client: Pool<Postgres>,
+        providers: MembershipProviders,
+        encryption_key: String,
+    ) -> Self {
+        Self {
+            providers,
+            patron_repository: PatronRepository::new(pg_client.clone(), encryption_key),
+            steam_accounts_repository: SteamAccountsRepository::new(pg_client.clone()),
+            events: PatronEventLog::new(PatronEventSource::Webhook),
+            pg_client,
+        }
+    }
+
+    /// Start the background worker task
+    pub(crate) fn start_background_worker(self: Arc<Self>) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            info!("Membership webhook worker started");
+            loop {
+                match self.claim_events().await {
+                    Ok(events) if !events.is_empty() => {
+                        futures::future::join_all(events.into_iter().map(|e| self.handle(e))).await;
+                    }
+                    Ok(_) => {
+                        if let Err(e) = self.remove_old_bodies().await {
+                            warn!("Failed to remove old membership webhook event bodies: {e}");
+                        }
+                        sleep(POLL_INTERVAL).await;
+                    }
+                    Err(e) => {
+                        warn!("Failed to claim membership webhook events: {e}");
+                        sleep(POLL_INTERVAL).await;
+                    }
+                }
+            }
+        })
+    }
+
+    /// Claims the oldest due event of each patron, pushing its next attempt back in case this
+    /// attempt gets lost.
+    async fn claim_events(&self) -> sqlx::Result<Vec<ClaimedEvent>> {
+        sqlx::query_as!(
+            ClaimedEvent,
+            r#"
+            UPDATE membership_webhook_events
+            SET attempts = attempts + 1, next_attempt_at = now() + make_interval(secs => $2)
+            WHERE id IN (
+                SELECT e.id
+                FROM membership_webhook_events e
+                WHERE e.status = 'pending' AND e.next_attempt_at <= now()
+                  AND NOT EXISTS (
+                      SELECT 1
+                      FROM membership_webhook_events p
+                      WHERE p.provider = e.provider
+                        AND p.provider_user_id = e.provider_user_id
+                        AND p.status = 'pending'
+                        AND p.id < e.id
+                  )
+                ORDER BY e.id
+                LIMIT $1
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING id, provider, event, provider_user_id, body AS "body!", attempts
+            "#,
+            BATCH_SIZE,
+            CLAIM_TIMEOUT_SECS
+        )
+        .fetch_all(&self.pg_client)
+        .await
+    }
+
+    async fn handle(&self, event: ClaimedEvent) {
+        let (status, error) = match self.process(&event).await {
+            Ok(WebhookEventStatus::Ignored) => (
+                WebhookEventStatus::Ignored,
+                Some("No patron with this provider user ID".to_owned()),
+            ),
+            Ok(WebhookEventStatus::Superseded) => (
+                WebhookEventStatus::Superseded,
+                Some("A later event of the patron was applied already".to_owned()),
+            ),
+            Ok(status) => (status, None),
+            Err(e) if event.attempts >= MAX_ATTEMPTS => {
+                warn!(
+                    "Marking membership webhook event {} as failed after {} attempts: {e}",
+                    event.id, event.attempts
+                );
+                (WebhookEventStatus::Failed, Some(e))
+            }
+            Err(e) => {
+                warn!(
+                    "Attempt {} of membership webhook event {} failed: {e}",
+                    event.attempts, event.id
+                );
+                (WebhookEventStatus::Pending, Some(e))
+            }
+        };
+        if let Err(e) = sqlx::query!(
+            r#"
+            UPDATE membership_webhook_events
+            SET status = $2, last_error = $3, next_attempt_at = now() + make_interval(secs => $4),
+                processed_at = CASE WHEN $2 = 'pending' THEN NULL ELSE now() END
+            WHERE id = $1
+            "#,
+            event.id,
+            status.to_string(),
+            error,
+            retry_delay_secs(event.attempts)
+        )
+        .execute(&self.pg_client)
+        .await
+        {
+            warn!(
+                "Failed to record attempt {} of membership webhook event {}: {e}",
+                event.attempts, event.id
+            );
+        }
+    }
+
+    /// Applies the membership of an event to its patron.
+    async fn process(&self, event: &ClaimedEvent) -> Result<WebhookEventStatus, String> {
+        let kind = event
+            .provider
+            .parse()
+            .map_err(|_| format!("Unknown provider {}", event.provider))?;
+        let provider = self
+            .providers
+            .get(kind)
+            .ok_or_else(|| format!("Provider {kind} is not configured"))?;
+        let membership = provider
+            .webhook_membership(&event.event, &event.body)
+            .map_err(|e| e.to_string())?;
+        let provider_user_id = &event.provider_user_id;
+
+        let Some(patron) = self
+            .patron_repository
+            .get_patron_by_provider_user_id(kind, provider_user_id)
+            .await
+            .map_err(|e| format!("Failed to look up patron: {e}"))?
+        else {
+            debug!("No patron found for {kind} user {provider_user_id}, ignoring");
+            return Ok(WebhookEventStatus::Ignored);
+        };
+
+        // The membership and its event are stored together, unless the event is superseded
+        let mut transaction = self
+            .pg_client
+            .begin()
+            .await
+            .map_err(|e| format!("Failed to begin transaction: {e}"))?;
+        let is_latest =
+            PatronRepository::mark_webhook_event_applied(&mut *transaction, patron.id, event.id)
+                .await
+                .map_err(|e| format!("Failed to mark event as applied: {e}"))?;
+        if !is_latest {
+            debug!(
+                "Skipping {kind} webhook event {} for user {provider_user_id}, a later one was applied",
+                event.id
+            );
+            return Ok(WebhookEventStatus::Superseded);
+        }
+        let changed = PatronRepository::update_patron_membership(
+            &mut *transaction,
+            patron.id,
+            membership.tier_id.clone(),
+            membership.pledge_amount_cents,
+            membership.is_active,
+        )
+        .await
+        .map_err(|e| format!("Failed to update membership: {e}"))?;
+        // Replayed events only change the membership if they were applied wrongly before
+        if changed {
+            self.events
+                .record(
+                    &mut *transaction,
+                    patron.id,
+                    PatronEventKind::MembershipUpdated,
+                    None,
+                    json!({
+                        "provider": kind,
+                        "event": event.event,
+                        "tier_id": membership.tier_id,
+                        "pledge_amount_cents": membership.pledge_amount_cents,
+                        "is_active": membership.is_active,
+                    }),
+                )
+                .await
+                .map_err(|e| format!("Failed to record membership update: {e}"))?;
+        }
+        transaction
+            .commit()
+            .await
+            .map_err(|e| format!("Failed to commit membership update: {e}"))?;
+
+        handle_downgrade_or_cancellation(
+            &self.pg_client,
+            &self.steam_accounts_repository,
+            self.events,
+            patron.id,
+            provider_user_id,
+            membership.pledge_amount_cents,
+            membership.is_active,
+            patron.slot_override,
+        )
+        .await?;
+        handle_reactivation(
+            &self.pg_client,
+            &self.steam_accounts_repository,
+            self.events,
+            patron.id,
+            provider_user_id,
+            membership.pledge_amount_cents,
+            membership.is_active,
+            patron.slot_override,
+        )
+        .await?;
+
+        info!(
+            "Applied {kind} webhook event {} ({}) for user {provider_user_id} (active: {}, pledge: {:?})",
+            event.id, event.event, membership.is_active, membership.pledge_amount_cents
+        );
+        Ok(WebhookEventStatus::Processed)
+    }
+
+    /// Removes the bodies of handled events older than [`BODY_RETENTION_SECS`].
+    async fn remove_old_bodies(&self) -> sqlx::Result<()> {
+        sqlx::query!(
+            r#"
+            UPDATE membership_webhook_events
+            SET body = NULL
+            WHERE body IS NOT NULL AND status <> 'pending'
+              AND received_at < now() - make_interval(secs => $1)
+            "#,
+            BODY_RETENTION_SECS
+        )
+        .execute(&self.pg_client)
+        .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    #[test]
+    fn test_dedupe_key() {
+        assert_eq!(
+            dedupe_key("members:update", "ABCdef01"),
+            "members:update:abcdef01"
+        );
+        assert_ne!(
+            dedupe_key("members:update", "abcdef01"),
+            dedupe_key("members:pledge:update", "abcdef01")
+        );
+    }
+
+    #[rstest]
+    #[case(1, 30.)]
+    #[case(2, 60.)]
+    #[case(5, 480.)]
+    #[case(i32::MAX, 3600.)]
+    fn test_retry_delay_secs(#[case] attempts: i32, #[case] expected: f64) {
+        assert!((retry_delay_secs(attempts) - expected).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn test_status_names_match_schema() {
+        assert_eq!(WebhookEventStatus::Pending.to_string(), "pending");
+        assert_eq!(WebhookEventStatus::Processed.to_string(), "processed");
+        assert_eq!(WebhookEventStatus::Ignored.to_string(), "ignored");
+        assert_eq!(WebhookEventStatus::Superseded.to_string(), "superseded");
+        assert_eq!(WebhookEventStatus::Failed.to_string(), "failed");
+    }
+}
@@ -1,5 +1,12 @@
 pub(super) mod assets;
+pub(super) mod callbacks;
+pub(super) mod custom_matches;
+pub(super) mod feature_flags;
+pub(super) mod leaderboard_resolver;
+pub(super) mod leaderboard_snapshots;
+pub(crate) mod membership;
 pub(crate) mod patreon;
 pub(super) mod rate_limiter;
 pub(crate) mod request_logger;
+pub(super) mod sql_jobs;
 pub(super) mod steam;
@@ -0,0 +1,128 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::Value;
+use sqlx::{PgConnection, Pool, Postgres};
+use strum::Display;
+use tracing::info;
+use uuid::Uuid;
+
+/// What happened to a patron or one of their Steam accounts
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Display)]
+#[strum(serialize_all = "snake_case")]
+pub(crate) enum PatronEventKind {
+    /// A Steam account was added
+    AccountAdded,
+    /// A Steam account was replaced by another one after its cooldown
+    AccountReplaced,
+    /// A Steam account was removed by the patron, starting its cooldown
+    AccountRemoved,
+    /// A removed Steam account was reactivated
+    AccountReactivated,
+    /// A Steam account was deactivated because of a downgrade or cancellation
+    AccountDeactivated,
+    /// The tier, pledge or status of the membership changed
+    MembershipUpdated,
+}
+
+/// Who caused a patron event
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Display)]
+#[strum(serialize_all = "snake_case")]
+pub(crate) enum PatronEventSource {
+    /// The patron through the `/v1/patron` endpoints
+    Patron,
+    /// A Patreon webhook
+    Webhook,
+    /// The hourly verification job
+    Verification,
+}
+
+/// A recorded patron event
+#[derive(Debug, Serialize)]
+pub(crate) struct PatronEvent {
+    id: i64,
+    created_at: DateTime<Utc>,
+    event: String,
+    source: String,
+    steam_id3: Option<i64>,
+    details: Option<Value>,
+}
+
+/// Appends events to the append-only `patron_events` table.
+#[derive(Clone, Copy)]
+pub(crate) struct PatronEventLog {
+    source: PatronEventSource,
+}
+
+impl PatronEventLog {
+    pub(crate) fn new(source: PatronEventSource) -> Self {
+        Self { source }
+    }
+
+    /// Records an event of a patron.
+    ///
+    /// The event has to be recorded in the transaction of the change, so either both or neither
+    /// are stored.
+    pub(crate) async fn record(
+        &self,
+        conn: &mut PgConnection,
+        patron_id: Uuid,
+        kind: PatronEventKind,
+        steam_id3: Option<i64>,
+        details: Value,
+    ) -> sqlx::Result<()> {
+        info!(
+            "Patron event {kind} of patron {patron_id} by {} (steam_id3: {steam_id3:?}): {details}",
+            self.source
+        );
+        let details = (!details.is_null()).then(|| details.to_string());
+        sqlx::query!(
+            r#"
+            INSERT INTO patron_events (patron_id, event, source, steam_id3, details)
+            VALUES ($1, $2, $3, $4, $5::text::jsonb)
+            "#,
+            patron_id,
+            kind.to_string(),
+            self.source.to_string(),
+            steam_id3,
+            details,
+        )
+        .execute(conn)
+        .await?;
+        Ok(())
+    }
+}
+
+/// Lists the events of a patron, newest first.
+///
+/// Only events older than `before_id` are returned if it is given, for pagination.
+pub(crate) async fn list_patron_events(
+    pg_client: &Pool<Postgres>,
+    patron_id: Uuid,
+    before_id: Option<i64>,
+    limit: i64,
+) -> sqlx::Result<Vec<PatronEvent>> {
+    Ok(sqlx::query!(
+        r#"
+        SELECT id, created_at, event, source, steam_id3, details::text
+        FROM patron_events
+        WHERE patron_id = $1 AND ($2::bigint IS NULL OR id < $2)
+        ORDER BY id DESC
+        LIMIT $3
+        "#,
+        patron_id,
+        before_id,
+        limit,
+    )
+    .fetch_all(pg_client)
+    .await?
+    .into_iter()
+    .map(|row| PatronEvent {
+        id: row.id,
+        created_at: row.created_at,
+        event: row.event,
+        source: row.source,
+        steam_id3: row.steam_id3,
+        details: row.details.and_then(|d| serde_json::from_str(&d).ok()),
+    })
+    .collect())
+}
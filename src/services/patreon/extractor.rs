@@ -31,7 +31,7 @@ impl FromRequestParts<AppState> for PatronSession {
         state: &AppState,
     ) -> Result<Self, Self::Rejection> {
         // Try to extract token from cookie first, then from Authorization header
-        let token = extract_token_from_cookie(&parts.headers)
+        let token = extract_token_from_cookie(&parts.headers, "patron_session")
             .or_else(|| extract_token_from_auth_header(&parts.headers));
 
         if let Some(token) = token {
@@ -66,21 +66,27 @@ impl FromRequestParts<AppState> for PatronSession {
     }
 }
 
-/// Extracts the JWT token from the `patron_session` cookie
-fn extract_token_from_cookie(headers: &axum::http::HeaderMap) -> Option<String> {
+/// Extracts the JWT token from a session cookie, e.g. `patron_session`
+pub(crate) fn extract_token_from_cookie(
+    headers: &axum::http::HeaderMap,
+    cookie_name: &str,
+) -> Option<String> {
     headers
         .get(axum::http::header::COOKIE)
         .and_then(|v| v.to_str().ok())
         .and_then(|cookies| {
             cookies.split(';').find_map(|cookie| {
                 let cookie = cookie.trim();
-                cookie.strip_prefix("patron_session=").map(str::to_string)
+                cookie
+                    .strip_prefix(cookie_name)
+                    .and_then(|c| c.strip_prefix('='))
+                    .map(str::to_string)
             })
         })
 }
 
 /// Extracts the JWT token from the `Authorization: Bearer <token>` header
-fn extract_token_from_auth_header(headers: &axum::http::HeaderMap) -> Option<String> {
+pub(crate) fn extract_token_from_auth_header(headers: &axum::http::HeaderMap) -> Option<String> {
     headers
         .get(axum::http::header::AUTHORIZATION)
         .and_then(|v| v.to_str().ok())
@@ -126,7 +132,7 @@ mod tests {
             HeaderValue::from_static("patron_session=test_token_123"),
         );
 
-        let token = extract_token_from_cookie(&headers);
+        let token = extract_token_from_cookie(&headers, "patron_session");
         assert_eq!(token, Some("test_token_123".to_string()));
     }
 
@@ -138,14 +144,28 @@ mod tests {
             HeaderValue::from_static("other=value; patron_session=test_token_456; another=thing"),
         );
 
-        let token = extract_token_from_cookie(&headers);
+        let token = extract_token_from_cookie(&headers, "patron_session");
         assert_eq!(token, Some("test_token_456".to_string()));
     }
 
+    #[test]
+    fn test_extract_token_from_cookie_by_name() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            http::header::COOKIE,
+            HeaderValue::from_static(
+                "steam_session_old=x; steam_session=steam_token; patron_session=patron_token",
+            ),
+        );
+
+        let token = extract_token_from_cookie(&headers, "steam_session");
+        assert_eq!(token, Some("steam_token".to_string()));
+    }
+
     #[test]
     fn test_extract_token_from_cookie_missing() {
         let headers = HeaderMap::new();
-        let token = extract_token_from_cookie(&headers);
+        let token = extract_token_from_cookie(&headers, "patron_session");
         assert_eq!(token, None);
     }
 
@@ -1,14 +1,37 @@
-use chrono::{Duration, Utc};
+use chrono::{DateTime, Duration, Utc};
 use jsonwebtoken::{DecodingKey, EncodingKey, Header, Validation, decode, encode};
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 use uuid::Uuid;
 
+/// Audience of patron session tokens
+const PATRON_AUDIENCE: &str = "patron";
+
+/// Audience of Steam session tokens
+const STEAM_AUDIENCE: &str = "steam";
+
 /// Claims stored in patron JWT session token
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub(crate) struct PatronClaims {
     /// Patron's database UUID
     pub(crate) patron_id: Uuid,
+    /// Audience, always [`PATRON_AUDIENCE`]
+    aud: String,
+    /// Expiration time (Unix timestamp)
+    pub(crate) exp: i64,
+}
+
+/// Claims stored in Steam JWT session token
+///
+/// The audience differs from [`PatronClaims`], so a Steam session token is never accepted as a
+/// patron session token and vice versa.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct SteamClaims {
+    /// `SteamID3` of the player verified by the Steam `OpenID` login
+    pub(crate) steam_id3: u32,
+    /// Audience, always [`STEAM_AUDIENCE`]
+    aud: String,
     /// Expiration time (Unix timestamp)
     pub(crate) exp: i64,
 }
@@ -24,36 +47,120 @@ pub(crate) enum JwtError {
 
 pub(crate) type JwtResult<T> = Result<T, JwtError>;
 
+/// Session tokens are valid for 7 days from creation.
+fn session_expiration() -> DateTime<Utc> {
+    Utc::now() + Duration::days(7)
+}
+
+fn encode_token<C: Serialize>(claims: &C, secret: &str) -> JwtResult<String> {
+    Ok(encode(
+        &Header::default(),
+        claims,
+        &EncodingKey::from_secret(secret.as_bytes()),
+    )?)
+}
+
+/// Decodes a token, which has to be issued for the given audience.
+fn decode_token<C: DeserializeOwned + Clone>(
+    token: &str,
+    secret: &str,
+    audience: &str,
+) -> JwtResult<C> {
+    let mut validation = Validation::default();
+    validation.set_audience(&[audience]);
+    validation.set_required_spec_claims(&["exp", "aud"]);
+    let token_data = decode::<C>(
+        token,
+        &DecodingKey::from_secret(secret.as_bytes()),
+        &validation,
+    )
+    .map_err(|_| JwtError::InvalidToken)?;
+
+    Ok(token_data.claims)
+}
+
 /// Creates a JWT session token for a patron
 ///
 /// Tokens are valid for 7 days from creation.
 pub(crate) fn create_session_token(patron_id: Uuid, secret: &str) -> JwtResult<String> {
-    let expiration = Utc::now() + Duration::days(7);
-
     let claims = PatronClaims {
         patron_id,
-        exp: expiration.timestamp(),
+        aud: PATRON_AUDIENCE.to_owned(),
+        exp: session_expiration().timestamp(),
     };
 
-    let token = encode(
-        &Header::default(),
-        &claims,
-        &EncodingKey::from_secret(secret.as_bytes()),
-    )?;
-
-    Ok(token)
+    encode_token(&claims, secret)
 }
 
 /// Validates a JWT session token and extracts the claims
 ///
 /// Returns an error if the token is invalid, expired, or has an invalid signature.
 pub(crate) fn validate_session_token(token: &str, secret: &str) -> JwtResult<PatronClaims> {
-    let token_data = decode::<PatronClaims>(
-        token,
-        &DecodingKey::from_secret(secret.as_bytes()),
-        &Validation::default(),
-    )
-    .map_err(|_| JwtError::InvalidToken)?;
+    decode_token(token, secret, PATRON_AUDIENCE)
+}
 
-    Ok(token_data.claims)
+/// Creates a JWT session token for a player verified by the Steam `OpenID` login
+///
+/// Tokens are valid for 7 days from creation.
+pub(crate) fn create_steam_session_token(steam_id3: u32, secret: &str) -> JwtResult<String> {
+    let claims = SteamClaims {
+        steam_id3,
+        aud: STEAM_AUDIENCE.to_owned(),
+        exp: session_expiration().timestamp(),
+    };
+
+    encode_token(&claims, secret)
+}
+
+/// Validates a Steam JWT session token and extracts the claims
+///
+/// Returns an error if the token is invalid, expired, or has an invalid signature.
+pub(crate) fn validate_steam_session_token(token: &str, secret: &str) -> JwtResult<SteamClaims> {
+    decode_token(token, secret, STEAM_AUDIENCE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &str = "test_secret_at_least_32_characters_long";
+
+    #[test]
+    fn test_steam_session_token_roundtrip() {
+        let token = create_steam_session_token(123_456, SECRET).unwrap();
+        let claims = validate_steam_session_token(&token, SECRET).unwrap();
+        assert_eq!(claims.steam_id3, 123_456);
+        assert!(validate_steam_session_token(&token, "other_secret").is_err());
+    }
+
+    #[test]
+    fn test_session_tokens_are_not_interchangeable() {
+        let steam_token = create_steam_session_token(123_456, SECRET).unwrap();
+        let patron_token = create_session_token(Uuid::new_v4(), SECRET).unwrap();
+
+        assert!(validate_session_token(&steam_token, SECRET).is_err());
+        assert!(validate_steam_session_token(&patron_token, SECRET).is_err());
+    }
+
+    #[test]
+    fn test_session_tokens_require_their_audience() {
+        let exp = session_expiration().timestamp();
+        let steam_audience = encode_token(
+            &PatronClaims {
+                patron_id: Uuid::new_v4(),
+                aud: STEAM_AUDIENCE.to_owned(),
+                exp,
+            },
+            SECRET,
+        )
+        .unwrap();
+        let no_audience = encode_token(
+            &serde_json::json!({ "patron_id": Uuid::new_v4(), "exp": exp }),
+            SECRET,
+        )
+        .unwrap();
+
+        assert!(validate_session_token(&steam_audience, SECRET).is_err());
+        assert!(validate_session_token(&no_audience, SECRET).is_err());
+    }
 }
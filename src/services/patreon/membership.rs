@@ -1,5 +1,9 @@
+use serde_json::json;
+use sqlx::{Pool, Postgres};
 use tracing::info;
 
+use super::events::{PatronEventKind, PatronEventLog};
+use super::forget_account_priorities;
 use super::steam_accounts_repository::SteamAccountsRepository;
 use super::types::calculate_slot_limit;
 
@@ -7,11 +11,14 @@ use super::types::calculate_slot_limit;
 ///
 /// When a patron re-subscribes (pays again) or has a slot override, reactivate their
 /// previously soft-deleted accounts so they don't have to re-add them manually.
+/// Every reactivated account is recorded in the patron's event history, in the same transaction.
 #[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
 pub(crate) async fn handle_reactivation(
+    pg_client: &Pool<Postgres>,
     steam_accounts_repository: &SteamAccountsRepository,
+    events: PatronEventLog,
     patron_id: uuid::Uuid,
-    patreon_user_id: &str,
+    provider_user_id: &str,
     pledge_amount_cents: Option<i32>,
     is_active: bool,
     slot_override: Option<i32>,
@@ -43,19 +50,40 @@ pub(crate) async fn handle_reactivation(
         return Ok(());
     }
 
-    let accounts_to_reactivate: Vec<uuid::Uuid> = deleted_accounts
-        .into_iter()
-        .take(available_slots)
-        .map(|a| a.id)
-        .collect();
+    let accounts_to_reactivate: Vec<_> =
+        deleted_accounts.into_iter().take(available_slots).collect();
 
-    let reactivated_count = steam_accounts_repository
-        .reactivate_accounts(&accounts_to_reactivate, patron_id)
+    let account_ids: Vec<uuid::Uuid> = accounts_to_reactivate.iter().map(|a| a.id).collect();
+    let mut transaction = pg_client
+        .begin()
         .await
-        .map_err(|e| format!("Failed to reactivate accounts: {e}"))?;
+        .map_err(|e| format!("Failed to begin transaction: {e}"))?;
+    let reactivated =
+        SteamAccountsRepository::reactivate_accounts(&mut *transaction, &account_ids, patron_id)
+            .await
+            .map_err(|e| format!("Failed to reactivate accounts: {e}"))?;
+
+    for &steam_id3 in &reactivated {
+        events
+            .record(
+                &mut *transaction,
+                patron_id,
+                PatronEventKind::AccountReactivated,
+                Some(steam_id3),
+                json!({ "slot_limit": slot_limit }),
+            )
+            .await
+            .map_err(|e| format!("Failed to record reactivation: {e}"))?;
+    }
+    transaction
+        .commit()
+        .await
+        .map_err(|e| format!("Failed to commit reactivation: {e}"))?;
+    forget_account_priorities(&reactivated).await;
 
     info!(
-        "Reactivated {reactivated_count} accounts for patron {patreon_user_id} (slot limit: {slot_limit})"
+        "Reactivated {} accounts for patron {provider_user_id} (slot limit: {slot_limit})",
+        reactivated.len()
     );
 
     Ok(())
@@ -66,11 +94,16 @@ pub(crate) async fn handle_reactivation(
 /// - If `is_active` is false and patron has no slot override, soft-delete ALL accounts
 /// - If patron has a slot override, respect that limit even when subscription is inactive
 /// - If new slot limit < active accounts count, soft-delete oldest accounts first
+///
+/// Every deactivated account is recorded in the patron's event history with the reason, in the
+/// same transaction.
 #[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
 pub(crate) async fn handle_downgrade_or_cancellation(
+    pg_client: &Pool<Postgres>,
     steam_accounts_repository: &SteamAccountsRepository,
+    events: PatronEventLog,
     patron_id: uuid::Uuid,
-    patreon_user_id: &str,
+    provider_user_id: &str,
     pledge_amount_cents: Option<i32>,
     is_active: bool,
     slot_override: Option<i32>,
@@ -85,7 +118,7 @@ pub(crate) async fn handle_downgrade_or_cancellation(
         return Ok(());
     }
 
-    let accounts_to_delete = if is_active || slot_override.is_some_and(|s| s > 0) {
+    let (accounts_to_delete, details) = if is_active || slot_override.is_some_and(|s| s > 0) {
         let new_slot_limit = calculate_slot_limit(slot_override, pledge_amount_cents);
         // Safe cast: practical slot limits will never exceed i32::MAX
         let active_count = active_accounts.len() as i32;
@@ -99,17 +132,20 @@ pub(crate) async fn handle_downgrade_or_cancellation(
         // Safe: we've verified active_count > new_slot_limit, so this is positive
         let excess_count = (active_count - new_slot_limit).unsigned_abs() as usize;
         info!(
-            "Patron {patreon_user_id} downgraded: {} active accounts, {} slots allowed, soft-deleting {} oldest",
+            "Patron {provider_user_id} downgraded: {} active accounts, {} slots allowed, soft-deleting {} oldest",
             active_count, new_slot_limit, excess_count
         );
-        active_accounts.into_iter().take(excess_count).collect()
+        (
+            active_accounts.into_iter().take(excess_count).collect(),
+            json!({ "reason": "downgrade", "slot_limit": new_slot_limit }),
+        )
     } else {
         // Patron cancelled with no slot override: soft-delete ALL accounts
         info!(
-            "Patron {patreon_user_id} cancelled, soft-deleting all {} accounts",
+            "Patron {provider_user_id} cancelled, soft-deleting all {} accounts",
             active_accounts.len()
         );
-        active_accounts
+        (active_accounts, json!({ "reason": "cancellation" }))
     };
 
     if accounts_to_delete.is_empty() {
@@ -117,14 +153,36 @@ pub(crate) async fn handle_downgrade_or_cancellation(
     }
 
     let account_ids: Vec<uuid::Uuid> = accounts_to_delete.iter().map(|a| a.id).collect();
-    let deleted_count = steam_accounts_repository
-        .soft_delete_accounts(&account_ids, patron_id)
+    let mut transaction = pg_client
+        .begin()
+        .await
+        .map_err(|e| format!("Failed to begin transaction: {e}"))?;
+    let deleted =
+        SteamAccountsRepository::soft_delete_accounts(&mut *transaction, &account_ids, patron_id)
+            .await
+            .map_err(|e| format!("Failed to soft-delete accounts: {e}"))?;
+
+    for &steam_id3 in &deleted {
+        events
+            .record(
+                &mut *transaction,
+                patron_id,
+                PatronEventKind::AccountDeactivated,
+                Some(steam_id3),
+                details.clone(),
+            )
+            .await
+            .map_err(|e| format!("Failed to record deactivation: {e}"))?;
+    }
+    transaction
+        .commit()
         .await
-        .map_err(|e| format!("Failed to soft-delete accounts: {e}"))?;
+        .map_err(|e| format!("Failed to commit deactivation: {e}"))?;
+    forget_account_priorities(&deleted).await;
 
     info!(
-        "Soft-deleted {} accounts for patron {patreon_user_id}",
-        deleted_count
+        "Soft-deleted {} accounts for patron {provider_user_id}",
+        deleted.len()
     );
 
     Ok(())
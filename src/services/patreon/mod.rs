@@ -1,15 +1,15 @@
 pub(crate) mod client;
+pub(crate) mod events;
 pub(crate) mod extractor;
 pub(crate) mod jwt;
 pub(crate) mod membership;
 pub(crate) mod repository;
 pub(crate) mod steam_accounts_repository;
 pub(crate) mod types;
-pub(crate) mod verification_job;
 pub(crate) mod webhook_types;
 
-use cached::TimedCache;
 use cached::proc_macro::cached;
+use cached::{Cached, TimedCache};
 use sqlx::{Pool, Postgres};
 
 #[cached(
@@ -39,3 +39,14 @@ pub(crate) async fn is_account_prioritized(
 
     Ok(result.exists)
 }
+
+/// Drops the cached priority of Steam accounts whose prioritization changed.
+///
+/// Has to be called once the change is committed, otherwise the old priority could be cached
+/// again.
+pub(crate) async fn forget_account_priorities(steam_id3s: &[i64]) {
+    let mut cache = IS_ACCOUNT_PRIORITIZED.lock().await;
+    for steam_id3 in steam_id3s {
+        cache.cache_remove(steam_id3);
+    }
+}
@@ -1,10 +1,11 @@
 #![allow(dead_code)]
 
 use chrono::{DateTime, Utc};
-use sqlx::{Pool, Postgres};
+use sqlx::{PgConnection, Pool, Postgres};
 use thiserror::Error;
 
 use super::types::{Patron, TokenCryptoError, decrypt_token, encrypt_token};
+use crate::services::membership::{MembershipProviderKind, ProviderTokens};
 
 /// Error type for patron repository operations
 #[derive(Debug, Error)]
@@ -13,6 +14,8 @@ pub(crate) enum PatronRepositoryError {
     Database(#[from] sqlx::Error),
     #[error("Token encryption error: {0}")]
     Encryption(#[from] TokenCryptoError),
+    #[error("Unknown membership provider: {0}")]
+    UnknownProvider(String),
 }
 
 pub(crate) type PatronRepositoryResult<T> = Result<T, PatronRepositoryError>;
@@ -24,9 +27,28 @@ pub(crate) struct PatronRepository {
     encryption_key: String,
 }
 
+/// A row of the `patrons` table, with encrypted tokens
+struct PatronRow {
+    id: uuid::Uuid,
+    provider: String,
+    provider_user_id: String,
+    email: Option<String>,
+    tier_id: Option<String>,
+    pledge_amount_cents: Option<i32>,
+    slot_override: Option<i32>,
+    is_active: bool,
+    access_token: Option<String>,
+    refresh_token: Option<String>,
+    token_expires_at: Option<DateTime<Utc>>,
+    last_verified_at: Option<DateTime<Utc>>,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+}
+
 /// Parameters for creating or updating a patron
 pub(crate) struct UpsertPatronParams {
-    pub(crate) patreon_user_id: String,
+    pub(crate) provider: MembershipProviderKind,
+    pub(crate) provider_user_id: String,
     pub(crate) email: Option<String>,
     pub(crate) tier_id: Option<String>,
     pub(crate) pledge_amount_cents: Option<i32>,
@@ -44,7 +66,42 @@ impl PatronRepository {
         }
     }
 
-    /// Creates or updates a patron record by `patreon_user_id`.
+    /// Maps a row to a patron, decrypting its tokens.
+    fn patron_from_row(&self, row: PatronRow) -> PatronRepositoryResult<Patron> {
+        let provider = row
+            .provider
+            .parse()
+            .map_err(|_| PatronRepositoryError::UnknownProvider(row.provider))?;
+        let access_token = row
+            .access_token
+            .as_ref()
+            .map(|t| decrypt_token(t, &self.encryption_key))
+            .transpose()?;
+        let refresh_token = row
+            .refresh_token
+            .as_ref()
+            .map(|t| decrypt_token(t, &self.encryption_key))
+            .transpose()?;
+
+        Ok(Patron {
+            id: row.id,
+            provider,
+            provider_user_id: row.provider_user_id,
+            email: row.email,
+            tier_id: row.tier_id,
+            pledge_amount_cents: row.pledge_amount_cents,
+            slot_override: row.slot_override,
+            is_active: row.is_active,
+            access_token,
+            refresh_token,
+            token_expires_at: row.token_expires_at,
+            last_verified_at: row.last_verified_at,
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+        })
+    }
+
+    /// Creates or updates a patron record by `provider` and `provider_user_id`.
     /// Encrypts `access_token` and `refresh_token` before storing.
     pub(crate) async fn create_or_update_patron(
         &self,
@@ -65,10 +122,12 @@ impl PatronRepository {
 
         let now = Utc::now();
 
-        let row = sqlx::query!(
+        let row = sqlx::query_as!(
+            PatronRow,
             r#"
             INSERT INTO patrons (
-                patreon_user_id,
+                provider,
+                provider_user_id,
                 email,
                 tier_id,
                 pledge_amount_cents,
@@ -79,8 +138,8 @@ impl PatronRepository {
                 last_verified_at,
                 updated_at
             )
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $9)
-            ON CONFLICT (patreon_user_id)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $10)
+            ON CONFLICT (provider, provider_user_id)
             DO UPDATE SET
                 email = COALESCE(EXCLUDED.email, patrons.email),
                 tier_id = EXCLUDED.tier_id,
@@ -93,7 +152,8 @@ impl PatronRepository {
                 updated_at = EXCLUDED.updated_at
             RETURNING
                 id,
-                patreon_user_id,
+                provider,
+                provider_user_id,
                 email,
                 tier_id,
                 pledge_amount_cents,
@@ -106,7 +166,8 @@ impl PatronRepository {
                 created_at,
                 updated_at
             "#,
-            params.patreon_user_id,
+            params.provider.to_string(),
+            params.provider_user_id,
             params.email,
             params.tier_id,
             params.pledge_amount_cents,
@@ -119,34 +180,7 @@ impl PatronRepository {
         .fetch_one(&self.pg_client)
         .await?;
 
-        // Decrypt tokens for the returned Patron struct
-        let decrypted_access_token = row
-            .access_token
-            .as_ref()
-            .map(|t| decrypt_token(t, &self.encryption_key))
-            .transpose()?;
-
-        let decrypted_refresh_token = row
-            .refresh_token
-            .as_ref()
-            .map(|t| decrypt_token(t, &self.encryption_key))
-            .transpose()?;
-
-        Ok(Patron {
-            id: row.id,
-            patreon_user_id: row.patreon_user_id,
-            email: row.email,
-            tier_id: row.tier_id,
-            pledge_amount_cents: row.pledge_amount_cents,
-            slot_override: row.slot_override,
-            is_active: row.is_active,
-            access_token: decrypted_access_token,
-            refresh_token: decrypted_refresh_token,
-            token_expires_at: row.token_expires_at,
-            last_verified_at: row.last_verified_at,
-            created_at: row.created_at,
-            updated_at: row.updated_at,
-        })
+        self.patron_from_row(row)
     }
 
     /// Gets a patron by their internal UUID.
@@ -155,11 +189,13 @@ impl PatronRepository {
         &self,
         patron_id: uuid::Uuid,
     ) -> PatronRepositoryResult<Option<Patron>> {
-        let row = sqlx::query!(
+        let row = sqlx::query_as!(
+            PatronRow,
             r#"
             SELECT
                 id,
-                patreon_user_id,
+                provider,
+                provider_user_id,
                 email,
                 tier_id,
                 pledge_amount_cents,
@@ -179,51 +215,23 @@ impl PatronRepository {
         .fetch_optional(&self.pg_client)
         .await?;
 
-        match row {
-            Some(row) => {
-                let decrypted_access_token = row
-                    .access_token
-                    .as_ref()
-                    .map(|t| decrypt_token(t, &self.encryption_key))
-                    .transpose()?;
-
-                let decrypted_refresh_token = row
-                    .refresh_token
-                    .as_ref()
-                    .map(|t| decrypt_token(t, &self.encryption_key))
-                    .transpose()?;
-
-                Ok(Some(Patron {
-                    id: row.id,
-                    patreon_user_id: row.patreon_user_id,
-                    email: row.email,
-                    tier_id: row.tier_id,
-                    pledge_amount_cents: row.pledge_amount_cents,
-                    slot_override: row.slot_override,
-                    is_active: row.is_active,
-                    access_token: decrypted_access_token,
-                    refresh_token: decrypted_refresh_token,
-                    token_expires_at: row.token_expires_at,
-                    last_verified_at: row.last_verified_at,
-                    created_at: row.created_at,
-                    updated_at: row.updated_at,
-                }))
-            }
-            None => Ok(None),
-        }
+        row.map(|row| self.patron_from_row(row)).transpose()
     }
 
-    /// Gets a patron by their Patreon user ID.
+    /// Gets a patron by their user ID on a membership provider.
     /// Decrypts `access_token` and `refresh_token` when reading.
-    pub(crate) async fn get_patron_by_patreon_user_id(
+    pub(crate) async fn get_patron_by_provider_user_id(
         &self,
-        patreon_user_id: &str,
+        provider: MembershipProviderKind,
+        provider_user_id: &str,
     ) -> PatronRepositoryResult<Option<Patron>> {
-        let row = sqlx::query!(
+        let row = sqlx::query_as!(
+            PatronRow,
             r#"
             SELECT
                 id,
-                patreon_user_id,
+                provider,
+                provider_user_id,
                 email,
                 tier_id,
                 pledge_amount_cents,
@@ -236,72 +244,44 @@ impl PatronRepository {
                 created_at,
                 updated_at
             FROM patrons
-            WHERE patreon_user_id = $1
+            WHERE provider = $1 AND provider_user_id = $2
             "#,
-            patreon_user_id,
+            provider.to_string(),
+            provider_user_id,
         )
         .fetch_optional(&self.pg_client)
         .await?;
 
-        match row {
-            Some(row) => {
-                let decrypted_access_token = row
-                    .access_token
-                    .as_ref()
-                    .map(|t| decrypt_token(t, &self.encryption_key))
-                    .transpose()?;
-
-                let decrypted_refresh_token = row
-                    .refresh_token
-                    .as_ref()
-                    .map(|t| decrypt_token(t, &self.encryption_key))
-                    .transpose()?;
-
-                Ok(Some(Patron {
-                    id: row.id,
-                    patreon_user_id: row.patreon_user_id,
-                    email: row.email,
-                    tier_id: row.tier_id,
-                    pledge_amount_cents: row.pledge_amount_cents,
-                    slot_override: row.slot_override,
-                    is_active: row.is_active,
-                    access_token: decrypted_access_token,
-                    refresh_token: decrypted_refresh_token,
-                    token_expires_at: row.token_expires_at,
-                    last_verified_at: row.last_verified_at,
-                    created_at: row.created_at,
-                    updated_at: row.updated_at,
-                }))
-            }
-            None => Ok(None),
-        }
+        row.map(|row| self.patron_from_row(row)).transpose()
     }
 
     /// Updates the tokens for a patron after a successful refresh.
-    /// Encrypts tokens before storing.
+    /// Encrypts tokens before storing, the refresh token is kept if no new one was issued.
     pub(crate) async fn update_patron_tokens(
         &self,
         patron_id: uuid::Uuid,
-        access_token: &str,
-        refresh_token: &str,
-        token_expires_at: DateTime<Utc>,
+        tokens: &ProviderTokens,
     ) -> PatronRepositoryResult<()> {
-        let encrypted_access_token = encrypt_token(access_token, &self.encryption_key)?;
-        let encrypted_refresh_token = encrypt_token(refresh_token, &self.encryption_key)?;
+        let encrypted_access_token = encrypt_token(&tokens.access_token, &self.encryption_key)?;
+        let encrypted_refresh_token = tokens
+            .refresh_token
+            .as_ref()
+            .map(|t| encrypt_token(t, &self.encryption_key))
+            .transpose()?;
         let now = Utc::now();
 
         sqlx::query!(
             r#"
             UPDATE patrons
             SET access_token = $1,
-                refresh_token = $2,
+                refresh_token = COALESCE($2, refresh_token),
                 token_expires_at = $3,
                 updated_at = $4
             WHERE id = $5
             "#,
             encrypted_access_token,
             encrypted_refresh_token,
-            token_expires_at,
+            tokens.expires_at,
             now,
             patron_id,
         )
@@ -311,25 +291,60 @@ impl PatronRepository {
         Ok(())
     }
 
+    /// Marks a membership webhook event as the last one applied to a patron.
+    ///
+    /// Returns `false` without changes if a later event was applied already, the event is
+    /// superseded then. The same event can be applied again, e.g. when it is replayed.
+    pub(crate) async fn mark_webhook_event_applied(
+        conn: &mut PgConnection,
+        patron_id: uuid::Uuid,
+        event_id: i64,
+    ) -> PatronRepositoryResult<bool> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE patrons
+            SET webhook_event_id = $2
+            WHERE id = $1 AND (webhook_event_id IS NULL OR webhook_event_id <= $2)
+            "#,
+            patron_id,
+            event_id
+        )
+        .execute(conn)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
     /// Updates the membership status for a patron during daily verification.
+    /// Returns whether the tier, pledge or status changed.
     pub(crate) async fn update_patron_membership(
-        &self,
+        conn: &mut PgConnection,
         patron_id: uuid::Uuid,
         tier_id: Option<String>,
         pledge_amount_cents: Option<i32>,
         is_active: bool,
-    ) -> PatronRepositoryResult<()> {
+    ) -> PatronRepositoryResult<bool> {
         let now = Utc::now();
 
-        sqlx::query!(
+        let row = sqlx::query!(
             r#"
-            UPDATE patrons
+            UPDATE patrons p
             SET tier_id = $1,
                 pledge_amount_cents = $2,
                 is_active = $3,
                 last_verified_at = $4,
                 updated_at = $4
-            WHERE id = $5
+            FROM (
+                SELECT id, tier_id, pledge_amount_cents, is_active
+                FROM patrons
+                WHERE id = $5
+                FOR UPDATE
+            ) previous
+            WHERE p.id = previous.id
+            RETURNING (
+                previous.tier_id IS DISTINCT FROM p.tier_id
+                OR previous.pledge_amount_cents IS DISTINCT FROM p.pledge_amount_cents
+                OR previous.is_active IS DISTINCT FROM p.is_active
+            ) AS "changed!"
             "#,
             tier_id,
             pledge_amount_cents,
@@ -337,21 +352,23 @@ impl PatronRepository {
             now,
             patron_id,
         )
-        .execute(&self.pg_client)
+        .fetch_optional(conn)
         .await?;
 
-        Ok(())
+        Ok(row.is_some_and(|row| row.changed))
     }
 
     /// Gets all patrons that have stored tokens (for daily verification).
-    /// Only returns patrons where both `access_token` and `refresh_token` are not null.
+    /// Only returns patrons with an `access_token`, not every provider issues refresh tokens.
     /// Decrypts tokens when reading.
     pub(crate) async fn get_all_patrons_with_tokens(&self) -> PatronRepositoryResult<Vec<Patron>> {
-        let rows = sqlx::query!(
+        let rows = sqlx::query_as!(
+            PatronRow,
             r#"
             SELECT
                 id,
-                patreon_user_id,
+                provider,
+                provider_user_id,
                 email,
                 tier_id,
                 pledge_amount_cents,
@@ -365,43 +382,13 @@ impl PatronRepository {
                 updated_at
             FROM patrons
             WHERE access_token IS NOT NULL
-              AND refresh_token IS NOT NULL
             "#,
         )
         .fetch_all(&self.pg_client)
         .await?;
 
-        let mut patrons = Vec::with_capacity(rows.len());
-        for row in rows {
-            let decrypted_access_token = row
-                .access_token
-                .as_ref()
-                .map(|t| decrypt_token(t, &self.encryption_key))
-                .transpose()?;
-
-            let decrypted_refresh_token = row
-                .refresh_token
-                .as_ref()
-                .map(|t| decrypt_token(t, &self.encryption_key))
-                .transpose()?;
-
-            patrons.push(Patron {
-                id: row.id,
-                patreon_user_id: row.patreon_user_id,
-                email: row.email,
-                tier_id: row.tier_id,
-                pledge_amount_cents: row.pledge_amount_cents,
-                slot_override: row.slot_override,
-                is_active: row.is_active,
-                access_token: decrypted_access_token,
-                refresh_token: decrypted_refresh_token,
-                token_expires_at: row.token_expires_at,
-                last_verified_at: row.last_verified_at,
-                created_at: row.created_at,
-                updated_at: row.updated_at,
-            });
-        }
-
-        Ok(patrons)
+        rows.into_iter()
+            .map(|row| self.patron_from_row(row))
+            .collect()
     }
 }
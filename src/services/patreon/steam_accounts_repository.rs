@@ -1,13 +1,10 @@
 #![allow(dead_code)]
 
-use cached::Cached;
 use chrono::{DateTime, Duration, Utc};
-use sqlx::{Pool, Postgres};
+use sqlx::{PgConnection, Pool, Postgres};
 use thiserror::Error;
 use uuid::Uuid;
 
-use crate::services::patreon::IS_ACCOUNT_PRIORITIZED;
-
 /// Error type for steam accounts repository operations
 #[derive(Debug, Error)]
 pub(crate) enum SteamAccountsRepositoryError {
@@ -219,7 +216,7 @@ impl SteamAccountsRepository {
 
     /// Adds a new Steam account to a patron's prioritized list.
     pub(crate) async fn add_steam_account(
-        &self,
+        conn: &mut PgConnection,
         patron_id: Uuid,
         steam_id3: i64,
     ) -> SteamAccountsRepositoryResult<SteamAccount> {
@@ -232,11 +229,9 @@ impl SteamAccountsRepository {
             patron_id,
             steam_id3,
         )
-        .fetch_one(&self.pg_client)
+        .fetch_one(conn)
         .await?;
 
-        IS_ACCOUNT_PRIORITIZED.lock().await.cache_remove(&steam_id3);
-
         Ok(SteamAccount {
             id: row.id,
             patron_id: row.patron_id,
@@ -247,13 +242,13 @@ impl SteamAccountsRepository {
     }
 
     /// Soft-deletes a Steam account by setting `deleted_at` to `NOW()`.
-    /// Verifies the account belongs to the specified patron.
+    /// Verifies the account belongs to the specified patron and returns its `steam_id3`.
     pub(crate) async fn soft_delete_account(
-        &self,
+        conn: &mut PgConnection,
         account_id: Uuid,
         patron_id: Uuid,
-    ) -> SteamAccountsRepositoryResult<()> {
-        let result = sqlx::query!(
+    ) -> SteamAccountsRepositoryResult<i64> {
+        sqlx::query_scalar!(
             r#"
             UPDATE prioritized_steam_accounts
             SET deleted_at = NOW()
@@ -265,29 +260,19 @@ impl SteamAccountsRepository {
             account_id,
             patron_id,
         )
-        .fetch_optional(&self.pg_client)
-        .await?;
-
-        match result {
-            None => Err(SteamAccountsRepositoryError::AccountNotFound),
-            Some(row) => {
-                IS_ACCOUNT_PRIORITIZED
-                    .lock()
-                    .await
-                    .cache_remove(&row.steam_id3);
-                Ok(())
-            }
-        }
+        .fetch_optional(conn)
+        .await?
+        .ok_or(SteamAccountsRepositoryError::AccountNotFound)
     }
 
     /// Hard-deletes a Steam account (permanently removes from database).
-    /// Verifies the account belongs to the specified patron.
+    /// Verifies the account belongs to the specified patron and returns its `steam_id3`.
     pub(crate) async fn hard_delete_account(
-        &self,
+        conn: &mut PgConnection,
         account_id: Uuid,
         patron_id: Uuid,
-    ) -> SteamAccountsRepositoryResult<()> {
-        let result = sqlx::query!(
+    ) -> SteamAccountsRepositoryResult<i64> {
+        sqlx::query_scalar!(
             r#"
             DELETE FROM prioritized_steam_accounts
             WHERE id = $1
@@ -297,25 +282,15 @@ impl SteamAccountsRepository {
             account_id,
             patron_id,
         )
-        .fetch_optional(&self.pg_client)
-        .await?;
-
-        match result {
-            None => Err(SteamAccountsRepositoryError::AccountNotFound),
-            Some(row) => {
-                IS_ACCOUNT_PRIORITIZED
-                    .lock()
-                    .await
-                    .cache_remove(&row.steam_id3);
-                Ok(())
-            }
-        }
+        .fetch_optional(conn)
+        .await?
+        .ok_or(SteamAccountsRepositoryError::AccountNotFound)
     }
 
     /// Reactivates a soft-deleted Steam account by setting `deleted_at` to NULL.
     /// Verifies the account belongs to the specified patron.
     pub(crate) async fn reactivate_account(
-        &self,
+        conn: &mut PgConnection,
         account_id: Uuid,
         patron_id: Uuid,
     ) -> SteamAccountsRepositoryResult<SteamAccount> {
@@ -331,25 +306,17 @@ impl SteamAccountsRepository {
             account_id,
             patron_id,
         )
-        .fetch_optional(&self.pg_client)
-        .await?;
+        .fetch_optional(conn)
+        .await?
+        .ok_or(SteamAccountsRepositoryError::AccountNotFound)?;
 
-        match row {
-            Some(row) => {
-                IS_ACCOUNT_PRIORITIZED
-                    .lock()
-                    .await
-                    .cache_remove(&row.steam_id3);
-                Ok(SteamAccount {
-                    id: row.id,
-                    patron_id: row.patron_id,
-                    steam_id3: row.steam_id3,
-                    created_at: row.created_at,
-                    deleted_at: row.deleted_at,
-                })
-            }
-            None => Err(SteamAccountsRepositoryError::AccountNotFound),
-        }
+        Ok(SteamAccount {
+            id: row.id,
+            patron_id: row.patron_id,
+            steam_id3: row.steam_id3,
+            created_at: row.created_at,
+            deleted_at: row.deleted_at,
+        })
     }
 
     /// Gets active (non-deleted) Steam accounts for a patron, ordered by `created_at ASC`.
@@ -425,17 +392,17 @@ impl SteamAccountsRepository {
     }
 
     /// Reactivates multiple soft-deleted Steam accounts by setting `deleted_at` to NULL.
-    /// Returns the number of accounts that were reactivated.
+    /// Returns the `steam_id3` of the accounts that were reactivated.
     pub(crate) async fn reactivate_accounts(
-        &self,
+        conn: &mut PgConnection,
         account_ids: &[Uuid],
         patron_id: Uuid,
-    ) -> SteamAccountsRepositoryResult<u64> {
+    ) -> SteamAccountsRepositoryResult<Vec<i64>> {
         if account_ids.is_empty() {
-            return Ok(0);
+            return Ok(vec![]);
         }
 
-        let result = sqlx::query!(
+        Ok(sqlx::query_scalar!(
             r#"
             UPDATE prioritized_steam_accounts
             SET deleted_at = NULL
@@ -447,32 +414,23 @@ impl SteamAccountsRepository {
             account_ids,
             patron_id,
         )
-        .fetch_all(&self.pg_client)
-        .await?;
-
-        for row in &result {
-            IS_ACCOUNT_PRIORITIZED
-                .lock()
-                .await
-                .cache_remove(&row.steam_id3);
-        }
-
-        Ok(result.len() as u64)
+        .fetch_all(conn)
+        .await?)
     }
 
     /// Soft-deletes multiple Steam accounts by their IDs.
     /// Used during patron downgrades to disable excess accounts.
-    /// Returns the number of accounts that were soft-deleted.
+    /// Returns the `steam_id3` of the accounts that were soft-deleted.
     pub(crate) async fn soft_delete_accounts(
-        &self,
+        conn: &mut PgConnection,
         account_ids: &[Uuid],
         patron_id: Uuid,
-    ) -> SteamAccountsRepositoryResult<u64> {
+    ) -> SteamAccountsRepositoryResult<Vec<i64>> {
         if account_ids.is_empty() {
-            return Ok(0);
+            return Ok(vec![]);
         }
 
-        let result = sqlx::query!(
+        Ok(sqlx::query_scalar!(
             r#"
             UPDATE prioritized_steam_accounts
             SET deleted_at = NOW()
@@ -484,16 +442,7 @@ impl SteamAccountsRepository {
             account_ids,
             patron_id,
         )
-        .fetch_all(&self.pg_client)
-        .await?;
-
-        for row in &result {
-            IS_ACCOUNT_PRIORITIZED
-                .lock()
-                .await
-                .cache_remove(&row.steam_id3);
-        }
-
-        Ok(result.len() as u64)
+        .fetch_all(conn)
+        .await?)
     }
 }
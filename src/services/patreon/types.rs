@@ -8,6 +8,8 @@ use serde::Deserialize;
 use thiserror::Error;
 use uuid::Uuid;
 
+use crate::services::membership::MembershipProviderKind;
+
 /// Response from Patreon OAuth token endpoint
 #[derive(Debug, Clone, Deserialize)]
 pub(crate) struct TokenResponse {
@@ -144,7 +146,9 @@ pub(crate) type PatreonResult<T> = Result<T, PatreonError>;
 #[derive(Debug, Clone)]
 pub(crate) struct Patron {
     pub(crate) id: Uuid,
-    pub(crate) patreon_user_id: String,
+    pub(crate) provider: MembershipProviderKind,
+    /// User ID on the membership provider
+    pub(crate) provider_user_id: String,
     pub(crate) email: Option<String>,
     pub(crate) tier_id: Option<String>,
     pub(crate) pledge_amount_cents: Option<i32>,
@@ -1,5 +1,7 @@
 use serde::Deserialize;
 
+use crate::services::membership::MembershipStatus;
+
 /// Patreon webhook event types parsed from the `X-Patreon-Event` header.
 #[derive(Debug, Clone, PartialEq, Eq)]
 #[allow(clippy::enum_variant_names)]
@@ -33,6 +35,46 @@ pub(crate) struct WebhookPayload {
     pub(crate) data: WebhookMemberData,
 }
 
+impl WebhookPayload {
+    /// Whether the member belongs to the given campaign.
+    pub(crate) fn is_campaign(&self, campaign_id: &str) -> bool {
+        self.data
+            .relationships
+            .campaign
+            .data
+            .as_ref()
+            .is_some_and(|c| c.id == campaign_id)
+    }
+
+    /// The Patreon user ID of the member.
+    pub(crate) fn patreon_user_id(&self) -> Option<&str> {
+        self.data
+            .relationships
+            .user
+            .data
+            .as_ref()
+            .map(|u| u.id.as_str())
+    }
+
+    /// The membership of the member.
+    pub(crate) fn membership(&self) -> MembershipStatus {
+        let attributes = &self.data.attributes;
+        MembershipStatus {
+            tier_id: self
+                .data
+                .relationships
+                .currently_entitled_tiers
+                .data
+                .first()
+                .map(|t| t.id.clone()),
+            pledge_amount_cents: attributes
+                .pledge_amount_cents
+                .or(attributes.currently_entitled_amount_cents),
+            is_active: attributes.patron_status.as_deref() == Some("active_patron"),
+        }
+    }
+}
+
 /// Member resource in the webhook payload (the primary `data` object).
 #[derive(Debug, Deserialize)]
 pub(crate) struct WebhookMemberData {
@@ -117,4 +159,45 @@ mod tests {
         );
         assert_eq!(PatreonWebhookEvent::from_header("unknown:event"), None);
     }
+
+    #[test]
+    fn test_payload_membership() {
+        let payload: WebhookPayload = serde_json::from_str(
+            r#"{
+                "data": {
+                    "attributes": {
+                        "patron_status": "active_patron",
+                        "currently_entitled_amount_cents": 500,
+                        "pledge_amount_cents": null
+                    },
+                    "relationships": {
+                        "user": {"data": {"id": "123", "type": "user"}},
+                        "currently_entitled_tiers": {"data": [{"id": "9", "type": "tier"}]},
+                        "campaign": {"data": {"id": "42", "type": "campaign"}}
+                    }
+                }
+            }"#,
+        )
+        .unwrap();
+        assert!(payload.is_campaign("42"));
+        assert!(!payload.is_campaign("43"));
+        assert_eq!(payload.patreon_user_id(), Some("123"));
+        assert_eq!(
+            payload.membership(),
+            MembershipStatus {
+                tier_id: Some("9".to_owned()),
+                pledge_amount_cents: Some(500),
+                is_active: true,
+            }
+        );
+    }
+
+    #[test]
+    fn test_payload_membership_of_former_patron() {
+        let payload: WebhookPayload =
+            serde_json::from_str(r#"{"data": {"attributes": {"patron_status": "former_patron"}}}"#)
+                .unwrap();
+        assert_eq!(payload.patreon_user_id(), None);
+        assert_eq!(payload.membership(), MembershipStatus::default());
+    }
 }
@@ -1,3 +1,4 @@
+use core::str::FromStr;
 use core::time::Duration;
 
 use axum::http::StatusCode;
@@ -13,7 +14,7 @@ use uuid::Uuid;
 use crate::error::{APIError, APIResult};
 use crate::services::rate_limiter::extractor::RateLimitKey;
 use crate::services::rate_limiter::types::QuotaType;
-use crate::services::rate_limiter::{Quota, Status};
+use crate::services::rate_limiter::{ApiKeyScope, Quota, Status};
 
 const MAX_TTL_MICROS: i64 = 60 * 60 * 1000 * 1000;
 
@@ -80,10 +81,7 @@ impl RateLimitClient {
             ));
         }
 
-        let prefix = rate_limit_key
-            .api_key
-            .map_or_else(|| rate_limit_key.ip.to_string(), |k| k.to_string());
-        let prefixed_key = format!("{prefix}:{key}");
+        let prefixed_key = format!("{}:{key}", rate_limit_key.client_id());
         // If incrementing the per-user key fails, we don't apply any limits
         if let Err(e) = self.increment_key(&prefixed_key).await {
             warn!("Failed to increment rate limit key: {e}, will not apply limits");
@@ -148,6 +146,50 @@ impl RateLimitClient {
         Ok(all_statuses.into_iter().min_by_key(Status::remaining))
     }
 
+    /// Ensures the request carries a valid API key that has been granted `scope`.
+    pub(crate) async fn require_scope(
+        &self,
+        rate_limit_key: &RateLimitKey,
+        scope: ApiKeyScope,
+    ) -> APIResult<()> {
+        let Some(api_key) = rate_limit_key.api_key else {
+            return Err(APIError::status_msg(
+                StatusCode::FORBIDDEN,
+                format!("API key with scope `{scope}` is required for this endpoint"),
+            ));
+        };
+        match get_api_key_scopes(&self.pg_client, api_key).await? {
+            None => Err(APIError::status_msg(
+                StatusCode::FORBIDDEN,
+                "Invalid API key",
+            )),
+            Some(scopes) if !scopes.contains(&scope) => Err(APIError::status_msg(
+                StatusCode::FORBIDDEN,
+                format!("API key is missing required scope `{scope}`"),
+            )),
+            Some(_) => Ok(()),
+        }
+    }
+
+    /// Reloads the cached validity and scopes of an API key after it was changed.
+    ///
+    /// Other instances pick up the change once their cache entries expire.
+    pub(crate) async fn refresh_api_key(&self, api_key: Uuid) -> sqlx::Result<()> {
+        is_api_key_valid_prime_cache(&self.pg_client, api_key).await?;
+        get_api_key_scopes_prime_cache(&self.pg_client, api_key).await?;
+        Ok(())
+    }
+
+    /// Reloads the cached custom quotas of an API key for a rate limit key.
+    pub(crate) async fn refresh_custom_quotas(
+        &self,
+        api_key: Uuid,
+        path: &str,
+    ) -> sqlx::Result<()> {
+        get_custom_quotas_prime_cache(&self.pg_client, api_key, path).await?;
+        Ok(())
+    }
+
     async fn check_requests(
         &self,
         key: &str,
@@ -204,6 +246,32 @@ async fn is_api_key_valid(pg_client: &Pool<Postgres>, api_key: Uuid) -> Result<b
     Ok(row.count.is_some_and(|c| c > 0))
 }
 
+#[cached(
+    ty = "TimedCache<Uuid, Option<Vec<ApiKeyScope>>>",
+    create = "{ TimedCache::with_lifespan(std::time::Duration::from_secs(10 * 60)) }",
+    convert = "{ api_key }",
+    sync_writes = "by_key",
+    key = "Uuid",
+    result = true
+)]
+async fn get_api_key_scopes(
+    pg_client: &Pool<Postgres>,
+    api_key: Uuid,
+) -> Result<Option<Vec<ApiKeyScope>>, sqlx::Error> {
+    let row = sqlx::query!(
+        "SELECT scopes FROM api_keys WHERE key = $1 AND disabled IS false",
+        api_key
+    )
+    .fetch_optional(pg_client)
+    .await?;
+    Ok(row.map(|row| {
+        row.scopes
+            .iter()
+            .filter_map(|s| ApiKeyScope::from_str(s).ok())
+            .collect()
+    }))
+}
+
 #[cached(
     ty = "TimedCache<String, Vec<Quota>>",
     create = "{ TimedCache::with_lifespan(std::time::Duration::from_secs(10 * 60)) }",
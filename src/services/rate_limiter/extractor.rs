@@ -1,15 +1,33 @@
-use core::net::Ipv4Addr;
+use core::net::{IpAddr, Ipv4Addr};
 
 use axum::extract::FromRequestParts;
 use axum::http::request::Parts;
 use uuid::Uuid;
 
 use crate::error::APIError;
+use crate::middleware::client_ip::ClientIp;
+use crate::utils::net::rate_limit_bucket;
 
 #[derive(Debug, Clone, Copy)]
 pub(crate) struct RateLimitKey {
     pub(crate) api_key: Option<Uuid>,
-    pub(super) ip: Ipv4Addr, // We do not have to take care of IPv6, as we use Cloudflare Pseudo IPv4
+    pub(super) ip: IpAddr,
+}
+
+impl RateLimitKey {
+    /// Identifies the client by its API key, or by its IP address if no key is present.
+    pub(crate) fn client_id(&self) -> String {
+        self.api_key
+            .map_or_else(|| rate_limit_bucket(self.ip), |k| k.to_string())
+    }
+
+    /// The key of a request made on behalf of `api_key`, whose limits it counts towards.
+    pub(crate) fn on_behalf_of(self, api_key: Uuid) -> Self {
+        Self {
+            api_key: Some(api_key),
+            ..self
+        }
+    }
 }
 
 impl<S> FromRequestParts<S> for RateLimitKey
@@ -20,11 +38,9 @@ where
 
     async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
         let ip = parts
-            .headers
-            .get("CF-Connecting-IP")
-            .or(parts.headers.get("X-Real-IP"))
-            .and_then(|v| v.to_str().ok().and_then(|s| s.parse().ok()))
-            .unwrap_or(Ipv4Addr::UNSPECIFIED);
+            .extensions
+            .get::<ClientIp>()
+            .map_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED), |ClientIp(ip)| *ip);
 
         let api_key = parts
             .headers
@@ -44,43 +60,40 @@ mod tests {
     use super::*;
 
     #[rstest]
-    #[case(Some("1.2.3.4"), Some("HEXE-d887508b-036e-42b5-89f3-0754617036bb"), Ipv4Addr::new(1, 2, 3, 4), Some(Uuid::parse_str("d887508b-036e-42b5-89f3-0754617036bb").unwrap()))]
-    #[case(Some("1.2.3.4"), Some("d887508b-036e-42b5-89f3-0754617036bb"), Ipv4Addr::new(1, 2, 3, 4), Some(Uuid::parse_str("d887508b-036e-42b5-89f3-0754617036bb").unwrap()))]
-    #[case(Some("1.2.3.4"), None, Ipv4Addr::new(1, 2, 3, 4), None)]
-    #[case(None, Some("HEXE-d887508b-036e-42b5-89f3-0754617036bb"), Ipv4Addr::UNSPECIFIED, Some(Uuid::parse_str("d887508b-036e-42b5-89f3-0754617036bb").unwrap()))]
-    #[case(None, Some("d887508b-036e-42b5-89f3-0754617036bb"), Ipv4Addr::UNSPECIFIED, Some(Uuid::parse_str("d887508b-036e-42b5-89f3-0754617036bb").unwrap()))]
-    #[case(None, None, Ipv4Addr::UNSPECIFIED, None)]
-    #[case(Some("1.2.3.4"), Some("HEXE-invalid"), Ipv4Addr::new(1, 2, 3, 4), None)]
-    #[case(Some("1.2.3.4"), Some("invalid"), Ipv4Addr::new(1, 2, 3, 4), None)]
-    #[case(
-        Some("invalid"),
-        Some("HEXE-d887508b-036e-42b5-89f3-0754617036bb"),
-        Ipv4Addr::UNSPECIFIED,
-        Some(Uuid::parse_str("d887508b-036e-42b5-89f3-0754617036bb").unwrap())
-    )]
+    #[case(Some("1.2.3.4"), Some("HEXE-d887508b-036e-42b5-89f3-0754617036bb"), "1.2.3.4", Some(Uuid::parse_str("d887508b-036e-42b5-89f3-0754617036bb").unwrap()))]
+    #[case(Some("1.2.3.4"), Some("d887508b-036e-42b5-89f3-0754617036bb"), "1.2.3.4", Some(Uuid::parse_str("d887508b-036e-42b5-89f3-0754617036bb").unwrap()))]
+    #[case(Some("1.2.3.4"), None, "1.2.3.4", None)]
+    #[case(Some("2001:db8::1"), None, "2001:db8::1", None)]
+    #[case(None, Some("HEXE-d887508b-036e-42b5-89f3-0754617036bb"), "0.0.0.0", Some(Uuid::parse_str("d887508b-036e-42b5-89f3-0754617036bb").unwrap()))]
+    #[case(None, Some("d887508b-036e-42b5-89f3-0754617036bb"), "0.0.0.0", Some(Uuid::parse_str("d887508b-036e-42b5-89f3-0754617036bb").unwrap()))]
+    #[case(None, None, "0.0.0.0", None)]
+    #[case(Some("1.2.3.4"), Some("HEXE-invalid"), "1.2.3.4", None)]
+    #[case(Some("1.2.3.4"), Some("invalid"), "1.2.3.4", None)]
     #[tokio::test]
     async fn test_from_request_parts(
         #[case] ip: Option<&str>,
         #[case] api_key: Option<&str>,
-        #[case] expected_ip: Ipv4Addr,
+        #[case] expected_ip: &str,
         #[case] expected_api_key: Option<Uuid>,
     ) {
         let mut headers = HeaderMap::new();
-        if let Some(ip) = ip {
-            headers.insert("CF-Connecting-IP", ip.parse().unwrap());
-        }
         if let Some(api_key) = api_key {
             headers.insert("X-API-Key", api_key.parse().unwrap());
         }
         let mut request = http::Request::new(());
         *request.headers_mut() = headers;
+        if let Some(ip) = ip {
+            request
+                .extensions_mut()
+                .insert(ClientIp(ip.parse().unwrap()));
+        }
         let (mut parts, ()) = request.into_parts();
 
         let rate_limit_key = RateLimitKey::from_request_parts(&mut parts, &())
             .await
             .unwrap();
 
-        assert_eq!(rate_limit_key.ip, expected_ip);
+        assert_eq!(rate_limit_key.ip, expected_ip.parse::<IpAddr>().unwrap());
         assert_eq!(rate_limit_key.api_key, expected_api_key);
     }
 }
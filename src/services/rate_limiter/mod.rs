@@ -3,4 +3,4 @@ pub(crate) mod extractor;
 mod types;
 
 pub(crate) use client::RateLimitClient;
-pub(crate) use types::{Quota, Status};
+pub(crate) use types::{ApiKeyScope, Quota, Status};
@@ -2,11 +2,31 @@ use core::time::Duration;
 
 use axum::http::HeaderMap;
 use chrono::{DateTime, Utc};
-use strum::EnumIs;
+use serde::{Deserialize, Serialize};
+use strum::{Display, EnumIs, EnumIter, EnumString};
 use tracing::warn;
 
 use crate::error::{APIError, APIResult};
 
+#[derive(
+    Debug, Clone, Copy, Eq, PartialEq, Hash, Serialize, Deserialize, Display, EnumString, EnumIter,
+)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub(crate) enum ApiKeyScope {
+    Sql,
+    CustomMatches,
+}
+
+impl ApiKeyScope {
+    pub(crate) fn description(self) -> &'static str {
+        match self {
+            Self::Sql => "Execute custom SQL queries",
+            Self::CustomMatches => "Create and manage custom matches",
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, Eq, PartialEq, EnumIs)]
 pub(super) enum QuotaType {
     IP,
@@ -95,3 +115,25 @@ impl Status {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use core::str::FromStr;
+
+    use rstest::rstest;
+
+    use super::*;
+
+    #[rstest]
+    #[case(ApiKeyScope::Sql, "sql")]
+    #[case(ApiKeyScope::CustomMatches, "custom_matches")]
+    fn test_api_key_scope_roundtrip(#[case] scope: ApiKeyScope, #[case] name: &str) {
+        assert_eq!(scope.to_string(), name);
+        assert_eq!(ApiKeyScope::from_str(name).unwrap(), scope);
+    }
+
+    #[test]
+    fn test_api_key_scope_unknown() {
+        assert!(ApiKeyScope::from_str("admin").is_err());
+    }
+}
@@ -0,0 +1,261 @@
+use core::time::Duration;
+use std::sync::Arc;
+
+use object_store::aws::AmazonS3;
+use object_store::path::Path as S3Path;
+use object_store::{ObjectStoreExt, WriteMultipart};
+use sqlx::{Pool, Postgres};
+use tokio::time::sleep;
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+use crate::routes::v1::sql::limits::{QueryLimits, QueryMode, fetch_query_tier};
+
+/// Interval for checking for new jobs while the queue is empty
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Maximum number of result parts uploaded to S3 at the same time
+const MAX_CONCURRENT_UPLOADS: usize = 2;
+
+/// Running jobs older than this were lost, e.g. because their instance was restarted.
+///
+/// Has to be longer than the highest `max_execution_time` of async queries.
+const STALE_JOB_TIMEOUT_SECS: f64 = 60. * 60.;
+
+/// Location of the result of a job on S3
+pub(crate) fn result_path(job_id: Uuid) -> S3Path {
+    S3Path::from(format!("sql-jobs/{job_id}.jsonl"))
+}
+
+#[derive(thiserror::Error, Debug)]
+enum SqlJobError {
+    #[error("Failed to fetch query tier: {0}")]
+    PostgreSQL(#[from] sqlx::Error),
+    #[error("Failed to execute query: {0}")]
+    Query(#[from] clickhouse::error::Error),
+    #[error("Failed to upload query result: {0}")]
+    Upload(#[from] object_store::Error),
+}
+
+impl SqlJobError {
+    /// Error message shown to the owner of the job, without internal details
+    fn public_message(&self) -> &'static str {
+        let Self::Query(e) = self else {
+            return "Internal error while running the query, please try again";
+        };
+        let message = e.to_string();
+        if message.contains("TIMEOUT_EXCEEDED") {
+            "Query exceeded the maximum execution time"
+        } else if message.contains("TOO_MANY_ROWS") {
+            "Query exceeded the maximum number of rows to read"
+        } else if message.contains("MEMORY_LIMIT_EXCEEDED") {
+            "Query exceeded the maximum memory usage"
+        } else {
+            "Query execution failed. Check your SQL syntax and try again."
+        }
+    }
+}
+
+struct ClaimedJob {
+    id: Uuid,
+    api_key: Uuid,
+    query: String,
+}
+
+/// Worker running queued custom SQL queries
+///
+/// Jobs are claimed from Postgres with `SKIP LOCKED`, so every instance can run a worker.
+/// Each worker runs one job at a time, with the async limits of the tier of the job's API key,
+/// and streams the result as `JSONEachRow` to S3.
+pub(crate) struct SqlJobWorker {
+    pg_client: Pool<Postgres>,
+    ch_client: clickhouse::Client,
+    s3_client: AmazonS3,
+}
+
+impl SqlJobWorker {
+    pub(crate) fn new(
+        pg_client: Pool<Postgres>,
+        ch_client: clickhouse::Client,
+        s3_client: AmazonS3,
+    ) -> Self {
+        Self {
+            pg_client,
+            ch_client,
+            s3_client,
+        }
+    }
+
+    /// Start the background worker task
+    pub(crate) fn start_background_worker(self: Arc<Self>) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            info!("SQL job worker started");
+            loop {
+                match self.claim_job().await {
+                    Ok(Some(job)) => self.run_job(job).await,
+                    Ok(None) => {
+                        if let Err(e) = self.fail_stale_jobs().await {
+                            warn!("Failed to fail stale SQL jobs: {e}");
+                        }
+                        sleep(POLL_INTERVAL).await;
+                    }
+                    Err(e) => {
+                        warn!("Failed to claim SQL job: {e}");
+                        sleep(POLL_INTERVAL).await;
+                    }
+                }
+            }
+        })
+    }
+
+    async fn claim_job(&self) -> sqlx::Result<Option<ClaimedJob>> {
+        sqlx::query_as!(
+            ClaimedJob,
+            r#"
+            UPDATE sql_jobs
+            SET status = 'running', started_at = now()
+            WHERE id = (
+                SELECT id
+                FROM sql_jobs
+                WHERE status = 'queued'
+                ORDER BY created_at
+                LIMIT 1
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING id, api_key, query
+            "#
+        )
+        .fetch_optional(&self.pg_client)
+        .await
+    }
+
+    async fn run_job(&self, job: ClaimedJob) {
+        debug!("Running SQL job {}: {}", job.id, job.query);
+        let result = match self.execute(&job).await {
+            Ok(row_count) => {
+                sqlx::query!(
+                    r#"
+                    UPDATE sql_jobs
+                    SET status = 'succeeded', row_count = $2, finished_at = now()
+                    WHERE id = $1
+                    "#,
+                    job.id,
+                    row_count
+                )
+                .execute(&self.pg_client)
+                .await
+            }
+            Err(e) => {
+                warn!("SQL job {} failed: {e}", job.id);
+                sqlx::query!(
+                    r#"
+                    UPDATE sql_jobs
+                    SET status = 'failed', error = $2, finished_at = now()
+                    WHERE id = $1
+                    "#,
+                    job.id,
+                    e.public_message()
+                )
+                .execute(&self.pg_client)
+                .await
+            }
+        };
+        if let Err(e) = result {
+            warn!("Failed to update status of SQL job {}: {e}", job.id);
+        }
+    }
+
+    /// Runs the query of a job and uploads its result, returning the number of rows.
+    ///
+    /// The result is streamed to S3 as a multipart upload, so it is never held in memory.
+    async fn execute(&self, job: &ClaimedJob) -> Result<i64, SqlJobError> {
+        let tier = fetch_query_tier(&self.pg_client, Some(job.api_key)).await?;
+        let limits = QueryLimits::new(tier, QueryMode::Async);
+        let mut cursor = limits
+            .apply(self.ch_client.query(&job.query))
+            .fetch_bytes("JSONEachRow")?;
+        let mut upload =
+            WriteMultipart::new(self.s3_client.put_multipart(&result_path(job.id)).await?);
+
+        let mut row_count = 0;
+        let streamed: Result<(), SqlJobError> = async {
+            while let Some(chunk) = cursor.next().await? {
+                // Every row of `JSONEachRow` ends with a newline
+                row_count += chunk.split(|&b| b == b'\n').count() - 1;
+                upload.wait_for_capacity(MAX_CONCURRENT_UPLOADS).await?;
+                upload.put(chunk);
+            }
+            Ok(())
+        }
+        .await;
+        if let Err(e) = streamed {
+            if let Err(abort_error) = upload.abort().await {
+                warn!(
+                    "Failed to abort result upload of SQL job {}: {abort_error}",
+                    job.id
+                );
+            }
+            return Err(e);
+        }
+        upload.finish().await?;
+        Ok(i64::try_from(row_count).unwrap_or(i64::MAX))
+    }
+
+    /// Marks jobs as failed that are running for too long.
+    async fn fail_stale_jobs(&self) -> sqlx::Result<()> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE sql_jobs
+            SET status = 'failed', error = 'Query was interrupted, please submit it again', finished_at = now()
+            WHERE status = 'running' AND started_at < now() - make_interval(secs => $1)
+            "#,
+            STALE_JOB_TIMEOUT_SECS
+        )
+        .execute(&self.pg_client)
+        .await?;
+        if result.rows_affected() > 0 {
+            warn!("Failed {} stale SQL jobs", result.rows_affected());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    #[rstest]
+    #[case(
+        "Code: 159. DB::Exception: Timeout exceeded: elapsed 300.1 seconds, maximum: 300. \
+         (TIMEOUT_EXCEEDED)",
+        "Query exceeded the maximum execution time"
+    )]
+    #[case(
+        "Code: 158. DB::Exception: Limit for rows (controlled by 'max_rows_to_read' setting) \
+         exceeded. (TOO_MANY_ROWS)",
+        "Query exceeded the maximum number of rows to read"
+    )]
+    #[case(
+        "Code: 241. DB::Exception: Memory limit (for query) exceeded. (MEMORY_LIMIT_EXCEEDED)",
+        "Query exceeded the maximum memory usage"
+    )]
+    #[case(
+        "Code: 62. DB::Exception: Syntax error. (SYNTAX_ERROR)",
+        "Query execution failed. Check your SQL syntax and try again."
+    )]
+    fn test_public_message(#[case] response: &str, #[case] expected: &str) {
+        let error = SqlJobError::Query(clickhouse::error::Error::BadResponse(response.to_owned()));
+        assert_eq!(error.public_message(), expected);
+    }
+
+    #[test]
+    fn test_public_message_hides_internal_errors() {
+        let error = SqlJobError::PostgreSQL(sqlx::Error::PoolTimedOut);
+        assert_eq!(
+            error.public_message(),
+            "Internal error while running the query, please try again"
+        );
+    }
+}
@@ -1,5 +1,7 @@
 use core::time::Duration;
 use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Instant;
 
 use base64::Engine;
 use base64::prelude::BASE64_STANDARD;
@@ -7,29 +9,39 @@ use cached::TimedCache;
 use cached::proc_macro::cached;
 use metrics::counter;
 use prost::Message;
-use rand::prelude::IndexedRandom;
+use redis::aio::MultiplexedConnection;
 use reqwest::Response;
 use serde_json::json;
-use tracing::{debug, error};
-use valveprotos::deadlock::CMsgClientToGcGetMatchMetaDataResponse;
+use tokio::time::sleep;
+use tracing::{debug, error, warn};
+use valveprotos::deadlock::{CMsgClientToGcGetMatchMetaDataResponse, EgcCitadelClientMessages};
 
 use crate::context::AppState;
 use crate::error::{APIError, APIResult};
 use crate::routes::v1::matches::types::ClickhouseSalts;
 use crate::services::rate_limiter::Quota;
 use crate::services::rate_limiter::extractor::RateLimitKey;
+use crate::services::steam::gc::{GcCall, GcRequest};
+use crate::services::steam::openid;
+use crate::services::steam::proxy_pool::ProxyPool;
+use crate::services::steam::single_flight::SingleFlight;
 use crate::services::steam::types::{
     GetPlayerSummariesResponse, Patch, Rss, SteamAccountNameError, SteamAccountVerifyError,
-    SteamProxyError, SteamProxyQuery, SteamProxyRawResponse, SteamProxyResponse, SteamProxyResult,
+    SteamProxyError, SteamProxyQuery, SteamProxyRawResponse, SteamProxyResult,
 };
 
 const RSS_ENDPOINT: &str = "https://forums.playdeadlock.com/forums/changelog.10/index.rss";
 
+/// Maximum number of proxies tried for a single idempotent call
+const MAX_PROXY_ATTEMPTS: usize = 3;
+const PROXY_RETRY_BACKOFF: Duration = Duration::from_millis(100);
+
 /// Client for interacting with the Steam API and proxy
 #[derive(Clone)]
 pub(crate) struct SteamClient {
     http_client: reqwest::Client,
-    steam_proxy_urls: Vec<String>,
+    proxy_pool: Arc<ProxyPool>,
+    single_flight: SingleFlight,
     steam_proxy_api_key: String,
     steam_api_key: String,
 }
@@ -37,26 +49,27 @@ pub(crate) struct SteamClient {
 impl SteamClient {
     pub(crate) fn new(
         http_client: reqwest::Client,
+        redis_client: MultiplexedConnection,
         steam_proxy_urls: Vec<String>,
         steam_proxy_api_key: String,
         steam_api_key: String,
     ) -> Self {
         Self {
             http_client,
-            steam_proxy_urls,
+            proxy_pool: Arc::new(ProxyPool::new(steam_proxy_urls)),
+            single_flight: SingleFlight::new(redis_client),
             steam_proxy_api_key,
             steam_api_key,
         }
     }
 
-    pub(crate) async fn call_steam_proxy<M: Message, R: Message + Default>(
-        &self,
-        query: SteamProxyQuery<M>,
-    ) -> SteamProxyResult<SteamProxyResponse<R>> {
-        self.call_steam_proxy_raw(query).await?.try_into()
+    /// Calls the Game Coordinator through the Steam proxy, e.g.
+    /// `steam_client.gc(CMsgClientToGcGetLeaderboard { .. }).await`.
+    pub(crate) fn gc<M: GcRequest>(&self, msg: M) -> GcCall<'_, M> {
+        GcCall::new(self, msg)
     }
 
-    pub(crate) async fn call_steam_proxy_raw<M: Message>(
+    pub(super) async fn call_steam_proxy_raw<M: Message>(
         &self,
         query: SteamProxyQuery<M>,
     ) -> SteamProxyResult<SteamProxyRawResponse> {
@@ -72,7 +85,23 @@ impl SteamClient {
             "data": encoded_message,
             "bot_username": query.username,
         });
-        match self.call_proxy(&query, &body).await {
+        let result = if is_read_only(query.msg_type) {
+            // Retries on other proxies happen inside the flight, so the lock has to outlive them
+            let timeout = (query.request_timeout + PROXY_RETRY_BACKOFF)
+                * u32::try_from(MAX_PROXY_ATTEMPTS).unwrap_or(u32::MAX);
+            self.single_flight
+                .run(
+                    query.msg_type,
+                    query.username.as_deref(),
+                    &serialized_message,
+                    timeout,
+                    || self.call_proxy(&query, &body),
+                )
+                .await
+        } else {
+            self.call_proxy(&query, &body).await
+        };
+        match result {
             Ok(r) => {
                 debug!(
                     "Successfully called Steam proxy for {}",
@@ -89,20 +118,70 @@ impl SteamClient {
         }
     }
 
+    /// Calls one of the proxies, preferring healthy ones.
+    ///
+    /// Idempotent messages are retried on other proxies if a proxy fails.
     async fn call_proxy<M: Message, T: serde::Serialize + ?Sized>(
         &self,
         query: &SteamProxyQuery<M>,
         body: &T,
     ) -> SteamProxyResult<SteamProxyRawResponse> {
-        let url = if self.steam_proxy_urls.len() == 1 {
-            #[allow(clippy::indexing_slicing, reason = "We checked the length")]
-            &self.steam_proxy_urls[0]
+        let max_attempts = if is_read_only(query.msg_type) {
+            MAX_PROXY_ATTEMPTS
         } else {
-            self.steam_proxy_urls
-                .choose(&mut rand::rng())
-                .ok_or(SteamProxyError::NoBaseUrl)?
+            1
         };
+        let mut tried = Vec::with_capacity(max_attempts);
+        let mut last_error = SteamProxyError::NoBaseUrl;
+        while tried.len() < max_attempts {
+            let Some(index) = self.proxy_pool.select(&tried) else {
+                break;
+            };
+            let Some(url) = self.proxy_pool.url(index) else {
+                break;
+            };
+            if !tried.is_empty() {
+                counter!("steam.proxy.retry", "msg_type" => query.msg_type.as_str_name())
+                    .increment(1);
+                sleep(PROXY_RETRY_BACKOFF * u32::try_from(tried.len()).unwrap_or(u32::MAX)).await;
+            }
+            tried.push(index);
+
+            let start = Instant::now();
+            match self.send_to_proxy(url, query, body).await {
+                Ok(response) => {
+                    self.proxy_pool.record_success(index, start.elapsed());
+                    return Ok(response);
+                }
+                Err(e) if is_proxy_failure(&e) => {
+                    warn!(
+                        "Steam proxy call for {} failed: {e}",
+                        query.msg_type.as_str_name()
+                    );
+                    self.proxy_pool.record_failure(index);
+                    // Only retry if the request never reached the proxy, otherwise it may already
+                    // have used up the cooldown of a bot
+                    if !is_connection_error(&e) {
+                        return Err(e);
+                    }
+                    last_error = e;
+                }
+                Err(e) => {
+                    // The proxy answered, the request itself was rejected
+                    self.proxy_pool.record_success(index, start.elapsed());
+                    return Err(e);
+                }
+            }
+        }
+        Err(last_error)
+    }
 
+    async fn send_to_proxy<M: Message, T: serde::Serialize + ?Sized>(
+        &self,
+        url: &str,
+        query: &SteamProxyQuery<M>,
+        body: &T,
+    ) -> SteamProxyResult<SteamProxyRawResponse> {
         self.http_client
             .post(url)
             .bearer_auth(&self.steam_proxy_api_key)
@@ -132,17 +211,17 @@ impl SteamClient {
             .map(|p| p.contains(&steam_id))
     }
 
-    pub(crate) async fn verify_user_owns_steam_id(
+    /// Verifies the `OpenID` parameters of a Steam login and returns the claimed `SteamID64`
+    pub(crate) async fn verify_open_id(
         &self,
         open_id_params: &std::collections::HashMap<String, String>,
-        steam_id: u64,
-    ) -> Result<(), SteamAccountVerifyError> {
+    ) -> Result<u64, SteamAccountVerifyError> {
         // make a request to the OpenID provider to verify the parameters
         let mut params = open_id_params.clone();
         params.insert("openid.mode".to_owned(), "check_authentication".to_owned());
         let response = self
             .http_client
-            .post("https://steamcommunity.com/openid/login")
+            .post(openid::STEAM_OPENID_ENDPOINT)
             .form(&params)
             .send()
             .await?
@@ -152,12 +231,16 @@ impl SteamClient {
         if !response.contains("is_valid:true") {
             return Err(SteamAccountVerifyError::VerificationFailed);
         }
+        openid::claimed_steam_id(open_id_params).ok_or(SteamAccountVerifyError::VerificationFailed)
+    }
+
+    pub(crate) async fn verify_user_owns_steam_id(
+        &self,
+        open_id_params: &std::collections::HashMap<String, String>,
+        steam_id: u64,
+    ) -> Result<(), SteamAccountVerifyError> {
         // check that the claimed Steam ID matches the provided Steam ID
-        let claimed_steam_id = open_id_params
-            .get("openid.claimed_id")
-            .and_then(|url| url.rsplit('/').next())
-            .and_then(|id_str| id_str.parse::<u64>().ok())
-            .ok_or_else(|| SteamAccountVerifyError::VerificationFailed)?;
+        let claimed_steam_id = self.verify_open_id(open_id_params).await?;
         if claimed_steam_id != steam_id {
             return Err(SteamAccountVerifyError::VerificationFailed);
         }
@@ -286,6 +369,36 @@ async fn fetch_patch_notes(http_client: &reqwest::Client) -> APIResult<Vec<Patch
         })
 }
 
+/// Whether the message only reads data, so identical calls can be coalesced and failed calls
+/// can be retried on another proxy.
+///
+/// Spectating a match doesn't change it, every spectator of it gets the same broadcast.
+fn is_read_only(msg_type: EgcCitadelClientMessages) -> bool {
+    matches!(
+        msg_type,
+        EgcCitadelClientMessages::KEMsgClientToGcGetMatchHistory
+            | EgcCitadelClientMessages::KEMsgClientToGcGetAccountStats
+            | EgcCitadelClientMessages::KEMsgClientToGcGetProfileCard
+            | EgcCitadelClientMessages::KEMsgClientToGcGetActiveMatches
+            | EgcCitadelClientMessages::KEMsgClientToGcGetMatchMetaData
+            | EgcCitadelClientMessages::KEMsgClientToGcGetLeaderboard
+            | EgcCitadelClientMessages::KEMsgClientToGcSpectateLobby
+    )
+}
+
+/// Whether the request failed before reaching the proxy
+fn is_connection_error(error: &SteamProxyError) -> bool {
+    matches!(error, SteamProxyError::Request(e) if e.is_connect())
+}
+
+/// Whether the error was caused by the proxy itself, rather than by the request
+fn is_proxy_failure(error: &SteamProxyError) -> bool {
+    match error {
+        SteamProxyError::Request(e) => e.status().is_none_or(|s| s.is_server_error()),
+        _ => false,
+    }
+}
+
 #[cached(
     ty = "TimedCache<u8, HashSet<u32>>",
     create = "{ TimedCache::with_lifespan(std::time::Duration::from_secs(24 * 60 * 60)) }",
@@ -0,0 +1,105 @@
+use axum::extract::{FromRequestParts, OptionalFromRequestParts};
+use axum::http::HeaderMap;
+use axum::http::request::Parts;
+use reqwest::StatusCode;
+
+use crate::context::AppState;
+use crate::error::APIError;
+use crate::services::patreon::extractor::{
+    extract_token_from_auth_header, extract_token_from_cookie,
+};
+use crate::services::patreon::jwt::validate_steam_session_token;
+
+/// Name of the cookie storing the Steam session token
+pub(crate) const STEAM_SESSION_COOKIE: &str = "steam_session";
+
+/// Authenticated Steam session of a player extracted from request
+///
+/// This extractor validates the player's JWT session token, issued by the Steam `OpenID` login,
+/// from either:
+/// - The `steam_session` cookie
+/// - The `Authorization: Bearer <token>` header
+///
+/// Returns 401 Unauthorized if the token is missing, invalid, or expired.
+#[derive(Debug, Clone)]
+pub(crate) struct SteamSession {
+    /// `SteamID3` of the verified player
+    pub(crate) steam_id3: u32,
+}
+
+impl SteamSession {
+    fn from_headers(headers: &HeaderMap, jwt_secret: &str) -> Result<Self, APIError> {
+        let token = extract_token_from_cookie(headers, STEAM_SESSION_COOKIE)
+            .or_else(|| extract_token_from_auth_header(headers))
+            .ok_or_else(|| {
+                APIError::status_msg(StatusCode::UNAUTHORIZED, "Missing authentication token")
+            })?;
+
+        let claims = validate_steam_session_token(&token, jwt_secret).map_err(|_| {
+            APIError::status_msg(StatusCode::UNAUTHORIZED, "Invalid or expired token")
+        })?;
+
+        Ok(Self {
+            steam_id3: claims.steam_id3,
+        })
+    }
+}
+
+impl FromRequestParts<AppState> for SteamSession {
+    type Rejection = APIError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        Self::from_headers(&parts.headers, &state.config.jwt_secret)
+    }
+}
+
+/// Endpoints also accepting other proofs of ownership treat a missing or invalid session as none.
+impl OptionalFromRequestParts<AppState> for SteamSession {
+    type Rejection = APIError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Option<Self>, Self::Rejection> {
+        Ok(Self::from_headers(&parts.headers, &state.config.jwt_secret).ok())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::{self, HeaderValue};
+
+    use super::*;
+    use crate::services::patreon::jwt::{create_session_token, create_steam_session_token};
+
+    const SECRET: &str = "test_secret_at_least_32_characters_long";
+
+    #[test]
+    fn test_from_headers_cookie() {
+        let token = create_steam_session_token(42, SECRET).unwrap();
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            http::header::COOKIE,
+            HeaderValue::from_str(&format!("steam_session={token}")).unwrap(),
+        );
+
+        let session = SteamSession::from_headers(&headers, SECRET).unwrap();
+        assert_eq!(session.steam_id3, 42);
+    }
+
+    #[test]
+    fn test_from_headers_rejects_patron_token() {
+        let token = create_session_token(uuid::Uuid::new_v4(), SECRET).unwrap();
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            http::header::AUTHORIZATION,
+            HeaderValue::from_str(&format!("Bearer {token}")).unwrap(),
+        );
+
+        assert!(SteamSession::from_headers(&headers, SECRET).is_err());
+        assert!(SteamSession::from_headers(&HeaderMap::new(), SECRET).is_err());
+    }
+}
@@ -0,0 +1,222 @@
+use core::future::IntoFuture;
+use core::time::Duration;
+
+use futures::future::BoxFuture;
+use prost::Message;
+use valveprotos::deadlock::{
+    CMsgAccountStats, CMsgCitadelProfileCard, CMsgClientToGcGetAccountStats,
+    CMsgClientToGcGetActiveMatches, CMsgClientToGcGetActiveMatchesResponse,
+    CMsgClientToGcGetLeaderboard, CMsgClientToGcGetLeaderboardResponse,
+    CMsgClientToGcGetMatchHistory, CMsgClientToGcGetMatchHistoryResponse,
+    CMsgClientToGcGetMatchMetaData, CMsgClientToGcGetMatchMetaDataResponse,
+    CMsgClientToGcGetProfileCard, CMsgClientToGcPartyAction, CMsgClientToGcPartyActionResponse,
+    CMsgClientToGcPartyCreate, CMsgClientToGcPartyCreateResponse, CMsgClientToGcPartyLeave,
+    CMsgClientToGcPartyLeaveResponse, CMsgClientToGcPartySetReadyState,
+    CMsgClientToGcPartySetReadyStateResponse, CMsgClientToGcPartyStartMatch,
+    CMsgClientToGcPartyStartMatchResponse, CMsgClientToGcSpectateLobby,
+    CMsgClientToGcSpectateLobbyResponse, EgcCitadelClientMessages,
+};
+
+use crate::services::steam::client::SteamClient;
+use crate::services::steam::types::{
+    SteamProxyQuery, SteamProxyRawResponse, SteamProxyResponse, SteamProxyResult,
+};
+
+/// Allows each bot 100 calls per day
+const DAILY_BOT_BUDGET: Duration = Duration::from_secs(24 * 60 * 60 / 100);
+
+/// A GC request message, tied to its response and to how the proxy routes it to a bot.
+pub(crate) trait GcRequest: Message + Sized + 'static {
+    type Response: Message + Default;
+
+    const MSG_TYPE: EgcCitadelClientMessages;
+    /// The bot has to be in all of these groups
+    const IN_ALL_GROUPS: &'static [&'static str] = &[];
+    /// The bot has to be in at least one of these groups
+    const IN_ANY_GROUPS: &'static [&'static str] = &[];
+    /// How long the bot can't be used for this message after the call
+    const COOLDOWN: Duration;
+    const REQUEST_TIMEOUT: Duration = Duration::from_secs(2);
+}
+
+impl GcRequest for CMsgClientToGcGetAccountStats {
+    type Response = CMsgAccountStats;
+    const MSG_TYPE: EgcCitadelClientMessages =
+        EgcCitadelClientMessages::KEMsgClientToGcGetAccountStats;
+    const COOLDOWN: Duration = Duration::from_secs(10);
+}
+
+impl GcRequest for CMsgClientToGcGetProfileCard {
+    type Response = CMsgCitadelProfileCard;
+    const MSG_TYPE: EgcCitadelClientMessages =
+        EgcCitadelClientMessages::KEMsgClientToGcGetProfileCard;
+    const COOLDOWN: Duration = Duration::from_secs(10);
+}
+
+impl GcRequest for CMsgClientToGcGetMatchHistory {
+    type Response = CMsgClientToGcGetMatchHistoryResponse;
+    const MSG_TYPE: EgcCitadelClientMessages =
+        EgcCitadelClientMessages::KEMsgClientToGcGetMatchHistory;
+    const IN_ALL_GROUPS: &'static [&'static str] = &["GetMatchHistory"];
+    const COOLDOWN: Duration = DAILY_BOT_BUDGET;
+    const REQUEST_TIMEOUT: Duration = Duration::from_secs(3);
+}
+
+impl GcRequest for CMsgClientToGcGetMatchMetaData {
+    type Response = CMsgClientToGcGetMatchMetaDataResponse;
+    const MSG_TYPE: EgcCitadelClientMessages =
+        EgcCitadelClientMessages::KEMsgClientToGcGetMatchMetaData;
+    const IN_ALL_GROUPS: &'static [&'static str] = &["GetMatchMetaData"];
+    const COOLDOWN: Duration = DAILY_BOT_BUDGET;
+}
+
+impl GcRequest for CMsgClientToGcSpectateLobby {
+    type Response = CMsgClientToGcSpectateLobbyResponse;
+    const MSG_TYPE: EgcCitadelClientMessages =
+        EgcCitadelClientMessages::KEMsgClientToGcSpectateLobby;
+    const IN_ANY_GROUPS: &'static [&'static str] = &["SpectateLobby", "SpectateLobbyOnDemand"];
+    const COOLDOWN: Duration = DAILY_BOT_BUDGET;
+}
+
+impl GcRequest for CMsgClientToGcGetActiveMatches {
+    type Response = CMsgClientToGcGetActiveMatchesResponse;
+    const MSG_TYPE: EgcCitadelClientMessages =
+        EgcCitadelClientMessages::KEMsgClientToGcGetActiveMatches;
+    const IN_ALL_GROUPS: &'static [&'static str] = &["LowRateLimitApis"];
+    const COOLDOWN: Duration = Duration::from_mins(10);
+}
+
+impl GcRequest for CMsgClientToGcGetLeaderboard {
+    type Response = CMsgClientToGcGetLeaderboardResponse;
+    const MSG_TYPE: EgcCitadelClientMessages =
+        EgcCitadelClientMessages::KEMsgClientToGcGetLeaderboard;
+    const COOLDOWN: Duration = Duration::from_mins(1);
+}
+
+impl GcRequest for CMsgClientToGcPartyCreate {
+    type Response = CMsgClientToGcPartyCreateResponse;
+    const MSG_TYPE: EgcCitadelClientMessages = EgcCitadelClientMessages::KEMsgClientToGcPartyCreate;
+    /// The bot stays in the party until the match starts
+    const COOLDOWN: Duration = Duration::from_hours(2);
+}
+
+impl GcRequest for CMsgClientToGcPartyAction {
+    type Response = CMsgClientToGcPartyActionResponse;
+    const MSG_TYPE: EgcCitadelClientMessages = EgcCitadelClientMessages::KEMsgClientToGcPartyAction;
+    const COOLDOWN: Duration = Duration::ZERO;
+}
+
+impl GcRequest for CMsgClientToGcPartySetReadyState {
+    type Response = CMsgClientToGcPartySetReadyStateResponse;
+    const MSG_TYPE: EgcCitadelClientMessages =
+        EgcCitadelClientMessages::KEMsgClientToGcPartySetReadyState;
+    const COOLDOWN: Duration = Duration::ZERO;
+}
+
+impl GcRequest for CMsgClientToGcPartyLeave {
+    type Response = CMsgClientToGcPartyLeaveResponse;
+    const MSG_TYPE: EgcCitadelClientMessages = EgcCitadelClientMessages::KEMsgClientToGcPartyLeave;
+    const COOLDOWN: Duration = Duration::ZERO;
+}
+
+impl GcRequest for CMsgClientToGcPartyStartMatch {
+    type Response = CMsgClientToGcPartyStartMatchResponse;
+    const MSG_TYPE: EgcCitadelClientMessages =
+        EgcCitadelClientMessages::KEMsgClientToGcPartyStartMatch;
+    const COOLDOWN: Duration = Duration::ZERO;
+}
+
+fn default_query<M: GcRequest>(msg: M) -> SteamProxyQuery<M> {
+    let groups = |groups: &[&str]| {
+        (!groups.is_empty()).then(|| groups.iter().map(|&g| g.to_owned()).collect())
+    };
+    SteamProxyQuery {
+        msg_type: M::MSG_TYPE,
+        msg,
+        in_all_groups: groups(M::IN_ALL_GROUPS),
+        in_any_groups: groups(M::IN_ANY_GROUPS),
+        cooldown_time: M::COOLDOWN,
+        soft_cooldown_millis: None,
+        request_timeout: M::REQUEST_TIMEOUT,
+        username: None,
+    }
+}
+
+/// A GC call with the defaults of its [`GcRequest`], created by [`SteamClient::gc`].
+///
+/// Awaiting it returns the decoded response.
+#[must_use]
+pub(crate) struct GcCall<'a, M: GcRequest> {
+    steam_client: &'a SteamClient,
+    query: SteamProxyQuery<M>,
+}
+
+impl<'a, M: GcRequest> GcCall<'a, M> {
+    pub(super) fn new(steam_client: &'a SteamClient, msg: M) -> Self {
+        Self {
+            steam_client,
+            query: default_query(msg),
+        }
+    }
+
+    /// Sends the message through a specific bot.
+    pub(crate) fn username(mut self, username: impl Into<Option<String>>) -> Self {
+        self.query.username = username.into();
+        self
+    }
+
+    /// Overrides the soft cooldown.
+    ///
+    /// The query leaves it unset by default (`None`), in which case half of the cooldown is sent
+    /// to the proxy when the call is made.
+    pub(crate) fn soft_cooldown(mut self, soft_cooldown: impl Into<Option<Duration>>) -> Self {
+        self.query.soft_cooldown_millis = soft_cooldown.into();
+        self
+    }
+
+    /// Sends the message, without decoding the response.
+    pub(crate) async fn raw(self) -> SteamProxyResult<SteamProxyRawResponse> {
+        self.steam_client.call_steam_proxy_raw(self.query).await
+    }
+}
+
+impl<'a, M: GcRequest> IntoFuture for GcCall<'a, M> {
+    type Output = SteamProxyResult<SteamProxyResponse<M::Response>>;
+    type IntoFuture = BoxFuture<'a, Self::Output>;
+
+    fn into_future(self) -> Self::IntoFuture {
+        Box::pin(async move { self.raw().await?.try_into() })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_query() {
+        let query = default_query(CMsgClientToGcGetMatchHistory::default());
+        assert_eq!(
+            query.msg_type,
+            EgcCitadelClientMessages::KEMsgClientToGcGetMatchHistory
+        );
+        assert_eq!(
+            query.in_all_groups,
+            Some(vec!["GetMatchHistory".to_owned()])
+        );
+        assert_eq!(query.in_any_groups, None);
+        assert_eq!(query.cooldown_time, Duration::from_secs(864));
+        assert_eq!(query.request_timeout, Duration::from_secs(3));
+        assert_eq!(query.username, None);
+
+        let query = default_query(CMsgClientToGcSpectateLobby::default());
+        assert_eq!(query.in_all_groups, None);
+        assert_eq!(
+            query.in_any_groups,
+            Some(vec![
+                "SpectateLobby".to_owned(),
+                "SpectateLobbyOnDemand".to_owned()
+            ])
+        );
+    }
+}
@@ -1,2 +1,7 @@
 pub(crate) mod client;
+pub(crate) mod extractor;
+pub(crate) mod gc;
+pub(crate) mod openid;
+mod proxy_pool;
+mod single_flight;
 pub(crate) mod types;
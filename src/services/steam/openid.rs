@@ -0,0 +1,170 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, TimeDelta, Utc};
+use url::Url;
+
+/// Steam `OpenID` 2.0 provider endpoint
+pub(crate) const STEAM_OPENID_ENDPOINT: &str = "https://steamcommunity.com/openid/login";
+
+/// Prefix of the claimed ID of a Steam account, followed by its `SteamID64`
+const CLAIMED_ID_PREFIX: &str = "https://steamcommunity.com/openid/id/";
+
+/// Logins whose response nonce is older than this are rejected
+const MAX_NONCE_AGE: TimeDelta = TimeDelta::minutes(5);
+
+/// Tolerated clock difference to Steam, for nonces from the future
+const MAX_CLOCK_SKEW: TimeDelta = TimeDelta::minutes(1);
+
+/// Used nonces are remembered until they aren't fresh anymore, [`MAX_NONCE_AGE`] plus
+/// [`MAX_CLOCK_SKEW`].
+pub(crate) const NONCE_TTL_SECS: u64 = 6 * 60;
+
+const OPENID_NS: &str = "http://specs.openid.net/auth/2.0";
+const IDENTIFIER_SELECT: &str = "http://specs.openid.net/auth/2.0/identifier_select";
+
+/// Builds the Steam `OpenID` login URL, redirecting back to `return_to` after the login.
+///
+/// The realm is the origin of `return_to`.
+pub(crate) fn build_login_url(return_to: &Url) -> String {
+    let realm = return_to.origin().ascii_serialization();
+    Url::parse_with_params(
+        STEAM_OPENID_ENDPOINT,
+        [
+            ("openid.ns", OPENID_NS),
+            ("openid.mode", "checkid_setup"),
+            ("openid.return_to", return_to.as_str()),
+            ("openid.realm", &realm),
+            ("openid.identity", IDENTIFIER_SELECT),
+            ("openid.claimed_id", IDENTIFIER_SELECT),
+        ],
+    )
+    .expect("Steam OpenID endpoint is a valid URL")
+    .into()
+}
+
+/// The `SteamID64` claimed by the `OpenID` parameters of a login.
+///
+/// The claim is only trustworthy after the parameters were verified with Steam.
+pub(crate) fn claimed_steam_id(open_id_params: &HashMap<String, String>) -> Option<u64> {
+    open_id_params
+        .get("openid.claimed_id")?
+        .strip_prefix(CLAIMED_ID_PREFIX)?
+        .parse()
+        .ok()
+}
+
+/// Checks that the `OpenID` parameters were issued for a login returning to `return_to`.
+///
+/// Without this check, parameters of a Steam login on any other site could be replayed.
+pub(crate) fn returns_to(open_id_params: &HashMap<String, String>, return_to: &Url) -> bool {
+    open_id_params
+        .get("openid.return_to")
+        .and_then(|r| Url::parse(r).ok())
+        .is_some_and(|mut r| {
+            r.set_query(None);
+            r == *return_to
+        })
+}
+
+/// The response nonce of a login, if it was issued recently.
+///
+/// Nonces start with the time they were issued at, e.g. `2024-01-01T12:00:00Z1a2b3c`. They are
+/// unique, so a login is only accepted once by remembering its nonce for [`NONCE_TTL_SECS`].
+pub(crate) fn fresh_nonce(
+    open_id_params: &HashMap<String, String>,
+    now: DateTime<Utc>,
+) -> Option<&str> {
+    let nonce = open_id_params.get("openid.response_nonce")?;
+    let issued_at = DateTime::parse_from_rfc3339(nonce.get(..20)?).ok()?;
+    let age = now.signed_duration_since(issued_at);
+    (-MAX_CLOCK_SKEW..=MAX_NONCE_AGE)
+        .contains(&age)
+        .then_some(nonce.as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| ((*k).to_owned(), (*v).to_owned()))
+            .collect()
+    }
+
+    #[test]
+    fn test_build_login_url() {
+        let return_to =
+            Url::parse("https://api.deadlock-api.com/v1/auth/steam/callback?state=abc").unwrap();
+        let url = Url::parse(&build_login_url(&return_to)).unwrap();
+        let query: HashMap<_, _> = url.query_pairs().into_owned().collect();
+
+        assert!(url.as_str().starts_with(STEAM_OPENID_ENDPOINT));
+        assert_eq!(query["openid.mode"], "checkid_setup");
+        assert_eq!(query["openid.return_to"], return_to.as_str());
+        assert_eq!(query["openid.realm"], "https://api.deadlock-api.com");
+        assert_eq!(query["openid.claimed_id"], IDENTIFIER_SELECT);
+    }
+
+    #[test]
+    fn test_claimed_steam_id() {
+        assert_eq!(
+            claimed_steam_id(&params(&[(
+                "openid.claimed_id",
+                "https://steamcommunity.com/openid/id/76561197960287930"
+            )])),
+            Some(76561197960287930)
+        );
+        assert_eq!(
+            claimed_steam_id(&params(&[(
+                "openid.claimed_id",
+                "https://example.com/openid/id/76561197960287930"
+            )])),
+            None
+        );
+        assert_eq!(claimed_steam_id(&params(&[])), None);
+    }
+
+    #[test]
+    fn test_returns_to() {
+        let return_to = Url::parse("https://api.deadlock-api.com/v1/auth/steam/callback").unwrap();
+        assert!(returns_to(
+            &params(&[(
+                "openid.return_to",
+                "https://api.deadlock-api.com/v1/auth/steam/callback?state=abc"
+            )]),
+            &return_to
+        ));
+        assert!(returns_to(
+            &params(&[(
+                "openid.return_to",
+                "https://API.deadlock-api.com:443/v1/auth/steam/callback?state=abc"
+            )]),
+            &return_to
+        ));
+        assert!(!returns_to(
+            &params(&[("openid.return_to", "https://example.com/callback?state=abc")]),
+            &return_to
+        ));
+        assert!(!returns_to(&params(&[]), &return_to));
+    }
+
+    #[test]
+    fn test_fresh_nonce() {
+        let now = DateTime::parse_from_rfc3339("2024-01-01T12:00:00Z")
+            .unwrap()
+            .to_utc();
+        let nonce = |nonce| params(&[("openid.response_nonce", nonce)]);
+
+        assert_eq!(
+            fresh_nonce(&nonce("2024-01-01T11:58:00Z1a2b3c"), now),
+            Some("2024-01-01T11:58:00Z1a2b3c")
+        );
+        assert!(fresh_nonce(&nonce("2024-01-01T12:00:30Zabc"), now).is_some());
+        assert!(fresh_nonce(&nonce("2024-01-01T11:50:00Zabc"), now).is_none());
+        assert!(fresh_nonce(&nonce("2024-01-01T12:05:00Zabc"), now).is_none());
+        assert!(fresh_nonce(&nonce("not a nonce"), now).is_none());
+        assert!(fresh_nonce(&params(&[]), now).is_none());
+    }
+}
@@ -0,0 +1,177 @@
+use core::time::Duration;
+use std::time::Instant;
+
+use md5::{Digest, Md5};
+use metrics::counter;
+use redis::aio::MultiplexedConnection;
+use redis::{AsyncCommands, ExistenceCheck, RedisResult, SetExpiry, SetOptions};
+use tokio::time::sleep;
+use tracing::warn;
+use valveprotos::deadlock::EgcCitadelClientMessages;
+
+use crate::services::steam::types::{SteamProxyRawResponse, SteamProxyResult};
+
+/// How long the leader's response is kept for followers to pick up
+const RESULT_TTL: Duration = Duration::from_secs(5);
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Redis key identifying identical GC calls
+fn flight_key(
+    msg_type: EgcCitadelClientMessages,
+    username: Option<&str>,
+    message: &[u8],
+) -> String {
+    let mut hasher = Md5::new();
+    hasher.update(message);
+    let digest = hex::encode(hasher.finalize());
+    match username {
+        Some(username) => format!("steam-flight:{}:{username}:{digest}", msg_type as i32),
+        None => format!("steam-flight:{}:{digest}", msg_type as i32),
+    }
+}
+
+/// Coalesces identical Steam proxy calls across all replicas.
+///
+/// The first caller becomes the leader and calls the proxy, everyone else waits for the
+/// leader's response instead of spending another bot cooldown on the same message. Only
+/// responses are shared, if the leader fails one of the followers takes over and calls the proxy
+/// again. If Redis is unavailable, or the leader takes too long, callers fall back to calling the
+/// proxy themselves.
+#[derive(Clone)]
+pub(super) struct SingleFlight {
+    redis_client: MultiplexedConnection,
+}
+
+impl SingleFlight {
+    pub(super) fn new(redis_client: MultiplexedConnection) -> Self {
+        Self { redis_client }
+    }
+
+    /// Runs `call` unless an identical call is already in flight, in which case its response is
+    /// returned.
+    ///
+    /// `timeout` is the longest the leader may take, followers stop waiting after it.
+    pub(super) async fn run<F, Fut>(
+        &self,
+        msg_type: EgcCitadelClientMessages,
+        username: Option<&str>,
+        message: &[u8],
+        timeout: Duration,
+        call: F,
+    ) -> SteamProxyResult<SteamProxyRawResponse>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = SteamProxyResult<SteamProxyRawResponse>>,
+    {
+        let key = flight_key(msg_type, username, message);
+        let lock_key = format!("{key}:lock");
+        let result_key = format!("{key}:result");
+        let mut redis_client = self.redis_client.clone();
+        let deadline = Instant::now() + timeout;
+        loop {
+            match Self::poll(&mut redis_client, &lock_key, &result_key, timeout).await {
+                Ok(Flight::Finished(response)) => {
+                    counter!("steam.proxy.coalesced", "msg_type" => msg_type.as_str_name())
+                        .increment(1);
+                    return Ok(response);
+                }
+                Ok(Flight::Leader) => break,
+                Ok(Flight::InFlight) if Instant::now() < deadline => sleep(POLL_INTERVAL).await,
+                Ok(Flight::InFlight) => {
+                    warn!("Timed out waiting for coalesced Steam proxy call {key}");
+                    return call().await;
+                }
+                Err(e) => {
+                    warn!("Failed to coalesce Steam proxy call {key}: {e}");
+                    return call().await;
+                }
+            }
+        }
+
+        let result = call().await;
+        if let Err(e) = Self::share(&mut redis_client, &lock_key, &result_key, &result).await {
+            warn!("Failed to share Steam proxy result {key}: {e}");
+        }
+        result
+    }
+
+    /// Shares the leader's response with the followers and releases the lock.
+    ///
+    /// Errors aren't shared, releasing the lock lets the next follower become the leader.
+    ///
+    /// The lock expires after the timeout, so a leader exceeding it may release a newer leader's
+    /// lock. That leader's followers then pick up this response instead.
+    async fn share(
+        redis_client: &mut MultiplexedConnection,
+        lock_key: &str,
+        result_key: &str,
+        result: &SteamProxyResult<SteamProxyRawResponse>,
+    ) -> Result<(), Box<dyn core::error::Error + Send + Sync>> {
+        let mut pipe = redis::pipe();
+        if let Ok(response) = result {
+            pipe.set_ex(
+                result_key,
+                serde_json::to_string(response)?,
+                RESULT_TTL.as_secs(),
+            );
+        }
+        pipe.del(lock_key).exec_async(redis_client).await?;
+        Ok(())
+    }
+
+    /// Returns the shared response if there is one, otherwise tries to become the leader.
+    async fn poll(
+        redis_client: &mut MultiplexedConnection,
+        lock_key: &str,
+        result_key: &str,
+        timeout: Duration,
+    ) -> RedisResult<Flight> {
+        let shared: Option<String> = redis_client.get(result_key).await?;
+        if let Some(response) = shared.and_then(|s| serde_json::from_str(&s).ok()) {
+            return Ok(Flight::Finished(response));
+        }
+        let options = SetOptions::default()
+            .conditional_set(ExistenceCheck::NX)
+            .with_expiration(SetExpiry::PX(
+                u64::try_from(timeout.as_millis()).unwrap_or(u64::MAX),
+            ));
+        let acquired: Option<String> = redis_client.set_options(lock_key, 1, options).await?;
+        Ok(if acquired.is_some() {
+            Flight::Leader
+        } else {
+            Flight::InFlight
+        })
+    }
+}
+
+enum Flight {
+    /// The response of an identical call is available
+    Finished(SteamProxyRawResponse),
+    /// The lock was acquired, the caller has to make the call
+    Leader,
+    /// Another caller is making the call
+    InFlight,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flight_key() {
+        let msg_type = EgcCitadelClientMessages::KEMsgClientToGcGetLeaderboard;
+        let key = flight_key(msg_type, None, b"message");
+        assert_eq!(key, flight_key(msg_type, None, b"message"));
+        assert_ne!(key, flight_key(msg_type, None, b"other message"));
+        assert_ne!(
+            key,
+            flight_key(
+                EgcCitadelClientMessages::KEMsgClientToGcGetProfileCard,
+                None,
+                b"message"
+            )
+        );
+        assert_ne!(key, flight_key(msg_type, Some("bot"), b"message"));
+        assert!(key.starts_with(&format!("steam-flight:{}:", msg_type as i32)));
+    }
+}
@@ -18,12 +18,13 @@ pub(crate) struct SteamProxyQuery<M: Message> {
     pub(crate) in_all_groups: Option<Vec<String>>,
     pub(crate) in_any_groups: Option<Vec<String>>,
     pub(crate) cooldown_time: Duration,
+    /// Half of `cooldown_time` is sent to the proxy if unset
     pub(crate) soft_cooldown_millis: Option<Duration>,
     pub(crate) request_timeout: Duration,
     pub(crate) username: Option<String>,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub(crate) struct SteamProxyRawResponse {
     pub(crate) data: String,
     pub(crate) username: String,
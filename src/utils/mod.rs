@@ -1,2 +1,3 @@
+pub(super) mod net;
 pub(super) mod parse;
 pub mod types;
@@ -0,0 +1,190 @@
+use core::net::{IpAddr, Ipv6Addr};
+use core::str::FromStr;
+use std::sync::LazyLock;
+
+use serde::{Deserialize, Deserializer};
+use thiserror::Error;
+
+/// An IP network in CIDR notation, e.g. `10.0.0.0/8` or `2001:db8::/32`.
+///
+/// A bare address without prefix length is treated as a single host.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub(crate) struct IpCidr {
+    network: IpAddr,
+    prefix_len: u8,
+}
+
+impl IpCidr {
+    pub(crate) fn contains(&self, ip: IpAddr) -> bool {
+        match (self.network, ip.to_canonical()) {
+            (IpAddr::V4(network), IpAddr::V4(ip)) => {
+                let mask = u32::MAX
+                    .checked_shl(32 - u32::from(self.prefix_len))
+                    .unwrap_or(0);
+                u32::from(network) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(network), IpAddr::V6(ip)) => {
+                let mask = u128::MAX
+                    .checked_shl(128 - u32::from(self.prefix_len))
+                    .unwrap_or(0);
+                u128::from(network) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+#[error("Invalid CIDR: {0}")]
+pub(crate) struct IpCidrParseError(String);
+
+impl FromStr for IpCidr {
+    type Err = IpCidrParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (addr, prefix_len) = s.split_once('/').map_or((s, None), |(a, p)| (a, Some(p)));
+        let network = IpAddr::from_str(addr)
+            .map_err(|_| IpCidrParseError(s.to_owned()))?
+            .to_canonical();
+        let max_prefix_len = if network.is_ipv4() { 32 } else { 128 };
+        let prefix_len = match prefix_len {
+            None => max_prefix_len,
+            Some(p) => p
+                .parse::<u8>()
+                .ok()
+                .filter(|&p| p <= max_prefix_len)
+                .ok_or_else(|| IpCidrParseError(s.to_owned()))?,
+        };
+        Ok(Self {
+            network,
+            prefix_len,
+        })
+    }
+}
+
+impl<'de> Deserialize<'de> for IpCidr {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// Networks that aren't publicly routable, like private, loopback and link-local networks, and
+/// IPv6 prefixes embedding IPv4 addresses.
+static NON_PUBLIC_NETWORKS: LazyLock<Vec<IpCidr>> = LazyLock::new(|| {
+    [
+        "0.0.0.0/8",
+        "10.0.0.0/8",
+        "100.64.0.0/10",
+        "127.0.0.0/8",
+        "169.254.0.0/16",
+        "172.16.0.0/12",
+        "192.0.0.0/24",
+        "192.0.2.0/24",
+        "192.168.0.0/16",
+        "198.18.0.0/15",
+        "198.51.100.0/24",
+        "203.0.113.0/24",
+        "224.0.0.0/3",
+        "::/127",
+        "64:ff9b::/96",
+        "64:ff9b:1::/48",
+        "100::/64",
+        "2001:db8::/32",
+        "2002::/16",
+        "fc00::/7",
+        "fe80::/10",
+        "ff00::/8",
+    ]
+    .into_iter()
+    .filter_map(|c| c.parse().ok())
+    .collect()
+});
+
+/// Whether an IP address is publicly routable, requests to user-supplied URLs must only reach
+/// these addresses.
+pub(crate) fn is_public_ip(ip: IpAddr) -> bool {
+    !NON_PUBLIC_NETWORKS
+        .iter()
+        .any(|network| network.contains(ip))
+}
+
+/// Returns the key under which a client is rate limited.
+///
+/// IPv6 clients usually get a whole /64 assigned, so they are grouped by that prefix.
+pub(crate) fn rate_limit_bucket(ip: IpAddr) -> String {
+    match ip.to_canonical() {
+        IpAddr::V4(ip) => ip.to_string(),
+        IpAddr::V6(ip) => {
+            let prefix = u128::from(ip) & (u128::MAX << 64);
+            format!("{}/64", Ipv6Addr::from(prefix))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    #[rstest]
+    #[case("10.0.0.0/8", "10.1.2.3", true)]
+    #[case("10.0.0.0/8", "11.1.2.3", false)]
+    #[case("192.168.1.1", "192.168.1.1", true)]
+    #[case("192.168.1.1", "192.168.1.2", false)]
+    #[case("0.0.0.0/0", "8.8.8.8", true)]
+    #[case("0.0.0.0/0", "2001:db8::1", false)]
+    #[case("::/0", "2001:db8::1", true)]
+    #[case("2001:db8::/32", "2001:db8:1::1", true)]
+    #[case("2001:db8::/32", "2001:db9::1", false)]
+    #[case("10.0.0.0/8", "::ffff:10.0.0.1", true)]
+    fn test_cidr_contains(#[case] cidr: &str, #[case] ip: &str, #[case] expected: bool) {
+        let cidr: IpCidr = cidr.parse().unwrap();
+        assert_eq!(cidr.contains(ip.parse().unwrap()), expected);
+    }
+
+    #[rstest]
+    #[case("10.0.0.0/33")]
+    #[case("::/129")]
+    #[case("not-an-ip")]
+    #[case("10.0.0.0/")]
+    fn test_cidr_invalid(#[case] cidr: &str) {
+        assert!(cidr.parse::<IpCidr>().is_err());
+    }
+
+    #[rstest]
+    #[case("1.1.1.1", true)]
+    #[case("2606:4700::1111", true)]
+    #[case("127.0.0.1", false)]
+    #[case("10.1.2.3", false)]
+    #[case("172.31.0.1", false)]
+    #[case("192.168.1.1", false)]
+    #[case("169.254.169.254", false)]
+    #[case("100.64.0.1", false)]
+    #[case("0.0.0.0", false)]
+    #[case("255.255.255.255", false)]
+    #[case("::", false)]
+    #[case("::1", false)]
+    #[case("::ffff:127.0.0.1", false)]
+    #[case("64:ff9b::a00:1", false)]
+    #[case("fd00::1", false)]
+    #[case("fe80::1", false)]
+    fn test_is_public_ip(#[case] ip: &str, #[case] expected: bool) {
+        assert_eq!(is_public_ip(ip.parse().unwrap()), expected);
+    }
+
+    #[rstest]
+    #[case("1.2.3.4", "1.2.3.4")]
+    #[case("::ffff:1.2.3.4", "1.2.3.4")]
+    #[case("2001:db8:1:2:3:4:5:6", "2001:db8:1:2::/64")]
+    #[case("2001:db8:1:2::ffff", "2001:db8:1:2::/64")]
+    fn test_rate_limit_bucket(#[case] ip: &str, #[case] expected: &str) {
+        assert_eq!(rate_limit_bucket(ip.parse().unwrap()), expected);
+    }
+}
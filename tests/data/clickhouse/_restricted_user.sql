@@ -1,13 +1,14 @@
 DROP USER IF EXISTS api_readonly_user;
 CREATE USER api_readonly_user IDENTIFIED BY 'testing'
     SETTINGS
-        readonly = 1, -- Only SELECT queries allowed. No DDL/DML.
+        readonly = 1, -- Only SELECT queries allowed. No DDL/DML. Only settings marked CHANGEABLE_IN_READONLY can be changed.
         allow_ddl = 0, -- DDL (CREATE, ALTER, DROP, etc.) is disallowed.
         allow_introspection_functions = 0, -- Introspection functions are disabled.
-        max_execution_time = 15, -- Max 30 seconds per query.
+        max_execution_time = 15 MAX 1800 CHANGEABLE_IN_READONLY, -- Max 15 seconds per query, the API raises it up to 30 minutes for query jobs.
         max_threads = 5, -- Max 5 threads per query.
         timeout_overflow_mode = 'throw', -- Error thrown if timeout is exceeded.
-        max_memory_usage = 5000000000, -- Max 5 GB RAM per query.
+        max_memory_usage = 5000000000 MAX 34359738368 CHANGEABLE_IN_READONLY, -- Max 5 GB RAM per query, the API raises it up to 32 GiB.
+        max_rows_to_read = 1000000000 MAX 200000000000 CHANGEABLE_IN_READONLY, -- Max 1 billion rows read per query, the API raises it up to 200 billion.
         max_memory_usage_for_user = 10000000000, -- Max 10 GB RAM for all concurrent queries by this user.
         max_bytes_before_external_group_by = 0, -- GROUP BY uses only RAM (no disk spill).
         max_bytes_before_external_sort = 0, -- ORDER BY uses only RAM (no disk spill).
@@ -18,5 +19,8 @@ CREATE USER api_readonly_user IDENTIFIED BY 'testing'
 ;
 GRANT SELECT ON default.* TO api_readonly_user;
 GRANT SHOW ON default.* TO api_readonly_user;
+GRANT dictGet ON default.* TO api_readonly_user;
 REVOKE SELECT ON default.match_salts FROM api_readonly_user;
 GRANT SELECT (match_id, cluster_id, metadata_salt, replay_salt, created_at) ON default.match_salts TO api_readonly_user;
+REVOKE SHOW COLUMNS ON default.match_salts FROM api_readonly_user;
+GRANT SHOW COLUMNS (match_id, cluster_id, metadata_salt, replay_salt, created_at) ON default.match_salts TO api_readonly_user;
@@ -0,0 +1,12 @@
+DROP TABLE IF EXISTS leaderboard_account_ids;
+
+CREATE TABLE IF NOT EXISTS leaderboard_account_ids
+(
+    region       UInt8,
+    account_name String,
+    account_id   UInt32,
+    confidence   Float64,
+    updated_at   DateTime
+    )
+    ENGINE = ReplacingMergeTree(updated_at)
+    ORDER BY (region, account_name);
@@ -0,0 +1,18 @@
+DROP TABLE IF EXISTS leaderboard_snapshots;
+
+CREATE TABLE IF NOT EXISTS leaderboard_snapshots
+(
+    snapshot_time DateTime,
+    region        UInt8,
+    hero_id       UInt32,
+    rank          UInt32,
+    account_name  String,
+    badge_level   Nullable(UInt32),
+    top_hero_ids  Array(UInt32)
+    )
+    ENGINE = ReplacingMergeTree
+    PARTITION BY toYYYYMM(snapshot_time)
+    ORDER BY (region, hero_id, snapshot_time, rank);
+
+ALTER TABLE leaderboard_snapshots
+    ADD INDEX idx_account_name account_name TYPE bloom_filter GRANULARITY 4;
@@ -1,9 +1,10 @@
--- Patrons table for storing Patreon OAuth data and subscription info
+-- Patrons table for storing membership provider OAuth data and subscription info
 create table if not exists patrons
 (
     id                   uuid        default gen_random_uuid() not null primary key,
-    patreon_user_id      text                                  not null
-        constraint patrons_patreon_user_id_unique unique,
+    provider             text        default 'patreon'         not null
+        constraint patrons_provider_check check (provider in ('patreon', 'github', 'mock')),
+    provider_user_id     text                                  not null,
     email                text,
     tier_id              text,
     pledge_amount_cents  integer,
@@ -13,8 +14,11 @@ create table if not exists patrons
     refresh_token        text,
     token_expires_at     timestamptz,
     last_verified_at     timestamptz,
+    -- Last membership webhook event applied to the patron, older events are skipped
+    webhook_event_id     bigint,
     created_at           timestamptz default current_timestamp not null,
-    updated_at           timestamptz default current_timestamp not null
+    updated_at           timestamptz default current_timestamp not null,
+    constraint patrons_provider_user_id_unique unique (provider, provider_user_id)
 );
 
 -- Prioritized Steam accounts linked to patrons
@@ -6,11 +6,13 @@ create table api_keys
         constraint api_keys_pk unique,
     data_access    boolean   default false             not null,
     disabled       boolean   default false             not null,
-    patron_id      uuid      references patrons(id)
+    patron_id      uuid      references patrons(id),
+    scopes         text[]    default '{}'              not null
 );
 
-insert into api_keys (key, comment, data_access, disabled)
-values ('fffd6bfd-2be9-4b7e-ab76-a9d1dca19b64', 'Test Key', true, false);
+insert into api_keys (key, comment, data_access, disabled, scopes)
+values ('fffd6bfd-2be9-4b7e-ab76-a9d1dca19b64', 'Test Key', true, false, '{sql,custom_matches}'),
+       ('5b2e8c1d-7f4a-4e3b-9c6d-1a0f2e3d4c5b', 'Other Test Key', false, false, '{custom_matches}');
 
 create table api_key_limits
 (
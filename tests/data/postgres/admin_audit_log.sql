@@ -0,0 +1,11 @@
+create table admin_audit_log
+(
+    id         bigserial                             not null primary key,
+    created_at timestamptz default current_timestamp not null,
+    action     text                                  not null,
+    target     text                                  not null,
+    details    jsonb,
+    client_ip  text
+);
+
+create index admin_audit_log_created_at_idx on admin_audit_log (created_at desc);
@@ -0,0 +1,35 @@
+create table callback_deliveries
+(
+    id                    uuid        default gen_random_uuid() not null primary key,
+    api_key               uuid                                  not null
+        constraint callback_deliveries_api_key_fkey references api_keys on delete cascade,
+    party_id              bigint                                not null,
+    event                 text                                  not null,
+    url                   text                                  not null,
+    secret                text                                  not null,
+    body                  text                                  not null,
+    status                text        default 'pending'         not null
+        constraint callback_deliveries_status_check check (status in ('pending', 'delivered', 'dead')),
+    attempts              integer     default 0                 not null,
+    -- Attempts made before the delivery was last retried from the dead-letter queue
+    attempts_before_retry integer     default 0                 not null,
+    next_attempt_at       timestamptz default current_timestamp not null,
+    created_at            timestamptz default current_timestamp not null,
+    delivered_at          timestamptz
+);
+
+create index callback_deliveries_pending_idx on callback_deliveries (next_attempt_at) where status = 'pending';
+
+create index callback_deliveries_api_key_idx on callback_deliveries (api_key, created_at);
+
+create table callback_delivery_attempts
+(
+    delivery_id  uuid                                  not null
+        constraint callback_delivery_attempts_delivery_id_fkey references callback_deliveries on delete cascade,
+    attempt      integer                               not null,
+    status_code  integer,
+    error        text,
+    duration_ms  integer                               not null,
+    attempted_at timestamptz default current_timestamp not null,
+    constraint callback_delivery_attempts_pkey primary key (delivery_id, attempt)
+);
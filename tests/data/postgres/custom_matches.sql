@@ -0,0 +1,21 @@
+create table custom_matches
+(
+    party_id        bigint                                not null primary key,
+    api_key         uuid                                  not null
+        constraint custom_matches_api_key_fkey references api_keys on delete cascade,
+    status          text        default 'created'         not null
+        constraint custom_matches_status_check check (status in ('created', 'filled', 'started', 'finished', 'expired')),
+    match_id        bigint,
+    callback_url    text,
+    callback_secret text,
+    -- Digest of the last lobby settings reported to the callback url
+    settings_digest text,
+    created_at      timestamptz default current_timestamp not null,
+    filled_at       timestamptz,
+    started_at      timestamptz,
+    finished_at     timestamptz
+);
+
+create index custom_matches_active_idx on custom_matches (created_at) where status in ('created', 'filled', 'started');
+
+create index custom_matches_api_key_idx on custom_matches (api_key, created_at);
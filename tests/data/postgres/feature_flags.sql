@@ -0,0 +1,19 @@
+create table feature_flags
+(
+    path            text                                  not null primary key,
+    enabled         boolean     default true              not null,
+    rollout_percent smallint    default 100               not null
+        constraint feature_flags_rollout_percent_check check (rollout_percent between 0 and 100),
+    degraded        boolean     default false             not null,
+    updated_at      timestamptz default current_timestamp not null
+);
+
+create table feature_flag_overrides
+(
+    path    text    not null
+        constraint feature_flag_overrides_path_fkey references feature_flags on delete cascade,
+    key     uuid    not null
+        constraint feature_flag_overrides_key_fkey references api_keys on delete cascade,
+    enabled boolean not null,
+    constraint feature_flag_overrides_pkey primary key (path, key)
+);
@@ -0,0 +1,24 @@
+-- Verified membership provider webhook deliveries, processed in order per patron by the webhook worker
+create table membership_webhook_events
+(
+    id               bigserial                             not null primary key,
+    provider         text                                  not null,
+    dedupe_key       text                                  not null,
+    event            text                                  not null,
+    provider_user_id text                                  not null,
+    -- Removed after the retention period, it contains personal data of the supporter
+    body             text,
+    status           text        default 'pending'         not null
+        constraint membership_webhook_events_status_check check (status in ('pending', 'processed', 'ignored',
+                                                                            'superseded', 'failed')),
+    attempts         integer     default 0                 not null,
+    next_attempt_at  timestamptz default current_timestamp not null,
+    last_error       text,
+    received_at      timestamptz default current_timestamp not null,
+    processed_at     timestamptz,
+    constraint membership_webhook_events_dedupe_key_unique unique (provider, dedupe_key)
+);
+
+create index membership_webhook_events_pending_idx on membership_webhook_events (provider, provider_user_id, id) where status = 'pending';
+
+create index membership_webhook_events_received_at_idx on membership_webhook_events (received_at);
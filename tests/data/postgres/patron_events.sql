@@ -0,0 +1,19 @@
+-- Append-only history of changes to patrons and their prioritized Steam accounts
+create table patron_events
+(
+    id         bigserial                             not null primary key,
+    -- Kept as history when the patron is deleted
+    patron_id  uuid
+        constraint patron_events_patron_fkey references patrons (id) on delete set null,
+    created_at timestamptz default current_timestamp not null,
+    event      text                                  not null
+        constraint patron_events_event_check check (event in ('account_added', 'account_replaced', 'account_removed',
+                                                              'account_reactivated', 'account_deactivated',
+                                                              'membership_updated')),
+    source     text                                  not null
+        constraint patron_events_source_check check (source in ('patron', 'webhook', 'verification')),
+    steam_id3  bigint,
+    details    jsonb
+);
+
+create index patron_events_patron_id_idx on patron_events (patron_id, id desc);
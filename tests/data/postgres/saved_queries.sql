@@ -0,0 +1,15 @@
+create table saved_queries
+(
+    id             uuid        default gen_random_uuid() not null primary key,
+    api_key        uuid                                  not null
+        constraint saved_queries_api_key_fkey references api_keys on delete cascade,
+    name           text                                  not null,
+    query          text                                  not null,
+    cache_ttl_secs integer     default 300               not null
+        constraint saved_queries_cache_ttl_secs_check check (cache_ttl_secs between 0 and 86400),
+    share_token    uuid
+        constraint saved_queries_share_token_key unique,
+    created_at     timestamptz default current_timestamp not null,
+    updated_at     timestamptz default current_timestamp not null,
+    constraint saved_queries_api_key_name_key unique (api_key, name)
+);
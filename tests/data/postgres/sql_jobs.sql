@@ -0,0 +1,18 @@
+create table sql_jobs
+(
+    id          uuid        default gen_random_uuid() not null primary key,
+    api_key     uuid                                  not null
+        constraint sql_jobs_api_key_fkey references api_keys on delete cascade,
+    query       text                                  not null,
+    status      text        default 'queued'          not null
+        constraint sql_jobs_status_check check (status in ('queued', 'running', 'succeeded', 'failed')),
+    error       text,
+    row_count   bigint,
+    created_at  timestamptz default current_timestamp not null,
+    started_at  timestamptz,
+    finished_at timestamptz
+);
+
+create index sql_jobs_queued_idx on sql_jobs (created_at) where status = 'queued';
+
+create index sql_jobs_api_key_idx on sql_jobs (api_key, created_at);
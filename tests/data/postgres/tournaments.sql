@@ -0,0 +1,72 @@
+create table tournaments
+(
+    id           uuid        default gen_random_uuid() not null primary key,
+    api_key      uuid                                  not null
+        constraint tournaments_api_key_fkey references api_keys on delete cascade,
+    name         text                                  not null,
+    format       text                                  not null
+        constraint tournaments_format_check check (format in ('single_elimination', 'double_elimination', 'swiss')),
+    best_of      integer                               not null
+        constraint tournaments_best_of_check check (best_of in (1, 3, 5, 7)),
+    swiss_rounds integer,
+    status       text        default 'running'         not null
+        constraint tournaments_status_check check (status in ('running', 'finished')),
+    created_at   timestamptz default current_timestamp not null,
+    finished_at  timestamptz
+);
+
+create index tournaments_api_key_idx on tournaments (api_key, created_at);
+
+create table tournament_teams
+(
+    id            uuid      default gen_random_uuid() not null primary key,
+    tournament_id uuid                                not null
+        constraint tournament_teams_tournament_id_fkey references tournaments on delete cascade,
+    seed          integer                             not null,
+    name          text                                not null,
+    account_ids   bigint[]                            not null,
+    constraint tournament_teams_seed_key unique (tournament_id, seed)
+);
+
+create table tournament_series
+(
+    id                   uuid                  not null primary key,
+    tournament_id        uuid                  not null
+        constraint tournament_series_tournament_id_fkey references tournaments on delete cascade,
+    bracket              text                  not null
+        constraint tournament_series_bracket_check check (bracket in ('winners', 'losers', 'grand_final', 'swiss')),
+    round                integer               not null,
+    position             integer               not null,
+    team1_id             uuid
+        constraint tournament_series_team1_id_fkey references tournament_teams,
+    team1_source_id      uuid
+        constraint tournament_series_team1_source_id_fkey references tournament_series,
+    team1_source_loser   boolean default false not null,
+    team2_id             uuid
+        constraint tournament_series_team2_id_fkey references tournament_teams,
+    team2_source_id      uuid
+        constraint tournament_series_team2_source_id_fkey references tournament_series,
+    team2_source_loser   boolean default false not null,
+    team1_wins           integer default 0     not null,
+    team2_wins           integer default 0     not null,
+    winner_id            uuid
+        constraint tournament_series_winner_id_fkey references tournament_teams,
+    finished_at          timestamptz,
+    lobby_reserved_until timestamptz,
+    constraint tournament_series_position_key unique (tournament_id, bracket, round, position)
+);
+
+create table tournament_games
+(
+    party_id    bigint                                not null primary key
+        constraint tournament_games_party_id_fkey references custom_matches on delete cascade,
+    series_id   uuid                                  not null
+        constraint tournament_games_series_id_fkey references tournament_series on delete cascade,
+    match_id    bigint,
+    winner_id   uuid
+        constraint tournament_games_winner_id_fkey references tournament_teams,
+    created_at  timestamptz default current_timestamp not null,
+    finished_at timestamptz
+);
+
+create index tournament_games_series_id_idx on tournament_games (series_id);
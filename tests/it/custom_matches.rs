@@ -0,0 +1,46 @@
+use reqwest::StatusCode;
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::pg_client;
+
+const API_KEY: &str = "fffd6bfd-2be9-4b7e-ab76-a9d1dca19b64";
+const OTHER_API_KEY: &str = "5b2e8c1d-7f4a-4e3b-9c6d-1a0f2e3d4c5b";
+
+async fn set_hero_locks(party_id: i64, api_key: &str) -> reqwest::Response {
+    reqwest::Client::new()
+        .put(format!(
+            "http://localhost:3000/v1/matches/custom/{party_id}/hero-locks"
+        ))
+        .header("X-API-Key", format!("HEXE-{api_key}"))
+        .json(&json!({ "locks": [] }))
+        .send()
+        .await
+        .expect("Failed to get response")
+}
+
+#[tokio::test]
+async fn test_hero_locks_of_other_api_key() {
+    // Lobbies are created by Steam bot accounts, which the test environment doesn't have, so the
+    // lobby is stored the same way the create endpoint does.
+    let pg_client = pg_client().await;
+    let party_id = (Uuid::new_v4().as_u64_pair().0 >> 1).cast_signed();
+    sqlx::query("INSERT INTO custom_matches (party_id, api_key) VALUES ($1, $2)")
+        .bind(party_id)
+        .bind(Uuid::parse_str(API_KEY).unwrap())
+        .execute(&pg_client)
+        .await
+        .expect("Failed to store lobby");
+
+    // Other keys can't tell the lobby apart from a lobby that doesn't exist
+    let response = set_hero_locks(party_id, OTHER_API_KEY).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    let body = response.text().await.expect("Failed to read response");
+    assert!(body.contains(&format!("Party {party_id} not found")));
+
+    // The creator gets past the ownership check, but no bot reported a state of the lobby
+    let response = set_hero_locks(party_id, API_KEY).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    let body = response.text().await.expect("Failed to read response");
+    assert!(body.contains(&format!("No state of party {party_id} found")));
+}
@@ -1,4 +1,6 @@
 use reqwest::Response;
+use sqlx::postgres::{PgConnectOptions, PgPoolOptions};
+use sqlx::{Pool, Postgres};
 
 /// Check the response for common errors
 ///
@@ -43,9 +45,31 @@ pub async fn request_endpoint(
     response
 }
 
+/// Connects to the Postgres database of the test environment
+///
+/// # Panics
+///
+/// Panics if Postgres is not configured or the connection fails
+pub async fn pg_client() -> Pool<Postgres> {
+    let env = |key: &str| std::env::var(key).expect("Postgres is not configured");
+    let options = PgConnectOptions::new()
+        .host(&env("POSTGRES_HOST"))
+        .port(env("POSTGRES_PORT").parse().expect("Invalid Postgres port"))
+        .username(&env("POSTGRES_USERNAME"))
+        .password(&env("POSTGRES_PASSWORD"))
+        .database(&env("POSTGRES_DBNAME"));
+    PgPoolOptions::new()
+        .max_connections(1)
+        .connect_with(options)
+        .await
+        .expect("Failed to connect to Postgres")
+}
+
 mod analytics;
 mod builds;
+mod custom_matches;
 mod info;
 mod patches;
 mod player;
 mod sql;
+mod tournaments;
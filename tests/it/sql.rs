@@ -21,6 +21,46 @@ async fn test_table_schema(#[case] table: &str) {
     assert!(!schema.is_empty());
 }
 
+#[tokio::test]
+async fn test_catalog() {
+    let response = request_endpoint("/v1/sql/catalog", []).await;
+    let catalog: serde_json::Value = response.json().await.expect("Failed to parse response");
+    let match_info = catalog["tables"]
+        .as_array()
+        .expect("Tables is not an array")
+        .iter()
+        .find(|t| t["name"] == "match_info")
+        .expect("match_info not in catalog");
+    assert!(
+        !match_info["columns"]
+            .as_array()
+            .expect("Columns is not an array")
+            .is_empty()
+    );
+    assert!(
+        catalog["dictionaries"]
+            .as_array()
+            .expect("Dictionaries is not an array")
+            .iter()
+            .any(|d| d["name"] == "match_info_dict")
+    );
+}
+
+#[rstest]
+#[case("items", 5)]
+#[case("match_info", 10)]
+#[tokio::test]
+async fn test_sample_rows(#[case] table: &str, #[case] limit: usize) {
+    let response = request_endpoint(
+        &format!("/v1/sql/tables/{table}/sample"),
+        [("limit", limit.to_string().as_str())],
+    )
+    .await;
+    let rows: Vec<serde_json::Value> = response.json().await.expect("Failed to parse response");
+    assert!(!rows.is_empty());
+    assert!(rows.len() <= limit);
+}
+
 #[rstest]
 #[case("SELECT 1", r#"[{"1":1}]"#)]
 #[case("SELECT COUNT() as count FROM match_info", r#"[{"count":100}]"#)]
@@ -0,0 +1,136 @@
+use deadlock_api_rust::routes::v1::tournaments::results::record_tournament_game;
+use reqwest::StatusCode;
+use serde_json::{Value, json};
+use uuid::Uuid;
+use valveprotos::deadlock::c_msg_match_meta_data_contents::{EMatchOutcome, MatchInfo, Players};
+use valveprotos::deadlock::{CMsgMatchMetaDataContents, ECitadelLobbyTeam};
+
+use crate::{check_response, pg_client, request_endpoint};
+
+const API_KEY: &str = "fffd6bfd-2be9-4b7e-ab76-a9d1dca19b64";
+
+async fn post_endpoint(endpoint: &str, body: &Value) -> reqwest::Response {
+    reqwest::Client::new()
+        .post(format!("http://localhost:3000{endpoint}"))
+        .header("X-API-Key", format!("HEXE-{API_KEY}"))
+        .json(body)
+        .send()
+        .await
+        .expect("Failed to get response")
+}
+
+fn metadata(winners: &[u32], losers: &[u32]) -> CMsgMatchMetaDataContents {
+    let players = |accounts: &[u32], team: ECitadelLobbyTeam| {
+        accounts
+            .iter()
+            .map(|&account_id| Players {
+                account_id: Some(account_id),
+                team: Some(team as i32),
+                ..Default::default()
+            })
+            .collect::<Vec<_>>()
+    };
+    CMsgMatchMetaDataContents {
+        match_info: Some(MatchInfo {
+            match_outcome: Some(EMatchOutcome::KEOutcomeTeamWin as i32),
+            winning_team: Some(ECitadelLobbyTeam::KECitadelLobbyTeamTeam0 as i32),
+            players: players(winners, ECitadelLobbyTeam::KECitadelLobbyTeamTeam0)
+                .into_iter()
+                .chain(players(losers, ECitadelLobbyTeam::KECitadelLobbyTeamTeam1))
+                .collect(),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn test_tournament_series_flow() {
+    let pg_client = pg_client().await;
+
+    // Create a tournament of two teams, which play a single series
+    let response = post_endpoint(
+        "/v1/tournaments",
+        &json!({
+            "name": "Test Tournament",
+            "format": "single_elimination",
+            "teams": [
+                {"name": "Team A", "account_ids": [1, 2]},
+                {"name": "Team B", "account_ids": [3, 4]},
+            ],
+        }),
+    )
+    .await;
+    check_response(&response);
+    let tournament: Value = response.json().await.expect("Failed to parse response");
+    let tournament_id = tournament["id"].as_str().expect("Missing tournament id");
+    let series = &tournament["series"][0];
+    let series_id = series["id"].as_str().expect("Missing series id");
+    let team_a = series["team1_id"].as_str().expect("Missing first team");
+    assert_eq!(series["status"], "ready");
+
+    // Create the lobby of the series. Lobbies are created by Steam bot accounts, which the test
+    // environment doesn't have, so the lobby is stored the same way the endpoint does.
+    let party_id = (Uuid::new_v4().as_u64_pair().0 >> 1).cast_signed();
+    let match_id = party_id;
+    sqlx::query(
+        "INSERT INTO custom_matches (party_id, api_key, status, match_id, started_at) VALUES ($1, $2, 'started', $3, now())",
+    )
+    .bind(party_id)
+    .bind(Uuid::parse_str(API_KEY).unwrap())
+    .bind(match_id)
+    .execute(&pg_client)
+    .await
+    .expect("Failed to store lobby");
+    sqlx::query("INSERT INTO tournament_games (party_id, series_id) VALUES ($1, $2)")
+        .bind(party_id)
+        .bind(Uuid::parse_str(series_id).unwrap())
+        .execute(&pg_client)
+        .await
+        .expect("Failed to store game");
+
+    // No other lobby can be created while the lobby is active
+    let response = post_endpoint(
+        &format!("/v1/tournaments/{tournament_id}/series/{series_id}/lobby"),
+        &json!({}),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+
+    // Play the match, which Team A wins, and finish it like the custom match tracker does
+    let mut transaction = pg_client.begin().await.unwrap();
+    sqlx::query(
+        "UPDATE custom_matches SET status = 'finished', finished_at = now() WHERE party_id = $1",
+    )
+    .bind(party_id)
+    .execute(&mut *transaction)
+    .await
+    .expect("Failed to finish match");
+    record_tournament_game(
+        &mut *transaction,
+        party_id.cast_unsigned(),
+        match_id.cast_unsigned(),
+        &metadata(&[1, 2], &[3, 4]),
+    )
+    .await
+    .expect("Failed to record game");
+    transaction.commit().await.unwrap();
+
+    // The result decides the series and finishes the tournament
+    let response = request_endpoint(&format!("/v1/tournaments/{tournament_id}"), []).await;
+    let tournament: Value = response.json().await.expect("Failed to parse response");
+    let series = &tournament["series"][0];
+    assert_eq!(tournament["status"], "finished");
+    assert_eq!(series["status"], "finished");
+    assert_eq!(series["winner_id"], team_a);
+    assert_eq!(series["team1_wins"], 1);
+    assert_eq!(series["games"][0]["match_id"], match_id);
+    assert_eq!(series["games"][0]["winner_id"], team_a);
+
+    let response = post_endpoint(
+        &format!("/v1/tournaments/{tournament_id}/series/{series_id}/lobby"),
+        &json!({}),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+}